@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// The attempts and sleeps accounted by a `DelayAccounting` or for a single call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DelayStats {
+    /// The number of retried calls
+    pub calls: u64,
+    /// The number of attempts made by those calls, including their first ones
+    pub attempts: u64,
+    /// The total time slept between attempts
+    pub total_sleep: Duration,
+    /// The longest single sleep between attempts
+    pub max_sleep: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    calls: AtomicU64,
+    retries: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl Counters {
+    fn record(&self, delay: Duration) {
+        let nanos = u64::try_from(delay.as_nanos()).unwrap_or(u64::MAX);
+        self.retries.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> DelayStats {
+        let calls = self.calls.load(Ordering::Relaxed);
+        DelayStats {
+            calls,
+            attempts: calls + self.retries.load(Ordering::Relaxed),
+            total_sleep: Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed)),
+            max_sleep: Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+fn named_accountings() -> &'static Mutex<HashMap<&'static str, DelayAccounting>> {
+    static NAMED: OnceLock<Mutex<HashMap<&'static str, DelayAccounting>>> = OnceLock::new();
+    NAMED.get_or_init(Default::default)
+}
+
+/// Accounting of the attempts and sleeps of retried calls, aggregated over all of them
+///
+/// The delays of each call are tracked with `DelayAccounting::track`, which works with any retry
+/// entry point taking a `Duration` iterator. Only a few relaxed atomic operations are done per
+/// delay, so that the accounting can be left enabled in production.
+///
+/// ```
+/// # use retry_block::DelayAccounting;
+/// # use retry_block::delay::Fixed;
+/// # use std::time::Duration;
+/// let accounting = DelayAccounting::named("fetch_user");
+///
+/// let mut delays = accounting.track(Fixed::exact(Duration::from_millis(1)).take(2));
+/// let value = retry_block::retry_fn(&mut delays, || Err::<(), _>("unavailable"));
+/// assert_eq!(value, Err("unavailable"));
+/// // for this call only
+/// assert_eq!(delays.stats().attempts, 3);
+/// assert_eq!(delays.stats().total_sleep, Duration::from_millis(2));
+///
+/// // for all the calls of the operation so far
+/// let stats = DelayAccounting::named("fetch_user").stats();
+/// assert_eq!(stats.calls, 1);
+/// assert_eq!(stats.max_sleep, Duration::from_millis(1));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DelayAccounting {
+    counters: Arc<Counters>,
+}
+
+impl DelayAccounting {
+    /// Create an accounting of its own
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the process-wide accounting of the given operation, created on first use
+    pub fn named(operation: &'static str) -> Self {
+        let mut named = named_accountings()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        named.entry(operation).or_default().clone()
+    }
+
+    /// Return the stats of all the operations accounted with `DelayAccounting::named`
+    pub fn all_named() -> Vec<(&'static str, DelayStats)> {
+        let named = named_accountings()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        named
+            .iter()
+            .map(|(operation, accounting)| (*operation, accounting.stats()))
+            .collect()
+    }
+
+    /// Account a new call retried with the given delays
+    pub fn track<D>(&self, durations: D) -> Accounted<D::IntoIter>
+    where
+        D: IntoIterator<Item = Duration>,
+    {
+        self.counters.calls.fetch_add(1, Ordering::Relaxed);
+        let call = Counters::default();
+        call.calls.store(1, Ordering::Relaxed);
+        Accounted {
+            inner: durations.into_iter(),
+            call,
+            total: self.counters.clone(),
+        }
+    }
+
+    /// Return the stats of all the calls tracked so far
+    pub fn stats(&self) -> DelayStats {
+        self.counters.stats()
+    }
+}
+
+/// The delays of the inner delay iterator, accounted for a single call and in the
+/// `DelayAccounting` that tracks it
+///
+/// Retry with a mutable reference to it to query the stats of the call afterwards.
+#[derive(Debug)]
+pub struct Accounted<T> {
+    inner: T,
+    call: Counters,
+    total: Arc<Counters>,
+}
+
+impl<T> Accounted<T> {
+    /// Return the stats of this call so far
+    pub fn stats(&self) -> DelayStats {
+        self.call.stats()
+    }
+}
+
+impl<T> Iterator for Accounted<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let next = self.inner.next()?;
+        self.call.record(next);
+        self.total.record(next);
+        Some(next)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DelayAccounting;
+    use std::time::Duration;
+
+    #[test]
+    fn aggregated_calls() {
+        let accounting = DelayAccounting::new();
+        let delays = [Duration::from_millis(3), Duration::from_millis(1)];
+        let value = crate::retry_fn(accounting.track(delays), || Err::<(), _>(()));
+        assert_eq!(value, Err(()));
+        let mut tried = false;
+        let mut delays = accounting.track(delays);
+        let value = crate::retry_fn(&mut delays, || {
+            let res = if tried { Ok(()) } else { Err(()) };
+            tried = true;
+            res
+        });
+        assert_eq!(value, Ok(()));
+        assert_eq!(delays.stats().attempts, 2);
+        assert_eq!(delays.stats().max_sleep, Duration::from_millis(3));
+
+        let stats = accounting.stats();
+        assert_eq!(stats.calls, 2);
+        assert_eq!(stats.attempts, 5);
+        assert_eq!(stats.total_sleep, Duration::from_millis(7));
+        assert_eq!(stats.max_sleep, Duration::from_millis(3));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{retry, OperationResult};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The errors of all the attempts of an operation, in order
+///
+/// There is always at least one error; the last one is reported as the `source` of the aggregate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AggregateError<E> {
+    errors: Vec<E>,
+    elapsed: Duration,
+}
+
+impl<E> AggregateError<E> {
+    /// The number of failed attempts
+    pub fn attempts(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// The time spent retrying, from the start of the first attempt to the end of the last one
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The errors of all the attempts, in order
+    pub fn errors(&self) -> &[E] {
+        &self.errors
+    }
+
+    /// The error of the last attempt
+    pub fn last(&self) -> &E {
+        self.errors
+            .last()
+            .expect("an aggregate error holds at least one error")
+    }
+
+    /// Return the errors of all the attempts, in order
+    pub fn into_errors(self) -> Vec<E> {
+        self.errors
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for AggregateError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "operation failed after {} attempt(s)", self.attempts())?;
+        for (i, e) in self.errors.iter().enumerate() {
+            write!(f, "\n  attempt {}: {}", i + 1, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: Error + 'static> Error for AggregateError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.last())
+    }
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// collecting the errors of all the attempts.
+///
+/// ```
+/// # use retry_block::retry_fn_collecting;
+/// # use retry_block::delay::NoDelay;
+/// let mut attempts = 0;
+/// let value = retry_fn_collecting(NoDelay.take(2), || {
+///     attempts += 1;
+///     Err::<(), _>(attempts)
+/// });
+/// assert_eq!(value.unwrap_err().errors(), &[1, 2, 3]);
+/// ```
+pub fn retry_fn_collecting<D, O, OR, R, E>(
+    durations: D,
+    mut operation: O,
+) -> Result<R, AggregateError<E>>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    let start = Instant::now();
+    let mut errors = Vec::new();
+    let res = retry!(durations, {
+        match operation().into() {
+            OperationResult::Ok(res) => OperationResult::Ok(res),
+            OperationResult::Retry(e) => {
+                errors.push(e);
+                OperationResult::Retry(())
+            }
+            OperationResult::Err(e) => {
+                errors.push(e);
+                OperationResult::Err(())
+            }
+        }
+    });
+    res.map_err(|()| AggregateError {
+        errors,
+        elapsed: start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delay::NoDelay;
+    use crate::retry_fn_collecting;
+    use std::error::Error;
+    use std::fmt;
+
+    #[derive(Debug)]
+    struct Failure(u32);
+
+    impl fmt::Display for Failure {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "failure #{}", self.0)
+        }
+    }
+
+    impl Error for Failure {}
+
+    #[test]
+    fn aggregate_report() {
+        let mut attempts = 0;
+        let err = retry_fn_collecting(NoDelay.take(1), || {
+            attempts += 1;
+            Err::<(), _>(Failure(attempts))
+        })
+        .unwrap_err();
+
+        assert_eq!(err.attempts(), 2);
+        assert_eq!(
+            err.to_string(),
+            "operation failed after 2 attempt(s)\n  attempt 1: failure #1\n  attempt 2: failure #2"
+        );
+        assert_eq!(err.source().unwrap().to_string(), "failure #2");
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{retry, OperationResult};
+use rayon::prelude::*;
+use std::time::Duration;
+
+/// Retry the given operation on each item of a batch in parallel on the rayon thread pool, each
+/// with its own copy of the given delays, and return the result of each item in order.
+///
+/// The operation is given a reference to its item, so that it can be attempted again.
+///
+/// Delays between attempts block the rayon thread retrying the item with `std::thread::sleep`,
+/// so that a batch with long delays keeps threads of the global pool idle, and can starve any
+/// other parallel work using it. Run such batches on a dedicated pool with
+/// `rayon::ThreadPool::install` instead:
+///
+/// ```
+/// # use retry_block::retry_batch;
+/// # use retry_block::delay::Fixed;
+/// # use std::time::Duration;
+/// let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+/// let results = pool.install(|| {
+///     retry_batch(Fixed::exact(Duration::from_millis(1)).take(2), vec![1, 2], |n: &i32| {
+///         Ok::<_, ()>(*n)
+///     })
+/// });
+/// assert_eq!(results, vec![Ok(1), Ok(2)]);
+/// ```
+///
+/// ```
+/// # use retry_block::retry_batch;
+/// # use retry_block::delay::NoDelay;
+/// let results = retry_batch(NoDelay.take(1), vec![1, 2, 3], |n: &i32| {
+///     if *n % 2 == 0 {
+///         Ok(n * 10)
+///     } else {
+///         Err("odd")
+///     }
+/// });
+/// assert_eq!(results, vec![Err("odd"), Ok(20), Err("odd")]);
+/// ```
+pub fn retry_batch<D, I, T, O, OR, R, E>(durations: D, items: I, operation: O) -> Vec<Result<R, E>>
+where
+    D: IntoIterator<Item = Duration> + Clone + Send + Sync,
+    I: IntoParallelIterator<Item = T>,
+    I::Iter: IndexedParallelIterator,
+    O: Fn(&T) -> OR + Sync,
+    OR: Into<OperationResult<R, E>>,
+    R: Send,
+    E: Send,
+{
+    items
+        .into_par_iter()
+        .map(|item| retry!(durations.clone(), { operation(&item) }))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::retry_batch;
+    use crate::delay::NoDelay;
+    use crate::OperationResult;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn partial_failures() {
+        let attempts: Vec<AtomicUsize> = (0..4).map(|_| AtomicUsize::new(0)).collect();
+        let results = retry_batch(NoDelay.take(2), 0..4, |n: &usize| {
+            // even items succeed on their second attempt, odd ones fail fatally
+            let attempt = attempts[*n].fetch_add(1, Ordering::SeqCst);
+            match (n % 2, attempt) {
+                (0, 0) => OperationResult::Retry("busy"),
+                (0, _) => OperationResult::Ok(*n),
+                _ => OperationResult::Err("invalid"),
+            }
+        });
+        assert_eq!(results, vec![Ok(0), Err("invalid"), Ok(2), Err("invalid")]);
+        let attempts: Vec<usize> = attempts.into_iter().map(AtomicUsize::into_inner).collect();
+        assert_eq!(attempts, vec![2, 1, 2, 1]);
+    }
+
+    #[test]
+    fn exhausted_retries() {
+        let attempts: Vec<AtomicUsize> = (0..3).map(|_| AtomicUsize::new(0)).collect();
+        let results = retry_batch(NoDelay.take(2), vec![0, 1, 2], |n: &usize| {
+            attempts[*n].fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(*n)
+        });
+        assert_eq!(results, vec![Err(0), Err(1), Err(2)]);
+        // each item has its own copy of the delays
+        for attempts in attempts {
+            assert_eq!(attempts.into_inner(), 3);
+        }
+    }
+
+    #[test]
+    fn ordered_results() {
+        let results = retry_batch(NoDelay.take(1), (0..1000).collect::<Vec<_>>(), |n: &u64| {
+            // later items finish first
+            std::thread::sleep(std::time::Duration::from_micros(1000 - n));
+            Ok::<_, ()>(n * 2)
+        });
+        assert_eq!(results, (0..1000).map(|n| Ok(n * 2)).collect::<Vec<_>>());
+    }
+}
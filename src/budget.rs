@@ -0,0 +1,242 @@
+use crate::delay::{Clock, SystemClock};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A live view of a `RetryBudget`, as returned by `RetryBudget::snapshot`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BudgetSnapshot {
+    /// The number of retries the budget currently allows
+    pub remaining: f64,
+    /// The deposits per second over the last complete window
+    pub deposit_rate: f64,
+    /// The withdrawals per second over the last complete window, including rejected ones
+    pub withdraw_rate: f64,
+    /// The number of withdrawals rejected since the creation of the budget
+    pub rejected: u64,
+}
+
+#[derive(Debug)]
+struct BudgetState {
+    tokens: f64,
+    window_start: Instant,
+    // (deposits, withdrawals) of the current and the last complete window
+    current: (u64, u64),
+    previous: (u64, u64),
+    rejected: u64,
+}
+
+/// A budget of retries shared by the requests of a service, so that retries stay a bounded
+/// proportion of the load rather than amplifying an outage
+///
+/// Each request deposits a fraction of a token, and each retry withdraws a whole one, or is
+/// denied if the budget is empty. With the `metrics` feature, the remaining tokens and the
+/// deposit and withdrawal rates of a named budget are reported as gauges labeled with its name.
+/// Windows are measured with a `Clock`, `SystemClock` by default.
+///
+/// ```
+/// # use retry_block::RetryBudget;
+/// # use retry_block::delay::NoDelay;
+/// # use std::sync::Arc;
+/// # use std::time::Duration;
+/// // retries may amount to 20% of requests, with at most 1 retry in store
+/// let budget = Arc::new(RetryBudget::new(0.2, 1.0, Duration::from_secs(10)));
+///
+/// budget.deposit();
+/// let mut attempts = 0;
+/// let value = retry_block::retry_fn(budget.budgeted(NoDelay.take(5)), || {
+///     attempts += 1;
+///     Err::<(), _>("unavailable")
+/// });
+/// assert_eq!(value, Err("unavailable"));
+/// assert_eq!(attempts, 2);
+/// assert_eq!(budget.snapshot().rejected, 1);
+/// ```
+#[derive(Debug)]
+pub struct RetryBudget<C = SystemClock> {
+    ratio: f64,
+    max_tokens: f64,
+    window: Duration,
+    name: Option<&'static str>,
+    clock: C,
+    state: Mutex<BudgetState>,
+}
+
+impl RetryBudget {
+    /// Create a budget where each request deposits `ratio` tokens, holding at most `max_tokens`
+    /// tokens and starting full
+    ///
+    /// Rates are measured over windows of the given duration.
+    pub fn new(ratio: f64, max_tokens: f64, window: Duration) -> Self {
+        Self::with_clock(ratio, max_tokens, window, SystemClock)
+    }
+}
+
+impl<C: Clock> RetryBudget<C> {
+    /// Create a budget like `RetryBudget::new`, measuring its windows with the given clock
+    pub fn with_clock(ratio: f64, max_tokens: f64, window: Duration, clock: C) -> Self {
+        Self {
+            ratio: ratio.max(0.0),
+            max_tokens: max_tokens.max(0.0),
+            window,
+            name: None,
+            state: Mutex::new(BudgetState {
+                tokens: max_tokens.max(0.0),
+                window_start: clock.now(),
+                current: (0, 0),
+                previous: (0, 0),
+                rejected: 0,
+            }),
+            clock,
+        }
+    }
+
+    /// Name the budget, to label its gauges with the `metrics` feature
+    pub fn named(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut BudgetState) -> R) -> R {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(state.window_start);
+        if elapsed >= self.window {
+            // a window without any activity leaves a last complete window without any either
+            state.previous = if elapsed >= self.window * 2 {
+                (0, 0)
+            } else {
+                state.current
+            };
+            state.current = (0, 0);
+            state.window_start = now;
+        }
+        let res = f(&mut state);
+        #[cfg(feature = "metrics")]
+        if let Some(name) = self.name {
+            let snapshot = self.snapshot_of(&state);
+            metrics::gauge!("retry_budget_remaining", "budget" => name).set(snapshot.remaining);
+            metrics::gauge!("retry_budget_deposit_rate", "budget" => name)
+                .set(snapshot.deposit_rate);
+            metrics::gauge!("retry_budget_withdraw_rate", "budget" => name)
+                .set(snapshot.withdraw_rate);
+        }
+        res
+    }
+
+    fn snapshot_of(&self, state: &BudgetState) -> BudgetSnapshot {
+        let window = self.window.as_secs_f64();
+        let rate = |count: u64| {
+            if window > 0.0 {
+                count as f64 / window
+            } else {
+                0.0
+            }
+        };
+        BudgetSnapshot {
+            remaining: state.tokens,
+            deposit_rate: rate(state.previous.0),
+            withdraw_rate: rate(state.previous.1),
+            rejected: state.rejected,
+        }
+    }
+
+    /// Record a request, making a fraction of a retry available
+    pub fn deposit(&self) {
+        self.with_state(|state| {
+            state.current.0 += 1;
+            state.tokens = (state.tokens + self.ratio).min(self.max_tokens);
+        })
+    }
+
+    /// Withdraw a retry from the budget, returning whether it is allowed
+    pub fn withdraw(&self) -> bool {
+        self.with_state(|state| {
+            state.current.1 += 1;
+            if state.tokens >= 1.0 {
+                state.tokens -= 1.0;
+                true
+            } else {
+                state.rejected += 1;
+                false
+            }
+        })
+    }
+
+    /// Return a live view of the budget, e.g. to alert when a service starts eating it
+    pub fn snapshot(&self) -> BudgetSnapshot {
+        self.with_state(|state| self.snapshot_of(state))
+    }
+
+    /// Follow the given delays as long as the budget allows each retry
+    pub fn budgeted<D>(self: &Arc<Self>, durations: D) -> Budgeted<D::IntoIter, C>
+    where
+        D: IntoIterator<Item = Duration>,
+    {
+        Budgeted {
+            inner: durations.into_iter(),
+            budget: self.clone(),
+        }
+    }
+}
+
+/// The delays of the inner delay iterator, each withdrawn from a `RetryBudget`, ending once the
+/// budget denies a retry
+#[derive(Debug)]
+pub struct Budgeted<T, C = SystemClock> {
+    inner: T,
+    budget: Arc<RetryBudget<C>>,
+}
+
+impl<T: Clone, C> Clone for Budgeted<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            budget: self.budget.clone(),
+        }
+    }
+}
+
+impl<T, C> Iterator for Budgeted<T, C>
+where
+    T: Iterator<Item = Duration>,
+    C: Clock,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let next = self.inner.next()?;
+        self.budget.withdraw().then_some(next)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RetryBudget;
+    use crate::time::MockTime;
+    use std::time::Duration;
+
+    #[test]
+    fn budget_rates() {
+        let time = MockTime::new();
+        let budget = RetryBudget::with_clock(0.5, 1.0, Duration::from_millis(50), time.clone());
+        assert!(budget.withdraw());
+        assert!(!budget.withdraw());
+        budget.deposit();
+        budget.deposit();
+        assert!(budget.withdraw());
+        assert!(!budget.withdraw());
+
+        time.advance(Duration::from_millis(60));
+        let snapshot = budget.snapshot();
+        assert_eq!(snapshot.remaining, 0.0);
+        assert_eq!(snapshot.rejected, 2);
+        assert_eq!(snapshot.deposit_rate, 40.0);
+        assert_eq!(snapshot.withdraw_rate, 80.0);
+
+        // a window without any activity in between
+        time.advance(Duration::from_millis(100));
+        let snapshot = budget.snapshot();
+        assert_eq!(snapshot.deposit_rate, 0.0);
+        assert_eq!(snapshot.withdraw_rate, 0.0);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{retry, OperationResult};
+use std::any::Any;
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Duration;
+
+/// What to do when an attempt of an operation panics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnPanic {
+    /// Retry the operation as if it returned a retryable error
+    Retry,
+    /// Stop retrying and return the panic as an error immediately
+    Abort,
+}
+
+/// The error of an operation whose attempts may panic
+///
+/// Panic payloads are only `Send`, so unlike the other error types of this crate, this one is not
+/// `Sync`; convert it using `panic_message` to share it across threads.
+#[derive(Debug)]
+pub enum PanicError<E> {
+    /// The last attempt panicked with the given payload
+    Panic(Box<dyn Any + Send + 'static>),
+    /// The last attempt returned the given error
+    Error(E),
+}
+
+impl<E> PanicError<E> {
+    /// Return the message of the panic, if any and if it was a string
+    pub fn panic_message(&self) -> Option<&str> {
+        match self {
+            Self::Panic(payload) => payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str)),
+            Self::Error(_) => None,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for PanicError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self, self.panic_message()) {
+            (Self::Panic(_), Some(message)) => write!(f, "operation panicked: {}", message),
+            (Self::Panic(_), None) => write!(f, "operation panicked"),
+            (Self::Error(e), _) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for PanicError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Panic(_) => None,
+            Self::Error(e) => Some(e),
+        }
+    }
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// catching panics of each attempt with `std::panic::catch_unwind`.
+///
+/// Panicking attempts are handled according to `on_panic`. Note that the panic hook still runs
+/// for each of them, and that the operation is asserted to be unwind safe: any state it mutates
+/// may be observed half-updated by the next attempt. The `catch_unwind = ...` argument of `retry!`
+/// does the same for a block.
+///
+/// ```
+/// # use retry_block::{retry_fn_catching, OnPanic};
+/// # use retry_block::delay::NoDelay;
+/// let mut attempts = 0;
+/// let value = retry_fn_catching(NoDelay.take(3), OnPanic::Retry, || {
+///     attempts += 1;
+///     if attempts < 3 {
+///         panic!("transient bad data");
+///     }
+///     Ok::<_, ()>(attempts)
+/// });
+/// assert_eq!(value.unwrap(), 3);
+/// ```
+pub fn retry_fn_catching<D, O, OR, R, E>(
+    durations: D,
+    on_panic: OnPanic,
+    mut operation: O,
+) -> Result<R, PanicError<E>>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    retry!(durations, {
+        match catch_unwind(AssertUnwindSafe(|| operation().into())) {
+            Ok(OperationResult::Ok(res)) => OperationResult::Ok(res),
+            Ok(OperationResult::Retry(e)) => OperationResult::Retry(PanicError::Error(e)),
+            Ok(OperationResult::Err(e)) => OperationResult::Err(PanicError::Error(e)),
+            Err(payload) => match on_panic {
+                OnPanic::Retry => OperationResult::Retry(PanicError::Panic(payload)),
+                OnPanic::Abort => OperationResult::Err(PanicError::Panic(payload)),
+            },
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delay::NoDelay;
+    use crate::{retry, retry_fn_catching, OnPanic, OperationResult};
+
+    #[test]
+    fn abort_on_panic() {
+        let mut attempts = 0;
+        let res = retry_fn_catching(NoDelay.take(3), OnPanic::Abort, || -> Result<(), ()> {
+            attempts += 1;
+            panic!("attempt {}", attempts)
+        });
+        assert_eq!(attempts, 1);
+        assert_eq!(res.unwrap_err().panic_message(), Some("attempt 1"));
+    }
+
+    #[test]
+    fn retry_on_panic_until_exhausted() {
+        let mut attempts = 0;
+        let res = retry_fn_catching(NoDelay.take(1), OnPanic::Retry, || {
+            attempts += 1;
+            if attempts == 1 {
+                panic!("first attempt");
+            }
+            OperationResult::<(), _>::Retry("second attempt")
+        });
+        assert_eq!(attempts, 2);
+        assert_eq!(res.unwrap_err().to_string(), "second attempt");
+    }
+
+    #[test]
+    fn macro_catch_unwind() {
+        let mut attempts = 0;
+        let res = retry!(NoDelay.take(3), catch_unwind = OnPanic::Abort, {
+            attempts += 1;
+            if attempts == 2 {
+                panic!("second attempt");
+            }
+            Err::<(), _>("first attempt")
+        });
+        assert_eq!(attempts, 2);
+        assert_eq!(res.unwrap_err().panic_message(), Some("second attempt"));
+    }
+}
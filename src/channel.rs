@@ -0,0 +1,253 @@
+//! Channel operations with backoff
+//!
+//! Send to a bounded channel with `try_send` rather than blocking on it, or receive with
+//! `try_recv`, retrying according to a delay iterator until an overall timeout, so that a pipeline
+//! stage backs off while the next one catches up instead of buffering without bound. As with
+//! `lock`, a delay that would outlast the timeout is clamped to half of what remains of it, so that
+//! one last attempt still fits, and the timeout is measured with `delay::SystemClock` by the
+//! blocking functions and with `time::TokioTime` by the asynchronous ones.
+//!
+//! ```
+//! # use retry_block::channel::{send_with_backoff, SendError};
+//! # use retry_block::delay::Fixed;
+//! # use std::time::Duration;
+//! let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+//! let delays = Fixed::exact(Duration::from_millis(5));
+//! send_with_backoff(&sender, 1, delays, Duration::from_millis(20)).unwrap();
+//! let error = send_with_backoff(&sender, 2, delays, Duration::from_millis(20)).unwrap_err();
+//! assert!(matches!(error, SendError::Full { value: 2, .. }));
+//!
+//! assert_eq!(receiver.recv(), Ok(1));
+//! send_with_backoff(&sender, 2, delays, Duration::from_millis(20)).unwrap();
+//! ```
+
+use crate::delay::{Clock, SystemClock};
+use std::sync::mpsc::{Receiver, SyncSender, TryRecvError, TrySendError};
+use std::time::Duration;
+
+/// The given delays, clamped to the given timeout from now on the given clock
+fn until_timeout<D, C>(durations: D, timeout: Duration, clock: C) -> impl Iterator<Item = Duration>
+where
+    D: IntoIterator<Item = Duration>,
+    C: Clock,
+{
+    let deadline = clock.now() + timeout;
+    let durations = crate::delay::__faulty(durations.into_iter());
+    crate::fit_deadline(durations, move || {
+        deadline.saturating_duration_since(clock.now())
+    })
+}
+
+/// The error of a value that could not be sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendError<T> {
+    /// The channel was still full after the given number of attempts, once the timeout expired
+    Full { value: T, attempts: usize },
+    /// The receiving half of the channel was dropped
+    Disconnected(T),
+}
+
+impl<T> SendError<T> {
+    /// Take back the value that could not be sent
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Full { value, .. } | Self::Disconnected(value) => value,
+        }
+    }
+}
+
+impl<T> std::fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Full { attempts, .. } => {
+                write!(f, "channel still full after {attempts} attempt(s)")
+            }
+            Self::Disconnected(_) => write!(f, "channel disconnected"),
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::error::Error for SendError<T> {}
+
+/// The error of a value that could not be received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    /// The channel was still empty after the given number of attempts, once the timeout expired
+    Empty { attempts: usize },
+    /// The channel is empty and its sending halves were dropped
+    Disconnected,
+}
+
+impl std::fmt::Display for RecvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty { attempts } => {
+                write!(f, "channel still empty after {attempts} attempt(s)")
+            }
+            Self::Disconnected => write!(f, "channel disconnected"),
+        }
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// Send the given value with `SyncSender::try_send`, retrying according to the given delays while
+/// the channel is full, until the given timeout
+pub fn send_with_backoff<T, D>(
+    sender: &SyncSender<T>,
+    mut value: T,
+    durations: D,
+    timeout: Duration,
+) -> Result<(), SendError<T>>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    let mut it = until_timeout(durations, timeout, SystemClock);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match sender.try_send(value) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Disconnected(value)) => return Err(SendError::Disconnected(value)),
+            Err(TrySendError::Full(full)) => match it.next() {
+                Some(duration) => {
+                    crate::sleep::thread_sleep(duration);
+                    value = full;
+                }
+                None => {
+                    return Err(SendError::Full {
+                        value: full,
+                        attempts,
+                    })
+                }
+            },
+        }
+    }
+}
+
+/// Receive a value with `Receiver::try_recv`, retrying according to the given delays while the
+/// channel is empty, until the given timeout
+pub fn recv_with_backoff<T, D>(
+    receiver: &Receiver<T>,
+    durations: D,
+    timeout: Duration,
+) -> Result<T, RecvError>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    let mut it = until_timeout(durations, timeout, SystemClock);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match receiver.try_recv() {
+            Ok(value) => return Ok(value),
+            Err(TryRecvError::Disconnected) => return Err(RecvError::Disconnected),
+            Err(TryRecvError::Empty) => match it.next() {
+                Some(duration) => crate::sleep::thread_sleep(duration),
+                None => return Err(RecvError::Empty { attempts }),
+            },
+        }
+    }
+}
+
+/// Send the given value with `tokio::sync::mpsc::Sender::try_send`, retrying according to the
+/// given delays while the channel is full, until the given timeout
+#[cfg(feature = "tokio")]
+pub async fn async_send_with_backoff<T, D>(
+    sender: &tokio::sync::mpsc::Sender<T>,
+    mut value: T,
+    durations: D,
+    timeout: Duration,
+) -> Result<(), SendError<T>>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    use tokio::sync::mpsc::error::TrySendError;
+
+    let mut it = until_timeout(durations, timeout, crate::time::TokioTime);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match sender.try_send(value) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Closed(value)) => return Err(SendError::Disconnected(value)),
+            Err(TrySendError::Full(full)) => match it.next() {
+                Some(duration) => {
+                    tokio::time::sleep(duration).await;
+                    value = full;
+                }
+                None => {
+                    return Err(SendError::Full {
+                        value: full,
+                        attempts,
+                    })
+                }
+            },
+        }
+    }
+}
+
+/// Receive a value with `tokio::sync::mpsc::Receiver::try_recv`, retrying according to the given
+/// delays while the channel is empty, until the given timeout
+#[cfg(feature = "tokio")]
+pub async fn async_recv_with_backoff<T, D>(
+    receiver: &mut tokio::sync::mpsc::Receiver<T>,
+    durations: D,
+    timeout: Duration,
+) -> Result<T, RecvError>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    use tokio::sync::mpsc::error::TryRecvError;
+
+    let mut it = until_timeout(durations, timeout, crate::time::TokioTime);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match receiver.try_recv() {
+            Ok(value) => return Ok(value),
+            Err(TryRecvError::Disconnected) => return Err(RecvError::Disconnected),
+            Err(TryRecvError::Empty) => match it.next() {
+                Some(duration) => tokio::time::sleep(duration).await,
+                None => return Err(RecvError::Empty { attempts }),
+            },
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod test {
+    use super::{async_recv_with_backoff, async_send_with_backoff, RecvError, SendError};
+    use crate::delay::Fixed;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn backed_off_until_drained() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+        let delays = Fixed::exact(Duration::from_millis(10));
+        let timeout = Duration::from_millis(100);
+        async_send_with_backoff(&sender, 1, delays, timeout)
+            .await
+            .unwrap();
+        let consumer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(25)).await;
+            let first = receiver.recv().await;
+            (first, receiver)
+        });
+        async_send_with_backoff(&sender, 2, delays, timeout)
+            .await
+            .unwrap();
+        let (first, mut receiver) = consumer.await.unwrap();
+        assert_eq!(first, Some(1));
+
+        assert_eq!(
+            async_recv_with_backoff(&mut receiver, delays, timeout).await,
+            Ok(2)
+        );
+        let error = async_recv_with_backoff(&mut receiver, delays, timeout).await;
+        assert!(matches!(error, Err(RecvError::Empty { attempts }) if attempts > 1));
+        drop(receiver);
+        let error = async_send_with_backoff(&sender, 3, delays, timeout).await;
+        assert_eq!(error, Err(SendError::Disconnected(3)));
+    }
+}
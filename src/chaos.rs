@@ -0,0 +1,237 @@
+//! Fault injection into retries, to chaos-test how an application behaves when they are exhausted
+//! (need `chaos` feature)
+//!
+//! A `FaultInjector` can end retries early, as if their delays had run out, and stretch the
+//! delays waited between attempts. It applies either to one delay strategy, using
+//! `FaultExt::with_faults`, or to every retry of the process once installed with
+//! `set_fault_injector`, without modifying any call site:
+//!
+//! ```
+//! # use retry_block::chaos::{set_fault_injector, Faults};
+//! # use retry_block::delay::NoDelay;
+//! # use retry_block::retry_fn;
+//! let _faults = set_fault_injector(Faults::new().give_up_after(1));
+//!
+//! let mut attempts = 0;
+//! let value = retry_fn(NoDelay.take(10), || {
+//!     attempts += 1;
+//!     Err::<(), _>("unavailable")
+//! });
+//! assert_eq!(value, Err("unavailable"));
+//! assert_eq!(attempts, 2);
+//! ```
+//!
+//! The global injector applies to the retries of the crate that follow a delay iterator: the
+//! retry macros and functions, `RetryFuture`, `RetryScope`, `RetryManager`, the retrying
+//! iterator, stream and channel adapters, the `tower` policies, `health` checks, `ureq` agents
+//! and persistent retries. It leaves alone the delays stepped through by hand with a
+//! `RetryState`, and the `retry_perpetual!` and `async_retry_perpetual!` retries that cannot give
+//! up, i.e. without escalation.
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// A hook forcing retries to give up early or stretching their delays
+pub trait FaultInjector: Send + Sync {
+    /// Whether to give up instead of retrying again after the given number of retries, leaving
+    /// the retry with its last error as if its delays were exhausted
+    fn give_up(&self, retries: usize) -> bool {
+        let _ = retries;
+        false
+    }
+
+    /// The delay to actually wait instead of the given one
+    fn stretch(&self, delay: Duration) -> Duration {
+        delay
+    }
+}
+
+/// A simple `FaultInjector` giving up after some number of retries and stretching all delays by
+/// a factor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Faults {
+    give_up_after: Option<usize>,
+    stretch: f64,
+}
+
+impl Faults {
+    /// Create a `Faults` injecting no fault
+    pub fn new() -> Self {
+        Self {
+            give_up_after: None,
+            stretch: 1.0,
+        }
+    }
+
+    /// Give up after the given number of retries
+    pub fn give_up_after(mut self, retries: usize) -> Self {
+        self.give_up_after = Some(retries);
+        self
+    }
+
+    /// Multiply each delay by the given factor
+    pub fn stretch(mut self, factor: f64) -> Self {
+        self.stretch = factor;
+        self
+    }
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FaultInjector for Faults {
+    fn give_up(&self, retries: usize) -> bool {
+        self.give_up_after.is_some_and(|max| retries >= max)
+    }
+
+    fn stretch(&self, delay: Duration) -> Duration {
+        delay.mul_f64(self.stretch.max(0.0))
+    }
+}
+
+type SharedInjector = Arc<dyn FaultInjector>;
+
+static GLOBAL: RwLock<Option<SharedInjector>> = RwLock::new(None);
+
+/// Install a fault injector applying to every retry of the process (see the module
+/// documentation), until the returned guard is dropped
+pub fn set_fault_injector(injector: impl FaultInjector + 'static) -> FaultGuard {
+    let mut global = GLOBAL.write().unwrap_or_else(|e| e.into_inner());
+    FaultGuard {
+        previous: global.replace(Arc::new(injector)),
+    }
+}
+
+/// Guard returned by `set_fault_injector`, restoring the previous fault injector when dropped
+#[must_use = "faults are only injected until the guard is dropped"]
+pub struct FaultGuard {
+    previous: Option<SharedInjector>,
+}
+
+impl Drop for FaultGuard {
+    fn drop(&mut self) {
+        *GLOBAL.write().unwrap_or_else(|e| e.into_inner()) = self.previous.take();
+    }
+}
+
+/// The delays of the inner delay iterator, altered by a fault injector
+#[derive(Clone)]
+pub struct Faulty<T> {
+    inner: T,
+    injector: Option<SharedInjector>,
+    retries: usize,
+}
+
+impl<T> Faulty<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    /// Alter the delays of the inner delay iterator with the given fault injector, as
+    /// `FaultExt::with_faults` does
+    pub fn new<U>(inner: U, injector: impl FaultInjector + 'static) -> Self
+    where
+        U: IntoIterator<Item = Duration, IntoIter = T>,
+    {
+        Self {
+            inner: inner.into_iter(),
+            injector: Some(Arc::new(injector)),
+            retries: 0,
+        }
+    }
+
+    /// Alter the delays of the inner delay iterator with the fault injector installed by
+    /// `set_fault_injector` when created, if any
+    pub fn global<U>(inner: U) -> Self
+    where
+        U: IntoIterator<Item = Duration, IntoIter = T>,
+    {
+        Self {
+            inner: inner.into_iter(),
+            injector: GLOBAL.read().unwrap_or_else(|e| e.into_inner()).clone(),
+            retries: 0,
+        }
+    }
+}
+
+impl<T> Iterator for Faulty<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let Some(injector) = &self.injector else {
+            return self.inner.next();
+        };
+        if injector.give_up(self.retries) {
+            return None;
+        }
+        self.retries += 1;
+        self.inner.next().map(|delay| injector.stretch(delay))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.inner.size_hint();
+        match self.injector {
+            // an injector may give up at any time
+            Some(_) => (0, upper),
+            None => (lower, upper),
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Faulty<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Faulty")
+            .field("inner", &self.inner)
+            .field("injected", &self.injector.is_some())
+            .field("retries", &self.retries)
+            .finish()
+    }
+}
+
+/// Extension trait applying a `FaultInjector` to any delay iterator
+pub trait FaultExt: Iterator<Item = Duration> + Sized {
+    /// Alter the delays of this iterator with the given fault injector
+    fn with_faults(self, injector: impl FaultInjector + 'static) -> Faulty<Self> {
+        Faulty::new(self, injector)
+    }
+}
+
+impl<T> FaultExt for T where T: Iterator<Item = Duration> {}
+
+#[cfg(test)]
+mod test {
+    use super::{FaultExt, Faults};
+    use crate::delay::Fixed;
+    use std::time::Duration;
+
+    #[test]
+    fn faulty_delays() {
+        let delays: Vec<_> = Fixed::exact(Duration::from_millis(10))
+            .with_faults(Faults::new().give_up_after(2).stretch(3.0))
+            .collect();
+        assert_eq!(delays, vec![Duration::from_millis(30); 2]);
+
+        let delays = Fixed::exact(Duration::from_millis(10)).take(3);
+        assert_eq!(
+            delays.clone().with_faults(Faults::new()).size_hint(),
+            (0, Some(3))
+        );
+        // without any injector installed, the delays are left as they are
+        assert_eq!(super::Faulty::global(delays).size_hint(), (3, Some(3)));
+
+        let mut attempts = 0;
+        let value = crate::retry!(
+            Fixed::exact(Duration::ZERO).with_faults(Faults::new().give_up_after(0)),
+            {
+                attempts += 1;
+                Err::<(), _>(attempts)
+            }
+        );
+        assert_eq!(value, Err(1));
+    }
+}
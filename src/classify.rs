@@ -0,0 +1,166 @@
+use crate::OperationResult;
+use std::error::Error;
+
+type Rule = Box<dyn Fn(&(dyn Error + 'static)) -> bool + Send + Sync>;
+
+/// A composable classifier of errors as retryable or fatal
+///
+/// Rules match errors of a given type anywhere in the chain of `Error::source`s of a classified
+/// error. An error matching any fatal rule is fatal, otherwise one matching any retry rule is
+/// retryable, and any other error is fatal. Classifiers can be built once, combined with
+/// `Classifier::or` and reused across services.
+///
+/// ```
+/// # use retry_block::{Classifier, OperationResult};
+/// # use std::io;
+/// let transient = Classifier::new()
+///     .retry_on::<io::Error>()
+///     .fatal_on(|e: &io::Error| e.kind() == io::ErrorKind::NotFound);
+///
+/// let timeout = io::Error::from(io::ErrorKind::TimedOut);
+/// assert!(transient.is_retryable(&timeout));
+/// assert!(!transient.is_retryable(&io::Error::from(io::ErrorKind::NotFound)));
+///
+/// let res = transient.classify(Err::<(), _>(timeout));
+/// assert!(matches!(res, OperationResult::Retry(_)));
+/// ```
+#[derive(Default)]
+pub struct Classifier {
+    retry: Vec<Rule>,
+    fatal: Vec<Rule>,
+}
+
+impl Classifier {
+    /// Create a classifier deeming every error fatal
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retry errors of the given type, or caused by one
+    pub fn retry_on<T>(self) -> Self
+    where
+        T: Error + 'static,
+    {
+        self.retry_when(|_: &T| true)
+    }
+
+    /// Retry errors of the given type matching the given predicate, or caused by one
+    pub fn retry_when<T, P>(mut self, predicate: P) -> Self
+    where
+        T: Error + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.retry.push(rule(predicate));
+        self
+    }
+
+    /// Give up on errors of the given type matching the given predicate, or caused by one, even
+    /// if they match a retry rule
+    pub fn fatal_on<T, P>(mut self, predicate: P) -> Self
+    where
+        T: Error + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.fatal.push(rule(predicate));
+        self
+    }
+
+    /// Combine the rules of this classifier with those of another one
+    pub fn or(mut self, other: Classifier) -> Self {
+        self.retry.extend(other.retry);
+        self.fatal.extend(other.fatal);
+        self
+    }
+
+    /// Whether the given error should be retried
+    pub fn is_retryable<E>(&self, error: &E) -> bool
+    where
+        E: Error + 'static,
+    {
+        self.is_retryable_dyn(error)
+    }
+
+    /// Whether the given type-erased error (e.g. from a `Box<dyn Error>`) should be retried
+    pub fn is_retryable_dyn(&self, error: &(dyn Error + 'static)) -> bool {
+        let matches = |rules: &[Rule]| rules.iter().any(|rule| rule(error));
+        !matches(&self.fatal) && matches(&self.retry)
+    }
+
+    /// Classify the given result, retrying its error if it is retryable
+    pub fn classify<R, E>(&self, result: Result<R, E>) -> OperationResult<R, E>
+    where
+        E: Error + 'static,
+    {
+        OperationResult::retry_if(result, |e| self.is_retryable(e))
+    }
+}
+
+impl std::fmt::Debug for Classifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Classifier")
+            .field("retry_rules", &self.retry.len())
+            .field("fatal_rules", &self.fatal.len())
+            .finish()
+    }
+}
+
+/// A rule matching errors of the given type matching the given predicate in an error chain
+fn rule<T, P>(predicate: P) -> Rule
+where
+    T: Error + 'static,
+    P: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    Box::new(move |error| {
+        let mut current = Some(error);
+        while let Some(error) = current {
+            if error.downcast_ref::<T>().is_some_and(&predicate) {
+                return true;
+            }
+            current = error.source();
+        }
+        false
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::Classifier;
+    use std::fmt;
+    use std::io;
+
+    #[derive(Debug)]
+    struct Http(u16, Option<io::Error>);
+
+    impl fmt::Display for Http {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "HTTP {}", self.0)
+        }
+    }
+
+    impl std::error::Error for Http {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            self.1.as_ref().map(|e| e as _)
+        }
+    }
+
+    #[test]
+    fn layered_classification() {
+        let http_5xx = Classifier::new().retry_when(|e: &Http| e.0 >= 500);
+        let classifier = Classifier::new()
+            .retry_on::<io::Error>()
+            .or(http_5xx)
+            .fatal_on(|e: &io::Error| e.kind() == io::ErrorKind::PermissionDenied);
+
+        assert!(classifier.is_retryable(&Http(503, None)));
+        assert!(!classifier.is_retryable(&Http(404, None)));
+        // classified through the source chain
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        assert!(classifier.is_retryable(&Http(404, Some(reset))));
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert!(!classifier.is_retryable(&Http(503, Some(denied))));
+
+        let boxed: Box<dyn std::error::Error> = Box::new(Http(500, None));
+        assert!(classifier.is_retryable_dyn(&*boxed));
+        assert!(!Classifier::new().is_retryable(&Http(500, None)));
+    }
+}
@@ -0,0 +1,377 @@
+use crate::delay;
+#[cfg(feature = "config")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A serializable retry configuration for a random range and finite retry count
+///
+/// It is (de)serializable with the `config` feature.
+///
+/// Besides its struct form, it can be parsed (and deserialized) from a compact string spec of the
+/// form `"<count>x <min_backoff>..<max_backoff>"`, where backoffs are integers suffixed with `ms`,
+/// `s`, `m` or `h`:
+///
+/// ```
+/// # use retry_block::RetryConfig;
+/// let config: RetryConfig = "5x 100ms..3s".parse().unwrap();
+/// assert_eq!(config.count, 5);
+/// assert_eq!(config.min_backoff, 100);
+/// assert_eq!(config.max_backoff, 3000);
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "config", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "config", serde(try_from = "RetryConfigRepr"))]
+pub struct RetryConfig {
+    /// how many times will we retry the operation
+    pub count: usize,
+    /// the minimum amount of milliseconds to wait before retrying
+    pub min_backoff: u64,
+    /// the maximum amount of milliseconds to wait before retrying
+    pub max_backoff: u64,
+}
+
+impl IntoIterator for RetryConfig {
+    type Item = Duration;
+    type IntoIter = std::iter::Take<delay::Range>;
+    fn into_iter(self) -> Self::IntoIter {
+        delay::Range::from_millis_inclusive(self.min_backoff, self.max_backoff).take(self.count)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for RetryConfig {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let (a, b): (u64, u64) = u.arbitrary()?;
+        Ok(RetryConfig {
+            count: u.arbitrary()?,
+            min_backoff: a.min(b),
+            max_backoff: a.max(b),
+        })
+    }
+}
+
+impl FromStr for RetryConfig {
+    type Err = ConfigError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConfigError::InvalidSpec(spec.to_owned());
+
+        let (count, backoff) = spec.trim().split_once('x').ok_or_else(invalid)?;
+        let (min_backoff, max_backoff) = backoff.split_once("..").ok_or_else(invalid)?;
+        let config = RetryConfig {
+            count: count.trim().parse().map_err(|_| invalid())?,
+            min_backoff: parse_millis(min_backoff).ok_or_else(invalid)?,
+            max_backoff: parse_millis(max_backoff).ok_or_else(invalid)?,
+        };
+        if config.min_backoff > config.max_backoff {
+            return Err(invalid());
+        }
+        Ok(config)
+    }
+}
+
+impl fmt::Display for RetryConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x {}ms..{}ms",
+            self.count, self.min_backoff, self.max_backoff
+        )
+    }
+}
+
+/// A serializable retry configuration like `RetryConfig`, whose backoffs are `Duration`s rather
+/// than implicit amounts of milliseconds
+///
+/// It is parsed (and deserialized) from the same compact string spec, and its struct form takes
+/// backoffs with an explicit unit, so that a bare number of unknown unit is rejected:
+///
+/// ```
+/// # #[cfg(feature = "config")]
+/// # {
+/// # use retry_block::RetryConfigV2;
+/// # use std::time::Duration;
+/// let config: RetryConfigV2 = serde_json::from_str(
+///     r#"{"count": 5, "min_backoff": "100ms", "max_backoff": "3s"}"#,
+/// ).unwrap();
+/// assert_eq!(config.max_backoff, Duration::from_secs(3));
+/// assert!(serde_json::from_str::<RetryConfigV2>(
+///     r#"{"count": 5, "min_backoff": 100, "max_backoff": 3000}"#,
+/// ).is_err());
+/// # }
+/// ```
+///
+/// Backoffs are serialized in whole milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "config", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "config",
+    serde(try_from = "RetryConfigV2Repr", into = "RetryConfigV2Repr")
+)]
+pub struct RetryConfigV2 {
+    /// how many times will we retry the operation
+    pub count: usize,
+    /// the minimum amount of time to wait before retrying
+    pub min_backoff: Duration,
+    /// the maximum amount of time to wait before retrying
+    pub max_backoff: Duration,
+}
+
+impl IntoIterator for RetryConfigV2 {
+    type Item = Duration;
+    type IntoIter = std::iter::Take<delay::Range>;
+    fn into_iter(self) -> Self::IntoIter {
+        RetryConfig::from(self).into_iter()
+    }
+}
+
+impl From<RetryConfig> for RetryConfigV2 {
+    fn from(config: RetryConfig) -> Self {
+        Self {
+            count: config.count,
+            min_backoff: Duration::from_millis(config.min_backoff),
+            max_backoff: Duration::from_millis(config.max_backoff),
+        }
+    }
+}
+
+impl From<RetryConfigV2> for RetryConfig {
+    /// Convert the backoffs into whole milliseconds, truncating any remainder
+    fn from(config: RetryConfigV2) -> Self {
+        let millis = |duration: Duration| duration.as_millis().try_into().unwrap_or(u64::MAX);
+        Self {
+            count: config.count,
+            min_backoff: millis(config.min_backoff),
+            max_backoff: millis(config.max_backoff),
+        }
+    }
+}
+
+impl FromStr for RetryConfigV2 {
+    type Err = ConfigError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        spec.parse::<RetryConfig>().map(Self::from)
+    }
+}
+
+impl fmt::Display for RetryConfigV2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        RetryConfig::from(*self).fmt(f)
+    }
+}
+
+/// Parse a duration such as `"100ms"` or `"3s"` into milliseconds
+fn parse_millis(duration: &str) -> Option<u64> {
+    let duration = duration.trim();
+    let unit_start = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (value, unit) = duration.split_at(unit_start);
+    let factor = match unit {
+        "ms" => 1,
+        "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        _ => return None,
+    };
+    value.parse::<u64>().ok()?.checked_mul(factor)
+}
+
+#[cfg(feature = "config")]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RetryConfigRepr {
+    Spec(String),
+    Struct {
+        count: usize,
+        min_backoff: u64,
+        max_backoff: u64,
+    },
+}
+
+#[cfg(feature = "config")]
+impl TryFrom<RetryConfigRepr> for RetryConfig {
+    type Error = ConfigError;
+
+    fn try_from(repr: RetryConfigRepr) -> Result<Self, Self::Error> {
+        match repr {
+            RetryConfigRepr::Spec(spec) => spec.parse(),
+            RetryConfigRepr::Struct {
+                count,
+                min_backoff,
+                max_backoff,
+            } => format!("{count}x {min_backoff}ms..{max_backoff}ms").parse(),
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RetryConfigV2Repr {
+    Spec(String),
+    Struct {
+        count: usize,
+        min_backoff: String,
+        max_backoff: String,
+    },
+}
+
+#[cfg(feature = "config")]
+impl From<RetryConfigV2> for RetryConfigV2Repr {
+    fn from(config: RetryConfigV2) -> Self {
+        let config = RetryConfig::from(config);
+        Self::Struct {
+            count: config.count,
+            min_backoff: format!("{}ms", config.min_backoff),
+            max_backoff: format!("{}ms", config.max_backoff),
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+impl TryFrom<RetryConfigV2Repr> for RetryConfigV2 {
+    type Error = ConfigError;
+
+    fn try_from(repr: RetryConfigV2Repr) -> Result<Self, Self::Error> {
+        match repr {
+            RetryConfigV2Repr::Spec(spec) => spec.parse(),
+            RetryConfigV2Repr::Struct {
+                count,
+                min_backoff,
+                max_backoff,
+            } => format!("{count}x {min_backoff}..{max_backoff}").parse(),
+        }
+    }
+}
+
+/// An error in a retry configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The string spec could not be parsed
+    InvalidSpec(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSpec(spec) => write!(
+                f,
+                "invalid retry config `{}`, expected e.g. `5x 100ms..300ms`",
+                spec
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod test {
+    use crate::{ConfigError, RetryConfig, RetryConfigV2};
+    use std::time::Duration;
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_configs() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let bytes: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        let mut u = Unstructured::new(&bytes);
+        while !u.is_empty() {
+            let config = RetryConfig::arbitrary(&mut u).unwrap();
+            assert!(config.min_backoff <= config.max_backoff);
+            let max_total = config.max_backoff as u128 * config.count.min(8) as u128;
+            let total: u128 = config.into_iter().take(8).map(|d| d.as_millis()).sum();
+            assert!(total <= max_total);
+        }
+    }
+
+    #[test]
+    fn parse_spec() {
+        let config: RetryConfig = " 5x 100ms .. 1m ".parse().unwrap();
+        assert_eq!(config.count, 5);
+        assert_eq!(config.min_backoff, 100);
+        assert_eq!(config.max_backoff, 60_000);
+        assert_eq!(config.to_string(), "5x 100ms..60000ms");
+        assert_eq!(
+            config
+                .to_string()
+                .parse::<RetryConfig>()
+                .unwrap()
+                .max_backoff,
+            60_000
+        );
+
+        for spec in [
+            "5 100ms..300ms",
+            "5x 100..300ms",
+            "5x 300ms..100ms",
+            "x 1s..2s",
+        ] {
+            assert_eq!(
+                spec.parse::<RetryConfig>().unwrap_err(),
+                ConfigError::InvalidSpec(spec.to_owned())
+            );
+        }
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn deserialize_spec_or_struct() {
+        let config: RetryConfig = serde_json::from_str(r#""3x 1s..2s""#).unwrap();
+        assert_eq!(
+            (config.count, config.min_backoff, config.max_backoff),
+            (3, 1000, 2000)
+        );
+
+        let config: RetryConfig =
+            serde_json::from_str(r#"{"count": 3, "min_backoff": 10, "max_backoff": 20}"#).unwrap();
+        assert_eq!(
+            (config.count, config.min_backoff, config.max_backoff),
+            (3, 10, 20)
+        );
+
+        assert!(serde_json::from_str::<RetryConfig>(r#""3 times""#).is_err());
+        // an inverted range is rejected in both forms, rather than panicking once iterated
+        assert!(serde_json::from_str::<RetryConfig>(
+            r#"{"count": 3, "min_backoff": 20, "max_backoff": 10}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn duration_config() {
+        let config: RetryConfigV2 = "3x 1s..1m".parse().unwrap();
+        assert_eq!(config.min_backoff, Duration::from_secs(1));
+        assert_eq!(config.max_backoff, Duration::from_secs(60));
+        assert_eq!(RetryConfig::from(config).max_backoff, 60_000);
+        assert_eq!(config.into_iter().count(), 3);
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn serialize_duration_config() {
+        let config: RetryConfigV2 = "3x 1s..1m".parse().unwrap();
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            json,
+            r#"{"count":3,"min_backoff":"1000ms","max_backoff":"60000ms"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<RetryConfigV2>(&json).unwrap(),
+            config
+        );
+        assert_eq!(
+            serde_json::from_str::<RetryConfigV2>(r#""3x 1s..1m""#).unwrap(),
+            config
+        );
+        assert!(serde_json::from_str::<RetryConfigV2>(
+            r#"{"count": 3, "min_backoff": "2s", "max_backoff": "1s"}"#
+        )
+        .is_err());
+    }
+}
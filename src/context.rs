@@ -0,0 +1,266 @@
+use crate::{DelayAccounting, OperationResult};
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+/// The state of a retried operation, given to each of its attempts
+#[derive(Debug)]
+pub struct RetryContext<E> {
+    attempt: usize,
+    start: Instant,
+    previous_error: Option<E>,
+    remaining_retries: Option<usize>,
+}
+
+impl<E> RetryContext<E> {
+    pub(crate) fn new(remaining_retries: Option<usize>) -> Self {
+        Self {
+            attempt: 1,
+            start: Instant::now(),
+            previous_error: None,
+            remaining_retries,
+        }
+    }
+
+    pub(crate) fn next_attempt(&mut self, error: E, remaining_retries: Option<usize>) {
+        self.attempt += 1;
+        self.previous_error = Some(error);
+        self.remaining_retries = remaining_retries;
+    }
+
+    /// The number of the current attempt, counted from 1
+    pub fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    /// The time elapsed since the start of the first attempt
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// The error of the previous attempt, if any
+    pub fn previous_error(&self) -> Option<&E> {
+        self.previous_error.as_ref()
+    }
+
+    /// The number of retries left after the current attempt, if the delay iterator knows it
+    pub fn remaining_retries(&self) -> Option<usize> {
+        self.remaining_retries
+    }
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// giving each attempt a `RetryContext`.
+///
+/// ```
+/// # use retry_block::retry_fn_with_context;
+/// # use retry_block::delay::NoDelay;
+/// let value = retry_fn_with_context(NoDelay.take(2), |context| {
+///     if context.attempt() < 3 {
+///         Err(context.attempt())
+///     } else {
+///         Ok(context.previous_error().copied())
+///     }
+/// });
+/// assert_eq!(value, Ok(Some(2)));
+/// ```
+pub fn retry_fn_with_context<D, O, OR, R, E>(durations: D, mut operation: O) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut(&RetryContext<E>) -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut it = crate::delay::__faulty(durations.into_iter());
+    let context = RefCell::new(RetryContext::new(it.size_hint().1));
+    crate::retry_loop(
+        |e| match it.next() {
+            Some(duration) => {
+                context.borrow_mut().next_attempt(e, it.size_hint().1);
+                Ok(duration)
+            }
+            None => Err(e),
+        },
+        crate::sleep::thread_sleep,
+        || operation(&context.borrow()).into(),
+    )
+}
+
+/// The final error of a named retried operation, along with how it was retried
+///
+/// Returned by `retry_fn_named`, `async_retry_fn_named` and the named arms of the retry macros.
+/// With the `tracing` feature, a `WARN` event with the same metadata is emitted whenever one is
+/// returned, and with the `metrics` feature, the `retry_gave_up_total` counter labeled with the
+/// operation is incremented.
+#[derive(Debug)]
+pub struct WithRetryContext<E> {
+    error: E,
+    operation: &'static str,
+    attempts: usize,
+    total_delay: Duration,
+}
+
+impl<E> WithRetryContext<E> {
+    /// Attach the given operation name, number of attempts and total delay to an error, reporting
+    /// that the operation gave up
+    pub(crate) fn new(
+        error: E,
+        operation: &'static str,
+        attempts: usize,
+        total_delay: Duration,
+    ) -> Self {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            operation,
+            attempts,
+            total_delay_ms = total_delay.as_millis() as u64,
+            "gave up retrying"
+        );
+        #[cfg(feature = "metrics")]
+        metrics::counter!("retry_gave_up_total", "operation" => operation).increment(1);
+        Self {
+            error,
+            operation,
+            attempts,
+            total_delay,
+        }
+    }
+
+    /// The name of the retried operation
+    pub fn operation(&self) -> &'static str {
+        self.operation
+    }
+
+    /// The number of attempts made, including the first one
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// The total time slept between attempts
+    pub fn total_delay(&self) -> Duration {
+        self.total_delay
+    }
+
+    /// The error of the last attempt
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Return the error of the last attempt
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+/// Used by the named arms of the retry macros to report that the operation gave up
+#[doc(hidden)]
+pub fn __with_retry_context<E>(
+    error: E,
+    operation: &'static str,
+    attempts: usize,
+    total_delay: Duration,
+) -> WithRetryContext<E> {
+    WithRetryContext::new(error, operation, attempts, total_delay)
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for WithRetryContext<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} failed after {} attempt(s) and {:?} of delay: {}",
+            self.operation, self.attempts, self.total_delay, self.error
+        )
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for WithRetryContext<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// attaching its name, number of attempts and total delay to the final error.
+///
+/// The delays are accounted in `DelayAccounting::named`, as with the named arms of the retry
+/// macros.
+///
+/// ```
+/// # use retry_block::retry_fn_named;
+/// # use retry_block::delay::NoDelay;
+/// let error = retry_fn_named("fetch", NoDelay.take(2), || Err::<(), _>("unreachable"))
+///     .unwrap_err();
+/// assert_eq!(error.attempts(), 3);
+/// assert_eq!(error.to_string(), "fetch failed after 3 attempt(s) and 0ns of delay: unreachable");
+/// ```
+pub fn retry_fn_named<D, O, OR, R, E>(
+    operation_name: &'static str,
+    durations: D,
+    mut operation: O,
+) -> Result<R, WithRetryContext<E>>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut it =
+        DelayAccounting::named(operation_name).track(crate::delay::__faulty(durations.into_iter()));
+    let res = crate::retry_loop(
+        |e| it.next().ok_or(e),
+        crate::sleep::thread_sleep,
+        || operation().into(),
+    );
+    res.map_err(|e| {
+        let stats = it.stats();
+        WithRetryContext::new(
+            e,
+            operation_name,
+            stats.attempts as usize,
+            stats.total_sleep,
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delay::NoDelay;
+    use crate::{retry_fn_named, retry_fn_with_context, DelayAccounting};
+    use std::time::Duration;
+
+    #[test]
+    fn context_attempts() {
+        let mut seen = Vec::new();
+        let value = retry_fn_with_context(NoDelay.take(2), |context| {
+            seen.push((
+                context.attempt(),
+                context.previous_error().copied(),
+                context.remaining_retries(),
+            ));
+            Err::<(), _>(context.attempt())
+        });
+        assert_eq!(value, Err(3));
+        assert_eq!(
+            seen,
+            vec![
+                (1, None, Some(2)),
+                (2, Some(1), Some(1)),
+                (3, Some(2), Some(0))
+            ]
+        );
+    }
+
+    #[test]
+    fn named_error() {
+        let error = retry_fn_named(
+            "named_error",
+            [Duration::from_millis(1), Duration::from_millis(2)],
+            || Err::<(), _>("nope"),
+        )
+        .unwrap_err();
+        assert_eq!(error.operation(), "named_error");
+        assert_eq!(error.attempts(), 3);
+        assert_eq!(error.total_delay(), Duration::from_millis(3));
+        assert_eq!(error.into_inner(), "nope");
+        let stats = DelayAccounting::named("named_error").stats();
+        assert_eq!((stats.calls, stats.attempts), (1, 3));
+        assert_eq!(stats.total_sleep, Duration::from_millis(3));
+    }
+}
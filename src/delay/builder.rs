@@ -0,0 +1,151 @@
+use super::{BoxedDelay, Capped, Exponential, Fibonacci, Fixed, IntoBoxed};
+#[cfg(any(feature = "random", feature = "fastrand"))]
+use super::{Jitter, JitterExt};
+use std::time::Duration;
+
+/// A builder of delay strategies where jitter is explicitly requested and applied to each delay
+///
+/// Unlike `Fixed::new` and `Exponential::new`, which randomize the initial delay once at
+/// construction (so that a `Fixed` yields the *same* random delay forever), the strategies built
+/// here are exact unless a `Jitter` policy is set, which then applies to every delay yielded.
+///
+/// ```
+/// # use retry_block::delay::DelayBuilder;
+/// # use std::time::Duration;
+/// let delays: Vec<_> = DelayBuilder::exponential(Duration::from_millis(100))
+///     .factor(2.0)
+///     .max_delay(Duration::from_millis(300))
+///     .max_retries(4)
+///     .build()
+///     .collect();
+/// assert_eq!(delays, [100, 200, 300, 300].map(Duration::from_millis));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DelayBuilder {
+    base: Base,
+    max_delay: Option<Duration>,
+    max_retries: Option<usize>,
+    name: Option<&'static str>,
+    #[cfg(any(feature = "random", feature = "fastrand"))]
+    jitter: Jitter,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Base {
+    Fixed(Duration),
+    Exponential(Duration, f64),
+    Fibonacci(Duration),
+}
+
+impl DelayBuilder {
+    fn with_base(base: Base) -> Self {
+        Self {
+            base,
+            max_delay: None,
+            max_retries: None,
+            name: None,
+            #[cfg(any(feature = "random", feature = "fastrand"))]
+            jitter: Jitter::None,
+        }
+    }
+
+    /// Starts building a strategy retrying after the given delay every time.
+    pub fn fixed(duration: Duration) -> Self {
+        Self::with_base(Base::Fixed(duration))
+    }
+
+    /// Starts building a strategy whose delays start at the given duration and are multiplied by
+    /// 2 on each retry, unless changed by `DelayBuilder::factor`.
+    pub fn exponential(initial: Duration) -> Self {
+        Self::with_base(Base::Exponential(initial, 2.0))
+    }
+
+    /// Starts building a strategy whose delays are the sum of the two previous ones, starting at
+    /// the given duration.
+    pub fn fibonacci(initial: Duration) -> Self {
+        Self::with_base(Base::Fibonacci(initial))
+    }
+
+    /// Sets the multiplication factor of an exponential strategy, and has no effect otherwise.
+    pub fn factor(mut self, factor: f64) -> Self {
+        if let Base::Exponential(_, ref mut current) = self.base {
+            *current = factor;
+        }
+        self
+    }
+
+    /// Caps each delay to `max`, after jitter is applied.
+    pub fn max_delay(mut self, max: Duration) -> Self {
+        self.max_delay = Some(max);
+        self
+    }
+
+    /// Limits the number of retries, i.e. of delays yielded.
+    pub fn max_retries(mut self, count: usize) -> Self {
+        self.max_retries = Some(count);
+        self
+    }
+
+    /// Names the retried operation, so that the delays of every strategy built with this name are
+    /// accounted together in `DelayAccounting::named`, as with the named arms of the retry macros.
+    ///
+    /// ```
+    /// # use retry_block::delay::DelayBuilder;
+    /// # use retry_block::DelayAccounting;
+    /// # use std::time::Duration;
+    /// let delays = DelayBuilder::fixed(Duration::ZERO)
+    ///     .max_retries(2)
+    ///     .name("refresh_token")
+    ///     .build();
+    /// let value = retry_block::retry_fn(delays, || Err::<(), _>("unavailable"));
+    /// assert_eq!(value, Err("unavailable"));
+    /// assert_eq!(DelayAccounting::named("refresh_token").stats().attempts, 3);
+    /// ```
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Applies the given jitter policy to each delay yielded. (need `random` feature)
+    ///
+    /// ```
+    /// # use retry_block::delay::{DelayBuilder, Jitter};
+    /// # use std::time::Duration;
+    /// let mut jittered = DelayBuilder::fixed(Duration::from_secs(1))
+    ///     .jitter(Jitter::Full)
+    ///     .build();
+    /// assert!(jittered.next().unwrap() <= Duration::from_secs(1));
+    /// ```
+    #[cfg(any(feature = "random", feature = "fastrand"))]
+    pub fn jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Builds the delay strategy.
+    pub fn build(self) -> BoxedDelay {
+        let delays = match self.base {
+            Base::Fixed(duration) => Fixed::exact(duration).into_boxed(),
+            Base::Exponential(initial, factor) => {
+                Exponential::exact_with_factor(initial, factor).into_boxed()
+            }
+            Base::Fibonacci(initial) => Fibonacci::exact(initial).into_boxed(),
+        };
+        #[cfg(any(feature = "random", feature = "fastrand"))]
+        let delays = delays.with_jitter(self.jitter).into_boxed();
+        let delays = match self.max_delay {
+            Some(max) => Capped::new(delays, max).into_boxed(),
+            None => delays,
+        };
+        let delays = match self.max_retries {
+            Some(count) => delays.take(count).into_boxed(),
+            None => delays,
+        };
+        match self.name {
+            Some(name) => crate::DelayAccounting::named(name)
+                .track(delays)
+                .into_boxed(),
+            None => delays,
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Conversions from the delay strategies of other retry crates
+
+use super::Exponential;
+#[cfg(feature = "backoff")]
+use super::{Capped, MaxElapsed};
+#[cfg(feature = "retry-compat")]
+use super::{Fixed, NoDelay};
+#[cfg(feature = "backoff")]
+use std::time::Duration;
+
+/// Keeps the initial interval, multiplier, maximum interval and maximum elapsed time of a
+/// `backoff::ExponentialBackoff`. The elapsed time is wall-clock time, including the time spent in
+/// attempts, but measured from the first retry delay rather than from the creation of the
+/// backoff. Its randomization factor is not carried over: apply `jitter` to the resulting delays
+/// instead.
+#[cfg(feature = "backoff")]
+impl From<backoff::ExponentialBackoff> for MaxElapsed<Capped<Exponential>> {
+    fn from(backoff: backoff::ExponentialBackoff) -> Self {
+        MaxElapsed::new(
+            Exponential::exact_with_factor(backoff.initial_interval, backoff.multiplier)
+                .max_delay(backoff.max_interval),
+            backoff.max_elapsed_time.unwrap_or(Duration::MAX),
+        )
+    }
+}
+
+/// Keeps the initial delay and multiplication factor of a `retry::delay::Exponential`, which are
+/// observed by taking its first two delays.
+#[cfg(feature = "retry-compat")]
+impl From<retry::delay::Exponential> for Exponential {
+    fn from(mut delays: retry::delay::Exponential) -> Self {
+        let first = delays.next().unwrap_or_default();
+        let second = delays.next().unwrap_or_default();
+        let factor = if first.is_zero() {
+            1.0
+        } else {
+            second.as_secs_f64() / first.as_secs_f64()
+        };
+        Exponential::exact_with_factor(first, factor)
+    }
+}
+
+#[cfg(feature = "retry-compat")]
+impl From<retry::delay::Fixed> for Fixed {
+    fn from(mut delays: retry::delay::Fixed) -> Self {
+        Fixed::exact(delays.next().unwrap_or_default())
+    }
+}
+
+#[cfg(feature = "retry-compat")]
+impl From<retry::delay::NoDelay> for NoDelay {
+    fn from(_: retry::delay::NoDelay) -> Self {
+        NoDelay
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    #[cfg(feature = "backoff")]
+    #[test]
+    fn from_backoff() {
+        use crate::delay::{Capped, Exponential, MaxElapsed};
+
+        let backoff = backoff::ExponentialBackoff {
+            initial_interval: Duration::from_secs(1),
+            multiplier: 2.0,
+            max_interval: Duration::from_secs(3),
+            max_elapsed_time: None,
+            ..Default::default()
+        };
+        let delays: MaxElapsed<Capped<Exponential>> = backoff.into();
+        assert_eq!(
+            delays.take(5).map(|d| d.as_secs()).collect::<Vec<_>>(),
+            vec![1, 2, 3, 3, 3]
+        );
+
+        // no time was slept, so that every delay ending within the elapsed time is yielded, as
+        // opposed to `Bounded` which would stop once their sum exceeds it
+        let backoff = backoff::ExponentialBackoff {
+            initial_interval: Duration::from_secs(1),
+            multiplier: 2.0,
+            max_interval: Duration::from_secs(3),
+            max_elapsed_time: Some(Duration::from_millis(2500)),
+            ..Default::default()
+        };
+        let delays: MaxElapsed<Capped<Exponential>> = backoff.into();
+        assert_eq!(delays.map(|d| d.as_secs()).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[cfg(feature = "retry-compat")]
+    #[test]
+    fn from_retry() {
+        use crate::delay::{Exponential, Fixed};
+
+        let delays: Exponential = retry::delay::Exponential::from_millis(10).into();
+        assert_eq!(
+            delays.take(3).map(|d| d.as_millis()).collect::<Vec<_>>(),
+            vec![10, 20, 40]
+        );
+
+        let mut delays: Fixed = retry::delay::Fixed::from_millis(10).into();
+        assert_eq!(delays.next(), Some(Duration::from_millis(10)));
+    }
+}
@@ -1,17 +1,162 @@
 //! Different types of delay for retryable operations.
 
-use std::time::Duration;
+use std::iter::FusedIterator;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
+mod builder;
+#[cfg(any(feature = "backoff", feature = "retry-compat"))]
+mod compat;
+mod observed;
+#[cfg(any(feature = "random", feature = "fastrand"))]
 mod random;
+pub mod registry;
+mod simulate;
 
-pub use random::{jitter, jitter_rng, Range};
+pub use builder::DelayBuilder;
+pub use observed::{LatencyObserver, Observed};
+#[cfg(feature = "random")]
+pub use random::jitter_rng;
+#[cfg(any(feature = "random", feature = "fastrand"))]
+pub use random::{jitter, seed, Jitter, JitterExt, Jittered, Range, SeedGuard, Shuffled};
+#[cfg(any(feature = "random", feature = "fastrand"))]
+pub use simulate::simulate_seeded;
+pub use simulate::{simulate, Simulation};
+
+/// An error in the parameters of a delay strategy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DelayError {
+    /// The minimum of a range of delays is greater than its maximum.
+    InvalidRange { min_millis: u64, max_millis: u64 },
+    /// No strategy is registered under this name.
+    UnknownStrategy(String),
+    /// This parameter of the strategy is not set.
+    MissingParameter(String),
+    /// This parameter of the strategy could not be parsed.
+    InvalidParameter { name: String, value: String },
+}
+
+impl std::fmt::Display for DelayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DelayError::InvalidRange {
+                min_millis,
+                max_millis,
+            } => write!(
+                f,
+                "invalid range of delays {}ms..{}ms",
+                min_millis, max_millis
+            ),
+            DelayError::UnknownStrategy(name) => write!(f, "unknown delay strategy {:?}", name),
+            DelayError::MissingParameter(name) => {
+                write!(f, "missing delay strategy parameter {:?}", name)
+            }
+            DelayError::InvalidParameter { name, value } => {
+                write!(f, "invalid delay strategy parameter {}={:?}", name, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DelayError {}
+
+/// The given delays, altered by the global fault injector if any.
+#[doc(hidden)]
+#[cfg(feature = "chaos")]
+pub fn __faulty<T>(delays: T) -> crate::chaos::Faulty<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    crate::chaos::Faulty::global(delays)
+}
+
+/// The given delays, as no fault injection is possible without the `chaos` feature.
+#[doc(hidden)]
+#[cfg(not(feature = "chaos"))]
+pub fn __faulty<T>(delays: T) -> T
+where
+    T: Iterator<Item = Duration>,
+{
+    delays
+}
+
+/// The delays returned by `__faulty`, to be stored in a struct.
+#[cfg(feature = "chaos")]
+pub(crate) type Faultable<T> = crate::chaos::Faulty<T>;
+
+/// The delays returned by `__faulty`, to be stored in a struct.
+#[cfg(not(feature = "chaos"))]
+pub(crate) type Faultable<T> = T;
+
+/// The default delays of the perpetual retry macros, only jittered with a random feature.
+#[doc(hidden)]
+pub fn __perpetual() -> Bounded<Exponential> {
+    #[cfg(any(feature = "random", feature = "fastrand"))]
+    let delays = Exponential::new(Duration::from_millis(100));
+    #[cfg(not(any(feature = "random", feature = "fastrand")))]
+    let delays = Exponential::exact(Duration::from_millis(100));
+    delays.bounded(Duration::from_secs(3600))
+}
+
+/// Apply full jitter to a duration, derived from a stable hash of the given key (e.g. a client id)
+/// rather than drawn at random.
+///
+/// A given key always gets the same proportion of the duration, so that the timing of a client
+/// can be reproduced when debugging it, while different keys are spread as evenly as with random
+/// jitter.
+///
+/// ```
+/// # use retry_block::delay::{jitter_keyed, Exponential};
+/// # use std::time::Duration;
+/// let client_id = 42;
+/// let delays: Vec<_> = Exponential::exact_with_factor(Duration::from_millis(100), 2.0)
+///     .take(3)
+///     .map(|delay| jitter_keyed(delay, client_id))
+///     .collect();
+/// assert_eq!(delays[0], jitter_keyed(Duration::from_millis(100), client_id));
+/// assert!(delays[0] < Duration::from_millis(100));
+/// ```
+pub fn jitter_keyed(duration: Duration, key: u64) -> Duration {
+    // the splitmix64 finalizer, whose output is well spread even for consecutive keys
+    let mut hash = key.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^= hash >> 31;
+    // the 53 high bits, as a proportion in [0, 1)
+    duration.mul_f64((hash >> 11) as f64 / (1u64 << 53) as f64)
+}
+
+/// A type-erased delay strategy, for use across `dyn` boundaries.
+pub type BoxedDelay = Box<dyn Iterator<Item = Duration> + Send>;
+
+/// Type-erasure for any delay strategy.
+pub trait IntoBoxed {
+    /// Boxes this delay strategy into a `BoxedDelay`.
+    fn into_boxed(self) -> BoxedDelay;
+}
+
+impl<T> IntoBoxed for T
+where
+    T: IntoIterator<Item = Duration>,
+    T::IntoIter: Send + 'static,
+{
+    fn into_boxed(self) -> BoxedDelay {
+        Box::new(self.into_iter())
+    }
+}
 
 /// The sum of cumulative retry delays is bounded by some finite amount.
+///
+/// Once a delay would exceed the bound, no more delays are yielded. Since how many delays fit is
+/// only known as they come, the size hint has no lower bound and the upper bound of the inner
+/// iterator.
 #[derive(Debug)]
 pub struct Bounded<T> {
     inner: T,
     acc: Duration,
     max: Duration,
+    exceeded: bool,
 }
 
 impl<T> Bounded<T>
@@ -26,6 +171,7 @@ where
             inner: inner.into_iter(),
             max,
             acc: Default::default(),
+            exceeded: false,
         }
     }
 }
@@ -37,19 +183,497 @@ where
     type Item = Duration;
 
     fn next(&mut self) -> Option<Duration> {
-        self.inner.next().filter(|next| {
-            if let Some(acc) = self.acc.checked_add(*next) {
+        if self.exceeded {
+            return None;
+        }
+        let next = self.inner.next()?;
+        match self.acc.checked_add(next) {
+            Some(acc) if acc <= self.max => {
                 self.acc = acc;
-                self.acc <= self.max
-            } else {
-                false
+                Some(next)
+            }
+            _ => {
+                self.exceeded = true;
+                None
             }
-        })
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.exceeded {
+            (0, Some(0))
+        } else {
+            (0, self.inner.size_hint().1)
+        }
     }
 }
 
-/// Each retry increases the delay since the last exponentially.
+impl<T> FusedIterator for Bounded<T> where T: FusedIterator<Item = Duration> {}
+
+/// A source of the current time for time-based delay combinators
+///
+/// Implemented by `SystemClock`, the sources of `retry_block::time` and by any `Fn() -> Instant`,
+/// so that tests and simulations can drive these combinators with a fake clock instead of real time.
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Returns the current wall-clock time, moving along with `now`.
+    ///
+    /// Used where the time is persisted, e.g. for the statuses of `persist::RetryHandle`. Defaults
+    /// to `SystemTime::now`.
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// The clock of `Instant::now`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> Instant,
+{
+    fn now(&self) -> Instant {
+        self()
+    }
+}
+
+/// The time elapsed since the first retry delay is bounded by some finite amount, as measured by
+/// a `Clock`.
+///
+/// Unlike `Bounded`, which sums the delays themselves, this also accounts for the time spent in
+/// the attempts: no delay that would end past the bound is yielded, nor any after it. As with
+/// `Bounded`, the size hint has no lower bound and the upper bound of the inner iterator.
+#[derive(Debug, Clone)]
+pub struct MaxElapsed<T, C = SystemClock> {
+    inner: T,
+    max: Duration,
+    clock: C,
+    start: Option<Instant>,
+    exceeded: bool,
+}
+
+impl<T> MaxElapsed<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    pub fn new<U>(inner: U, max: Duration) -> Self
+    where
+        U: IntoIterator<Item = Duration, IntoIter = T>,
+    {
+        Self::with_clock(inner, max, SystemClock)
+    }
+}
+
+impl<T, C> MaxElapsed<T, C>
+where
+    T: Iterator<Item = Duration>,
+    C: Clock,
+{
+    /// Creates a new `MaxElapsed` measuring time with the given clock.
+    pub fn with_clock<U>(inner: U, max: Duration, clock: C) -> Self
+    where
+        U: IntoIterator<Item = Duration, IntoIter = T>,
+    {
+        Self {
+            inner: inner.into_iter(),
+            max,
+            clock,
+            start: None,
+            exceeded: false,
+        }
+    }
+}
+
+impl<T, C> Iterator for MaxElapsed<T, C>
+where
+    T: Iterator<Item = Duration>,
+    C: Clock,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        if self.exceeded {
+            return None;
+        }
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(*self.start.get_or_insert(now));
+        let next = self.inner.next()?;
+        if elapsed.checked_add(next).is_some_and(|end| end <= self.max) {
+            Some(next)
+        } else {
+            self.exceeded = true;
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.exceeded {
+            (0, Some(0))
+        } else {
+            (0, self.inner.size_hint().1)
+        }
+    }
+}
+
+impl<T, C> FusedIterator for MaxElapsed<T, C>
+where
+    T: FusedIterator<Item = Duration>,
+    C: Clock,
+{
+}
+
+#[test]
+fn bounded_fused() {
+    let delays = [3, 1, 1].map(Duration::from_secs);
+    let mut bounded = Bounded::new(delays, Duration::from_secs(2));
+    assert_eq!(bounded.size_hint(), (0, Some(3)));
+    assert_eq!(bounded.next(), None);
+    // smaller delays after the bound was exceeded are not yielded either
+    assert_eq!(bounded.next(), None);
+    assert_eq!(bounded.size_hint(), (0, Some(0)));
+    assert_eq!(
+        Fixed::exact(Duration::ZERO).take(2).size_hint(),
+        (2, Some(2))
+    );
+}
+
+#[test]
+fn max_elapsed_with_clock() {
+    use std::cell::Cell;
+
+    let start = Instant::now();
+    let now = Cell::new(start);
+    let mut delays = MaxElapsed::with_clock(
+        Fixed::exact(Duration::from_secs(1)),
+        Duration::from_secs(5),
+        || now.get(),
+    );
+    assert_eq!(delays.next(), Some(Duration::from_secs(1)));
+    now.set(start + Duration::from_secs(3));
+    assert_eq!(delays.next(), Some(Duration::from_secs(1)));
+    now.set(start + Duration::from_secs(4) + Duration::from_millis(1));
+    assert_eq!(delays.next(), None);
+}
+
+/// Each retry delay is capped to some finite amount.
+#[derive(Debug, Clone)]
+pub struct Capped<T> {
+    inner: T,
+    max: Duration,
+}
+
+impl<T> Capped<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    pub fn new<U>(inner: U, max: Duration) -> Self
+    where
+        U: IntoIterator<Item = Duration, IntoIter = T>,
+    {
+        Self {
+            inner: inner.into_iter(),
+            max,
+        }
+    }
+}
+
+impl<T> Iterator for Capped<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        self.inner.next().map(|next| next.min(self.max))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> FusedIterator for Capped<T> where T: FusedIterator<Item = Duration> {}
+
+/// The number of attempts is limited to some finite amount, i.e. one more than the number of
+/// delays yielded.
+#[derive(Debug, Clone)]
+pub struct MaxAttempts<T> {
+    inner: T,
+    max_attempts: usize,
+    remaining: usize,
+}
+
+impl<T> MaxAttempts<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    /// Creates a new `MaxAttempts` allowing at most `max_attempts` attempts, including the first.
+    pub fn new<U>(inner: U, max_attempts: usize) -> Self
+    where
+        U: IntoIterator<Item = Duration, IntoIter = T>,
+    {
+        Self {
+            inner: inner.into_iter(),
+            max_attempts,
+            remaining: max_attempts.saturating_sub(1),
+        }
+    }
+
+    /// The maximum number of attempts, including the first.
+    pub fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+}
+
+impl<T> Iterator for MaxAttempts<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        self.remaining = self.remaining.checked_sub(1)?;
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.inner.size_hint();
+        let upper = upper.map_or(self.remaining, |upper| upper.min(self.remaining));
+        (lower.min(self.remaining), Some(upper))
+    }
+}
+
+impl<T> FusedIterator for MaxAttempts<T> where T: FusedIterator<Item = Duration> {}
+
+/// Exactly some number of attempts are made, i.e. one less delays are yielded, which makes the
+/// number of attempts left known at any point.
+///
+/// Should the inner delays run out early, their last one is repeated, or no delay at all is waited
+/// if there were none.
+///
+/// ```
+/// # use retry_block::delay::{ExactAttempts, Schedule};
+/// # use std::time::Duration;
+/// let mut delays = ExactAttempts::new(Schedule::from_millis([10]), 3);
+/// assert_eq!(delays.remaining(), 2);
+/// assert_eq!(delays.next(), Some(Duration::from_millis(10)));
+/// assert_eq!(delays.next(), Some(Duration::from_millis(10)));
+/// assert_eq!(delays.remaining(), 0);
+/// assert_eq!(delays.next(), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExactAttempts<T> {
+    inner: T,
+    last: Duration,
+    remaining: usize,
+}
+
+impl<T> ExactAttempts<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    /// Creates a new `ExactAttempts` making `attempts` attempts, including the first.
+    pub fn new<U>(inner: U, attempts: usize) -> Self
+    where
+        U: IntoIterator<Item = Duration, IntoIter = T>,
+    {
+        Self {
+            inner: inner.into_iter(),
+            last: Duration::ZERO,
+            remaining: attempts.saturating_sub(1),
+        }
+    }
+
+    /// The number of attempts left after the current one, i.e. of delays left to yield.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl<T> Iterator for ExactAttempts<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        self.remaining = self.remaining.checked_sub(1)?;
+        if let Some(next) = self.inner.next() {
+            self.last = next;
+        }
+        Some(self.last)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for ExactAttempts<T> where T: Iterator<Item = Duration> {}
+
+impl<T> FusedIterator for ExactAttempts<T> where T: Iterator<Item = Duration> {}
+
+#[test]
+fn max_attempts() {
+    let mut attempts = 0;
+    let value = crate::retry!(MaxAttempts::new(NoDelay, 3), {
+        attempts += 1;
+        Err::<(), _>(attempts)
+    });
+    assert_eq!(value, Err(3));
+    assert_eq!(MaxAttempts::new(NoDelay, 0).next(), None);
+}
+
+/// The first retry happens without any delay, before the inner delays are followed.
+///
+/// Many transient failures (e.g. a dropped keep-alive connection) succeed when re-issued right
+/// away, so waiting the base backoff first only adds latency. Note that this adds one retry to a
+/// finite inner delay iterator.
 #[derive(Debug, Clone)]
+pub struct FirstImmediate<T> {
+    inner: T,
+    first: bool,
+}
+
+impl<T> FirstImmediate<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    pub fn new<U>(inner: U) -> Self
+    where
+        U: IntoIterator<Item = Duration, IntoIter = T>,
+    {
+        Self {
+            inner: inner.into_iter(),
+            first: true,
+        }
+    }
+}
+
+impl<T> Iterator for FirstImmediate<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        if std::mem::take(&mut self.first) {
+            Some(Duration::ZERO)
+        } else {
+            self.inner.next()
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.inner.size_hint();
+        let first = usize::from(self.first);
+        (
+            lower.saturating_add(first),
+            upper.and_then(|upper| upper.checked_add(first)),
+        )
+    }
+}
+
+impl<T> FusedIterator for FirstImmediate<T> where T: FusedIterator<Item = Duration> {}
+
+#[test]
+fn first_immediate() {
+    let delays: Vec<_> = Fixed::exact(Duration::from_millis(10))
+        .first_immediate()
+        .take(3)
+        .collect();
+    assert_eq!(
+        delays,
+        vec![
+            Duration::ZERO,
+            Duration::from_millis(10),
+            Duration::from_millis(10)
+        ]
+    );
+    assert_eq!(FirstImmediate::new(NoDelay.take(2)).count(), 3);
+}
+
+/// Each retry delay is recorded into a shared log as it is yielded.
+///
+/// Useful to assert backoff behavior in tests, or to log the schedule actually followed.
+#[derive(Debug, Clone)]
+pub struct Recorded<T> {
+    inner: T,
+    log: Arc<Mutex<Vec<Duration>>>,
+}
+
+impl<T> Recorded<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    /// Creates a new `Recorded` with an empty log.
+    pub fn new<U>(inner: U) -> Self
+    where
+        U: IntoIterator<Item = Duration, IntoIter = T>,
+    {
+        Self::with_log(inner, Arc::default())
+    }
+
+    /// Creates a new `Recorded` appending to the given log.
+    pub fn with_log<U>(inner: U, log: Arc<Mutex<Vec<Duration>>>) -> Self
+    where
+        U: IntoIterator<Item = Duration, IntoIter = T>,
+    {
+        Self {
+            inner: inner.into_iter(),
+            log,
+        }
+    }
+
+    /// The log the delays are recorded into.
+    pub fn log(&self) -> Arc<Mutex<Vec<Duration>>> {
+        self.log.clone()
+    }
+}
+
+impl<T> Iterator for Recorded<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let next = self.inner.next()?;
+        self.log
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(next);
+        Some(next)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> FusedIterator for Recorded<T> where T: FusedIterator<Item = Duration> {}
+
+#[test]
+fn recorded() {
+    let delays = Recorded::new(Fixed::exact(Duration::from_millis(1)).take(2));
+    let log = delays.log();
+    assert_eq!(delays.count(), 2);
+    assert_eq!(*log.lock().unwrap(), vec![Duration::from_millis(1); 2]);
+}
+
+/// Each retry increases the delay since the last exponentially.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "config", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Exponential {
     current: Duration,
     factor: f64,
@@ -58,10 +682,15 @@ pub struct Exponential {
 impl Exponential {
     /// Creates a new `Exponential` using a random proportion of the given
     /// duration as the initial delay.
+    ///
+    /// The jitter is only applied once, to the initial delay. Use `DelayBuilder` to explicitly
+    /// request jitter applied to each delay instead.
+    #[cfg(any(feature = "random", feature = "fastrand"))]
     pub fn new(duration: Duration) -> Self {
         Self::with_factor(duration, duration.as_millis() as f64)
     }
 
+    #[cfg(any(feature = "random", feature = "fastrand"))]
     /// Creates a new `Exponential` using a random proportion of the given
     /// duration as the initial delay and a variable multiplication factor.
     pub fn with_factor(base: Duration, factor: f64) -> Self {
@@ -73,23 +702,67 @@ impl Exponential {
 
     /// Creates a new `Exponential` using the given duration as the initial
     /// delay.
-    pub fn exact(duration: Duration) -> Self {
+    pub const fn exact(duration: Duration) -> Self {
         Self::exact_with_factor(duration, duration.as_millis() as f64)
     }
 
     /// Creates a new `Exponential` using the given duration as the initial
     /// delay and a variable multiplication factor.
-    pub fn exact_with_factor(base: Duration, factor: f64) -> Self {
+    pub const fn exact_with_factor(base: Duration, factor: f64) -> Self {
         Self {
             current: base,
             factor,
         }
     }
 
+    /// Creates a new `Exponential` using the given number of milliseconds as the initial delay.
+    ///
+    /// ```
+    /// # use retry_block::delay::Exponential;
+    /// # use std::time::Duration;
+    /// let mut delays = Exponential::from_millis(100).factor(2.0);
+    /// assert_eq!(delays.next(), Some(Duration::from_millis(100)));
+    /// assert_eq!(delays.next(), Some(Duration::from_millis(200)));
+    /// ```
+    pub const fn from_millis(millis: u64) -> Self {
+        Self::exact(Duration::from_millis(millis))
+    }
+
+    /// Creates a new `Exponential` using the given number of seconds as the initial delay.
+    pub const fn from_secs(secs: u64) -> Self {
+        Self::exact(Duration::from_secs(secs))
+    }
+
+    /// Sets the multiplication factor of this exponential delay generator.
+    pub const fn factor(mut self, factor: f64) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    #[cfg(any(feature = "random", feature = "fastrand"))]
+    /// Uses a random proportion of the next delay as the initial delay if `enabled`, as
+    /// `Exponential::new` does, or leaves it exact otherwise.
+    pub fn jitter(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.current = jitter(self.current);
+        }
+        self
+    }
+
     /// Applies an upper bound of `max` to this exponential delay generator.
     pub fn bounded(self, max: Duration) -> Bounded<Self> {
         Bounded::new(self, max)
     }
+
+    /// Caps each delay of this exponential delay generator to `max`.
+    pub fn max_delay(self, max: Duration) -> Capped<Self> {
+        Capped::new(self, max)
+    }
+
+    /// Retries once immediately before following this exponential delay generator.
+    pub fn first_immediate(self) -> FirstImmediate<Self> {
+        FirstImmediate::new(self)
+    }
 }
 
 impl Iterator for Exponential {
@@ -100,7 +773,7 @@ impl Iterator for Exponential {
             const NANOS_PER_SEC: u32 = 1_000_000_000;
             const MAX_NANOS_F64: f64 = ((u64::MAX as u128 + 1) * (NANOS_PER_SEC as u128)) as f64;
             let nanos = secs * (NANOS_PER_SEC as f64);
-            if !nanos.is_finite() || nanos >= MAX_NANOS_F64 || nanos < 0.0 {
+            if !nanos.is_finite() || !(0.0..MAX_NANOS_F64).contains(&nanos) {
                 None
             } else {
                 Some(Duration::from_secs_f64(secs))
@@ -114,8 +787,15 @@ impl Iterator for Exponential {
 
         Some(duration)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
 }
 
+impl FusedIterator for Exponential {}
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
 impl From<Duration> for Exponential {
     fn from(duration: Duration) -> Self {
         Self::new(duration)
@@ -140,6 +820,16 @@ fn exponential_overflow() {
     assert_eq!(iter.next(), Some(Duration::MAX));
 }
 
+#[test]
+fn exponential_with_max_delay() {
+    let mut iter = Exponential::exact_with_factor(Duration::from_secs(1), 2.0)
+        .max_delay(Duration::from_secs(3));
+    assert_eq!(iter.next(), Some(Duration::from_secs(1)));
+    assert_eq!(iter.next(), Some(Duration::from_secs(2)));
+    assert_eq!(iter.next(), Some(Duration::from_secs(3)));
+    assert_eq!(iter.next(), Some(Duration::from_secs(3)));
+}
+
 #[test]
 fn exponential_with_upper_bound() {
     let mut iter =
@@ -158,13 +848,16 @@ fn exponential_with_upper_bound() {
 ///
 /// See ["A Performance Comparison of Different Backoff Algorithms under Different Rebroadcast Probabilities for MANETs."](http://www.comp.leeds.ac.uk/ukpew09/papers/12.pdf)
 /// for more details.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "config", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Fibonacci {
     curr: Duration,
     next: Duration,
 }
 
 impl Fibonacci {
+    #[cfg(any(feature = "random", feature = "fastrand"))]
     /// Creates a new `Fibonacci` using a random proportion of the given duration.
     pub fn new(duration: Duration) -> Fibonacci {
         let duration = jitter(duration);
@@ -174,12 +867,22 @@ impl Fibonacci {
         }
     }
     /// Creates a new `Fibonacci` using the given duration.
-    pub fn exact(duration: Duration) -> Fibonacci {
+    pub const fn exact(duration: Duration) -> Fibonacci {
         Fibonacci {
             curr: duration,
             next: duration,
         }
     }
+
+    /// Caps each delay of this fibonacci delay generator to `max`.
+    pub fn max_delay(self, max: Duration) -> Capped<Self> {
+        Capped::new(self, max)
+    }
+
+    /// Retries once immediately before following this fibonacci delay generator.
+    pub fn first_immediate(self) -> FirstImmediate<Self> {
+        FirstImmediate::new(self)
+    }
 }
 
 impl Iterator for Fibonacci {
@@ -194,8 +897,15 @@ impl Iterator for Fibonacci {
 
         Some(duration)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
 }
 
+impl FusedIterator for Fibonacci {}
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
 impl From<Duration> for Fibonacci {
     fn from(duration: Duration) -> Self {
         Self::new(duration)
@@ -213,6 +923,16 @@ fn fibonacci() {
     assert_eq!(iter.next(), Some(Duration::from_millis(80)));
 }
 
+#[test]
+fn fibonacci_with_max_delay() {
+    let mut iter = Fibonacci::exact(Duration::from_millis(10)).max_delay(Duration::from_millis(25));
+    assert_eq!(iter.next(), Some(Duration::from_millis(10)));
+    assert_eq!(iter.next(), Some(Duration::from_millis(10)));
+    assert_eq!(iter.next(), Some(Duration::from_millis(20)));
+    assert_eq!(iter.next(), Some(Duration::from_millis(25)));
+    assert_eq!(iter.next(), Some(Duration::from_millis(25)));
+}
+
 #[test]
 fn fibonacci_saturated() {
     let mut iter = Fibonacci::exact(Duration::MAX);
@@ -220,14 +940,94 @@ fn fibonacci_saturated() {
     assert_eq!(iter.next(), Some(Duration::MAX));
 }
 
+/// Each retry divides the delay since the last by a factor, down to a minimum.
+///
+/// This is the reverse of `Exponential`, e.g. for health-check loops that should probe rarely
+/// right after a failure and more frequently as recovery becomes likely.
+///
+/// ```
+/// # use retry_block::delay::Decay;
+/// # use std::time::Duration;
+/// let delays: Vec<_> = Decay::new(Duration::from_secs(8), Duration::from_secs(1))
+///     .take(5)
+///     .collect();
+/// assert_eq!(delays, [8, 4, 2, 1, 1].map(Duration::from_secs));
+/// ```
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "config", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Decay {
+    current: Duration,
+    min: Duration,
+    factor: f64,
+}
+
+impl Decay {
+    /// Creates a new `Decay` halving the given initial delay on each retry, down to `min`.
+    pub const fn new(initial: Duration, min: Duration) -> Self {
+        Self::with_factor(initial, min, 2.0)
+    }
+
+    /// Creates a new `Decay` dividing the given initial delay by `factor` on each retry, down to
+    /// `min`.
+    pub const fn with_factor(initial: Duration, min: Duration, factor: f64) -> Self {
+        Self {
+            current: initial,
+            min,
+            factor,
+        }
+    }
+}
+
+impl Iterator for Decay {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let duration = self.current.max(self.min);
+        // a factor that would not shrink the delay leaves it as is
+        self.current = if self.factor > 1.0 {
+            self.current.div_f64(self.factor)
+        } else {
+            self.current
+        };
+        Some(duration)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+impl FusedIterator for Decay {}
+
+#[test]
+fn decay() {
+    let mut iter = Decay::with_factor(Duration::from_secs(9), Duration::from_millis(500), 3.0);
+    assert_eq!(iter.next(), Some(Duration::from_secs(9)));
+    assert_eq!(iter.next(), Some(Duration::from_secs(3)));
+    assert_eq!(iter.next(), Some(Duration::from_secs(1)));
+    assert_eq!(iter.next(), Some(Duration::from_millis(500)));
+    assert_eq!(iter.next(), Some(Duration::from_millis(500)));
+
+    let mut iter = Decay::with_factor(Duration::from_secs(1), Duration::ZERO, 0.5);
+    assert_eq!(iter.next(), Some(Duration::from_secs(1)));
+    assert_eq!(iter.next(), Some(Duration::from_secs(1)));
+}
+
 /// Each retry uses a fixed delay.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "config", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Fixed {
     duration: Duration,
 }
 
 impl Fixed {
     /// Creates a new `Fixed` using a random proportion of the given duration in milliseconds.
+    ///
+    /// The jitter is only applied once, so the same random delay is yielded forever. Use
+    /// `DelayBuilder` to explicitly request jitter applied to each delay instead.
+    #[cfg(any(feature = "random", feature = "fastrand"))]
     pub fn new(duration: Duration) -> Self {
         Fixed {
             duration: jitter(duration),
@@ -235,9 +1035,14 @@ impl Fixed {
     }
 
     /// Creates a new `Fixed` using the given duration in milliseconds.
-    pub fn exact(duration: Duration) -> Self {
+    pub const fn exact(duration: Duration) -> Self {
         Fixed { duration }
     }
+
+    /// Retries once immediately before following this fixed delay generator.
+    pub fn first_immediate(self) -> FirstImmediate<Self> {
+        FirstImmediate::new(self)
+    }
 }
 
 impl Iterator for Fixed {
@@ -246,16 +1051,104 @@ impl Iterator for Fixed {
     fn next(&mut self) -> Option<Duration> {
         Some(self.duration)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
 }
 
+impl FusedIterator for Fixed {}
+
 impl From<Duration> for Fixed {
     fn from(duration: Duration) -> Self {
         Self { duration }
     }
 }
 
+/// Each retry uses the next delay of a fixed-size schedule, until it runs out.
+///
+/// The schedule is stored inline, so it can be built in a `const` and copied around without any
+/// heap allocation or stack of iterator adapters:
+///
+/// ```
+/// # use retry_block::delay::Schedule;
+/// # use std::time::Duration;
+/// const SCHEDULE: Schedule<3> = Schedule::from_millis([10, 100, 1000]);
+/// assert_eq!(SCHEDULE.len(), 3);
+/// assert_eq!(
+///     SCHEDULE.collect::<Vec<_>>(),
+///     [10, 100, 1000].map(Duration::from_millis)
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule<const N: usize> {
+    delays: [Duration; N],
+    next: usize,
+}
+
+impl<const N: usize> Schedule<N> {
+    /// Creates a new `Schedule` following the given delays.
+    pub const fn new(delays: [Duration; N]) -> Self {
+        Self { delays, next: 0 }
+    }
+
+    /// Creates a new `Schedule` following the given delays in milliseconds.
+    pub const fn from_millis(millis: [u64; N]) -> Self {
+        let mut delays = [Duration::ZERO; N];
+        let mut i = 0;
+        while i < N {
+            delays[i] = Duration::from_millis(millis[i]);
+            i += 1;
+        }
+        Self::new(delays)
+    }
+}
+
+impl<const N: usize> Iterator for Schedule<N> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let next = *self.delays.get(self.next)?;
+        self.next += 1;
+        Some(next)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = N - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<const N: usize> ExactSizeIterator for Schedule<N> {}
+
+impl<const N: usize> FusedIterator for Schedule<N> {}
+
+impl<const N: usize> From<[Duration; N]> for Schedule<N> {
+    fn from(delays: [Duration; N]) -> Self {
+        Self::new(delays)
+    }
+}
+
+#[test]
+fn schedule() {
+    let mut attempts = 0;
+    let value = crate::retry!(Schedule::from_millis([0, 0]), {
+        attempts += 1;
+        Err::<(), _>(attempts)
+    });
+    assert_eq!(value, Err(3));
+
+    let mut schedule = Schedule::from([Duration::from_secs(1); 2]);
+    let copy = schedule;
+    assert_eq!(schedule.next(), Some(Duration::from_secs(1)));
+    assert_eq!(schedule.len(), 1);
+    assert_eq!(copy.len(), 2);
+}
+
 /// Each retry happens immediately without any delay.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "config", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct NoDelay;
 
 impl Iterator for NoDelay {
@@ -264,13 +1157,122 @@ impl Iterator for NoDelay {
     fn next(&mut self) -> Option<Duration> {
         Some(Duration::default())
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+impl FusedIterator for NoDelay {}
+
+/// Any of the delay strategies of this module, serializable as a tagged enum (need `config`
+/// feature)
+///
+/// ```
+/// # use retry_block::delay::DelayStrategy;
+/// # use std::time::Duration;
+/// let strategy: DelayStrategy = serde_json::from_str(
+///     r#"{"strategy": "fixed", "duration": {"secs": 1, "nanos": 0}}"#,
+/// )
+/// .unwrap();
+/// assert_eq!(strategy.take(1).collect::<Vec<_>>(), vec![Duration::from_secs(1)]);
+/// ```
+#[cfg(feature = "config")]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum DelayStrategy {
+    Fixed(Fixed),
+    Exponential(Exponential),
+    Fibonacci(Fibonacci),
+    #[cfg(any(feature = "random", feature = "fastrand"))]
+    Range(Range),
+    NoDelay,
+}
+
+#[cfg(feature = "config")]
+impl Iterator for DelayStrategy {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        match self {
+            Self::Fixed(delays) => delays.next(),
+            Self::Exponential(delays) => delays.next(),
+            Self::Fibonacci(delays) => delays.next(),
+            #[cfg(any(feature = "random", feature = "fastrand"))]
+            Self::Range(delays) => delays.next(),
+            Self::NoDelay => NoDelay.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Self::Fixed(delays) => delays.size_hint(),
+            Self::Exponential(delays) => delays.size_hint(),
+            Self::Fibonacci(delays) => delays.size_hint(),
+            #[cfg(any(feature = "random", feature = "fastrand"))]
+            Self::Range(delays) => delays.size_hint(),
+            Self::NoDelay => NoDelay.size_hint(),
+        }
+    }
 }
 
+#[cfg(feature = "config")]
+impl FusedIterator for DelayStrategy {}
+
 #[cfg(test)]
 mod test {
     use crate::delay::Exponential;
     use std::time::Duration;
 
+    #[test]
+    fn test_const_delays() {
+        use crate::delay::{Fibonacci, Fixed, NoDelay};
+
+        const FIXED: Fixed = Fixed::exact(Duration::from_millis(10));
+        static EXPONENTIAL: Exponential =
+            Exponential::exact_with_factor(Duration::from_millis(10), 2.0);
+        const FIBONACCI: Fibonacci = Fibonacci::exact(Duration::from_millis(10));
+        const NO_DELAY: NoDelay = NoDelay;
+
+        assert_eq!(FIXED.take(2).count(), 2);
+        assert_eq!(
+            EXPONENTIAL.take(2).collect::<Vec<_>>(),
+            vec![Duration::from_millis(10), Duration::from_millis(20)]
+        );
+        assert_eq!(FIBONACCI.take(2).count(), 2);
+        assert_eq!(NO_DELAY.take(2).count(), 2);
+    }
+
+    #[cfg(all(feature = "config", any(feature = "random", feature = "fastrand")))]
+    #[test]
+    fn test_serde_strategies() {
+        use crate::delay::{DelayStrategy, Range};
+
+        let strategy = DelayStrategy::Exponential(Exponential::exact_with_factor(
+            Duration::from_millis(10),
+            2.0,
+        ));
+        let json = serde_json::to_string(&strategy).unwrap();
+        let strategy: DelayStrategy = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            strategy.take(2).collect::<Vec<_>>(),
+            vec![Duration::from_millis(10), Duration::from_millis(20)]
+        );
+
+        let range = Range::from_millis_inclusive(5, 5);
+        let json = serde_json::to_string(&range).unwrap();
+        let mut range: Range = serde_json::from_str(&json).unwrap();
+        assert_eq!(range.next(), Some(Duration::from_millis(5)));
+
+        // a degenerate range yields a constant delay
+        let degenerate = r#"{"min_millis": 5, "max_millis": 5, "inclusive": false}"#;
+        let mut range: Range = serde_json::from_str(degenerate).unwrap();
+        assert_eq!(range.next(), Some(Duration::from_millis(5)));
+
+        let invalid = r#"{"min_millis": 6, "max_millis": 5, "inclusive": false}"#;
+        assert!(serde_json::from_str::<Range>(invalid).is_err());
+    }
+
     #[test]
     fn test_bounded_overflow() {
         let mut delays = Exponential::exact_with_factor(Duration::MAX, 1.0).bounded(Duration::MAX);
@@ -279,4 +1281,39 @@ mod test {
 
         assert_eq!(delays.next(), None);
     }
+
+    #[test]
+    fn keyed_jitter() {
+        use crate::delay::jitter_keyed;
+
+        let second = Duration::from_secs(1);
+        assert_eq!(jitter_keyed(second, 7), jitter_keyed(second, 7));
+        assert_ne!(jitter_keyed(second, 7), jitter_keyed(second, 8));
+        // consecutive client ids land in every tenth of the duration
+        let mut tenths = [0; 10];
+        for key in 0..1000 {
+            let delay = jitter_keyed(second, key);
+            assert!(delay < second);
+            tenths[(delay.as_millis() / 100) as usize] += 1;
+        }
+        assert!(tenths.iter().all(|count| (50..150).contains(count)));
+    }
+
+    #[cfg(any(feature = "random", feature = "fastrand"))]
+    #[test]
+    fn seeded_simulation() {
+        use crate::delay::{simulate_seeded, Fixed, Jitter, JitterExt};
+
+        let strategy = Fixed::exact(Duration::from_millis(100))
+            .with_jitter(Jitter::Full)
+            .take(2);
+        let simulation = simulate_seeded(strategy.clone(), 5, 7, 10);
+        assert_eq!(simulation, simulate_seeded(strategy, 5, 7, 10));
+        // the strategy ends before the retries asked for
+        assert_eq!(simulation.delays().len(), 2);
+        assert_eq!(simulation.runs(), 10);
+        let [min, median, max] = [0.0, 50.0, 100.0].map(|p| simulation.percentile(p));
+        assert!(min[0] <= median[0] && median[0] <= max[0]);
+        assert_eq!(max.len(), 2);
+    }
 }
@@ -0,0 +1,152 @@
+use std::iter::FusedIterator;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The smoothing factor of the latency average unless set with `Observed::with_alpha`
+const DEFAULT_ALPHA: f64 = 0.3;
+
+#[derive(Debug)]
+struct Average {
+    alpha: f64,
+    latency: Option<f64>,
+}
+
+/// A handle recording the latencies of attempts into an `Observed` delay strategy
+#[derive(Debug, Clone)]
+pub struct LatencyObserver {
+    average: Arc<Mutex<Average>>,
+}
+
+impl LatencyObserver {
+    /// Record the latency of an attempt
+    pub fn record(&self, latency: Duration) {
+        let mut average = self.average.lock().unwrap_or_else(|e| e.into_inner());
+        let latency = latency.as_secs_f64();
+        average.latency = Some(match average.latency {
+            Some(previous) => average.alpha * latency + (1.0 - average.alpha) * previous,
+            None => latency,
+        });
+    }
+
+    /// Run the given attempt, recording how long it took
+    pub fn observe<R>(&self, attempt: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let res = attempt();
+        self.record(start.elapsed());
+        res
+    }
+
+    /// The exponentially weighted moving average of the latencies recorded so far, if any
+    pub fn average(&self) -> Option<Duration> {
+        let average = self.average.lock().unwrap_or_else(|e| e.into_inner());
+        average.latency.map(Duration::from_secs_f64)
+    }
+}
+
+/// Each retry waits in proportion to the recent latency of the attempts, so that a degraded
+/// downstream is backed off from more than one that fails fast.
+///
+/// Latencies are recorded through the `LatencyObserver` of the strategy and smoothed into an
+/// exponentially weighted moving average; each delay is that average times a factor, clamped to
+/// the given bounds. The minimum is used until a latency is recorded.
+///
+/// ```
+/// # use retry_block::delay::Observed;
+/// # use std::time::Duration;
+/// let delays = Observed::new(2.0, Duration::from_millis(10), Duration::from_secs(1));
+/// let observer = delays.observer();
+///
+/// let mut attempts = 0;
+/// let value = retry_block::retry_fn(delays.take(2), || {
+///     observer.observe(|| {
+///         attempts += 1;
+///         if attempts < 3 { Err("timed out") } else { Ok(attempts) }
+///     })
+/// });
+/// assert_eq!(value, Ok(3));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Observed {
+    observer: LatencyObserver,
+    factor: f64,
+    min: Duration,
+    max: Duration,
+}
+
+impl Observed {
+    /// Creates a new `Observed` waiting `factor` times the average latency, between `min` and
+    /// `max`.
+    pub fn new(factor: f64, min: Duration, max: Duration) -> Self {
+        Self {
+            observer: LatencyObserver {
+                average: Arc::new(Mutex::new(Average {
+                    alpha: DEFAULT_ALPHA,
+                    latency: None,
+                })),
+            },
+            factor: factor.max(0.0),
+            min,
+            max: max.max(min),
+        }
+    }
+
+    /// Sets the weight of each new latency in the average, between 0 (exclusive) and 1, where
+    /// higher values adapt faster to changes.
+    pub fn with_alpha(self, alpha: f64) -> Self {
+        let mut average = self
+            .observer
+            .average
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        average.alpha = alpha.clamp(f64::EPSILON, 1.0);
+        drop(average);
+        self
+    }
+
+    /// The handle through which the latencies of attempts are recorded.
+    pub fn observer(&self) -> LatencyObserver {
+        self.observer.clone()
+    }
+}
+
+impl Iterator for Observed {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = match self.observer.average() {
+            // capped before the conversion, which would panic on overflow
+            Some(average) => (average.as_secs_f64() * self.factor).min(self.max.as_secs_f64()),
+            None => 0.0,
+        };
+        Some(Duration::from_secs_f64(delay).clamp(self.min, self.max))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+impl FusedIterator for Observed {}
+
+#[cfg(test)]
+mod test {
+    use super::Observed;
+    use std::time::Duration;
+
+    #[test]
+    fn adapted_to_latency() {
+        let mut delays =
+            Observed::new(2.0, Duration::from_millis(10), Duration::from_secs(1)).with_alpha(0.5);
+        let observer = delays.observer();
+        assert_eq!(delays.next(), Some(Duration::from_millis(10)));
+
+        observer.record(Duration::from_millis(100));
+        assert_eq!(delays.next(), Some(Duration::from_millis(200)));
+        observer.record(Duration::from_millis(300));
+        assert_eq!(observer.average(), Some(Duration::from_millis(200)));
+        assert_eq!(delays.next(), Some(Duration::from_millis(400)));
+
+        observer.record(Duration::from_secs(10));
+        assert_eq!(delays.next(), Some(Duration::from_secs(1)));
+    }
+}
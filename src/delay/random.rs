@@ -1,28 +1,211 @@
 use std::{
+    cell::RefCell,
+    iter::FusedIterator,
+    marker::PhantomData,
     ops::{Range as StdRange, RangeInclusive},
     time::Duration,
 };
 
-use rand::{
-    distributions::{Distribution, Uniform},
-    thread_rng,
-};
+use super::DelayError;
+
+/// The source of randomness of delays: `rand` with the `random` feature, or `fastrand` otherwise
+#[cfg(feature = "random")]
+mod backend {
+    use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+
+    pub(super) type SeededRng = StdRng;
+
+    pub(super) fn seeded(seed: u64) -> SeededRng {
+        StdRng::seed_from_u64(seed)
+    }
+
+    pub(super) fn f64(seeded: Option<&mut SeededRng>) -> f64 {
+        match seeded {
+            Some(rng) => rng.gen(),
+            None => thread_rng().gen(),
+        }
+    }
+
+    pub(super) fn u64(seeded: Option<&mut SeededRng>, min: u64, max: u64) -> u64 {
+        match seeded {
+            Some(rng) => rng.gen_range(min..=max),
+            None => thread_rng().gen_range(min..=max),
+        }
+    }
+}
+
+/// The source of randomness of delays: `rand` with the `random` feature, or `fastrand` otherwise
+#[cfg(not(feature = "random"))]
+mod backend {
+    pub(super) type SeededRng = fastrand::Rng;
+
+    pub(super) fn seeded(seed: u64) -> SeededRng {
+        fastrand::Rng::with_seed(seed)
+    }
+
+    pub(super) fn f64(seeded: Option<&mut SeededRng>) -> f64 {
+        match seeded {
+            Some(rng) => rng.f64(),
+            None => fastrand::f64(),
+        }
+    }
+
+    pub(super) fn u64(seeded: Option<&mut SeededRng>, min: u64, max: u64) -> u64 {
+        match seeded {
+            Some(rng) => rng.u64(min..=max),
+            None => fastrand::u64(min..=max),
+        }
+    }
+}
+
+use backend::SeededRng;
+
+thread_local! {
+    static SEEDED_RNG: RefCell<Option<SeededRng>> = const { RefCell::new(None) };
+}
+
+/// A random proportion between 0 and 1, from the seeded RNG of the current thread if any.
+fn random_proportion() -> f64 {
+    SEEDED_RNG.with(|seeded| backend::f64(seeded.borrow_mut().as_mut()))
+}
+
+/// A random integer between the given bounds (inclusive), from the seeded RNG of the current
+/// thread if any.
+fn random_between(min: u64, max: u64) -> u64 {
+    SEEDED_RNG.with(|seeded| backend::u64(seeded.borrow_mut().as_mut(), min, max))
+}
+
+/// Make all random delays of the current thread derive from the given seed until the returned
+/// guard is dropped. (need `random` or `fastrand` feature)
+///
+/// This makes jittered retry timing reproducible, e.g. to replay a failure in CI. The `retry!`
+/// macro also accepts a `seed = ...` argument applying this to its whole expansion.
+///
+/// ```
+/// # use retry_block::delay::{seed, Fixed};
+/// # use std::time::Duration;
+/// let first = {
+///     let _seed = seed(42);
+///     Fixed::new(Duration::from_secs(1)).next()
+/// };
+/// let second = {
+///     let _seed = seed(42);
+///     Fixed::new(Duration::from_secs(1)).next()
+/// };
+/// assert_eq!(first, second);
+///
+/// let value = retry_block::retry!(seed = 42, Fixed::new(Duration::from_millis(1)).take(3), {
+///     Ok::<_, ()>(42)
+/// });
+/// assert_eq!(value, Ok(42));
+/// ```
+pub fn seed(seed: u64) -> SeedGuard {
+    let previous = SEEDED_RNG.with(|seeded| seeded.replace(Some(backend::seeded(seed))));
+    SeedGuard {
+        previous,
+        thread_bound: PhantomData,
+    }
+}
+
+/// Guard returned by `seed`, restoring the previous random source of the thread when dropped.
+/// (need `random` or `fastrand` feature)
+#[must_use = "random delays are only seeded until the guard is dropped"]
+pub struct SeedGuard {
+    previous: Option<SeededRng>,
+    thread_bound: PhantomData<*const ()>,
+}
+
+impl Drop for SeedGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        SEEDED_RNG.with(|seeded| *seeded.borrow_mut() = previous);
+    }
+}
 
-/// Each retry uses a duration randomly chosen from a range. (need `random` feature)
+/// Each retry uses a duration randomly chosen from a range. (need `random` or `fastrand`
+/// feature)
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "RangeRepr", try_from = "RangeRepr")
+)]
 pub struct Range {
-    distribution: Uniform<u64>,
+    bounds: RangeRepr,
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Range {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let (a, b): (u64, u64) = u.arbitrary()?;
+        Ok(Self::from_millis_inclusive(a.min(b), a.max(b)))
+    }
+}
+
+/// The serialized form of a `Range`
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "config", derive(serde::Serialize, serde::Deserialize))]
+struct RangeRepr {
+    min_millis: u64,
+    max_millis: u64,
+    inclusive: bool,
+}
+
+impl From<Range> for RangeRepr {
+    fn from(range: Range) -> Self {
+        range.bounds
+    }
+}
+
+impl TryFrom<RangeRepr> for Range {
+    type Error = DelayError;
+
+    fn try_from(repr: RangeRepr) -> Result<Self, DelayError> {
+        let range = Self::try_from_millis(repr.min_millis, repr.max_millis)?;
+        Ok(if repr.inclusive {
+            range
+        } else {
+            Self::from_millis_exclusive(repr.min_millis, repr.max_millis)
+        })
+    }
 }
 
 impl Range {
+    /// Create a new `Range` between the given millisecond durations, including the maximum value,
+    /// or return an error if the minimum is greater than the maximum.
+    ///
+    /// ```
+    /// # use retry_block::delay::{DelayError, Range};
+    /// # use std::time::Duration;
+    /// let mut range = Range::try_from_millis(100, 100).unwrap();
+    /// assert_eq!(range.next(), Some(Duration::from_millis(100)));
+    /// assert!(matches!(Range::try_from_millis(200, 100), Err(DelayError::InvalidRange { .. })));
+    /// ```
+    pub fn try_from_millis(minimum: u64, maximum: u64) -> Result<Self, DelayError> {
+        if minimum > maximum {
+            return Err(DelayError::InvalidRange {
+                min_millis: minimum,
+                max_millis: maximum,
+            });
+        }
+        Ok(Self::from_millis_inclusive(minimum, maximum))
+    }
+
     /// Create a new `Range` between the given millisecond durations, excluding the maximum value.
     ///
+    /// A degenerate range, where the minimum is equal to the maximum, yields a constant delay.
+    ///
     /// # Panics
     ///
-    /// Panics if the minimum is greater than or equal to the maximum.
+    /// Panics if the minimum is greater than the maximum.
     pub fn from_millis_exclusive(minimum: u64, maximum: u64) -> Self {
+        assert!(minimum <= maximum, "invalid delay range");
         Range {
-            distribution: Uniform::new(minimum, maximum),
+            bounds: RangeRepr {
+                min_millis: minimum,
+                max_millis: maximum,
+                inclusive: false,
+            },
         }
     }
 
@@ -30,10 +213,15 @@ impl Range {
     ///
     /// # Panics
     ///
-    /// Panics if the minimum is greater than or equal to the maximum.
+    /// Panics if the minimum is greater than the maximum.
     pub fn from_millis_inclusive(minimum: u64, maximum: u64) -> Self {
+        assert!(minimum <= maximum, "invalid delay range");
         Range {
-            distribution: Uniform::new_inclusive(minimum, maximum),
+            bounds: RangeRepr {
+                min_millis: minimum,
+                max_millis: maximum,
+                inclusive: true,
+            },
         }
     }
 }
@@ -42,12 +230,28 @@ impl Iterator for Range {
     type Item = Duration;
 
     fn next(&mut self) -> Option<Duration> {
-        Some(Duration::from_millis(
-            self.distribution.sample(&mut thread_rng()),
-        ))
+        let RangeRepr {
+            min_millis,
+            max_millis,
+            inclusive,
+        } = self.bounds;
+        let max_millis = if inclusive || min_millis == max_millis {
+            max_millis
+        } else {
+            max_millis - 1
+        };
+        Some(Duration::from_millis(random_between(
+            min_millis, max_millis,
+        )))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
     }
 }
 
+impl FusedIterator for Range {}
+
 impl From<StdRange<Duration>> for Range {
     fn from(range: StdRange<Duration>) -> Self {
         Self::from_millis_exclusive(range.start.as_millis() as u64, range.end.as_millis() as u64)
@@ -63,23 +267,225 @@ impl From<RangeInclusive<Duration>> for Range {
     }
 }
 
-/// Apply full random jitter to a duration. (need `random` feature)
+/// Apply full random jitter to a duration. (need `random` or `fastrand` feature)
 pub fn jitter(duration: Duration) -> Duration {
-    jitter_rng(duration, &mut thread_rng())
+    duration.mul_f64(random_proportion())
 }
 
+/// Apply full random jitter to a duration, using the given `rand` RNG. (need `random` feature)
+#[cfg(feature = "random")]
 pub fn jitter_rng(duration: Duration, rng: &mut impl rand::Rng) -> Duration {
     duration.mul_f64(rng.gen())
 }
 
+/// A random jitter policy, applied to each delay of a delay iterator by `JitterExt::with_jitter`.
+/// (need `random` or `fastrand` feature)
+///
+/// See ["Exponential Backoff And Jitter"](https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/)
+/// for a comparison of these policies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Jitter {
+    /// Each delay is left as is.
+    None,
+    /// Each delay is a random proportion of itself, between zero and itself.
+    Full,
+    /// Each delay is half of itself, plus a random proportion of the other half.
+    Equal,
+    /// Each delay is randomly chosen between itself and three times the previous jittered delay,
+    /// and capped to `max`, which keeps the delays from growing without bound.
+    Decorrelated { max: Duration },
+    /// Each delay is randomly chosen within the given ratio of itself, e.g. `Ratio(0.1)` yields
+    /// delays within ±10% of the inner ones. The ratio is clamped between 0 and 1, and NaN is
+    /// taken as 0.
+    Ratio(f64),
+}
+
+impl Jitter {
+    fn apply(self, duration: Duration, previous: Option<Duration>) -> Duration {
+        let proportion = random_proportion();
+        match self {
+            Jitter::None => duration,
+            Jitter::Full => duration.mul_f64(proportion),
+            Jitter::Equal => duration / 2 + (duration / 2).mul_f64(proportion),
+            Jitter::Decorrelated { max } => {
+                let upper = previous.map_or(duration, |previous| {
+                    previous.saturating_mul(3).max(duration)
+                });
+                (duration + (upper - duration).mul_f64(proportion)).min(max)
+            }
+            Jitter::Ratio(ratio) => {
+                let ratio = if ratio.is_nan() {
+                    0.0
+                } else {
+                    ratio.clamp(0.0, 1.0)
+                };
+                duration.mul_f64(1.0 - ratio + 2.0 * ratio * proportion)
+            }
+        }
+    }
+}
+
+/// Each delay of the inner delay iterator is randomized by a `Jitter` policy.
+/// (need `random` or `fastrand` feature)
+#[derive(Debug, Clone)]
+pub struct Jittered<T> {
+    inner: T,
+    jitter: Jitter,
+    previous: Option<Duration>,
+}
+
+impl<T> Jittered<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    pub fn new<U>(inner: U, jitter: Jitter) -> Self
+    where
+        U: IntoIterator<Item = Duration, IntoIter = T>,
+    {
+        Self {
+            inner: inner.into_iter(),
+            jitter,
+            previous: None,
+        }
+    }
+}
+
+impl<T> Iterator for Jittered<T>
+where
+    T: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let next = self.jitter.apply(self.inner.next()?, self.previous);
+        self.previous = Some(next);
+        Some(next)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> FusedIterator for Jittered<T> where T: FusedIterator<Item = Duration> {}
+
+/// The delays of a finite inner delay iterator, in random order. (need `random` or `fastrand`
+/// feature)
+///
+/// Unlike jitter, which randomizes each delay around its own value, this keeps the delays (and so
+/// their total) as they are but randomizes which attempt waits for which, so that clients which
+/// failed together do not retry together. It composes with `take` and `Bounded` like any delay
+/// iterator.
+///
+/// ```
+/// # use retry_block::delay::{Bounded, Fibonacci, Shuffled};
+/// # use std::time::Duration;
+/// let exact = Fibonacci::exact(Duration::from_millis(10)).take(5);
+/// let mut delays: Vec<_> = Shuffled::seeded(exact.clone(), 42).collect();
+/// assert_eq!(delays, Shuffled::seeded(exact.clone(), 42).collect::<Vec<_>>());
+/// delays.sort();
+/// assert_eq!(delays, exact.clone().collect::<Vec<_>>());
+///
+/// let bounded = Bounded::new(Shuffled::new(exact), Duration::from_millis(50));
+/// assert!(bounded.count() < 5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Shuffled {
+    delays: std::vec::IntoIter<Duration>,
+}
+
+impl Shuffled {
+    /// Shuffles the given delays with the seeded RNG of the current thread if any (see `seed`).
+    ///
+    /// The delays are all collected at once, so they must be finite.
+    pub fn new<U>(inner: U) -> Self
+    where
+        U: IntoIterator<Item = Duration>,
+    {
+        Self::shuffle(inner, |max| random_between(0, max))
+    }
+
+    /// Shuffles the given delays with an RNG seeded with the given seed, so that the order is
+    /// reproducible.
+    pub fn seeded<U>(inner: U, seed: u64) -> Self
+    where
+        U: IntoIterator<Item = Duration>,
+    {
+        let mut rng = backend::seeded(seed);
+        Self::shuffle(inner, |max| backend::u64(Some(&mut rng), 0, max))
+    }
+
+    /// Fisher-Yates shuffle, given a random integer between 0 and a bound (inclusive).
+    fn shuffle<U>(inner: U, mut random: impl FnMut(u64) -> u64) -> Self
+    where
+        U: IntoIterator<Item = Duration>,
+    {
+        let mut delays: Vec<_> = inner.into_iter().collect();
+        for i in (1..delays.len()).rev() {
+            delays.swap(i, random(i as u64) as usize);
+        }
+        Self {
+            delays: delays.into_iter(),
+        }
+    }
+}
+
+impl Iterator for Shuffled {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        self.delays.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.delays.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Shuffled {}
+
+impl FusedIterator for Shuffled {}
+
+/// Extension trait applying a `Jitter` policy to any delay iterator. (need `random` or
+/// `fastrand` feature)
+///
+/// ```
+/// # use retry_block::delay::{Exponential, Jitter, JitterExt};
+/// # use std::time::Duration;
+/// let delays = Exponential::exact_with_factor(Duration::from_millis(100), 2.0)
+///     .with_jitter(Jitter::Equal)
+///     .take(3);
+/// for (delay, exact) in delays.zip([100, 200, 400]) {
+///     assert!(delay >= Duration::from_millis(exact / 2));
+///     assert!(delay <= Duration::from_millis(exact));
+/// }
+/// ```
+pub trait JitterExt: Iterator<Item = Duration> + Sized {
+    /// Randomizes each delay of this iterator according to the given policy.
+    fn with_jitter(self, jitter: Jitter) -> Jittered<Self> {
+        Jittered::new(self, jitter)
+    }
+}
+
+impl<T> JitterExt for T where T: Iterator<Item = Duration> {}
+
 #[cfg(test)]
 mod test {
-    use crate::delay::jitter_rng;
-    use rand::SeedableRng;
-    use rand_xorshift::XorShiftRng;
+    use crate::delay::{seed, Fixed, Jitter, JitterExt, Range};
     use std::time::Duration;
+
+    #[cfg(feature = "random")]
     #[test]
     fn test_jitter_1_sec() {
+        use crate::delay::jitter_rng;
+        use rand::SeedableRng;
+        use rand_xorshift::XorShiftRng;
+
         let mut rng = XorShiftRng::seed_from_u64(0);
 
         let duration = Duration::from_millis(1000);
@@ -88,4 +494,64 @@ mod test {
             jitter_rng(duration, &mut rng)
         )
     }
+
+    #[test]
+    fn test_seeded_range() {
+        let delays = |s| {
+            let _seed = seed(s);
+            Range::from_millis_exclusive(0, 1000)
+                .take(5)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(delays(42), delays(42));
+        assert_ne!(delays(42), delays(43));
+    }
+
+    #[test]
+    fn jitter_policies() {
+        let second = Duration::from_secs(1);
+        let delays = |jitter| Fixed::exact(second).with_jitter(jitter).take(20);
+
+        assert!(delays(Jitter::None).all(|delay| delay == second));
+        assert!(delays(Jitter::Full).all(|delay| delay <= second));
+        assert!(delays(Jitter::Equal).all(|delay| delay >= second / 2 && delay <= second));
+        assert!(delays(Jitter::Ratio(0.1))
+            .all(|delay| delay >= second.mul_f64(0.9) && delay <= second.mul_f64(1.1)));
+        assert!(delays(Jitter::Ratio(f64::NAN)).all(|delay| delay == second));
+        assert!(delays(Jitter::Ratio(f64::INFINITY)).all(|delay| delay <= second * 2));
+        let max = second * 10;
+        assert!(delays(Jitter::Decorrelated { max }).all(|delay| delay >= second && delay <= max));
+
+        let mut decorrelated = Fixed::exact(second).with_jitter(Jitter::Decorrelated { max });
+        let mut previous = decorrelated.next().unwrap();
+        assert_eq!(previous, second);
+        for delay in decorrelated.take(20) {
+            assert!(delay <= previous * 3 && delay <= max);
+            previous = delay;
+        }
+        // the cap applies to the inner delays as well
+        let mut capped = Fixed::exact(max * 2).with_jitter(Jitter::Decorrelated { max });
+        assert_eq!(capped.next(), Some(max));
+    }
+
+    #[test]
+    fn shuffled_with_thread_seed() {
+        use crate::delay::Shuffled;
+
+        let exact: Vec<_> = (1..=20).map(Duration::from_secs).collect();
+        let shuffled = |s| {
+            let _seed = seed(s);
+            Shuffled::new(exact.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(shuffled(42), shuffled(42));
+        assert_ne!(shuffled(42), shuffled(43));
+        assert_ne!(shuffled(42), exact);
+        assert_eq!(Shuffled::new(exact.clone()).take(3).len(), 3);
+    }
+
+    #[test]
+    fn degenerate_range() {
+        let mut range = Range::from_millis_exclusive(10, 10);
+        assert_eq!(range.next(), Some(Duration::from_millis(10)));
+    }
 }
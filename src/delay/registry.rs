@@ -0,0 +1,205 @@
+//! Delay strategies built by name at runtime
+//!
+//! A `Registry` maps strategy names to factories building a `BoxedDelay` from string parameters,
+//! e.g. to construct the strategy of a job from a database row. It knows the following
+//! strategies, which all accept an optional `count` parameter limiting the number of retries:
+//!
+//! - `"no_delay"`
+//! - `"fixed"`, with a `millis` parameter
+//! - `"exponential"`, with a `millis` parameter and an optional `factor` (2 by default)
+//! - `"fibonacci"`, with a `millis` parameter
+//! - `"range"`, with `min_millis` and `max_millis` parameters (inclusive), with the `random` or
+//!   `fastrand` feature
+//!
+//! ```
+//! # use retry_block::delay::registry::{Params, Registry};
+//! # use retry_block::delay::IntoBoxed;
+//! # use std::time::Duration;
+//! let mut registry = Registry::new();
+//! registry.register("linear", |params| {
+//!     let step = Duration::from_millis(params.get_parsed("millis")?);
+//!     Ok((1..).map(move |n| step * n).into_boxed())
+//! });
+//!
+//! let params = Params::from([("millis", "10"), ("count", "3")]);
+//! let delays: Vec<_> = registry.build("linear", &params).unwrap().collect();
+//! assert_eq!(
+//!     delays,
+//!     vec![Duration::from_millis(10), Duration::from_millis(20), Duration::from_millis(30)]
+//! );
+//! ```
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
+use super::Range;
+use super::{BoxedDelay, DelayError, Exponential, Fibonacci, Fixed, IntoBoxed, NoDelay};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The parameters of a delay strategy, by name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params(HashMap<String, String>);
+
+impl Params {
+    /// Create empty parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the given parameter
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.0.insert(name.into(), value.into());
+    }
+
+    /// Return the raw value of the given parameter, if set
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    /// Parse the given parameter, if set
+    pub fn get_parsed_opt<T: FromStr>(&self, name: &str) -> Result<Option<T>, DelayError> {
+        self.get(name)
+            .map(|value| {
+                value.parse().map_err(|_| DelayError::InvalidParameter {
+                    name: name.to_owned(),
+                    value: value.to_owned(),
+                })
+            })
+            .transpose()
+    }
+
+    /// Parse the given parameter, which must be set
+    pub fn get_parsed<T: FromStr>(&self, name: &str) -> Result<T, DelayError> {
+        self.get_parsed_opt(name)?
+            .ok_or_else(|| DelayError::MissingParameter(name.to_owned()))
+    }
+}
+
+impl<K, V, const N: usize> From<[(K, V); N]> for Params
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn from(params: [(K, V); N]) -> Self {
+        Self(
+            params
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+type Factory = Box<dyn Fn(&Params) -> Result<BoxedDelay, DelayError> + Send + Sync>;
+
+/// A set of named delay strategies
+pub struct Registry {
+    factories: HashMap<String, Factory>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Registry {
+    /// Create a registry of the strategies of this crate
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register("no_delay", |_| Ok(NoDelay.into_boxed()));
+        registry.register("fixed", |params| {
+            let duration = Duration::from_millis(params.get_parsed("millis")?);
+            Ok(Fixed::exact(duration).into_boxed())
+        });
+        registry.register("exponential", |params| {
+            let duration = Duration::from_millis(params.get_parsed("millis")?);
+            let factor = params.get_parsed_opt("factor")?.unwrap_or(2.0);
+            Ok(Exponential::exact_with_factor(duration, factor).into_boxed())
+        });
+        registry.register("fibonacci", |params| {
+            let duration = Duration::from_millis(params.get_parsed("millis")?);
+            Ok(Fibonacci::exact(duration).into_boxed())
+        });
+        #[cfg(any(feature = "random", feature = "fastrand"))]
+        registry.register("range", |params| {
+            let range = Range::try_from_millis(
+                params.get_parsed("min_millis")?,
+                params.get_parsed("max_millis")?,
+            )?;
+            Ok(range.into_boxed())
+        });
+        registry
+    }
+
+    /// Create a registry without any strategy
+    pub fn empty() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Register a strategy under the given name, replacing any previous one
+    pub fn register<F>(&mut self, name: impl Into<String>, factory: F)
+    where
+        F: Fn(&Params) -> Result<BoxedDelay, DelayError> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.into(), Box::new(factory));
+    }
+
+    /// Build the strategy of the given name with the given parameters
+    pub fn build(&self, name: &str, params: &Params) -> Result<BoxedDelay, DelayError> {
+        let factory = self
+            .factories
+            .get(name)
+            .ok_or_else(|| DelayError::UnknownStrategy(name.to_owned()))?;
+        let delays = factory(params)?;
+        Ok(match params.get_parsed_opt("count")? {
+            Some(count) => delays.take(count).into_boxed(),
+            None => delays,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Params, Registry};
+    use crate::delay::DelayError;
+    use std::time::Duration;
+
+    #[test]
+    fn builtin_strategies() {
+        let registry = Registry::new();
+        let params = Params::from([("millis", "10"), ("count", "3")]);
+        let delays: Vec<_> = registry.build("exponential", &params).unwrap().collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(10),
+                Duration::from_millis(20),
+                Duration::from_millis(40)
+            ]
+        );
+
+        assert!(matches!(
+            registry.build("fixed", &Params::new()),
+            Err(DelayError::MissingParameter(name)) if name == "millis"
+        ));
+        assert!(matches!(
+            registry.build("fixed", &Params::from([("millis", "soon")])),
+            Err(DelayError::InvalidParameter { .. })
+        ));
+        #[cfg(any(feature = "random", feature = "fastrand"))]
+        assert!(matches!(
+            registry.build(
+                "range",
+                &Params::from([("min_millis", "2"), ("max_millis", "1")])
+            ),
+            Err(DelayError::InvalidRange { .. })
+        ));
+        assert!(matches!(
+            registry.build("quadratic", &Params::new()),
+            Err(DelayError::UnknownStrategy(_))
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+/// The concrete delays of a strategy, as computed by `simulate` or `simulate_seeded`
+///
+/// With a jittered strategy, each run draws different delays: `delays` and `cumulative` describe
+/// the first one, and `percentile` the spread of all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simulation {
+    runs: Vec<Vec<Duration>>,
+}
+
+impl Simulation {
+    /// The delays before each retry of the first run, fewer than asked for if the strategy ended
+    pub fn delays(&self) -> &[Duration] {
+        &self.runs[0]
+    }
+
+    /// The time spent waiting by the first run up to each retry
+    pub fn cumulative(&self) -> Vec<Duration> {
+        self.delays()
+            .iter()
+            .scan(Duration::ZERO, |total, delay| {
+                *total = total.saturating_add(*delay);
+                Some(*total)
+            })
+            .collect()
+    }
+
+    /// The time spent waiting by the first run in total
+    pub fn total(&self) -> Duration {
+        self.cumulative().last().copied().unwrap_or_default()
+    }
+
+    /// The number of runs simulated
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// The given percentile (between 0 and 100) of the delay before each retry over all the runs
+    ///
+    /// Only the retries reached by every run are included.
+    pub fn percentile(&self, percentile: f64) -> Vec<Duration> {
+        let retries = self.runs.iter().map(Vec::len).min().unwrap_or(0);
+        (0..retries)
+            .map(|retry| {
+                let mut delays: Vec<_> = self.runs.iter().map(|run| run[retry]).collect();
+                delays.sort_unstable();
+                // nearest rank
+                let rank = (percentile.clamp(0.0, 100.0) / 100.0 * delays.len() as f64).ceil();
+                delays[(rank as usize).saturating_sub(1)]
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Simulation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (retry, (delay, total)) in self.delays().iter().zip(self.cumulative()).enumerate() {
+            writeln!(f, "retry {}: {:?} (total {:?})", retry + 1, delay, total)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compute the delays the given strategy yields before the first `retries` retries, without
+/// waiting for them
+///
+/// ```
+/// # use retry_block::delay::{simulate, Fixed};
+/// # use std::time::Duration;
+/// let simulation = simulate(Fixed::exact(Duration::from_millis(100)), 3);
+/// assert_eq!(simulation.cumulative()[1], Duration::from_millis(200));
+/// assert_eq!(simulation.total(), Duration::from_millis(300));
+/// print!("{}", simulation);
+/// ```
+pub fn simulate<D>(strategy: D, retries: usize) -> Simulation
+where
+    D: IntoIterator<Item = Duration>,
+{
+    Simulation {
+        runs: vec![strategy.into_iter().take(retries).collect()],
+    }
+}
+
+/// Compute the delays the given strategy yields before the first `retries` retries in the given
+/// number of runs, drawing random delays from the given seed (need `random` or `fastrand`
+/// feature)
+///
+/// ```
+/// # use retry_block::delay::{simulate_seeded, Fixed, Jitter, JitterExt};
+/// # use std::time::Duration;
+/// let strategy = Fixed::exact(Duration::from_secs(1)).with_jitter(Jitter::Full);
+/// let simulation = simulate_seeded(strategy, 2, 42, 1000);
+/// let [low, high] = [5.0, 95.0].map(|p| simulation.percentile(p)[0]);
+/// assert!(low < Duration::from_millis(200) && high > Duration::from_millis(800));
+/// ```
+#[cfg(any(feature = "random", feature = "fastrand"))]
+pub fn simulate_seeded<D>(strategy: D, retries: usize, seed: u64, runs: usize) -> Simulation
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    let _seed = super::seed(seed);
+    Simulation {
+        runs: (0..runs.max(1))
+            .map(|_| strategy.clone().into_iter().take(retries).collect())
+            .collect(),
+    }
+}
@@ -0,0 +1,117 @@
+/// What an `Escalation` does once retrying gave up too many consecutive times
+enum Action<'a> {
+    Hook(Box<dyn FnMut(usize) + Send + 'a>),
+    Panic,
+    Abort,
+}
+
+/// A policy escalating endless retries, e.g. of `retry_perpetual!`, once they gave up a given
+/// number of consecutive times, rather than letting them mask an outage
+///
+/// Each time the delays of a supervised operation run out, `Escalation::gave_up` is called, and
+/// each time it succeeds, `Escalation::succeeded`. When the count of consecutive give-ups reaches
+/// a multiple of the threshold, the policy calls its hook with the count, panics, or aborts the
+/// process so that its supervisor (e.g. systemd or Kubernetes) restarts it.
+///
+/// ```
+/// # use retry_block::Escalation;
+/// # use retry_block::delay::NoDelay;
+/// let mut alerts = Vec::new();
+/// let mut escalation = Escalation::hook(2, |gave_up| alerts.push(gave_up));
+/// for _ in 0..5 {
+///     if retry_block::retry_fn(NoDelay.take(1), || Err::<(), _>("unavailable")).is_err() {
+///         escalation.gave_up();
+///     }
+/// }
+/// drop(escalation);
+/// assert_eq!(alerts, vec![2, 4]);
+/// ```
+pub struct Escalation<'a> {
+    after: usize,
+    consecutive: usize,
+    action: Action<'a>,
+}
+
+impl<'a> Escalation<'a> {
+    fn new(after: usize, action: Action<'a>) -> Self {
+        Self {
+            after: after.max(1),
+            consecutive: 0,
+            action,
+        }
+    }
+
+    /// Call the given hook with the number of consecutive give-ups every `after` of them
+    pub fn hook(after: usize, hook: impl FnMut(usize) + Send + 'a) -> Self {
+        Self::new(after, Action::Hook(Box::new(hook)))
+    }
+
+    /// Panic after `after` consecutive give-ups
+    pub fn panic(after: usize) -> Self {
+        Self::new(after, Action::Panic)
+    }
+
+    /// Abort the process after `after` consecutive give-ups
+    pub fn abort(after: usize) -> Self {
+        Self::new(after, Action::Abort)
+    }
+
+    /// The number of consecutive give-ups so far
+    pub fn consecutive(&self) -> usize {
+        self.consecutive
+    }
+
+    /// Record that retrying gave up, and escalate if it did too many consecutive times
+    pub fn gave_up(&mut self) {
+        self.consecutive += 1;
+        // `after` is at least 1
+        if self.consecutive % self.after != 0 {
+            return;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::error!(consecutive = self.consecutive, "retrying keeps giving up");
+        match &mut self.action {
+            Action::Hook(hook) => hook(self.consecutive),
+            Action::Panic => panic!("retrying gave up {} consecutive times", self.consecutive),
+            Action::Abort => std::process::abort(),
+        }
+    }
+
+    /// Record that retrying succeeded, resetting the count of consecutive give-ups
+    pub fn succeeded(&mut self) {
+        self.consecutive = 0;
+    }
+}
+
+impl std::fmt::Debug for Escalation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = match self.action {
+            Action::Hook(_) => "hook",
+            Action::Panic => "panic",
+            Action::Abort => "abort",
+        };
+        f.debug_struct("Escalation")
+            .field("after", &self.after)
+            .field("consecutive", &self.consecutive)
+            .field("action", &action)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Escalation;
+
+    #[test]
+    #[should_panic(expected = "retrying gave up 3 consecutive times")]
+    fn panicked_once_exceeded() {
+        let mut escalation = Escalation::panic(3);
+        escalation.gave_up();
+        escalation.gave_up();
+        escalation.succeeded();
+        escalation.gave_up();
+        escalation.gave_up();
+        assert_eq!(escalation.consecutive(), 2);
+        escalation.gave_up();
+    }
+}
@@ -0,0 +1,203 @@
+//! Retried filesystem operations
+//!
+//! Filesystem operations often fail transiently, e.g. when a file is locked by another process
+//! (which is commonplace on Windows with antivirus scanners and file indexers). The functions of
+//! this module retry the operations of `std::fs` on errors classified as transient by
+//! `is_transient`, following `DEFAULT_CONFIG`; a `RetryFs` follows a given `RetryConfig` instead.
+//!
+//! ```
+//! # use retry_block::fs;
+//! # let dir = std::env::temp_dir().join(format!("retry-block-fs-doc-{}", std::process::id()));
+//! # std::fs::create_dir_all(&dir).unwrap();
+//! let path = dir.join("hello.txt");
+//! fs::retry_write(&path, "hello").unwrap();
+//! assert_eq!(fs::retry_read_to_string(&path).unwrap(), "hello");
+//! fs::retry_remove_file(&path).unwrap();
+//! # std::fs::remove_dir(&dir).unwrap();
+//! ```
+
+use crate::{retry_fn, OperationResult, RetryConfig};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// The retry configuration of the functions of this module
+pub const DEFAULT_CONFIG: RetryConfig = RetryConfig {
+    count: 5,
+    min_backoff: 10,
+    max_backoff: 100,
+};
+
+/// Whether the given error is likely to go away on retry
+///
+/// On Windows, `PermissionDenied` is transient as well, as it is what a file pending deletion or
+/// opened without sharing by another process fails with; elsewhere it is permanent.
+pub fn is_transient(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    {
+        // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+        if matches!(error.raw_os_error(), Some(32 | 33))
+            || error.kind() == io::ErrorKind::PermissionDenied
+        {
+            return true;
+        }
+    }
+    matches!(
+        error.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ResourceBusy
+    )
+}
+
+/// Retried filesystem operations following a given `RetryConfig`
+#[derive(Debug, Clone)]
+pub struct RetryFs {
+    config: RetryConfig,
+}
+
+impl Default for RetryFs {
+    fn default() -> Self {
+        Self::new(DEFAULT_CONFIG)
+    }
+}
+
+impl RetryFs {
+    /// Create retried filesystem operations following the given config
+    pub fn new(config: RetryConfig) -> Self {
+        Self { config }
+    }
+
+    fn retry<R>(&self, mut operation: impl FnMut() -> io::Result<R>) -> io::Result<R> {
+        retry_fn(self.config.clone(), || {
+            OperationResult::retry_if(operation(), is_transient)
+        })
+    }
+
+    /// Retried `File::open`
+    pub fn open(&self, path: impl AsRef<Path>) -> io::Result<File> {
+        self.retry(|| File::open(&path))
+    }
+
+    /// Retried `File::create`
+    pub fn create(&self, path: impl AsRef<Path>) -> io::Result<File> {
+        self.retry(|| File::create(&path))
+    }
+
+    /// Retried `std::fs::read`
+    pub fn read(&self, path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        self.retry(|| std::fs::read(&path))
+    }
+
+    /// Retried `std::fs::read_to_string`
+    pub fn read_to_string(&self, path: impl AsRef<Path>) -> io::Result<String> {
+        self.retry(|| std::fs::read_to_string(&path))
+    }
+
+    /// Retried `std::fs::write`
+    pub fn write(&self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+        self.retry(|| std::fs::write(&path, &contents))
+    }
+
+    /// Retried `std::fs::remove_file`
+    pub fn remove_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.retry(|| std::fs::remove_file(&path))
+    }
+
+    /// Retried `std::fs::remove_dir_all`
+    pub fn remove_dir_all(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.retry(|| std::fs::remove_dir_all(&path))
+    }
+
+    /// Retried `std::fs::create_dir_all`
+    pub fn create_dir_all(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.retry(|| std::fs::create_dir_all(&path))
+    }
+
+    /// Retried `std::fs::rename`
+    pub fn rename(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+        self.retry(|| std::fs::rename(&from, &to))
+    }
+
+    /// Retried `std::fs::copy`
+    pub fn copy(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<u64> {
+        self.retry(|| std::fs::copy(&from, &to))
+    }
+}
+
+/// `File::open`, retried following `DEFAULT_CONFIG`
+pub fn retry_open(path: impl AsRef<Path>) -> io::Result<File> {
+    RetryFs::default().open(path)
+}
+
+/// `File::create`, retried following `DEFAULT_CONFIG`
+pub fn retry_create(path: impl AsRef<Path>) -> io::Result<File> {
+    RetryFs::default().create(path)
+}
+
+/// `std::fs::read`, retried following `DEFAULT_CONFIG`
+pub fn retry_read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    RetryFs::default().read(path)
+}
+
+/// `std::fs::read_to_string`, retried following `DEFAULT_CONFIG`
+pub fn retry_read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    RetryFs::default().read_to_string(path)
+}
+
+/// `std::fs::write`, retried following `DEFAULT_CONFIG`
+pub fn retry_write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    RetryFs::default().write(path, contents)
+}
+
+/// `std::fs::remove_file`, retried following `DEFAULT_CONFIG`
+pub fn retry_remove_file(path: impl AsRef<Path>) -> io::Result<()> {
+    RetryFs::default().remove_file(path)
+}
+
+/// `std::fs::remove_dir_all`, retried following `DEFAULT_CONFIG`
+pub fn retry_remove_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    RetryFs::default().remove_dir_all(path)
+}
+
+/// `std::fs::create_dir_all`, retried following `DEFAULT_CONFIG`
+pub fn retry_create_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    RetryFs::default().create_dir_all(path)
+}
+
+/// `std::fs::rename`, retried following `DEFAULT_CONFIG`
+pub fn retry_rename(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    RetryFs::default().rename(from, to)
+}
+
+/// `std::fs::copy`, retried following `DEFAULT_CONFIG`
+pub fn retry_copy(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<u64> {
+    RetryFs::default().copy(from, to)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_transient, RetryFs};
+    use crate::RetryConfig;
+    use std::io::{Error, ErrorKind};
+
+    #[test]
+    fn fatal_errors() {
+        let fs = RetryFs::new(RetryConfig {
+            count: 100,
+            min_backoff: 1000,
+            max_backoff: 1000,
+        });
+        // not found is not retried, or this would take 100s
+        let error = fs.read("/this/path/does/not/exist").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn permission_denied() {
+        let error = Error::from(ErrorKind::PermissionDenied);
+        assert_eq!(is_transient(&error), cfg!(windows));
+        assert!(is_transient(&Error::from(ErrorKind::Interrupted)));
+    }
+}
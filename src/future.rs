@@ -80,8 +80,17 @@
 //! }
 //! ```
 
+#[cfg(feature = "tokio")]
 use crate::async_retry;
-use crate::OperationResult;
+#[cfg(feature = "tokio")]
+use crate::TokioSleeper;
+use crate::{async_retry_with, AsyncSleeper, OperationResult};
+#[cfg(feature = "tokio")]
+use crate::{RetryContext, RetryError, WithRetryContext};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
 use std::time::Duration;
 
 /// Retry the given operation until it succeeds, or until the given `Duration`
@@ -93,15 +102,1273 @@ use std::time::Duration;
 /// **Warning**: Capturing outside values in async blocks of `FnMut`s will not work all the
 /// time because async blocks may create references that outlive their scope.
 ///
-/// You may have to wrap your data with `Arc<Mutex<_>>` or use `futures::Stream`
+/// You may have to wrap your data with `Arc<Mutex<_>>`, use `futures::Stream`, or pass it to
+/// each attempt with `async_retry_call`
 ///
 /// </pre></div>
-pub async fn async_retry_fn<D, O, F, OR, R, E>(durations: D, mut operation: O) -> Result<R, E>
+#[cfg(feature = "tokio")]
+pub async fn async_retry_fn<D, O, F, OR, R, E>(durations: D, operation: O) -> Result<R, E>
 where
     D: IntoIterator<Item = Duration>,
     O: FnMut() -> F,
-    F: std::future::Future<Output = OR>,
+    F: Future<Output = OR>,
     OR: Into<OperationResult<R, E>>,
 {
-    async_retry!(durations, { operation().await })
+    async_retry_fn_with_sleeper(TokioSleeper, durations, operation).await
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// waiting between attempts with the given `AsyncSleeper`.
+///
+/// ```
+/// use retry_block::future::async_retry_fn_with_sleeper;
+/// use retry_block::delay::Fixed;
+/// use std::time::Duration;
+///
+/// async fn sleep(duration: Duration) {
+///     // hand the delay over to the timer of your runtime of choice
+/// #   let _ = duration;
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut tried = false;
+/// let result = async_retry_fn_with_sleeper(sleep, Fixed::new(Duration::from_secs(1)), || {
+///     let res = if tried { Ok(42) } else { Err("try again") };
+///     tried = true;
+///     async move { res }
+/// })
+/// .await;
+/// assert_eq!(result, Ok(42));
+/// # }
+/// ```
+pub async fn async_retry_fn_with_sleeper<Sl, D, O, F, OR, R, E>(
+    sleeper: Sl,
+    durations: D,
+    mut operation: O,
+) -> Result<R, E>
+where
+    Sl: AsyncSleeper,
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    async_retry_with!(|duration| sleeper.sleep(duration), durations, {
+        operation().await
+    })
+}
+
+/// Retry the given blocking operation until it succeeds, or until the given `Duration` iterator
+/// ends, running each attempt on `tokio::task::spawn_blocking` and sleeping asynchronously between
+/// them.
+///
+/// Panics of an attempt are isolated from the caller and considered retryable, as with
+/// `async_retry_spawn`.
+///
+/// ```
+/// use retry_block::future::async_retry_blocking;
+/// use retry_block::delay::NoDelay;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut attempts = 0;
+///     let result = async_retry_blocking(NoDelay, move || {
+///         attempts += 1;
+///         // e.g. a synchronous database driver
+///         std::thread::sleep(std::time::Duration::from_millis(1));
+///         if attempts < 3 {
+///             Err("try again")
+///         } else {
+///             Ok(attempts)
+///         }
+///     })
+///     .await;
+///     assert_eq!(result.unwrap(), 3);
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn async_retry_blocking<D, O, OR, R, E>(
+    durations: D,
+    operation: O,
+) -> Result<R, SpawnError<E>>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> OR + Send + 'static,
+    OR: Into<OperationResult<R, E>> + Send + 'static,
+{
+    let operation = std::sync::Arc::new(std::sync::Mutex::new(operation));
+    retry_spawned(durations, || {
+        let operation = operation.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut operation = operation.lock().unwrap_or_else(|e| e.into_inner());
+            (*operation)()
+        })
+    })
+    .await
+}
+
+/// Retry the given operation on a cloneable input until it succeeds, or until the given `Duration`
+/// iterator ends.
+///
+/// A fresh clone of the input is moved into each attempt, which is the supported way around the
+/// capture problem of `async_retry_fn`.
+///
+/// ```
+/// use retry_block::future::async_retry_call;
+/// use retry_block::delay::NoDelay;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let attempts = Arc::new(AtomicUsize::new(0));
+///
+///     let result = async_retry_call(NoDelay, attempts, |attempts| async move {
+///         if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+///             Err("try again")
+///         } else {
+///             Ok(attempts.load(Ordering::SeqCst))
+///         }
+///     })
+///     .await;
+///     assert_eq!(result, Ok(3));
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn async_retry_call<D, I, O, F, OR, R, E>(
+    durations: D,
+    input: I,
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    I: Clone,
+    O: FnMut(I) -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    async_retry!(durations, { operation(input.clone()).await })
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// waiting until the given rate limiter is ready before each attempt (need `governor` feature).
+///
+/// The wait of a retried attempt overlaps with its delay, so that it lasts for the longest of the
+/// two rather than for their sum. This is how `persist::RetryHandle::with_rate_limiter` limits
+/// persistent retries.
+///
+/// ```
+/// use governor::{Quota, RateLimiter};
+/// use retry_block::future::async_retry_fn_rate_limited;
+/// use retry_block::delay::NoDelay;
+/// use std::time::{Duration, Instant};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let limiter = RateLimiter::direct(Quota::with_period(Duration::from_millis(10)).unwrap());
+///     let start = Instant::now();
+///     let result = async_retry_fn_rate_limited(NoDelay.take(2), &limiter, || async {
+///         Err::<(), _>("unavailable")
+///     })
+///     .await;
+///     assert_eq!(result, Err("unavailable"));
+///     assert!(start.elapsed() >= Duration::from_millis(20));
+/// }
+/// ```
+#[cfg(all(feature = "governor", feature = "tokio"))]
+pub async fn async_retry_fn_rate_limited<D, O, F, OR, R, E>(
+    durations: D,
+    rate_limiter: &governor::DefaultDirectRateLimiter,
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    async_retry!(durations, {
+        rate_limiter.until_ready().await;
+        operation().await
+    })
+}
+
+/// Retry the operations prepared by the given factory until one succeeds, or until the given
+/// `Duration` iterator ends.
+///
+/// This is `retry_fn_prepared` for asynchronous operations: the factory is called with the index
+/// of the attempt and returns a `FnOnce` operation, which is free to move what it captured into
+/// the future it returns.
+///
+/// ```
+/// use retry_block::future::async_retry_fn_prepared;
+/// use retry_block::delay::NoDelay;
+///
+/// struct Request(Vec<u8>);
+///
+/// impl Request {
+///     async fn send(self, attempt: usize) -> Result<usize, &'static str> {
+///         if attempt < 2 { Err("try again") } else { Ok(self.0.len()) }
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let payload = vec![1, 2, 3];
+///     let result = async_retry_fn_prepared(NoDelay, |attempt| {
+///         let request = Request(payload.clone());
+///         move || request.send(attempt)
+///     })
+///     .await;
+///     assert_eq!(result, Ok(3));
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn async_retry_fn_prepared<D, P, O, F, OR, R, E>(
+    durations: D,
+    mut prepare: P,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    P: FnMut(usize) -> O,
+    O: FnOnce() -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut attempt = 0;
+    async_retry!(durations, {
+        let operation = prepare(attempt);
+        attempt += 1;
+        operation().await
+    })
+}
+
+/// Retry the given operation on a mutable state until it succeeds, or until the given `Duration`
+/// iterator ends.
+///
+/// The state is lent to each attempt, which sidesteps the `FnMut` capture problem of
+/// `async_retry_fn`. The operation is an async closure, so that the future of each attempt can
+/// borrow the state.
+///
+/// ```
+/// use retry_block::future::async_retry_fn_with_state;
+/// use retry_block::delay::NoDelay;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut attempts = Vec::new();
+///
+///     let result = async_retry_fn_with_state(NoDelay, &mut attempts, async |attempts| {
+///         attempts.push(attempts.len());
+///         if attempts.len() < 3 {
+///             Err("try again")
+///         } else {
+///             Ok(attempts.len())
+///         }
+///     })
+///     .await;
+///
+///     assert_eq!(result, Ok(3));
+///     assert_eq!(attempts, vec![0, 1, 2]);
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn async_retry_fn_with_state<D, S, O, OR, R, E>(
+    durations: D,
+    state: &mut S,
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: AsyncFnMut(&mut S) -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    async_retry!(durations, { operation(state).await })
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// giving each attempt a `RetryContext`.
+///
+/// The future returned by the operation cannot borrow the context: copy what it needs out of it.
+///
+/// ```
+/// use retry_block::future::async_retry_fn_with_context;
+/// use retry_block::delay::NoDelay;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let result = async_retry_fn_with_context(NoDelay.take(2), |context| {
+///         let attempt = context.attempt();
+///         async move {
+///             if attempt < 3 {
+///                 Err("try again")
+///             } else {
+///                 Ok(attempt)
+///             }
+///         }
+///     })
+///     .await;
+///     assert_eq!(result, Ok(3));
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn async_retry_fn_with_context<D, O, F, OR, R, E>(
+    durations: D,
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut(&RetryContext<E>) -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut it = crate::delay::__faulty(durations.into_iter());
+    // a mutex rather than a cell, so that the returned future is `Send`
+    let context = std::sync::Mutex::new(RetryContext::new(it.size_hint().1));
+    let lock = || context.lock().unwrap_or_else(|e| e.into_inner());
+    async_retry_loop(
+        |e| match it.next() {
+            Some(duration) => {
+                lock().next_attempt(e, it.size_hint().1);
+                Ok(duration)
+            }
+            None => Err(e),
+        },
+        || {
+            let attempt = operation(&lock());
+            async { attempt.await.into() }
+        },
+    )
+    .await
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// giving each attempt the next of the given endpoints, wrapping around, along with its index.
+///
+/// ```
+/// use retry_block::future::async_retry_fn_rotating;
+/// use retry_block::delay::NoDelay;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let replicas = ["primary:80", "secondary:80"];
+///     let value = async_retry_fn_rotating(NoDelay.take(2), &replicas, |replica, _| {
+///         let replica = *replica;
+///         async move {
+///             if replica == "secondary:80" {
+///                 Ok(replica)
+///             } else {
+///                 Err("unreachable")
+///             }
+///         }
+///     })
+///     .await;
+///     assert_eq!(value, Ok("secondary:80"));
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Panics if there are no endpoints.
+#[cfg(feature = "tokio")]
+pub async fn async_retry_fn_rotating<D, T, O, F, OR, R, E>(
+    durations: D,
+    endpoints: &[T],
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut(&T, usize) -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    assert!(!endpoints.is_empty(), "no endpoints to rotate between");
+    let mut attempt = 0;
+    async_retry!(durations, {
+        let endpoint = &endpoints[attempt % endpoints.len()];
+        let res = operation(endpoint, attempt).await;
+        attempt += 1;
+        res
+    })
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends, and
+/// tell whether a final error came from running out of delays or from `OperationResult::Err`.
+///
+/// ```
+/// use retry_block::future::async_retry_fn_classified;
+/// use retry_block::delay::NoDelay;
+/// use retry_block::RetryError;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let value = async_retry_fn_classified(NoDelay.take(1), || async { Err::<(), _>("unavailable") }).await;
+///     let error: RetryError<_> = value.unwrap_err();
+///     assert!(error.is_gave_up());
+///     assert_eq!(error.attempts(), 2);
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn async_retry_fn_classified<D, O, F, OR, R, E>(
+    durations: D,
+    mut operation: O,
+) -> Result<R, RetryError<E>>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    let durations = durations.into_iter();
+    let max_attempts = durations
+        .size_hint()
+        .1
+        .map(|retries| retries.saturating_add(1));
+    let start = tokio::time::Instant::now();
+    let mut attempts = 0;
+    let res = async_retry!(durations, {
+        attempts += 1;
+        match operation().await.into() {
+            OperationResult::Ok(res) => OperationResult::Ok(res),
+            OperationResult::Retry(e) => OperationResult::Retry((e, false)),
+            OperationResult::Err(e) => OperationResult::Err((e, true)),
+        }
+    });
+    res.map_err(|(e, fatal)| RetryError::new(e, fatal, attempts, start.elapsed(), max_attempts))
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// attaching its name, number of attempts and total delay to the final error.
+///
+/// The delays are accounted in `DelayAccounting::named`, as with the named arms of the retry
+/// macros.
+///
+/// ```
+/// use retry_block::future::async_retry_fn_named;
+/// use retry_block::delay::NoDelay;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let error = async_retry_fn_named("fetch", NoDelay.take(2), || async {
+///         Err::<(), _>("unreachable")
+///     })
+///     .await
+///     .unwrap_err();
+///     assert_eq!(error.attempts(), 3);
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn async_retry_fn_named<D, O, F, OR, R, E>(
+    operation_name: &'static str,
+    durations: D,
+    mut operation: O,
+) -> Result<R, WithRetryContext<E>>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut it = crate::DelayAccounting::named(operation_name)
+        .track(crate::delay::__faulty(durations.into_iter()));
+    let res = async_retry_loop(
+        |e| it.next().ok_or(e),
+        || {
+            let attempt = operation();
+            async { attempt.await.into() }
+        },
+    )
+    .await;
+    res.map_err(|e| {
+        let stats = it.stats();
+        WithRetryContext::new(
+            e,
+            operation_name,
+            stats.attempts as usize,
+            stats.total_sleep,
+        )
+    })
+}
+
+/// Retry the given operation until it succeeds, until the given `Duration` iterator ends, or until
+/// the given time budget is spent.
+///
+/// Each attempt is passed the remaining time budget, e.g. to set a timeout on the request it makes.
+/// A delay (e.g. a jittered one) that would outlast the budget is clamped to half of what remains
+/// of it, so that one last attempt still fits; the last error is returned after it.
+///
+/// ```
+/// use retry_block::future::async_retry_fn_with_deadline;
+/// use retry_block::delay::Fixed;
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let result = async_retry_fn_with_deadline(
+///         Fixed::new(Duration::from_millis(10)),
+///         Duration::from_millis(100),
+///         |remaining| async move {
+///             tokio::time::timeout(remaining, std::future::pending::<()>())
+///                 .await
+///                 .map_err(|_| "timed out")
+///         },
+///     )
+///     .await;
+///     assert_eq!(result, Err("timed out"));
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn async_retry_fn_with_deadline<D, O, F, OR, R, E>(
+    durations: D,
+    budget: Duration,
+    operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut(Duration) -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    async_retry_fn_with_deadline_using(crate::time::TokioTime, durations, budget, operation).await
+}
+
+/// Retry the given operation like `async_retry_fn_with_deadline`, measuring the budget and waiting
+/// for delays with the given `TimeSource`
+///
+/// ```
+/// use retry_block::future::async_retry_fn_with_deadline_using;
+/// use retry_block::delay::Fixed;
+/// use retry_block::time::MockTime;
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut attempts = 0;
+///     let result = async_retry_fn_with_deadline_using(
+///         MockTime::new(),
+///         Fixed::exact(Duration::from_secs(10)),
+///         Duration::from_secs(100),
+///         |_| {
+///             attempts += 1;
+///             async { Err::<(), _>("unavailable") }
+///         },
+///     )
+///     .await;
+///     assert_eq!(result, Err("unavailable"));
+///     assert_eq!(attempts, 11);
+/// }
+/// ```
+pub async fn async_retry_fn_with_deadline_using<T, D, O, F, OR, R, E>(
+    time: T,
+    durations: D,
+    budget: Duration,
+    mut operation: O,
+) -> Result<R, E>
+where
+    T: crate::time::TimeSource,
+    D: IntoIterator<Item = Duration>,
+    O: FnMut(Duration) -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    let deadline = time.now() + budget;
+    let remaining = || deadline.saturating_duration_since(time.now());
+    let durations = crate::fit_deadline(durations, remaining);
+    async_retry_with!(|duration| time.sleep(duration), durations, {
+        operation(remaining()).await
+    })
+}
+
+/// The asynchronous counterpart of `retry_loop`, sleeping with tokio
+#[cfg(feature = "tokio")]
+pub(crate) async fn async_retry_loop<R, E, F>(
+    mut next_delay: impl FnMut(E) -> Result<Duration, E>,
+    mut attempt: impl FnMut() -> F,
+) -> Result<R, E>
+where
+    F: Future<Output = OperationResult<R, E>>,
+{
+    loop {
+        match attempt().await {
+            OperationResult::Ok(res) => return Ok(res),
+            OperationResult::Err(e) => return Err(e),
+            OperationResult::Retry(e) => match next_delay(e) {
+                // skip the timer registration for zero delays
+                Ok(duration) if duration.is_zero() => {}
+                Ok(duration) => tokio::time::sleep(duration).await,
+                Err(e) => return Err(e),
+            },
+        }
+    }
+}
+
+/// The error of an operation whose attempts are spawned as separate tasks
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub enum SpawnError<E> {
+    /// The task of the last attempt panicked or was cancelled
+    Join {
+        error: tokio::task::JoinError,
+        attempts: usize,
+        elapsed: Duration,
+    },
+    /// The last attempt returned the given error
+    Error {
+        error: E,
+        attempts: usize,
+        elapsed: Duration,
+    },
+}
+
+#[cfg(feature = "tokio")]
+impl<E> SpawnError<E> {
+    /// The number of attempts made, including the first one
+    pub fn attempts(&self) -> usize {
+        match self {
+            Self::Join { attempts, .. } | Self::Error { attempts, .. } => *attempts,
+        }
+    }
+
+    /// The time elapsed since the start of the first attempt
+    pub fn elapsed(&self) -> Duration {
+        match self {
+            Self::Join { elapsed, .. } | Self::Error { elapsed, .. } => *elapsed,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<E: std::fmt::Display> std::fmt::Display for SpawnError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Join { error, .. } => error.fmt(f),
+            Self::Error { error, .. } => error.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<E: std::error::Error + 'static> std::error::Error for SpawnError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Join { error, .. } => Some(error),
+            Self::Error { error, .. } => Some(error),
+        }
+    }
+}
+
+/// Retry the given operation, classifying the error of its last attempt as a `SpawnError`
+#[cfg(feature = "tokio")]
+async fn retry_spawned<D, O, F, OR, R, E>(durations: D, mut attempt: O) -> Result<R, SpawnError<E>>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    F: Future<Output = Result<OR, tokio::task::JoinError>>,
+    OR: Into<OperationResult<R, E>>,
+{
+    let start = tokio::time::Instant::now();
+    let mut attempts = 0;
+    let res = async_retry!(durations, {
+        attempts += 1;
+        match attempt().await.map(Into::into) {
+            Ok(OperationResult::Ok(res)) => OperationResult::Ok(res),
+            Ok(OperationResult::Retry(e)) => OperationResult::Retry(Ok(e)),
+            Ok(OperationResult::Err(e)) => OperationResult::Err(Ok(e)),
+            Err(e) => OperationResult::Retry(Err(e)),
+        }
+    });
+    let elapsed = start.elapsed();
+    res.map_err(|e| match e {
+        Ok(error) => SpawnError::Error {
+            error,
+            attempts,
+            elapsed,
+        },
+        Err(error) => SpawnError::Join {
+            error,
+            attempts,
+            elapsed,
+        },
+    })
+}
+
+/// The error of an operation whose retries can be cancelled
+#[derive(Debug, Clone)]
+pub enum CancelError<E> {
+    /// Retrying was cancelled while waiting for the next attempt, after the given error
+    Cancelled {
+        error: E,
+        attempts: usize,
+        elapsed: Duration,
+    },
+    /// The last attempt returned the given error
+    Error {
+        error: E,
+        attempts: usize,
+        elapsed: Duration,
+    },
+}
+
+impl<E> CancelError<E> {
+    /// Whether retrying was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, Self::Cancelled { .. })
+    }
+
+    /// The number of attempts made, including the first one
+    pub fn attempts(&self) -> usize {
+        match self {
+            Self::Cancelled { attempts, .. } | Self::Error { attempts, .. } => *attempts,
+        }
+    }
+
+    /// The time elapsed since the start of the first attempt
+    pub fn elapsed(&self) -> Duration {
+        match self {
+            Self::Cancelled { elapsed, .. } | Self::Error { elapsed, .. } => *elapsed,
+        }
+    }
+
+    /// The error of the last attempt
+    pub fn error(&self) -> &E {
+        match self {
+            Self::Cancelled { error, .. } | Self::Error { error, .. } => error,
+        }
+    }
+
+    /// Return the error of the last attempt
+    pub fn into_inner(self) -> E {
+        match self {
+            Self::Cancelled { error, .. } | Self::Error { error, .. } => error,
+        }
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for CancelError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cancelled { error, .. } => write!(f, "retrying was cancelled after: {}", error),
+            Self::Error { error, .. } => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for CancelError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error())
+    }
+}
+
+/// Retry the given operation until it succeeds, until the given `Duration` iterator ends, or until
+/// the given cancellation future completes while waiting for the next attempt.
+///
+/// ```
+/// use retry_block::future::{async_retry_fn_cancellable, CancelError};
+/// use retry_block::delay::Fixed;
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let cancel = tokio::time::sleep(Duration::from_millis(10));
+///     let result = async_retry_fn_cancellable(Fixed::new(Duration::from_secs(60)), cancel, || async {
+///         Err::<(), _>("try again")
+///     })
+///     .await;
+///     let error = result.unwrap_err();
+///     assert!(matches!(error, CancelError::Cancelled { error: "try again", .. }));
+///     assert_eq!(error.attempts(), 1);
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn async_retry_fn_cancellable<D, C, O, F, OR, R, E>(
+    durations: D,
+    cancel: C,
+    mut operation: O,
+) -> Result<R, CancelError<E>>
+where
+    D: IntoIterator<Item = Duration>,
+    C: Future<Output = ()>,
+    O: FnMut() -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    tokio::pin!(cancel);
+    let mut it = crate::delay::__faulty(durations.into_iter());
+    let start = tokio::time::Instant::now();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let (error, cancelled) = match operation().await.into() {
+            OperationResult::Ok(res) => break Ok(res),
+            OperationResult::Err(e) => (e, false),
+            OperationResult::Retry(e) => match it.next() {
+                Some(duration) => tokio::select! {
+                    _ = tokio::time::sleep(duration) => continue,
+                    _ = &mut cancel => (e, true),
+                },
+                None => (e, false),
+            },
+        };
+        let elapsed = start.elapsed();
+        break Err(if cancelled {
+            CancelError::Cancelled {
+                error,
+                attempts,
+                elapsed,
+            }
+        } else {
+            CancelError::Error {
+                error,
+                attempts,
+                elapsed,
+            }
+        });
+    }
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// spawning each attempt as its own task.
+///
+/// Panics and cancellations of an attempt's task are isolated from the caller and considered
+/// retryable.
+///
+/// ```
+/// use retry_block::future::async_retry_spawn;
+/// use retry_block::delay::NoDelay;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let attempts = Arc::new(AtomicUsize::new(0));
+///
+///     let result = async_retry_spawn(NoDelay.take(1), || {
+///         let attempts = attempts.clone();
+///         async move {
+///             if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+///                 panic!("the first attempt panics");
+///             }
+///             Ok::<_, ()>(42)
+///         }
+///     })
+///     .await;
+///
+///     assert_eq!(result.unwrap(), 42);
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn async_retry_spawn<D, O, F, OR, R, E>(
+    durations: D,
+    mut operation: O,
+) -> Result<R, SpawnError<E>>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    F: Future<Output = OR> + Send + 'static,
+    OR: Into<OperationResult<R, E>> + Send + 'static,
+{
+    retry_spawned(durations, || tokio::spawn(operation())).await
+}
+
+pub(crate) enum State<F, SF> {
+    Ready,
+    Attempt(Pin<Box<F>>),
+    Sleep(Pin<Box<SF>>),
+    Done,
+}
+
+/// A future that retries an operation until it succeeds, or until the given `Duration` iterator
+/// ends.
+///
+/// Unlike `async_retry!` and `async_retry_fn`, this is a nameable type that can be stored in
+/// structs, boxed, raced with `select!` or polled manually.
+///
+/// ```
+/// use retry_block::future::RetryFuture;
+/// use retry_block::delay::Fixed;
+/// use std::time::Duration;
+///
+/// type Attempt = std::future::Ready<Result<u32, &'static str>>;
+///
+/// fn connect() -> Attempt {
+///     std::future::ready(Ok(42))
+/// }
+///
+/// struct Connection {
+///     retrying: RetryFuture<
+///         Fixed,
+///         fn() -> Attempt,
+///         Attempt,
+///         u32,
+///         &'static str,
+///         fn(Duration) -> tokio::time::Sleep,
+///         tokio::time::Sleep,
+///     >,
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let connection = Connection {
+///         retrying: RetryFuture::new(Fixed::new(Duration::from_millis(1)), connect),
+///     };
+///
+///     assert_eq!(connection.retrying.await, Ok(42));
+/// }
+/// ```
+pub struct RetryFuture<D, O, F, R, E, S, SF> {
+    durations: crate::delay::Faultable<D>,
+    operation: O,
+    sleep: S,
+    state: State<F, SF>,
+    output: PhantomData<fn() -> Result<R, E>>,
+}
+
+impl<D, O, F, R, E, S, SF> RetryFuture<D, O, F, R, E, S, SF>
+where
+    D: Iterator<Item = Duration>,
+{
+    /// Create a new `RetryFuture` from a delay iterator, an operation factory and a custom
+    /// asynchronous sleep function
+    pub fn with_sleep<I>(durations: I, operation: O, sleep: S) -> Self
+    where
+        I: IntoIterator<Item = Duration, IntoIter = D>,
+    {
+        Self {
+            durations: crate::delay::__faulty(durations.into_iter()),
+            operation,
+            sleep,
+            state: State::Ready,
+            output: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<D, O, F, R, E>
+    RetryFuture<D, O, F, R, E, fn(Duration) -> tokio::time::Sleep, tokio::time::Sleep>
+where
+    D: Iterator<Item = Duration>,
+{
+    /// Create a new `RetryFuture` from a delay iterator and an operation factory, sleeping with
+    /// `tokio::time::sleep`
+    pub fn new<I>(durations: I, operation: O) -> Self
+    where
+        I: IntoIterator<Item = Duration, IntoIter = D>,
+    {
+        Self::with_sleep(durations, operation, tokio::time::sleep)
+    }
+}
+
+// The pending attempt and sleep futures are boxed, so no field is ever structurally pinned.
+impl<D, O, F, R, E, S, SF> Unpin for RetryFuture<D, O, F, R, E, S, SF> {}
+
+impl<D, O, F, OR, R, E, S, SF> Future for RetryFuture<D, O, F, R, E, S, SF>
+where
+    D: Iterator<Item = Duration>,
+    O: FnMut() -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+    S: FnMut(Duration) -> SF,
+    SF: Future<Output = ()>,
+{
+    type Output = Result<R, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                State::Ready => this.state = State::Attempt(Box::pin((this.operation)())),
+                State::Attempt(attempt) => match ready!(attempt.as_mut().poll(cx)).into() {
+                    OperationResult::Ok(res) => {
+                        this.state = State::Done;
+                        return Poll::Ready(Ok(res));
+                    }
+                    OperationResult::Err(e) => {
+                        this.state = State::Done;
+                        return Poll::Ready(Err(e));
+                    }
+                    OperationResult::Retry(e) => {
+                        if let Some(duration) = this.durations.next() {
+                            this.state = State::Sleep(Box::pin((this.sleep)(duration)));
+                        } else {
+                            this.state = State::Done;
+                            return Poll::Ready(Err(e));
+                        }
+                    }
+                },
+                State::Sleep(sleep) => {
+                    ready!(sleep.as_mut().poll(cx));
+                    this.state = State::Ready;
+                }
+                State::Done => panic!("`RetryFuture` polled after completion"),
+            }
+        }
+    }
+}
+
+/// An extension trait for operation factories, i.e. closures returning a new future for each
+/// attempt, to retry them as a `RetryFuture`
+///
+/// ```
+/// use retry_block::future::FutureFactoryExt;
+/// use retry_block::delay::Exponential;
+/// use std::time::Duration;
+///
+/// async fn get(url: &str) -> Result<String, &'static str> {
+///     Ok(format!("GET {url}"))
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let url = "https://example.com";
+///     let body = (|| get(url))
+///         .retry(Exponential::exact(Duration::from_millis(10)).take(3))
+///         .await;
+///     assert_eq!(body.unwrap(), "GET https://example.com");
+/// }
+/// ```
+pub trait FutureFactoryExt<F>: FnMut() -> F + Sized
+where
+    F: Future,
+{
+    /// Retry the futures returned by this factory according to the given delays, sleeping with
+    /// `tokio::time::sleep`
+    #[cfg(feature = "tokio")]
+    #[allow(clippy::type_complexity)]
+    fn retry<I, R, E>(
+        self,
+        durations: I,
+    ) -> RetryFuture<
+        I::IntoIter,
+        Self,
+        F,
+        R,
+        E,
+        fn(Duration) -> tokio::time::Sleep,
+        tokio::time::Sleep,
+    >
+    where
+        I: IntoIterator<Item = Duration>,
+        F::Output: Into<OperationResult<R, E>>,
+    {
+        RetryFuture::new(durations, self)
+    }
+
+    /// Retry the futures returned by this factory according to the given delays, sleeping with
+    /// the given asynchronous sleep function
+    fn retry_with_sleep<I, R, E, S, SF>(
+        self,
+        durations: I,
+        sleep: S,
+    ) -> RetryFuture<I::IntoIter, Self, F, R, E, S, SF>
+    where
+        I: IntoIterator<Item = Duration>,
+        F::Output: Into<OperationResult<R, E>>,
+        S: FnMut(Duration) -> SF,
+        SF: Future<Output = ()>,
+    {
+        RetryFuture::with_sleep(durations, self, sleep)
+    }
+}
+
+impl<O, F> FutureFactoryExt<F> for O
+where
+    O: FnMut() -> F,
+    F: Future,
+{
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod test {
+    use crate::delay::NoDelay;
+    use crate::future::{async_retry_spawn, FutureFactoryExt, RetryFuture, SpawnError};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn spawned_state() {
+        use crate::future::async_retry_fn_with_state;
+
+        // the future of each attempt holds the state across an await point, and is still Send
+        let retried = tokio::spawn(async {
+            let mut attempts = Vec::new();
+            let result = async_retry_fn_with_state(NoDelay, &mut attempts, async |attempts| {
+                tokio::task::yield_now().await;
+                attempts.push(());
+                if attempts.len() < 3 {
+                    Err(attempts.len())
+                } else {
+                    Ok(attempts.len())
+                }
+            })
+            .await;
+            (result, attempts.len())
+        });
+        assert_eq!(retried.await.unwrap(), (Ok(3), 3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn spawned_context_and_name() {
+        use crate::future::{async_retry_fn_named, async_retry_fn_with_context};
+        use crate::DelayAccounting;
+
+        let delays = [Duration::from_millis(1), Duration::from_millis(2)];
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let context = tokio::spawn({
+            let seen = seen.clone();
+            async_retry_fn_with_context(delays, move |context| {
+                let attempt = context.attempt();
+                seen.lock()
+                    .unwrap()
+                    .push((attempt, context.previous_error().copied()));
+                async move { Err::<(), _>(attempt) }
+            })
+        });
+        assert_eq!(context.await.unwrap(), Err(3));
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![(1, None), (2, Some(1)), (3, Some(2))]
+        );
+
+        let named = tokio::spawn(async_retry_fn_named("spawned_name", delays, || async {
+            Err::<(), _>("unavailable")
+        }));
+        let error = named.await.unwrap().unwrap_err();
+        assert_eq!(
+            (error.attempts(), error.total_delay()),
+            (3, Duration::from_millis(3))
+        );
+        let stats = DelayAccounting::named("spawned_name").stats();
+        assert_eq!((stats.calls, stats.attempts), (1, 3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deadline_clamped_delay() {
+        use crate::delay::Fixed;
+        use crate::future::async_retry_fn_with_deadline;
+
+        let start = tokio::time::Instant::now();
+        let mut attempts = Vec::new();
+        let result = async_retry_fn_with_deadline(
+            Fixed::exact(Duration::from_secs(4)),
+            Duration::from_secs(10),
+            |remaining| {
+                attempts.push((start.elapsed().as_secs(), remaining.as_secs()));
+                async { Err::<(), _>("unavailable") }
+            },
+        )
+        .await;
+        assert_eq!(result, Err("unavailable"));
+        // the third delay would outlast the deadline, and is clamped to half of what remains
+        assert_eq!(attempts, vec![(0, 10), (4, 6), (8, 2), (9, 1)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn classified_error() {
+        use crate::delay::Fixed;
+        use crate::future::async_retry_fn_classified;
+        use crate::OperationResult;
+
+        let delays = Fixed::exact(Duration::from_secs(1)).take(2);
+        let error = async_retry_fn_classified(delays, || async { Err::<(), _>("unavailable") })
+            .await
+            .unwrap_err();
+        assert!(error.is_gave_up());
+        assert_eq!(error.attempts(), 3);
+        assert_eq!(error.max_attempts(), Some(3));
+        assert_eq!(error.elapsed(), Duration::from_secs(2));
+
+        let mut attempts = 0;
+        let error = async_retry_fn_classified(Fixed::exact(Duration::from_secs(1)), || {
+            attempts += 1;
+            let res = if attempts < 2 {
+                OperationResult::Retry("unavailable")
+            } else {
+                OperationResult::<(), _>::Err("rejected")
+            };
+            async move { res }
+        })
+        .await
+        .unwrap_err();
+        assert!(error.is_fatal());
+        assert_eq!((error.attempts(), error.max_attempts()), (2, None));
+        assert_eq!(error.elapsed(), Duration::from_secs(1));
+        assert_eq!(error.to_string(), "fatal error: rejected");
+    }
+
+    #[tokio::test]
+    async fn retry_spawn_exhausted() {
+        let mut attempts = 0;
+        let res = async_retry_spawn(NoDelay.take(2), || {
+            attempts += 1;
+            async { Err::<(), _>("failed") }
+        })
+        .await;
+        let error = res.unwrap_err();
+        assert!(matches!(
+            error,
+            SpawnError::Error {
+                error: "failed",
+                ..
+            }
+        ));
+        assert_eq!(error.attempts(), 3);
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn retry_future_retries_until_exhausted() {
+        let mut attempts = 0;
+        let res: Result<(), _> = RetryFuture::new(NoDelay.take(2), || {
+            attempts += 1;
+            std::future::ready(Err(attempts))
+        })
+        .await;
+        assert_eq!(res, Err(3));
+    }
+
+    #[tokio::test]
+    async fn retry_future_factory() {
+        let mut attempts = 0;
+        let res = (|| {
+            attempts += 1;
+            std::future::ready(if attempts < 3 {
+                Err(attempts)
+            } else {
+                Ok(attempts)
+            })
+        })
+        .retry(NoDelay.take(2))
+        .await;
+        assert_eq!(res, Ok(3));
+
+        let mut slept = 0;
+        let res = (|| std::future::ready(Err::<(), _>("failed")))
+            .retry_with_sleep(NoDelay.take(2), |_| {
+                slept += 1;
+                std::future::ready(())
+            })
+            .await;
+        assert_eq!((res, slept), (Err("failed"), 2));
+    }
+
+    #[tokio::test]
+    async fn retry_future_custom_sleep() {
+        let mut slept = Vec::new();
+        let mut attempts = 0;
+        let res: Result<_, ()> = RetryFuture::with_sleep(
+            [Duration::from_millis(1), Duration::from_millis(2)],
+            || {
+                attempts += 1;
+                std::future::ready(if attempts == 3 { Ok(attempts) } else { Err(()) })
+            },
+            |duration| {
+                slept.push(duration);
+                std::future::ready(())
+            },
+        )
+        .await;
+        assert_eq!(res, Ok(3));
+        assert_eq!(slept, [Duration::from_millis(1), Duration::from_millis(2)]);
+    }
 }
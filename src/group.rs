@@ -0,0 +1,402 @@
+use crate::future::async_retry_fn;
+use crate::OperationResult;
+use std::any::Any;
+use std::future::{poll_fn, Future};
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+
+type Attempt<'a> = Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send + 'a>>;
+
+/// A group of differently-typed operations retried concurrently with the same delays
+///
+/// The operations, their futures and results are `Send`, so that a group can be run on any task,
+/// e.g. one spawned with `tokio::spawn`.
+///
+/// ```
+/// use retry_block::RetryGroup;
+/// use retry_block::delay::NoDelay;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut group = RetryGroup::new(NoDelay.take(3));
+///     let database = group.add(|| async { Ok::<_, String>("connected") });
+///     let cache = group.add(|| async { Err::<u32, _>(()) });
+///
+///     let mut results = group.run_all().await;
+///     assert_eq!(results.take(database), Some(Ok("connected")));
+///     assert_eq!(results.take(cache), Some(Err(())));
+/// }
+/// ```
+pub struct RetryGroup<'a, D> {
+    durations: D,
+    attempts: Vec<Attempt<'a>>,
+}
+
+/// The key to the result of an operation of a `RetryGroup`
+pub struct GroupKey<R, E> {
+    index: usize,
+    output: PhantomData<fn() -> Result<R, E>>,
+}
+
+impl<R, E> Clone for GroupKey<R, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R, E> Copy for GroupKey<R, E> {}
+
+/// The results of all the operations of a `RetryGroup`
+pub struct GroupResults {
+    results: Vec<Option<Box<dyn Any + Send>>>,
+}
+
+impl<'a, D> RetryGroup<'a, D>
+where
+    D: IntoIterator<Item = Duration> + Clone + Send + 'a,
+{
+    /// Create a new empty group retrying its operations with the given cloneable delay iterator
+    pub fn new(durations: D) -> Self {
+        Self {
+            durations,
+            attempts: Vec::new(),
+        }
+    }
+
+    /// Add an operation to the group, returning the key to its result
+    pub fn add<O, F, OR, R, E>(&mut self, operation: O) -> GroupKey<R, E>
+    where
+        O: FnMut() -> F + Send + 'a,
+        F: Future<Output = OR> + Send + 'a,
+        OR: Into<OperationResult<R, E>> + 'a,
+        R: Send + 'static,
+        E: Send + 'static,
+        D::IntoIter: Send,
+    {
+        let durations = self.durations.clone();
+        self.attempts.push(Box::pin(async move {
+            Box::new(async_retry_fn(durations, operation).await) as Box<dyn Any + Send>
+        }));
+        GroupKey {
+            index: self.attempts.len() - 1,
+            output: PhantomData,
+        }
+    }
+
+    /// Retry all the operations of the group concurrently until they all succeed or give up
+    pub async fn run_all(mut self) -> GroupResults {
+        let mut results: Vec<Option<Box<dyn Any + Send>>> =
+            self.attempts.iter().map(|_| None).collect();
+        poll_fn(|cx| {
+            let mut done = true;
+            for (result, attempt) in results.iter_mut().zip(&mut self.attempts) {
+                if result.is_none() {
+                    match attempt.as_mut().poll(cx) {
+                        Poll::Ready(res) => *result = Some(res),
+                        Poll::Pending => done = false,
+                    }
+                }
+            }
+            if done {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+        GroupResults { results }
+    }
+}
+
+impl GroupResults {
+    /// Take the result of the operation with the given key, if not already taken
+    pub fn take<R: 'static, E: 'static>(&mut self, key: GroupKey<R, E>) -> Option<Result<R, E>> {
+        let result: Box<dyn Any> = self.results.get_mut(key.index)?.take()?;
+        result.downcast().ok().map(|result| *result)
+    }
+}
+
+/// Retry several equivalent operations concurrently (e.g. against replica endpoints) until the
+/// given number of them succeed, each with its own copy of the given delays
+///
+/// Only the operations that failed are retried. As soon as `required` operations succeeded, the
+/// others are cancelled and the successful results are returned along with the index of their
+/// operation, in order of completion. As soon as a quorum can no longer be reached, the errors of
+/// the operations that gave up are returned instead.
+///
+/// ```
+/// use retry_block::retry_quorum;
+/// use retry_block::delay::NoDelay;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let replicas = ["a", "b", "down"];
+///     let writes = replicas.iter().map(|replica| {
+///         move || async move {
+///             if *replica == "down" {
+///                 Err(format!("{} is down", replica))
+///             } else {
+///                 Ok(*replica)
+///             }
+///         }
+///     });
+///     let written = retry_quorum(NoDelay.take(2), writes, 2).await.unwrap();
+///     assert_eq!(written.len(), 2);
+/// }
+/// ```
+pub async fn retry_quorum<D, I, O, F, OR, R, E>(
+    durations: D,
+    operations: I,
+    required: usize,
+) -> Result<Vec<(usize, R)>, Vec<(usize, E)>>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    I: IntoIterator<Item = O>,
+    O: FnMut() -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut attempts: Vec<_> = operations
+        .into_iter()
+        .map(|operation| Some(Box::pin(async_retry_fn(durations.clone(), operation))))
+        .collect();
+    let mut successes = Vec::new();
+    let mut errors = Vec::new();
+    poll_fn(|cx| {
+        for (index, slot) in attempts.iter_mut().enumerate() {
+            let Some(attempt) = slot else { continue };
+            if let Poll::Ready(result) = attempt.as_mut().poll(cx) {
+                *slot = None;
+                match result {
+                    Ok(res) => successes.push((index, res)),
+                    Err(e) => errors.push((index, e)),
+                }
+            }
+        }
+        let running = attempts.iter().filter(|slot| slot.is_some()).count();
+        if successes.len() >= required || successes.len() + running < required {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    if successes.len() >= required {
+        Ok(successes)
+    } else {
+        Err(errors)
+    }
+}
+
+type Task<'a, R, E> = Pin<Box<dyn Future<Output = Result<R, E>> + 'a>>;
+
+/// The scope of `retry_scope`, in which retried operations are spawned
+pub struct SpawnScope<'a, R, E> {
+    tasks: Vec<Task<'a, R, E>>,
+    cancel_on_error: bool,
+}
+
+impl<'a, R: 'a, E: 'a> SpawnScope<'a, R, E> {
+    /// Retry the given operation with the given delays concurrently with the rest of the scope,
+    /// returning the index of its result
+    pub fn spawn_retry<D, O, F, OR>(&mut self, durations: D, operation: O) -> usize
+    where
+        D: IntoIterator<Item = Duration> + 'a,
+        O: FnMut() -> F + 'a,
+        F: Future<Output = OR> + 'a,
+        OR: Into<OperationResult<R, E>> + 'a,
+    {
+        self.tasks
+            .push(Box::pin(async_retry_fn(durations, operation)));
+        self.tasks.len() - 1
+    }
+
+    /// Whether the operations still running are cancelled as soon as one of them gives up with an
+    /// error (on by default)
+    pub fn cancel_on_error(&mut self, cancel: bool) {
+        self.cancel_on_error = cancel;
+    }
+}
+
+/// Retry the operations spawned by the given function concurrently, each with its own delays, and
+/// wait for all of them
+///
+/// The results are returned in order of spawning. Unless disabled with
+/// `SpawnScope::cancel_on_error`, once an operation gives up with an error, the others still
+/// running are cancelled and have no result. Unlike tasks spawned on a runtime, the operations may
+/// borrow from their surroundings, as they are all polled by the returned future.
+///
+/// ```
+/// use retry_block::retry_scope;
+/// use retry_block::delay::NoDelay;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let results = retry_scope(|scope| {
+///         scope.spawn_retry(NoDelay.take(3), || async { Ok("database") });
+///         scope.spawn_retry(NoDelay.take(3), || async { Err("cache is down") });
+///         scope.spawn_retry(NoDelay, || std::future::pending::<Result<_, &str>>());
+///     })
+///     .await;
+///     assert_eq!(results, vec![Some(Ok("database")), Some(Err("cache is down")), None]);
+/// }
+/// ```
+pub async fn retry_scope<'a, S, R, E>(spawn: S) -> Vec<Option<Result<R, E>>>
+where
+    S: FnOnce(&mut SpawnScope<'a, R, E>),
+{
+    let mut scope = SpawnScope {
+        tasks: Vec::new(),
+        cancel_on_error: true,
+    };
+    spawn(&mut scope);
+    let cancel_on_error = scope.cancel_on_error;
+    let mut tasks: Vec<_> = scope.tasks.into_iter().map(Some).collect();
+    let mut results: Vec<_> = tasks.iter().map(|_| None).collect();
+    poll_fn(|cx| {
+        for (result, slot) in results.iter_mut().zip(&mut tasks) {
+            let Some(task) = slot else { continue };
+            if let Poll::Ready(res) = task.as_mut().poll(cx) {
+                *slot = None;
+                let failed = res.is_err();
+                *result = Some(res);
+                if failed && cancel_on_error {
+                    return Poll::Ready(());
+                }
+            }
+        }
+        if tasks.iter().all(Option::is_none) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    results
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delay::NoDelay;
+    use crate::{retry_quorum, retry_scope, RetryGroup};
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn spawned_group() {
+        let mut group = RetryGroup::new(NoDelay.take(2));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let flaky = group.add({
+            let calls = calls.clone();
+            move || {
+                let calls = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    if calls < 3 {
+                        Err(calls)
+                    } else {
+                        Ok(format!("after {} calls", calls))
+                    }
+                }
+            }
+        });
+        let failing = group.add(|| async { Err::<(), _>("unavailable") });
+
+        let mut results = tokio::spawn(group.run_all()).await.unwrap();
+        assert_eq!(results.take(flaky), Some(Ok("after 3 calls".to_string())));
+        assert_eq!(results.take(failing), Some(Err("unavailable")));
+        // each result is taken once
+        assert_eq!(results.take(flaky), None);
+    }
+
+    #[tokio::test]
+    async fn concurrent_group() {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let mut sender = Some(sender);
+        let receiver = tokio::sync::Mutex::new(Some(receiver));
+        let mut group = RetryGroup::new(NoDelay.take(1));
+        // waits for the next operation, which it would deadlock on if run sequentially
+        let waiting = group.add(|| async {
+            let receiver = receiver.lock().await.take();
+            match receiver {
+                Some(receiver) => receiver.await.map_err(|_| ()),
+                None => Err(()),
+            }
+        });
+        let sending = group.add(move || {
+            let sent = sender.take().map(|sender| sender.send(42));
+            async move { sent.ok_or(()).map(drop) }
+        });
+
+        let mut results = group.run_all().await;
+        assert_eq!(results.take(waiting), Some(Ok(42)));
+        assert_eq!(results.take(sending), Some(Ok(())));
+    }
+
+    #[tokio::test]
+    async fn reached_quorum() {
+        let calls = Cell::new(0);
+        let operations = (0..4).map(|index| {
+            let calls = &calls;
+            move || async move {
+                calls.set(calls.get() + 1);
+                match index {
+                    0 | 2 => Ok(index),
+                    // never completes, and is cancelled once the quorum is reached
+                    3 => std::future::pending().await,
+                    _ => Err(index),
+                }
+            }
+        });
+        let successes = retry_quorum(NoDelay.take(1), operations, 2).await.unwrap();
+        assert_eq!(successes, vec![(0, 0), (2, 2)]);
+        // the failed operation was retried once, the pending one was cancelled
+        assert_eq!(calls.get(), 5);
+    }
+
+    #[tokio::test]
+    async fn unreachable_quorum() {
+        let calls = Cell::new(0);
+        let operations = (0..3).map(|index| {
+            let calls = &calls;
+            move || async move {
+                calls.set(calls.get() + 1);
+                if index == 0 {
+                    Ok(index)
+                } else {
+                    Err(index)
+                }
+            }
+        });
+        let errors = retry_quorum(NoDelay.take(1), operations, 2)
+            .await
+            .unwrap_err();
+        assert_eq!(errors, vec![(1, 1), (2, 2)]);
+        // only the failed operations were retried
+        assert_eq!(calls.get(), 5);
+    }
+
+    #[tokio::test]
+    async fn scope_without_cancellation() {
+        let calls = Cell::new(0);
+        let results = retry_scope(|scope| {
+            scope.cancel_on_error(false);
+            scope.spawn_retry(NoDelay.take(1), || async { Err::<u32, _>("fatal") });
+            scope.spawn_retry(NoDelay.take(3), || {
+                calls.set(calls.get() + 1);
+                let calls = calls.get();
+                async move {
+                    if calls < 3 {
+                        Err("not yet")
+                    } else {
+                        Ok(calls)
+                    }
+                }
+            });
+        })
+        .await;
+        assert_eq!(results, vec![Some(Err("fatal")), Some(Ok(3))]);
+    }
+}
@@ -0,0 +1,146 @@
+//! Retries paused while a dependency is known to be down
+//!
+//! A single health check (or circuit breaker, or operator switch) publishes the `Health` of a
+//! dependency on a `tokio::sync::watch` channel, and every retry loop using that dependency
+//! subscribes to it: while it is down, they stop attempting and keep their delays for later,
+//! instead of each burning through its schedule during the outage.
+
+use crate::OperationResult;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// The health of a dependency, as published on a `watch` channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Health {
+    /// The dependency is available, or thought to be
+    #[default]
+    Up,
+    /// The dependency is known to be unavailable
+    Down,
+}
+
+/// Wait until the dependency is up, or until its health is no longer published
+async fn healthy(health: &mut watch::Receiver<Health>) {
+    while *health.borrow_and_update() == Health::Down {
+        if health.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Wait until the dependency goes down, forever if its health is no longer published
+async fn went_down(health: &mut watch::Receiver<Health>) {
+    loop {
+        if health.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+        if *health.borrow_and_update() == Health::Down {
+            return;
+        }
+    }
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// pausing while the given receiver tells that the dependency is down
+///
+/// No attempt is made while the dependency is down: a retryable failure waits for it to be up
+/// again without taking a delay, and a delay is cut short if it goes down meanwhile. Once it is up
+/// again, the next attempt is made right away. If the sender is dropped, the last health it
+/// published is ignored from then on.
+///
+/// ```
+/// use retry_block::delay::Fixed;
+/// use retry_block::health::{async_retry_fn_with_health, Health};
+/// use std::time::Duration;
+///
+/// #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// async fn main() {
+///     let (health, receiver) = tokio::sync::watch::channel(Health::Down);
+///     tokio::spawn(async move {
+///         // e.g. a health check
+///         tokio::time::sleep(Duration::from_secs(600)).await;
+///         health.send(Health::Up).unwrap();
+///     });
+///     let mut attempts = 0;
+///     let result = async_retry_fn_with_health(
+///         receiver,
+///         Fixed::exact(Duration::from_secs(1)).take(3),
+///         || {
+///             attempts += 1;
+///             async { Ok::<_, ()>("connected") }
+///         },
+///     )
+///     .await;
+///     assert_eq!(result, Ok("connected"));
+///     assert_eq!(attempts, 1);
+/// }
+/// ```
+pub async fn async_retry_fn_with_health<D, O, F, OR, R, E>(
+    mut health: watch::Receiver<Health>,
+    durations: D,
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut it = crate::delay::__faulty(durations.into_iter());
+    loop {
+        healthy(&mut health).await;
+        match operation().await.into() {
+            OperationResult::Ok(res) => break Ok(res),
+            OperationResult::Err(e) => break Err(e),
+            OperationResult::Retry(e) => {
+                if *health.borrow_and_update() == Health::Down {
+                    continue;
+                }
+                let Some(duration) = it.next() else {
+                    break Err(e);
+                };
+                tokio::select! {
+                    _ = tokio::time::sleep(duration) => {}
+                    _ = went_down(&mut health) => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{async_retry_fn_with_health, Health};
+    use crate::delay::Fixed;
+    use std::cell::Cell;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn paused_while_down() {
+        let start = Instant::now();
+        let (health, receiver) = tokio::sync::watch::channel(Health::Up);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            health.send(Health::Down).unwrap();
+            tokio::time::sleep(Duration::from_secs(100)).await;
+            health.send(Health::Up).unwrap();
+        });
+        let attempts = Cell::new(Vec::new());
+        let result = async_retry_fn_with_health(
+            receiver,
+            Fixed::exact(Duration::from_secs(60)).take(1),
+            || {
+                let mut at = attempts.take();
+                at.push(start.elapsed().as_secs());
+                attempts.set(at);
+                async { Err::<(), _>(()) }
+            },
+        )
+        .await;
+        // the only delay was cut short by the outage, and the last attempt made once it was over
+        assert_eq!(result, Err(()));
+        assert_eq!(attempts.take(), vec![0, 105]);
+    }
+}
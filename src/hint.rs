@@ -0,0 +1,240 @@
+use crate::{OperationResult, RetryableError};
+use std::time::{Duration, SystemTime};
+
+/// What a failed attempt tells about retrying it, beyond its error
+///
+/// Carried along with the error by `Hinted`, so that the hint reaches whatever handles the final
+/// error (a give-up hook, a persisted `Status::Failure`, a report) rather than being dropped once
+/// the next delay is chosen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hint {
+    /// The delay to wait before the next attempt, instead of the next delay of the policy
+    pub suggested_delay: Option<Duration>,
+    /// The earliest time of the next attempt, e.g. when a quota is replenished
+    pub do_not_retry_before: Option<SystemTime>,
+    /// Why the attempt failed, in words fit for logs and reports
+    pub reason: Option<String>,
+}
+
+impl Hint {
+    /// The delay to wait before the next attempt, given the next delay of the policy
+    ///
+    /// The suggested delay replaces the scheduled one, and the wait is lengthened if needed to
+    /// last until the earliest time of the next attempt.
+    pub fn next_delay(&self, scheduled: Duration) -> Duration {
+        let delay = self.suggested_delay.unwrap_or(scheduled);
+        let until = self
+            .do_not_retry_before
+            .and_then(|not_before| not_before.duration_since(SystemTime::now()).ok());
+        until.map_or(delay, |until| delay.max(until))
+    }
+}
+
+/// An error along with a `Hint` about retrying the attempt that failed with it
+///
+/// Returned in `OperationResult::Retry` by `OperationResult::retry_with`, it is a retryable
+/// `RetryableError` whatever its error, whose `RetryableError::next_delay` is `Hint::next_delay`:
+/// the hint is honored wherever such errors are, by `retry_fn_hinted`, by `tower` retries and by
+/// a `RetryInjector` returning `RetryableError::next_delay` from its `reschedule`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hinted<E> {
+    /// The error of the attempt
+    pub error: E,
+    /// The hint about retrying it
+    pub hint: Hint,
+}
+
+impl<E> Hinted<E> {
+    /// Attach the given hint to an error
+    pub fn new(error: E, hint: Hint) -> Self {
+        Self { error, hint }
+    }
+
+    /// Returns the error, dropping the hint.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for Hinted<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.hint.reason {
+            Some(reason) => write!(f, "{} ({})", self.error, reason),
+            None => self.error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for Hinted<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl<E> RetryableError for Hinted<E> {
+    fn is_retryable(&self) -> bool {
+        true
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        self.hint.suggested_delay
+    }
+
+    fn next_delay(&self, scheduled: Duration) -> Duration {
+        self.hint.next_delay(scheduled)
+    }
+}
+
+impl<T, E> OperationResult<T, Hinted<E>> {
+    /// Retry with the given error, along with a hint about retrying it
+    ///
+    /// ```
+    /// # use retry_block::{Hint, OperationResult};
+    /// # use std::time::Duration;
+    /// let hint = Hint {
+    ///     suggested_delay: Some(Duration::from_secs(60)),
+    ///     reason: Some("quota exceeded".to_string()),
+    ///     ..Hint::default()
+    /// };
+    /// let res = OperationResult::<(), _>::retry_with(429, hint);
+    /// assert!(matches!(res, OperationResult::Retry(e) if e.error == 429));
+    /// ```
+    pub fn retry_with(error: E, hint: Hint) -> Self {
+        OperationResult::Retry(Hinted::new(error, hint))
+    }
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// waiting as suggested by its errors instead of the next delay.
+///
+/// The delay after each error is its `RetryableError::next_delay`, e.g. as hinted by a `Hinted`
+/// error. It replaces the next delay but still uses it up, so that the policy keeps deciding how
+/// many attempts are made. The final error keeps its hint.
+///
+/// ```
+/// # use retry_block::{retry_fn_hinted, Hint, OperationResult};
+/// # use retry_block::delay::Fixed;
+/// # use std::time::{Duration, Instant};
+/// let start = Instant::now();
+/// let error = retry_fn_hinted(Fixed::exact(Duration::from_secs(3600)).take(1), || {
+///     let hint = Hint {
+///         suggested_delay: Some(Duration::from_millis(1)),
+///         reason: Some("throttled".to_string()),
+///         ..Hint::default()
+///     };
+///     OperationResult::<(), _>::retry_with("unavailable", hint)
+/// })
+/// .unwrap_err();
+/// assert!(start.elapsed() < Duration::from_secs(3600));
+/// assert_eq!(error.to_string(), "unavailable (throttled)");
+/// ```
+pub fn retry_fn_hinted<D, O, OR, R, E>(durations: D, mut operation: O) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> OR,
+    OR: Into<OperationResult<R, E>>,
+    E: RetryableError,
+{
+    let mut it = crate::delay::__faulty(durations.into_iter());
+    crate::retry_loop(
+        |e: E| it.next().map(|duration| e.next_delay(duration)).ok_or(e),
+        crate::sleep::thread_sleep,
+        || operation().into(),
+    )
+}
+
+/// Retry the given asynchronous operation until it succeeds, or until the given `Duration`
+/// iterator ends, waiting as suggested by its errors instead of the next delay.
+///
+/// See `retry_fn_hinted`.
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub async fn async_retry_fn_hinted<D, O, F, OR, R, E>(
+    durations: D,
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    F: std::future::Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+    E: RetryableError,
+{
+    let mut it = crate::delay::__faulty(durations.into_iter());
+    crate::future::async_retry_loop(
+        |e: E| it.next().map(|duration| e.next_delay(duration)).ok_or(e),
+        || {
+            let attempt = operation();
+            async { attempt.await.into() }
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{retry_fn_hinted, Hint, Hinted};
+    use crate::delay::Fixed;
+    use crate::{OperationResult, RetryableError};
+    use std::time::{Duration, Instant, SystemTime};
+
+    struct Throttled;
+
+    impl RetryableError for Throttled {
+        fn is_retryable(&self) -> bool {
+            true
+        }
+
+        fn retry_after(&self) -> Option<Duration> {
+            Some(Duration::ZERO)
+        }
+    }
+
+    #[test]
+    fn suggested_delays() {
+        let start = Instant::now();
+        let mut attempts = 0;
+        let res = retry_fn_hinted(Fixed::exact(Duration::from_secs(3600)).take(2), || {
+            attempts += 1;
+            OperationResult::<(), _>::Retry(Throttled)
+        });
+        assert!(res.is_err());
+        assert_eq!(attempts, 3);
+        assert!(start.elapsed() < Duration::from_secs(3600));
+
+        let hinted = Hinted::new(
+            (),
+            Hint {
+                suggested_delay: Some(Duration::ZERO),
+                ..Hint::default()
+            },
+        );
+        assert!(hinted.is_retryable());
+        assert_eq!(hinted.next_delay(Duration::from_secs(1)), Duration::ZERO);
+    }
+
+    #[test]
+    fn hinted_delays() {
+        let second = Duration::from_secs(1);
+        assert_eq!(Hint::default().next_delay(second), second);
+
+        let hint = Hint {
+            suggested_delay: Some(Duration::ZERO),
+            ..Hint::default()
+        };
+        assert_eq!(hint.next_delay(second), Duration::ZERO);
+
+        let hint = Hint {
+            do_not_retry_before: Some(SystemTime::now() + Duration::from_secs(60)),
+            ..Hint::default()
+        };
+        assert!(hint.next_delay(second) > Duration::from_secs(59));
+
+        let hint = Hint {
+            do_not_retry_before: Some(SystemTime::now() - Duration::from_secs(60)),
+            ..Hint::default()
+        };
+        assert_eq!(hint.next_delay(second), second);
+    }
+}
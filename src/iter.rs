@@ -0,0 +1,83 @@
+//! Iterator adapters retrying fallible iterators
+//!
+//! # Usage
+//!
+//! ```
+//! use retry_block::iter::IteratorRetryExt;
+//! use retry_block::delay::Fixed;
+//! use std::time::Duration;
+//!
+//! // a paginated cursor whose second page fails to load once
+//! let pages = vec![Ok(1), Err("timeout"), Ok(2), Ok(3)].into_iter();
+//!
+//! let pages: Result<Vec<u32>, &str> = pages
+//!     .retry_next(Fixed::exact(Duration::from_millis(1)).take(1))
+//!     .collect();
+//!
+//! assert_eq!(pages, Ok(vec![1, 2, 3]));
+//! ```
+
+use std::time::Duration;
+
+/// An extension trait for iterators yielding `Result`s to retry failed calls to `next`
+pub trait IteratorRetryExt<T, E>: Iterator<Item = Result<T, E>> + Sized {
+    /// Retry each call to `next` that yields an error with an independent copy of the given
+    /// delays, until it yields a success or the delays (or the iterator) end, in which case the
+    /// last error is yielded
+    fn retry_next<D>(self, durations: D) -> RetryNext<Self, D>
+    where
+        D: IntoIterator<Item = Duration> + Clone,
+    {
+        RetryNext {
+            iter: self,
+            durations,
+        }
+    }
+}
+
+impl<I, T, E> IteratorRetryExt<T, E> for I where I: Iterator<Item = Result<T, E>> {}
+
+/// Iterator for the `retry_next` method
+#[derive(Debug, Clone)]
+pub struct RetryNext<I, D> {
+    iter: I,
+    durations: D,
+}
+
+impl<I, D, T, E> Iterator for RetryNext<I, D>
+where
+    I: Iterator<Item = Result<T, E>>,
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    type Item = Result<T, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut res = self.iter.next()?;
+        let mut it = crate::delay::__faulty(self.durations.clone().into_iter());
+        while res.is_err() {
+            let Some(duration) = it.next() else { break };
+            crate::sleep::thread_sleep(duration);
+            // an iterator ending while retrying still yields its last error
+            let Some(next) = self.iter.next() else { break };
+            res = next;
+        }
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delay::NoDelay;
+    use crate::iter::IteratorRetryExt;
+
+    #[test]
+    fn retry_next_exhausted() {
+        let mut iter = vec![Err(1), Err(2), Err(3), Ok(4), Err(5)]
+            .into_iter()
+            .retry_next(NoDelay.take(1));
+        assert_eq!(iter.next(), Some(Err(2)));
+        assert_eq!(iter.next(), Some(Ok(4)));
+        assert_eq!(iter.next(), Some(Err(5)));
+        assert_eq!(iter.next(), None);
+    }
+}
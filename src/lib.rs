@@ -20,12 +20,12 @@
 //! ```
 //! # use retry_block::retry;
 //! # use retry_block::delay::Fixed;
-//! # use std::time::Duration;
+//! # use std::time::{Duration, Instant};
 //! # use retry_block::OperationResult;
 //!
 //! let mut collection = vec![1, 2, 3].into_iter();
 //!
-//! let result = retry!(Fixed::new(Duration::from_millis(100)).take(1), {
+//! let result = retry!(Fixed::exact(Duration::from_millis(100)).take(1), {
 //!     match collection.next() {
 //!         Some(n) if n == 3 => Ok("n is 3!"),
 //!         Some(_) => Err("n must be 3!"),
@@ -68,20 +68,21 @@ assert!(result.is_err());
 "##
 )]
 #![cfg_attr(
-    feature = "random",
+    any(feature = "random", feature = "fastrand"),
     doc = r##"
 Random jitter is applied by default to any delay strategy, but you can make it fixed using `exact`
-or add random jitter to any delay strategy using the `jitter` function:
+or apply a `Jitter` policy to each delay of any delay strategy using `with_jitter`:
 
 ```
 # use retry_block::retry_fn;
 # use retry_block::OperationResult;
-# use retry_block::delay::{Exponential, jitter};
+# use retry_block::delay::{Exponential, Jitter, JitterExt};
 # use std::time::Duration;
 
 let mut collection = vec![1, 2, 3].into_iter();
 
-let result = retry_fn(Exponential::exact(Duration::from_millis(10)).map(jitter).take(3), || {
+let delays = Exponential::exact(Duration::from_millis(10)).with_jitter(Jitter::Full);
+let result = retry_fn(delays.take(3), || {
     match collection.next() {
         Some(n) if n == 3 => Ok("n is 3!"),
         Some(_) => Err("n must be 3!"),
@@ -107,7 +108,7 @@ assert!(result.is_ok());
 //! # use std::time::Duration;
 //!
 //! let mut collection = vec![1, 2].into_iter();
-//! let value = retry!(Fixed::new(Duration::from_millis(1)), {
+//! let value = retry!(Fixed::exact(Duration::from_millis(1)), {
 //!     match collection.next() {
 //!         Some(n) if n == 2 => OperationResult::Ok(n),
 //!         Some(_) => OperationResult::Retry("not 2"),
@@ -120,41 +121,139 @@ assert!(result.is_ok());
 //!
 //! # Features
 //!
+//! Without default features, the crate has no dependencies and offers the delay strategies, the
+//! blocking retry functions and the macros; randomized delays (e.g. `Fixed::new`) need one of the
+//! random features.
+//!
 //! - `random`: offer some random delay utilities (on by default)
-//! - `config`: offer serializable retry config (on by default)
-//! - `future`: offer asynchronous retry mechanisms (on by default)
+//! - `fastrand`: offer the same random delay utilities using `fastrand` rather than `rand`, for a
+//!   smaller dependency tree when `random` is disabled
+//! - `config`: make `RetryConfig` and the delay strategies serializable, enabling `random` (on by
+//!   default). `RetryConfig` itself and the `fs` module only need `random` or `fastrand`
+//! - `future`: offer runtime-agnostic asynchronous retry mechanisms (on by default)
+//! - `tokio`: use `tokio::time::sleep` for the default asynchronous retry mechanisms (on by default)
+//! - `stream`: offer stream adapters retrying each item (on by default)
+//! - `persist`: offer retries persisted across restarts through a `RetryInjector`, running on tokio
+//!   (on by default)
+//! - `tower`: offer `tower` middleware retrying requests
+//! - `ureq`: offer a blocking `ureq` agent retrying requests, for applications avoiding async
+//! - `cron`: offer cron schedules for persistent retries
+//! - `json`: offer a JSON codec for persistent retry storage
+//! - `bincode`: offer a bincode codec for persistent retry storage
+//! - `blake3`: offer BLAKE3 content hashes as ids of persistent retry inputs
+//! - `bytes`: offer injectors of raw `bytes::Bytes` payloads, retried without copying them
+//! - `backoff`: convert the `ExponentialBackoff` of the `backoff` crate into delays
+//! - `retry-compat`: convert the delays and operation results of the `retry` crate
+//! - `test-util`: offer an in-memory reference injector and contract checks to test persistent
+//!   retries and custom injectors with
+//! - `governor`: rate limit the attempts of persistent retries with a `governor` rate limiter
+//! - `signals`: stop retrying on SIGINT/SIGTERM instead of finishing the current delay
+//! - `rayon`: retry the items of a batch in parallel on the rayon thread pool
+//! - `tonic`: classify `tonic::Status` errors as retryable, honoring server pushback
+//! - `tracing`: emit a `tracing` event when a named retried operation gives up
+//! - `metrics`: report the remaining tokens and rates of named retry budgets as `metrics` gauges
+//! - `prometheus`: expose the stats of accounted retries to a `prometheus` registry
+//! - `chaos`: inject faults into retries, to test how an application behaves when they give up
+//! - `arbitrary`: implement `Arbitrary` for delay strategies and `RetryConfig`, to fuzz retry
+//!   policies
 
-use serde::Deserialize;
 use std::time::Duration;
 
+mod accounting;
+mod aggregate;
+#[cfg(feature = "rayon")]
+mod batch;
+mod budget;
+mod catch;
+pub mod channel;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+mod classify;
+#[cfg(any(feature = "random", feature = "fastrand"))]
+mod config;
+mod context;
 pub mod delay;
+mod escalation;
+#[cfg(any(feature = "random", feature = "fastrand"))]
+pub mod fs;
 #[cfg(feature = "future")]
 pub mod future;
+#[cfg(all(feature = "future", feature = "tokio"))]
+mod group;
+#[cfg(feature = "tokio")]
+pub mod health;
+mod hint;
+pub mod iter;
+pub mod lock;
 mod r#macro;
+#[cfg(feature = "tokio")]
+mod manager;
+pub mod net;
+mod partial;
+#[cfg(feature = "persist")]
 pub mod persist;
+mod policy;
+pub mod prelude;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "tokio")]
+mod scheduler;
+mod scope;
+mod select;
+#[cfg(feature = "signals")]
+pub mod signal;
+mod sleep;
+mod state;
+#[cfg(feature = "stream")]
+pub mod stream;
+pub mod time;
+#[cfg(feature = "tonic")]
+mod tonic;
+#[cfg(feature = "tower")]
+pub mod tower;
+#[cfg(feature = "ureq")]
+pub mod ureq;
 
+pub use accounting::{Accounted, DelayAccounting, DelayStats};
+pub use aggregate::{retry_fn_collecting, AggregateError};
+#[cfg(feature = "rayon")]
+pub use batch::retry_batch;
+pub use budget::{BudgetSnapshot, Budgeted, RetryBudget};
+pub use catch::{retry_fn_catching, OnPanic, PanicError};
+pub use classify::Classifier;
+#[cfg(any(feature = "random", feature = "fastrand"))]
+pub use config::{ConfigError, RetryConfig, RetryConfigV2};
+#[doc(hidden)]
+pub use context::__with_retry_context;
+pub use context::{retry_fn_named, retry_fn_with_context, RetryContext, WithRetryContext};
+pub use escalation::Escalation;
+#[cfg(feature = "future")]
 pub use future::*;
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub use group::{retry_quorum, retry_scope, GroupKey, GroupResults, RetryGroup, SpawnScope};
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub use hint::async_retry_fn_hinted;
+pub use hint::{retry_fn_hinted, Hint, Hinted};
+#[cfg(feature = "tokio")]
+pub use manager::RetryManager;
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub use partial::async_retry_fn_partial;
+pub use partial::{retry_fn_partial, Degraded, PartialResult};
+#[cfg(feature = "tokio")]
+pub use policy::scope_default_policy;
+pub use policy::{default_delays, with_default_policy, DefaultPolicy};
+#[cfg(feature = "tokio")]
+pub use scheduler::{RetryScheduler, SchedulerSleep};
+pub use scope::{RetryScope, ScopeReport};
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub use select::async_retry_fn_selecting;
+pub use select::{retry_fn_selecting, ScheduleSelector};
+#[cfg(feature = "tokio")]
+pub use sleep::TokioSleeper;
+pub use sleep::{yield_now, AsyncSleeper, YieldNow};
+pub use state::{RetryState, Step};
 
-/// A serializable retry configuration for a random range and finite retry count
-#[derive(Debug, Deserialize, Clone)]
-pub struct RetryConfig {
-    /// how many times will we retry the operation
-    pub count: usize,
-    /// the minimum amount of milliseconds to wait before retrying
-    pub min_backoff: u64,
-    /// the maximum amount of milliseconds to wait before retrying
-    pub max_backoff: u64,
-}
-
-impl IntoIterator for RetryConfig {
-    type Item = Duration;
-    type IntoIter = std::iter::Take<delay::Range>;
-    fn into_iter(self) -> Self::IntoIter {
-        delay::Range::from_millis_inclusive(self.min_backoff, self.max_backoff).take(self.count)
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum OperationResult<T, E> {
     /// Contains the success value.
     Ok(T),
@@ -173,6 +272,274 @@ impl<T, E> From<Result<T, E>> for OperationResult<T, E> {
     }
 }
 
+#[cfg(feature = "retry-compat")]
+impl<T, E> From<retry::OperationResult<T, E>> for OperationResult<T, E> {
+    fn from(item: retry::OperationResult<T, E>) -> Self {
+        match item {
+            retry::OperationResult::Ok(v) => OperationResult::Ok(v),
+            retry::OperationResult::Retry(e) => OperationResult::Retry(e),
+            retry::OperationResult::Err(e) => OperationResult::Err(e),
+        }
+    }
+}
+
+impl<T> From<Option<T>> for OperationResult<T, ()> {
+    fn from(item: Option<T>) -> Self {
+        Self::from_option(item, ())
+    }
+}
+
+impl<T, E> OperationResult<T, E> {
+    /// Convert an `Option` into an `OperationResult`, retrying with the given error on `None`
+    ///
+    /// ```
+    /// # use retry_block::OperationResult;
+    /// assert_eq!(OperationResult::from_option(Some(1), "empty"), OperationResult::Ok(1));
+    /// assert_eq!(OperationResult::<i32, _>::from_option(None, "empty"), OperationResult::Retry("empty"));
+    /// ```
+    pub fn from_option(item: Option<T>, error: E) -> Self {
+        match item {
+            Some(v) => OperationResult::Ok(v),
+            None => OperationResult::Retry(error),
+        }
+    }
+
+    /// Convert a `Result` into an `OperationResult`, retrying on the errors matching the given
+    /// predicate and failing immediately on the others
+    ///
+    /// ```
+    /// # use retry_block::OperationResult;
+    /// let is_transient = |e: &&str| *e == "timeout";
+    /// assert_eq!(OperationResult::<(), _>::retry_if(Err("timeout"), is_transient), OperationResult::Retry("timeout"));
+    /// assert_eq!(OperationResult::<(), _>::retry_if(Err("not found"), is_transient), OperationResult::Err("not found"));
+    /// ```
+    pub fn retry_if<P>(result: Result<T, E>, predicate: P) -> Self
+    where
+        P: FnOnce(&E) -> bool,
+    {
+        match result {
+            Ok(v) => OperationResult::Ok(v),
+            Err(e) if predicate(&e) => OperationResult::Retry(e),
+            Err(e) => OperationResult::Err(e),
+        }
+    }
+
+    /// Returns `true` if the result is `Ok`.
+    pub fn is_ok(&self) -> bool {
+        matches!(self, OperationResult::Ok(_))
+    }
+
+    /// Returns `true` if the result is `Retry`.
+    pub fn is_retry(&self) -> bool {
+        matches!(self, OperationResult::Retry(_))
+    }
+
+    /// Returns `true` if the result is `Err`.
+    pub fn is_err(&self) -> bool {
+        matches!(self, OperationResult::Err(_))
+    }
+
+    /// Converts into an `Option` of the success value, discarding any error.
+    pub fn ok(self) -> Option<T> {
+        match self {
+            OperationResult::Ok(v) => Some(v),
+            OperationResult::Retry(_) | OperationResult::Err(_) => None,
+        }
+    }
+
+    /// Converts into an `Option` of the error value of either `Retry` or `Err`, discarding any
+    /// success value.
+    pub fn err(self) -> Option<E> {
+        match self {
+            OperationResult::Ok(_) => None,
+            OperationResult::Retry(e) | OperationResult::Err(e) => Some(e),
+        }
+    }
+
+    /// Converts into a `Result`, mapping both `Retry` and `Err` to `Err`.
+    pub fn into_result(self) -> Result<T, E> {
+        self.into_result_with(|e, _| e)
+    }
+
+    /// Converts into a `Result`, mapping the error value of both `Retry` and `Err` with the given
+    /// function, which is also told whether the error was retryable.
+    ///
+    /// ```
+    /// # use retry_block::OperationResult;
+    /// let res = OperationResult::<(), _>::Retry("timeout").into_result_with(|e, was_retry| {
+    ///     if was_retry {
+    ///         format!("transient: {}", e)
+    ///     } else {
+    ///         format!("fatal: {}", e)
+    ///     }
+    /// });
+    /// assert_eq!(res, Err("transient: timeout".to_owned()));
+    /// ```
+    pub fn into_result_with<F, O>(self, op: O) -> Result<T, F>
+    where
+        O: FnOnce(E, bool) -> F,
+    {
+        match self {
+            OperationResult::Ok(v) => Ok(v),
+            OperationResult::Retry(e) => Err(op(e, true)),
+            OperationResult::Err(e) => Err(op(e, false)),
+        }
+    }
+
+    /// Returns the success value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the result is `Retry` or `Err`, with a panic message provided by the error value.
+    #[track_caller]
+    pub fn unwrap(self) -> T
+    where
+        E: std::fmt::Debug,
+    {
+        match self {
+            OperationResult::Ok(v) => v,
+            OperationResult::Retry(e) => {
+                panic!(
+                    "called `OperationResult::unwrap()` on a `Retry` value: {:?}",
+                    e
+                )
+            }
+            OperationResult::Err(e) => {
+                panic!(
+                    "called `OperationResult::unwrap()` on an `Err` value: {:?}",
+                    e
+                )
+            }
+        }
+    }
+
+    /// Returns the success value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the result is `Retry` or `Err`, with a panic message including the given message
+    /// and the error value.
+    #[track_caller]
+    pub fn expect(self, msg: &str) -> T
+    where
+        E: std::fmt::Debug,
+    {
+        match self {
+            OperationResult::Ok(v) => v,
+            OperationResult::Retry(e) | OperationResult::Err(e) => panic!("{}: {:?}", msg, e),
+        }
+    }
+}
+
+/// The final error of a retried operation, telling whether it gave up or failed fatally, along
+/// with how it was retried
+///
+/// Returned by `retry_fn_classified` and `async_retry_fn_classified`, e.g. to alert differently on
+/// a downstream service permanently rejecting an operation and on retries running out.
+#[derive(Debug, Clone)]
+pub struct RetryError<E> {
+    error: E,
+    fatal: bool,
+    attempts: usize,
+    elapsed: Duration,
+    max_attempts: Option<usize>,
+}
+
+impl<E> RetryError<E> {
+    /// Classify the error of the last of the given attempts, made out of at most `max_attempts`
+    pub(crate) fn new(
+        error: E,
+        fatal: bool,
+        attempts: usize,
+        elapsed: Duration,
+        max_attempts: Option<usize>,
+    ) -> Self {
+        Self {
+            error,
+            fatal,
+            attempts,
+            elapsed,
+            max_attempts,
+        }
+    }
+
+    /// Returns `true` if the delays were exhausted.
+    pub fn is_gave_up(&self) -> bool {
+        !self.fatal
+    }
+
+    /// Returns `true` if the operation returned `OperationResult::Err`.
+    pub fn is_fatal(&self) -> bool {
+        self.fatal
+    }
+
+    /// The number of attempts made, including the first one
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// The time elapsed since the start of the first attempt
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The number of attempts allowed by the delays, if they are bounded
+    pub fn max_attempts(&self) -> Option<usize> {
+        self.max_attempts
+    }
+
+    /// The error of the last attempt
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Returns the error of the last attempt.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.fatal {
+            write!(f, "fatal error: {}", self.error)
+        } else {
+            write!(
+                f,
+                "gave up retrying after {} attempt(s): {}",
+                self.attempts, self.error
+            )
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for RetryError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An error that knows whether the operation that produced it is worth retrying
+pub trait RetryableError {
+    /// Whether the failed operation should be retried rather than given up on immediately
+    fn is_retryable(&self) -> bool;
+
+    /// The delay suggested by the error (e.g. by a server pushing back) before the next attempt,
+    /// to be waited instead of the next delay of the retry policy
+    fn retry_after(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The delay to wait before the next attempt, given the next delay of the retry policy
+    ///
+    /// This is how the delay suggested by an error is honored everywhere, by `retry_fn_hinted`,
+    /// the `tower` policies and `persist::RetryInjector::reschedule` implementations: the
+    /// `retry_after` delay if any, and the scheduled one otherwise.
+    fn next_delay(&self, scheduled: Duration) -> Duration {
+        self.retry_after().unwrap_or(scheduled)
+    }
+}
+
 /// Retry the given operation until it succeeds, or until the given `Duration`
 /// iterator ends.
 pub fn retry_fn<D, O, OR, R, E>(durations: D, mut operation: O) -> Result<R, E>
@@ -183,3 +550,776 @@ where
 {
     retry!(durations, { operation() })
 }
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends,
+/// giving each attempt the next of the given endpoints, wrapping around, along with its index.
+///
+/// This makes failing over across replicas part of the retry.
+///
+/// ```
+/// # use retry_block::retry_fn_rotating;
+/// # use retry_block::delay::NoDelay;
+/// let replicas = ["primary:80", "secondary:80"];
+/// let mut tried = Vec::new();
+/// let value = retry_fn_rotating(NoDelay.take(2), &replicas, |replica, attempt| {
+///     tried.push((*replica, attempt));
+///     Err::<(), _>("unreachable")
+/// });
+/// assert_eq!(value, Err("unreachable"));
+/// assert_eq!(tried, vec![("primary:80", 0), ("secondary:80", 1), ("primary:80", 2)]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if there are no endpoints.
+pub fn retry_fn_rotating<D, T, O, OR, R, E>(
+    durations: D,
+    endpoints: &[T],
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut(&T, usize) -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    assert!(!endpoints.is_empty(), "no endpoints to rotate between");
+    let mut attempt = 0;
+    retry!(durations, {
+        let endpoint = &endpoints[attempt % endpoints.len()];
+        let res = operation(endpoint, attempt);
+        attempt += 1;
+        res
+    })
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends, and
+/// tell whether a final error came from running out of delays or from `OperationResult::Err`.
+///
+/// ```
+/// # use retry_block::{retry_fn_classified, OperationResult};
+/// # use retry_block::delay::NoDelay;
+/// let error = retry_fn_classified(NoDelay.take(1), || Err::<(), _>("unavailable")).unwrap_err();
+/// assert!(error.is_gave_up());
+/// assert_eq!((error.attempts(), error.max_attempts()), (2, Some(2)));
+/// assert_eq!(error.into_inner(), "unavailable");
+///
+/// let value = retry_fn_classified(NoDelay.take(1), || OperationResult::<(), _>::Err("rejected"));
+/// let error = value.unwrap_err();
+/// assert!(error.is_fatal());
+/// assert_eq!(error.attempts(), 1);
+/// ```
+pub fn retry_fn_classified<D, O, OR, R, E>(
+    durations: D,
+    mut operation: O,
+) -> Result<R, RetryError<E>>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    let durations = durations.into_iter();
+    let max_attempts = durations
+        .size_hint()
+        .1
+        .map(|retries| retries.saturating_add(1));
+    let start = std::time::Instant::now();
+    let mut attempts = 0;
+    let res = retry!(durations, {
+        attempts += 1;
+        match operation().into() {
+            OperationResult::Ok(res) => OperationResult::Ok(res),
+            OperationResult::Retry(e) => OperationResult::Retry((e, false)),
+            OperationResult::Err(e) => OperationResult::Err((e, true)),
+        }
+    });
+    res.map_err(|(e, fatal)| RetryError::new(e, fatal, attempts, start.elapsed(), max_attempts))
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends, and
+/// return the number of attempts made along with the result.
+///
+/// ```
+/// # use retry_block::retry_fn_counted;
+/// # use retry_block::delay::NoDelay;
+/// let mut tried = false;
+/// let value = retry_fn_counted(NoDelay, || {
+///     if tried {
+///         Ok(42)
+///     } else {
+///         tried = true;
+///         Err("try again")
+///     }
+/// });
+/// assert_eq!(value, Ok((42, 2)));
+/// ```
+pub fn retry_fn_counted<D, O, OR, R, E>(
+    durations: D,
+    mut operation: O,
+) -> Result<(R, usize), (E, usize)>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut attempts = 0;
+    let res = retry!(durations, {
+        attempts += 1;
+        operation()
+    });
+    res.map(|res| (res, attempts)).map_err(|e| (e, attempts))
+}
+
+/// Retry the operations prepared by the given factory until one succeeds, or until the given
+/// `Duration` iterator ends.
+///
+/// The factory is called with the index of the attempt and returns a `FnOnce` operation, so that
+/// attempts can consume what they are given (e.g. a request builder consumed when sent) without
+/// the operation itself having to be `FnMut`.
+///
+/// ```
+/// # use retry_block::retry_fn_prepared;
+/// # use retry_block::delay::NoDelay;
+/// struct Request(Vec<u8>);
+///
+/// impl Request {
+///     fn send(self, attempt: usize) -> Result<usize, &'static str> {
+///         if attempt < 2 { Err("try again") } else { Ok(self.0.len()) }
+///     }
+/// }
+///
+/// let payload = vec![1, 2, 3];
+/// let value = retry_fn_prepared(NoDelay, |attempt| {
+///     let request = Request(payload.clone());
+///     move || request.send(attempt)
+/// });
+/// assert_eq!(value, Ok(3));
+/// ```
+pub fn retry_fn_prepared<D, P, O, OR, R, E>(durations: D, mut prepare: P) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    P: FnMut(usize) -> O,
+    O: FnOnce() -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut attempt = 0;
+    retry!(durations, {
+        let operation = prepare(attempt);
+        attempt += 1;
+        operation()
+    })
+}
+
+/// Retry the given operation on a mutable state until it succeeds, or until the given `Duration`
+/// iterator ends.
+///
+/// ```
+/// # use retry_block::retry_fn_with_state;
+/// # use retry_block::delay::NoDelay;
+/// let mut attempts = 0;
+/// let value = retry_fn_with_state(NoDelay, &mut attempts, |attempts| {
+///     *attempts += 1;
+///     if *attempts < 3 {
+///         Err("try again")
+///     } else {
+///         Ok(*attempts)
+///     }
+/// });
+/// assert_eq!(value, Ok(3));
+/// ```
+pub fn retry_fn_with_state<D, S, O, OR, R, E>(
+    durations: D,
+    state: &mut S,
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut(&mut S) -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    retry!(durations, { operation(state) })
+}
+
+/// A resource used by the attempts of an operation, see `retry_with_resource`
+#[derive(Debug)]
+pub struct Resource<T> {
+    inner: T,
+    valid: bool,
+}
+
+impl<T> Resource<T> {
+    /// Mark the resource as invalid (e.g. a broken connection), so that it is acquired again
+    /// before the next attempt
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+}
+
+impl<T> std::ops::Deref for Resource<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> std::ops::DerefMut for Resource<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+/// Retry the given operation on a resource until it succeeds, or until the given `Duration`
+/// iterator ends.
+///
+/// The resource is acquired before the first attempt, and kept for the next ones unless an
+/// attempt invalidates it, in which case it is acquired again. Failing to acquire the resource
+/// counts as a failed attempt.
+///
+/// ```
+/// # use retry_block::retry_with_resource;
+/// # use retry_block::delay::NoDelay;
+/// let mut connections = 0;
+/// let value = retry_with_resource(
+///     NoDelay.take(2),
+///     || {
+///         connections += 1;
+///         Ok::<_, &str>(connections)
+///     },
+///     |connection| {
+///         if **connection < 2 {
+///             connection.invalidate();
+///             Err("broken connection")
+///         } else {
+///             Ok(**connection)
+///         }
+///     },
+/// );
+/// assert_eq!(value, Ok(2));
+/// ```
+pub fn retry_with_resource<D, A, AR, T, O, OR, R, E>(
+    durations: D,
+    mut acquire: A,
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    A: FnMut() -> AR,
+    AR: Into<OperationResult<T, E>>,
+    O: FnMut(&mut Resource<T>) -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut resource = None;
+    let mut acquire = || match acquire().into() {
+        OperationResult::Ok(inner) => OperationResult::Ok(Resource { inner, valid: true }),
+        OperationResult::Retry(e) => OperationResult::Retry(e),
+        OperationResult::Err(e) => OperationResult::Err(e),
+    };
+    retry!(durations, {
+        let acquired = match resource.take() {
+            Some(resource) => OperationResult::Ok(resource),
+            None => acquire(),
+        };
+        match acquired {
+            OperationResult::Ok(mut acquired) => {
+                let res = operation(&mut acquired).into();
+                if acquired.valid {
+                    resource = Some(acquired);
+                }
+                res
+            }
+            OperationResult::Retry(e) => OperationResult::Retry(e),
+            OperationResult::Err(e) => OperationResult::Err(e),
+        }
+    })
+}
+
+/// Retry the body of a transaction until it succeeds, or until the given `Duration` iterator
+/// ends.
+///
+/// Each attempt begins a new transaction and runs the body in it. When the body fails, the
+/// transaction is rolled back before waiting for the next attempt (or before returning the error
+/// of the last one), e.g. to retry database transactions aborted by serialization failures. The
+/// body is responsible for committing the transaction. Failing to begin a transaction counts as
+/// a failed attempt.
+///
+/// ```
+/// # use retry_block::retry_txn;
+/// # use retry_block::delay::NoDelay;
+/// let mut log = Vec::new();
+/// let mut conflicts = 1;
+/// let value = retry_txn(
+///     NoDelay.take(2),
+///     || Ok::<_, &str>(Vec::new()),
+///     |txn| {
+///         txn.push("write");
+///         if conflicts > 0 {
+///             conflicts -= 1;
+///             Err("serialization failure")
+///         } else {
+///             Ok(txn.len())
+///         }
+///     },
+///     |txn| log.push(format!("rolled back {:?}", txn)),
+/// );
+/// assert_eq!(value, Ok(1));
+/// assert_eq!(log, vec![r#"rolled back ["write"]"#]);
+/// ```
+pub fn retry_txn<D, B, BR, T, O, OR, RB, R, E>(
+    durations: D,
+    mut begin: B,
+    mut body: O,
+    mut rollback: RB,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    B: FnMut() -> BR,
+    BR: Into<OperationResult<T, E>>,
+    O: FnMut(&mut T) -> OR,
+    OR: Into<OperationResult<R, E>>,
+    RB: FnMut(T),
+{
+    retry!(durations, {
+        match begin().into() {
+            OperationResult::Ok(mut txn) => match body(&mut txn).into() {
+                OperationResult::Ok(res) => OperationResult::Ok(res),
+                failed => {
+                    rollback(txn);
+                    failed
+                }
+            },
+            OperationResult::Retry(e) => OperationResult::Retry(e),
+            OperationResult::Err(e) => OperationResult::Err(e),
+        }
+    })
+}
+
+/// Retry the given boxed operation until it succeeds, or until the given boxed delay strategy ends.
+///
+/// This is an object-safe counterpart of `retry_fn`, for strategies and operations that have to
+/// cross a `dyn` boundary.
+///
+/// ```
+/// # use retry_block::{retry_dyn, OperationResult};
+/// # use retry_block::delay::{BoxedDelay, IntoBoxed, NoDelay};
+/// let delays: BoxedDelay = NoDelay.take(2).into_boxed();
+/// let mut attempts = 0;
+/// let value = retry_dyn(
+///     delays,
+///     Box::new(move || {
+///         attempts += 1;
+///         if attempts < 3 {
+///             OperationResult::Retry("try again")
+///         } else {
+///             OperationResult::Ok(attempts)
+///         }
+///     }),
+/// );
+/// assert_eq!(value, Ok(3));
+/// ```
+pub fn retry_dyn<R, E>(
+    durations: delay::BoxedDelay,
+    mut operation: Box<dyn FnMut() -> OperationResult<R, E> + '_>,
+) -> Result<R, E> {
+    retry!(durations, { operation() })
+}
+
+/// Retry the given operation until it succeeds, until the given `Duration` iterator ends, or until
+/// the given time budget is spent.
+///
+/// Each attempt is passed the remaining time budget, so that it can bound its own duration
+/// accordingly. A delay (e.g. a jittered one) that would outlast the budget is clamped to half of
+/// what remains of it, so that one last attempt still fits; the last error is returned after it.
+///
+/// ```
+/// # use retry_block::retry_fn_with_deadline;
+/// # use retry_block::delay::Fixed;
+/// # use std::time::Duration;
+/// let value = retry_fn_with_deadline(
+///     Fixed::exact(Duration::from_millis(10)),
+///     Duration::from_millis(100),
+///     |remaining| {
+///         if remaining > Duration::from_millis(50) {
+///             Err("not enough time left")
+///         } else {
+///             Ok(remaining)
+///         }
+///     },
+/// );
+/// assert!(value.unwrap() <= Duration::from_millis(50));
+/// ```
+pub fn retry_fn_with_deadline<D, O, OR, R, E>(
+    durations: D,
+    budget: Duration,
+    operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut(Duration) -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    deadline_loop(
+        &delay::SystemClock,
+        sleep::thread_sleep,
+        durations,
+        budget,
+        operation,
+    )
+}
+
+/// Retry the given operation like `retry_fn_with_deadline`, measuring the budget and waiting for
+/// delays with the given `TimeSource`
+///
+/// The current thread is blocked on each sleep of the time source, so that its sleeps must not
+/// need a runtime, as those of `time::StdTime` and `time::MockTime`.
+///
+/// ```
+/// # use retry_block::retry_fn_with_deadline_using;
+/// # use retry_block::delay::Fixed;
+/// # use retry_block::time::MockTime;
+/// # use std::time::Duration;
+/// let mut attempts = 0;
+/// let result = retry_fn_with_deadline_using(
+///     MockTime::new(),
+///     Fixed::exact(Duration::from_secs(10)),
+///     Duration::from_secs(100),
+///     |_| {
+///         attempts += 1;
+///         Err::<(), _>("unavailable")
+///     },
+/// );
+/// assert_eq!(result, Err("unavailable"));
+/// assert_eq!(attempts, 11);
+/// ```
+pub fn retry_fn_with_deadline_using<T, D, O, OR, R, E>(
+    time: T,
+    durations: D,
+    budget: Duration,
+    operation: O,
+) -> Result<R, E>
+where
+    T: time::TimeSource,
+    D: IntoIterator<Item = Duration>,
+    O: FnMut(Duration) -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    deadline_loop(
+        &time,
+        |duration| sleep::block_on(time.sleep(duration)),
+        durations,
+        budget,
+        operation,
+    )
+}
+
+fn deadline_loop<D, O, OR, R, E>(
+    clock: &impl delay::Clock,
+    mut sleep: impl FnMut(Duration),
+    durations: D,
+    budget: Duration,
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut(Duration) -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    let deadline = clock.now() + budget;
+    let remaining = || deadline.saturating_duration_since(clock.now());
+    let mut durations = fit_deadline(delay::__faulty(durations.into_iter()), remaining);
+    retry_loop(
+        |e| durations.next().ok_or(e),
+        &mut sleep,
+        || operation(remaining()).into(),
+    )
+}
+
+/// The loop of the retries whose next delay depends on the error of the failed attempt, e.g. as
+/// suggested by it (`retry_fn_hinted`) or selected after it (`retry_fn_selecting`)
+///
+/// `next_delay` is given each retryable error, and the retry gives up with the error it hands
+/// back instead of a delay.
+pub(crate) fn retry_loop<R, E>(
+    mut next_delay: impl FnMut(E) -> Result<Duration, E>,
+    mut sleep: impl FnMut(Duration),
+    mut attempt: impl FnMut() -> OperationResult<R, E>,
+) -> Result<R, E> {
+    loop {
+        match attempt() {
+            OperationResult::Ok(res) => return Ok(res),
+            OperationResult::Err(e) => return Err(e),
+            OperationResult::Retry(e) => match next_delay(e) {
+                Ok(duration) => sleep(duration),
+                Err(e) => return Err(e),
+            },
+        }
+    }
+}
+
+/// Clamp the first delay that would outlast the remaining time budget to half of it, and end the
+/// delays after it.
+pub(crate) fn fit_deadline<D>(
+    durations: D,
+    remaining: impl Fn() -> Duration,
+) -> impl Iterator<Item = Duration>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    let mut clamped = false;
+    durations.into_iter().map_while(move |duration| {
+        let remaining = remaining();
+        if clamped || remaining.is_zero() {
+            None
+        } else if duration < remaining {
+            Some(duration)
+        } else {
+            clamped = true;
+            Some(remaining / 2)
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delay::{Fixed, NoDelay};
+    use crate::time::MockTime;
+    use crate::{
+        retry, retry_fn_with_deadline_using, retry_txn, retry_with_resource, OperationResult,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn retry_option() {
+        assert_eq!(OperationResult::from(Some(1)), OperationResult::Ok(1));
+        assert_eq!(
+            OperationResult::<i32, ()>::from(None),
+            OperationResult::Retry(())
+        );
+
+        let mut collection = vec![None, None, Some(3)].into_iter();
+        let value = retry!(NoDelay.take(2), { collection.next().flatten() });
+        assert_eq!(value, Ok(3));
+    }
+
+    #[test]
+    fn deadline_stops_sleeping() {
+        let mut budgets = Vec::new();
+        let value = retry_fn_with_deadline_using(
+            MockTime::new(),
+            Fixed::exact(Duration::from_millis(20)),
+            Duration::from_millis(50),
+            |remaining| {
+                budgets.push(remaining.as_millis());
+                Err::<(), _>("failed")
+            },
+        );
+        assert_eq!(value, Err("failed"));
+        // the delay before the last attempt is clamped to half of the remaining budget
+        assert_eq!(budgets, vec![50, 30, 10, 5]);
+    }
+
+    #[test]
+    fn resource_reacquired_when_invalid() {
+        let mut acquired = 0;
+        let mut attempts = 0;
+        let value = retry_with_resource(
+            NoDelay.take(3),
+            || {
+                acquired += 1;
+                Ok::<_, ()>(acquired)
+            },
+            |resource| {
+                attempts += 1;
+                if attempts == 2 {
+                    resource.invalidate();
+                }
+                if attempts < 4 {
+                    Err(())
+                } else {
+                    Ok(**resource)
+                }
+            },
+        );
+        // acquired before the first attempt, then again after the invalidating second one
+        assert_eq!(value, Ok(2));
+    }
+
+    #[test]
+    fn txn_rolled_back_before_next_begin() {
+        let events = std::cell::RefCell::new(Vec::new());
+        let value = retry_txn(
+            NoDelay.take(1),
+            || {
+                events.borrow_mut().push("begin");
+                Ok::<_, &str>(())
+            },
+            |_| {
+                events.borrow_mut().push("body");
+                Err::<(), _>("conflict")
+            },
+            |_| events.borrow_mut().push("rollback"),
+        );
+        assert_eq!(value, Err("conflict"));
+        assert_eq!(
+            *events.borrow(),
+            vec!["begin", "body", "rollback", "begin", "body", "rollback"]
+        );
+
+        let value = retry_txn(
+            NoDelay.take(1),
+            || OperationResult::<(), _>::Err("unavailable"),
+            |_| Ok::<(), _>(()),
+            |_| unreachable!(),
+        );
+        assert_eq!(value, Err("unavailable"));
+    }
+
+    #[test]
+    fn named_fatal() {
+        let mut attempts = 0;
+        let value: Result<(), _> = retry!("named_fatal", NoDelay.take(3), {
+            attempts += 1;
+            crate::fatal!(if attempts < 2 { Ok(()) } else { Err("fatal") });
+            Err("retry")
+        });
+        let error = value.unwrap_err();
+        assert_eq!((error.operation(), error.attempts()), ("named_fatal", 2));
+        assert_eq!(error.into_inner(), "fatal");
+        assert_eq!(
+            crate::DelayAccounting::named("named_fatal")
+                .stats()
+                .attempts,
+            2
+        );
+    }
+
+    #[cfg(all(feature = "future", feature = "tokio"))]
+    #[tokio::test]
+    async fn async_named_fatal() {
+        let mut attempts = 0;
+        let value: Result<(), _> = crate::async_retry!("async_named_fatal", NoDelay.take(3), {
+            attempts += 1;
+            crate::fatal!(if attempts < 2 { Ok(()) } else { Err("fatal") });
+            Err("retry")
+        });
+        let error = value.unwrap_err();
+        assert_eq!(
+            (error.operation(), error.attempts()),
+            ("async_named_fatal", 2)
+        );
+        assert_eq!(error.into_inner(), "fatal");
+
+        let sleep = |_| std::future::ready(());
+        let value: Result<(), _> =
+            crate::async_retry_with!("async_named_fatal_with", sleep, NoDelay.take(3), {
+                crate::fatal!(Err("fatal"));
+                Err("retry")
+            });
+        let error = value.unwrap_err();
+        assert_eq!(error.attempts(), 1);
+        assert_eq!(error.into_inner(), "fatal");
+    }
+
+    #[cfg(all(feature = "future", feature = "tokio"))]
+    #[tokio::test]
+    async fn zero_delays_skipped() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let sleeps = AtomicUsize::new(0);
+        let sleep = |_| {
+            sleeps.fetch_add(1, Ordering::Relaxed);
+            std::future::ready(())
+        };
+        let durations = [Duration::ZERO, Duration::from_millis(1), Duration::ZERO];
+        let value = crate::async_retry_with!(sleep, durations, { Err::<(), _>(()) });
+        assert_eq!(value, Err(()));
+        assert_eq!(sleeps.load(Ordering::Relaxed), 1);
+    }
+
+    #[cfg(all(feature = "future", feature = "tokio"))]
+    #[tokio::test]
+    async fn zero_delays_yielded() {
+        use crate::delay::NoDelay;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let released = Arc::new(AtomicBool::new(false));
+        tokio::spawn({
+            let released = released.clone();
+            async move { released.store(true, Ordering::Relaxed) }
+        });
+        // the spawned task can only run on this single threaded runtime if the retry yields
+        let value = crate::async_retry!(NoDelay.take(1), yield_now = true, {
+            if released.load(Ordering::Relaxed) {
+                Ok(())
+            } else {
+                Err(())
+            }
+        });
+        assert_eq!(value, Ok(()));
+    }
+
+    #[test]
+    fn error_types() {
+        fn assert_error<E: std::error::Error + Send + Sync + 'static>() {}
+
+        #[cfg(any(feature = "random", feature = "fastrand"))]
+        assert_error::<crate::ConfigError>();
+        assert_error::<crate::AggregateError<std::io::Error>>();
+        assert_error::<crate::WithRetryContext<std::io::Error>>();
+        assert_error::<crate::lock::LockTimeout>();
+        assert_error::<crate::delay::DelayError>();
+        assert_error::<crate::RetryError<std::io::Error>>();
+        #[cfg(all(feature = "future", feature = "tokio"))]
+        assert_error::<crate::SpawnError<std::io::Error>>();
+        #[cfg(all(feature = "future", feature = "tokio"))]
+        assert_error::<crate::CancelError<std::io::Error>>();
+    }
+
+    #[test]
+    fn accessors() {
+        let ok = OperationResult::<i32, &str>::Ok(1);
+        let retry = OperationResult::<i32, &str>::Retry("retry");
+        let err = OperationResult::<i32, &str>::Err("err");
+
+        assert!(ok.is_ok() && !ok.is_retry() && !ok.is_err());
+        assert!(!retry.is_ok() && retry.is_retry() && !retry.is_err());
+        assert!(!err.is_ok() && !err.is_retry() && err.is_err());
+
+        assert_eq!(
+            (ok.clone().ok(), retry.clone().ok(), err.clone().ok()),
+            (Some(1), None, None)
+        );
+        assert_eq!(
+            (ok.clone().err(), retry.err(), err.err()),
+            (None, Some("retry"), Some("err"))
+        );
+        assert_eq!(ok.clone().unwrap(), 1);
+        assert_eq!(ok.expect("ok"), 1);
+    }
+
+    #[test]
+    fn into_result() {
+        assert_eq!(OperationResult::<i32, &str>::Ok(1).into_result(), Ok(1));
+        assert_eq!(
+            OperationResult::<i32, &str>::Retry("retry").into_result(),
+            Err("retry")
+        );
+        assert_eq!(
+            OperationResult::<i32, &str>::Err("err")
+                .into_result_with(|e, was_retry| (e, was_retry)),
+            Err(("err", false))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "called `OperationResult::unwrap()` on a `Retry` value: \"retry\"")]
+    fn unwrap_retry() {
+        OperationResult::<i32, &str>::Retry("retry").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected ok: \"err\"")]
+    fn expect_err() {
+        OperationResult::<i32, &str>::Err("err").expect("expected ok");
+    }
+}
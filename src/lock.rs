@@ -0,0 +1,331 @@
+//! Lock acquisition with backoff
+//!
+//! Acquire a lock with `try_lock` rather than blocking on it, e.g. to avoid deadlocks, retrying
+//! according to a delay iterator until an overall timeout. A delay that would outlast the timeout
+//! is clamped to half of what remains of it, so that one last attempt still fits. The timeout is
+//! measured with `delay::SystemClock` by the blocking functions, and with `time::TokioTime` by the
+//! asynchronous ones, which thus follow the paused time of tokio in tests.
+//!
+//! Semaphore permits can likewise be acquired with `acquire_with_retry`, which gives up once its
+//! delays run out, e.g. to back off while a connection pool is exhausted.
+//!
+//! ```
+//! # use retry_block::lock::try_lock_with_backoff;
+//! # use retry_block::delay::Fixed;
+//! # use std::sync::Mutex;
+//! # use std::time::Duration;
+//! let mutex = Mutex::new(0);
+//! let guard = mutex.lock().unwrap();
+//! let delays = Fixed::exact(Duration::from_millis(5));
+//! let error = try_lock_with_backoff(&mutex, delays, Duration::from_millis(20)).unwrap_err();
+//! assert!(error.attempts() > 1);
+//!
+//! drop(guard);
+//! *try_lock_with_backoff(&mutex, delays, Duration::from_millis(20)).unwrap() += 1;
+//! ```
+
+use crate::delay::{Clock, SystemClock};
+use crate::retry;
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+use std::time::Duration;
+
+/// The error of a lock that could not be acquired before the timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockTimeout {
+    attempts: usize,
+    elapsed: Duration,
+}
+
+impl LockTimeout {
+    /// The number of attempts made to acquire the lock
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// The time spent trying to acquire the lock
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+impl std::fmt::Display for LockTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "lock not acquired after {} attempt(s) in {:?}",
+            self.attempts, self.elapsed
+        )
+    }
+}
+
+impl std::error::Error for LockTimeout {}
+
+/// A poisoned lock is acquired anyway, since retrying would not unpoison it
+fn unpoisoned<G>(result: Result<G, TryLockError<G>>) -> Option<G> {
+    match result {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+fn acquire<D, G>(
+    durations: D,
+    timeout: Duration,
+    mut try_acquire: impl FnMut() -> Option<G>,
+) -> Result<G, LockTimeout>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    let start = SystemClock.now();
+    let elapsed = move || SystemClock.now().saturating_duration_since(start);
+    let durations = crate::fit_deadline(durations, move || timeout.saturating_sub(elapsed()));
+    let mut attempts = 0;
+    retry!(durations, {
+        attempts += 1;
+        try_acquire()
+    })
+    .map_err(|()| LockTimeout {
+        attempts,
+        elapsed: elapsed(),
+    })
+}
+
+/// Lock the given mutex with `Mutex::try_lock`, retrying according to the given delays until the
+/// given timeout
+pub fn try_lock_with_backoff<'a, T, D>(
+    mutex: &'a Mutex<T>,
+    durations: D,
+    timeout: Duration,
+) -> Result<MutexGuard<'a, T>, LockTimeout>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    acquire(durations, timeout, || unpoisoned(mutex.try_lock()))
+}
+
+/// Lock the given lock for reading with `RwLock::try_read`, retrying according to the given delays
+/// until the given timeout
+pub fn try_read_with_backoff<'a, T, D>(
+    lock: &'a RwLock<T>,
+    durations: D,
+    timeout: Duration,
+) -> Result<RwLockReadGuard<'a, T>, LockTimeout>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    acquire(durations, timeout, || unpoisoned(lock.try_read()))
+}
+
+/// Lock the given lock for writing with `RwLock::try_write`, retrying according to the given
+/// delays until the given timeout
+pub fn try_write_with_backoff<'a, T, D>(
+    lock: &'a RwLock<T>,
+    durations: D,
+    timeout: Duration,
+) -> Result<RwLockWriteGuard<'a, T>, LockTimeout>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    acquire(durations, timeout, || unpoisoned(lock.try_write()))
+}
+
+#[cfg(all(feature = "future", feature = "tokio"))]
+async fn async_acquire<D, G>(
+    durations: D,
+    timeout: Duration,
+    mut try_acquire: impl FnMut() -> Option<G>,
+) -> Result<G, LockTimeout>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    use crate::time::TokioTime;
+
+    let start = TokioTime.now();
+    let elapsed = move || TokioTime.now().saturating_duration_since(start);
+    let durations = crate::fit_deadline(durations, move || timeout.saturating_sub(elapsed()));
+    let mut attempts = 0;
+    crate::async_retry!(durations, {
+        attempts += 1;
+        try_acquire()
+    })
+    .map_err(|()| LockTimeout {
+        attempts,
+        elapsed: elapsed(),
+    })
+}
+
+/// Lock the given mutex with `tokio::sync::Mutex::try_lock`, retrying according to the given
+/// delays until the given timeout
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub async fn async_try_lock_with_backoff<'a, T, D>(
+    mutex: &'a tokio::sync::Mutex<T>,
+    durations: D,
+    timeout: Duration,
+) -> Result<tokio::sync::MutexGuard<'a, T>, LockTimeout>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    async_acquire(durations, timeout, || mutex.try_lock().ok()).await
+}
+
+/// Lock the given lock for reading with `tokio::sync::RwLock::try_read`, retrying according to the
+/// given delays until the given timeout
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub async fn async_try_read_with_backoff<'a, T, D>(
+    lock: &'a tokio::sync::RwLock<T>,
+    durations: D,
+    timeout: Duration,
+) -> Result<tokio::sync::RwLockReadGuard<'a, T>, LockTimeout>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    async_acquire(durations, timeout, || lock.try_read().ok()).await
+}
+
+/// Lock the given lock for writing with `tokio::sync::RwLock::try_write`, retrying according to
+/// the given delays until the given timeout
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub async fn async_try_write_with_backoff<'a, T, D>(
+    lock: &'a tokio::sync::RwLock<T>,
+    durations: D,
+    timeout: Duration,
+) -> Result<tokio::sync::RwLockWriteGuard<'a, T>, LockTimeout>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    async_acquire(durations, timeout, || lock.try_write().ok()).await
+}
+
+/// The error of a semaphore permit that could not be acquired
+#[cfg(all(feature = "future", feature = "tokio"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcquireError {
+    /// No permit was available after the given number of attempts, once the delays ran out
+    Exhausted { attempts: usize },
+    /// The semaphore was closed
+    Closed,
+}
+
+#[cfg(all(feature = "future", feature = "tokio"))]
+impl std::fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exhausted { attempts } => {
+                write!(f, "no permit available after {attempts} attempt(s)")
+            }
+            Self::Closed => write!(f, "semaphore closed"),
+        }
+    }
+}
+
+#[cfg(all(feature = "future", feature = "tokio"))]
+impl std::error::Error for AcquireError {}
+
+#[cfg(all(feature = "future", feature = "tokio"))]
+async fn async_acquire_permit<D, P>(
+    durations: D,
+    mut try_acquire: impl FnMut() -> Result<P, tokio::sync::TryAcquireError>,
+) -> Result<P, AcquireError>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    use crate::OperationResult;
+    use tokio::sync::TryAcquireError;
+
+    let mut attempts = 0;
+    crate::async_retry!(durations, {
+        attempts += 1;
+        match try_acquire() {
+            Ok(permit) => OperationResult::Ok(permit),
+            Err(TryAcquireError::NoPermits) => {
+                OperationResult::Retry(AcquireError::Exhausted { attempts })
+            }
+            Err(TryAcquireError::Closed) => OperationResult::Err(AcquireError::Closed),
+        }
+    })
+}
+
+/// Acquire a permit of the given semaphore with `Semaphore::try_acquire`, retrying according to
+/// the given delays, e.g. to back off while a connection pool is exhausted
+///
+/// ```
+/// # use retry_block::lock::{acquire_with_retry, AcquireError};
+/// # use retry_block::delay::Fixed;
+/// # use std::time::Duration;
+/// # use tokio::sync::Semaphore;
+/// # #[tokio::main]
+/// # async fn main() {
+/// let pool = Semaphore::new(1);
+/// let delays = Fixed::exact(Duration::from_millis(1)).take(2);
+/// let permit = acquire_with_retry(&pool, delays.clone()).await.unwrap();
+/// let error = acquire_with_retry(&pool, delays).await.unwrap_err();
+/// assert_eq!(error, AcquireError::Exhausted { attempts: 3 });
+/// # drop(permit);
+/// # }
+/// ```
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub async fn acquire_with_retry<D>(
+    semaphore: &tokio::sync::Semaphore,
+    durations: D,
+) -> Result<tokio::sync::SemaphorePermit<'_>, AcquireError>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    async_acquire_permit(durations, || semaphore.try_acquire()).await
+}
+
+/// Acquire an owned permit of the given semaphore with `Semaphore::try_acquire_owned`, retrying
+/// according to the given delays
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub async fn acquire_owned_with_retry<D>(
+    semaphore: std::sync::Arc<tokio::sync::Semaphore>,
+    durations: D,
+) -> Result<tokio::sync::OwnedSemaphorePermit, AcquireError>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    async_acquire_permit(durations, || semaphore.clone().try_acquire_owned()).await
+}
+
+#[cfg(all(test, feature = "future", feature = "tokio"))]
+mod test {
+    use super::{acquire_owned_with_retry, async_try_write_with_backoff, AcquireError};
+    use crate::delay::Fixed;
+    use std::time::Duration;
+    use tokio::sync::RwLock;
+
+    #[tokio::test(start_paused = true)]
+    async fn async_write_timeout() {
+        let lock = RwLock::new(0);
+        let _reader = lock.read().await;
+        let error = async_try_write_with_backoff(
+            &lock,
+            Fixed::exact(Duration::from_millis(10)),
+            Duration::from_millis(35),
+        )
+        .await
+        .unwrap_err();
+        // 3 delays of 10ms, then one clamped to 2.5ms
+        assert_eq!(error.attempts(), 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn semaphore_permits() {
+        let pool = std::sync::Arc::new(tokio::sync::Semaphore::new(1));
+        let permit = pool.clone().acquire_owned().await.unwrap();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(15)).await;
+            drop(permit);
+        });
+        let delays = Fixed::exact(Duration::from_millis(10)).take(3);
+        assert!(acquire_owned_with_retry(pool.clone(), delays.clone())
+            .await
+            .is_ok());
+        release.await.unwrap();
+
+        pool.close();
+        let error = acquire_owned_with_retry(pool, delays).await.unwrap_err();
+        assert_eq!(error, AcquireError::Closed);
+    }
+}
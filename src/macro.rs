@@ -11,7 +11,7 @@
 ///
 /// let value = retry!(
 ///     // an `IntoIterator<Item = Duration>`
-///     Fixed::new(Duration::from_millis(1)),
+///     Fixed::exact(Duration::from_millis(1)),
 ///
 ///     // a block that returns an `Into<OperationResult<_, _>>`
 ///     {
@@ -26,17 +26,229 @@
 /// assert_eq!(value, 42);
 /// ```
 ///
+/// A `seed = ...` argument makes all random delays within the expansion, including those of the
+/// delay iterator's construction, derive from the given seed, as with `delay::seed` (need `random`
+/// feature).
+///
+/// A `map_err = ...` argument converts the final error, whether the operation gave up or failed
+/// fatally:
+///
+/// ```
+/// # use retry_block::retry;
+/// # use retry_block::delay::NoDelay;
+/// let value: Result<(), String> = retry!(NoDelay.take(1), map_err = |e: &str| e.to_uppercase(), {
+///     Err("try again")
+/// });
+/// assert_eq!(value, Err("TRY AGAIN".to_string()));
+/// ```
+///
+/// An `initial_delay = ...` argument sleeps once before the first attempt, e.g. when it is known
+/// to fail right away:
+///
+/// ```
+/// # use retry_block::retry;
+/// # use retry_block::delay::NoDelay;
+/// # use std::time::Duration;
+/// let value = retry!(NoDelay.take(1), initial_delay = Duration::from_millis(1), {
+///     Ok::<_, ()>(42)
+/// });
+/// assert_eq!(value, Ok(42));
+/// ```
+///
+/// A `max_attempts = ...` argument limits the number of attempts, including the first one, even
+/// with an infinite delay iterator:
+///
+/// ```
+/// # use retry_block::retry;
+/// # use retry_block::delay::NoDelay;
+/// let mut attempts = 0;
+/// let value = retry!(NoDelay, max_attempts = 3, {
+///     attempts += 1;
+///     Err::<(), _>(attempts)
+/// });
+/// assert_eq!(value, Err(3));
+/// ```
+///
+/// A `catch_unwind = ...` argument catches the panics of each attempt and handles them according
+/// to the given `OnPanic`, as `retry_fn_catching` does, returning a `PanicError`. The block then
+/// runs in a closure, which it cannot `break` or `return` out of:
+///
+/// ```
+/// # use retry_block::{retry, OnPanic};
+/// # use retry_block::delay::NoDelay;
+/// let mut attempts = 0;
+/// let value = retry!(NoDelay.take(1), catch_unwind = OnPanic::Retry, {
+///     attempts += 1;
+///     if attempts == 1 {
+///         panic!("transient bad data");
+///     }
+///     Ok::<_, ()>(attempts)
+/// });
+/// assert_eq!(value.unwrap(), 2);
+/// ```
+///
+/// A leading operation name attaches it, along with the number of attempts and total delay, to
+/// the final error as a `WithRetryContext`, and accounts the delays in
+/// `DelayAccounting::named`, so that failures (and their `tracing` events and `metrics`
+/// counters, with these features) can be aggregated per logical operation:
+///
+/// ```
+/// # use retry_block::retry;
+/// # use retry_block::delay::NoDelay;
+/// let error = retry!("fetch_user", NoDelay.take(1), { Err::<(), _>("unreachable") }).unwrap_err();
+/// assert_eq!(error.operation(), "fetch_user");
+/// assert_eq!(error.attempts(), 2);
+/// ```
 #[macro_export]
 macro_rules! retry {
+    ($name:literal, $durations:expr, $block:block) => {{
+        let mut state = $crate::RetryState::new(
+            $crate::DelayAccounting::named($name)
+                .track($crate::delay::__faulty($durations.into_iter())),
+        );
+        let mut attempts = 0;
+        // `fatal!` breaks out with the bare error as well, so it is only wrapped after the loop
+        let res = loop {
+            attempts += 1;
+            match state.next_step($block) {
+                $crate::Step::Attempt => {}
+                $crate::Step::Sleep(duration) => std::thread::sleep(duration),
+                $crate::Step::Done(res) => break res,
+            }
+        };
+        res.map_err(|e| $crate::__with_retry_context(e, $name, attempts, state.total_delay()))
+    }};
+    (seed = $seed:expr, $durations:expr, $block:block) => {{
+        let _seed = $crate::delay::seed($seed);
+        $crate::retry!($durations, $block)
+    }};
+    ($durations:expr, map_err = $map_err:expr, $block:block) => {
+        $crate::retry!($durations, $block).map_err($map_err)
+    };
+    ($durations:expr, initial_delay = $initial_delay:expr, $block:block) => {{
+        std::thread::sleep($initial_delay);
+        $crate::retry!($durations, $block)
+    }};
+    ($durations:expr, max_attempts = $max_attempts:expr, $block:block) => {
+        $crate::retry!(
+            $crate::delay::MaxAttempts::new($durations, $max_attempts),
+            $block
+        )
+    };
+    ($durations:expr, catch_unwind = $on_panic:expr, $block:block) => {
+        $crate::retry_fn_catching($durations, $on_panic, || $block)
+    };
     ($durations:expr, $block:block) => {{
-        let mut it = $durations.into_iter();
+        let mut it = $crate::delay::__faulty($durations.into_iter());
+        loop {
+            match $block.into() {
+                $crate::OperationResult::Ok(res) => break Ok(res),
+                $crate::OperationResult::Err(e) => break Err(e),
+                $crate::OperationResult::Retry(e) => {
+                    if let Some(duration) = it.next() {
+                        // skip the syscall for zero delays
+                        if !duration.is_zero() {
+                            std::thread::sleep(duration)
+                        }
+                    } else {
+                        break Err(e);
+                    }
+                }
+            }
+        }
+    }};
+}
+
+/// Retry a block with a custom asynchronous sleep function
+///
+/// Retry a block that returns an `Into<OperationResult<O, E>>` until it succeeds, or until the given `Duration`
+/// iterator ends; and return a `Result<O, E>`. Between attempts, the given sleep function is called
+/// with the next delay and the future it returns is awaited, which makes this macro independent of
+/// any particular async runtime.
+///
+/// This macro uses `.await` and is only suitable in an async context.
+///
+/// ```
+/// # use retry_block::async_retry_with;
+/// # use retry_block::delay::Fixed;
+/// # use std::time::Duration;
+/// async fn sleep(duration: Duration) {
+///     // hand the delay over to the timer of your runtime of choice
+/// #   let _ = duration;
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut tried = false;
+///
+/// let value = async_retry_with!(
+///     // a `FnMut(Duration) -> impl Future<Output = ()>`
+///     sleep,
+///
+///     // an `IntoIterator<Item = Duration>`
+///     Fixed::new(Duration::from_millis(1)),
+///
+///     // a block that returns an `Into<OperationResult<_, _>>`
+///     {
+///         if tried {
+///             Ok(42)
+///         } else {
+///             tried = true;
+///             Err("try again")
+///         }
+///     }
+/// );
+/// assert_eq!(value, Ok(42));
+/// # }
+/// ```
+#[cfg(feature = "future")]
+#[macro_export]
+macro_rules! async_retry_with {
+    ($name:literal, $sleep:expr, $durations:expr, $block:block) => {{
+        #[allow(unused_mut)]
+        let mut sleep = $sleep;
+        let mut state = $crate::RetryState::new(
+            $crate::DelayAccounting::named($name)
+                .track($crate::delay::__faulty($durations.into_iter())),
+        );
+        let mut attempts = 0;
+        // `fatal!` breaks out with the bare error as well, so it is only wrapped after the loop
+        let res = loop {
+            attempts += 1;
+            match state.next_step($block) {
+                $crate::Step::Attempt => {}
+                $crate::Step::Sleep(duration) => sleep(duration).await,
+                $crate::Step::Done(res) => break res,
+            }
+        };
+        res.map_err(|e| $crate::__with_retry_context(e, $name, attempts, state.total_delay()))
+    }};
+    ($sleep:expr, $durations:expr, map_err = $map_err:expr, $block:block) => {
+        $crate::async_retry_with!($sleep, $durations, $block).map_err($map_err)
+    };
+    ($sleep:expr, $durations:expr, initial_delay = $initial_delay:expr, $block:block) => {{
+        #[allow(unused_mut)]
+        let mut sleep = $sleep;
+        sleep($initial_delay).await;
+        $crate::async_retry_with!(sleep, $durations, $block)
+    }};
+    ($sleep:expr, $durations:expr, yield_now = $yield_now:expr, $block:block) => {{
+        #[allow(unused_mut)]
+        let mut sleep = $sleep;
+        let yield_now: bool = $yield_now;
+        let mut it = $crate::delay::__faulty($durations.into_iter());
         loop {
             match $block.into() {
                 $crate::OperationResult::Ok(res) => break Ok(res),
                 $crate::OperationResult::Err(e) => break Err(e),
                 $crate::OperationResult::Retry(e) => {
                     if let Some(duration) = it.next() {
-                        std::thread::sleep(duration)
+                        // skip the timer registration for zero delays
+                        if !duration.is_zero() {
+                            sleep(duration).await;
+                        } else if yield_now {
+                            $crate::yield_now().await;
+                        }
                     } else {
                         break Err(e);
                     }
@@ -44,6 +256,9 @@ macro_rules! retry {
             }
         }
     }};
+    ($sleep:expr, $durations:expr, $block:block) => {
+        $crate::async_retry_with!($sleep, $durations, yield_now = false, $block)
+    };
 }
 
 /// Retry a block with `tokio::time::sleep`
@@ -55,7 +270,7 @@ macro_rules! retry {
 ///
 /// ```
 /// # use retry_block::async_retry;
-/// # use retry_block::delay::Fixed;
+/// # use retry_block::delay::{Fixed, NoDelay};
 /// # use std::time::Duration;
 /// #[tokio::main]
 /// async fn main() {
@@ -76,32 +291,103 @@ macro_rules! retry {
 ///         }
 ///     );
 ///     assert_eq!(value, Ok(42));
+///
+///     // the final error can be converted with a `map_err = ...` argument
+///     let value: Result<(), String> = async_retry!(Fixed::new(Duration::from_millis(1)).take(1), map_err = String::from, {
+///         Err("try again")
+///     });
+///     assert_eq!(value, Err("try again".to_string()));
+///
+///     // and a first attempt known to fail right away can be delayed with `initial_delay = ...`
+///     let value = async_retry!(Fixed::new(Duration::from_millis(1)), initial_delay = Duration::from_millis(1), {
+///         Ok::<_, ()>(42)
+///     });
+///     assert_eq!(value, Ok(42));
+///
+///     // and errors that should not be retried can be propagated with `fatal!`
+///     let value = async_retry!(Fixed::new(Duration::from_millis(1)), {
+///         let _id: u32 = retry_block::fatal!("x".parse().map_err(|_| "invalid id"));
+///         Err::<u32, _>("unreachable")
+///     });
+///     assert_eq!(value, Err("invalid id"));
+///
+///     // and a leading operation name is attached to the final error, as with `retry!`
+///     let error = async_retry!("fetch_user", Fixed::new(Duration::from_millis(1)).take(1), {
+///         Err::<(), _>("unreachable")
+///     })
+///     .unwrap_err();
+///     assert_eq!(error.operation(), "fetch_user");
+///
+///     // and zero delays can yield to the other tasks with `yield_now = true` instead of
+///     // retrying right away
+///     let value = async_retry!(NoDelay.take(3), yield_now = true, { Ok::<_, ()>(42) });
+///     assert_eq!(value, Ok(42));
 /// }
 /// ```
-#[cfg(feature = "future")]
+#[cfg(all(feature = "future", feature = "tokio"))]
 #[macro_export]
 macro_rules! async_retry {
-    ($durations:expr, $block:block) => {{
-        let mut it = $durations.into_iter();
-        loop {
-            match $block.into() {
-                $crate::OperationResult::Ok(res) => break Ok(res),
-                $crate::OperationResult::Err(e) => break Err(e),
-                $crate::OperationResult::Retry(e) => {
-                    if let Some(duration) = it.next() {
-                        tokio::time::sleep(duration).await;
-                    } else {
-                        break Err(e);
-                    }
-                }
-            }
-        }
-    }};
+    ($name:literal, $durations:expr, $block:block) => {
+        $crate::async_retry_with!($name, tokio::time::sleep, $durations, $block)
+    };
+    ($durations:expr, map_err = $map_err:expr, $block:block) => {
+        $crate::async_retry!($durations, $block).map_err($map_err)
+    };
+    ($durations:expr, initial_delay = $initial_delay:expr, $block:block) => {
+        $crate::async_retry_with!(
+            tokio::time::sleep,
+            $durations,
+            initial_delay = $initial_delay,
+            $block
+        )
+    };
+    ($durations:expr, yield_now = $yield_now:expr, $block:block) => {
+        $crate::async_retry_with!(
+            tokio::time::sleep,
+            $durations,
+            yield_now = $yield_now,
+            $block
+        )
+    };
+    ($durations:expr, max_attempts = $max_attempts:expr, $block:block) => {
+        $crate::async_retry!(
+            $crate::delay::MaxAttempts::new($durations, $max_attempts),
+            $block
+        )
+    };
+    ($durations:expr, $block:block) => {
+        $crate::async_retry_with!(tokio::time::sleep, $durations, $block)
+    };
 }
 
 /// Retry an operation forever with exponential delay until it succeeds
 ///
+/// With `escalate = &mut escalation`, each time the delays (a cloneable `Duration` iterator
+/// given first, or the default ones) run out is reported to the given `Escalation` before they
+/// start over.
+///
+/// Without the `random` and `fastrand` features, the default delays start at exactly 100ms
+/// rather than at a random proportion of it.
+///
 /// ```
+/// # use retry_block::{retry_perpetual, Escalation};
+/// # use retry_block::delay::NoDelay;
+/// let mut alerts = Vec::new();
+/// let mut escalation = Escalation::hook(2, |gave_up| alerts.push(gave_up));
+/// let mut attempts = 0;
+/// let value = retry_perpetual!(NoDelay.take(1), escalate = &mut escalation, {
+///     attempts += 1;
+///     if attempts < 9 { Err("unavailable") } else { Ok(attempts) }
+/// });
+/// assert_eq!(value, 9);
+/// drop(escalation);
+/// // 4 rounds of 2 attempts gave up before the last one succeeded
+/// assert_eq!(alerts, vec![2, 4]);
+/// ```
+///
+/// ```
+/// # #[cfg(any(feature = "random", feature = "fastrand"))]
+/// # {
 /// # use retry_block::{retry_perpetual, retry};
 /// # use retry_block::delay::Exponential;
 /// # use std::time::Duration;
@@ -114,13 +400,37 @@ macro_rules! async_retry {
 ///     // ...
 /// #   Ok::<(), ()>(())
 /// }).unwrap();
+/// # }
 /// ```
 #[macro_export]
 macro_rules! retry_perpetual {
+    ($durations:expr, escalate = $escalation:expr, $block:block) => {{
+        let escalation: &mut $crate::Escalation = $escalation;
+        loop {
+            let mut it = $crate::delay::__faulty($durations.clone().into_iter());
+            let res = loop {
+                match $block {
+                    Ok(res) => break Some(res),
+                    Err(_) => match it.next() {
+                        Some(duration) => std::thread::sleep(duration),
+                        None => break None,
+                    },
+                }
+            };
+            match res {
+                Some(res) => {
+                    escalation.succeeded();
+                    break res;
+                }
+                None => escalation.gave_up(),
+            }
+        }
+    }};
+    (escalate = $escalation:expr, $block:block) => {
+        $crate::retry_perpetual!($crate::delay::__perpetual(), escalate = $escalation, $block)
+    };
     ($block:block) => {{
-        let mut it = $crate::delay::Exponential::new(std::time::Duration::from_millis(100))
-            .bounded(std::time::Duration::from_secs(3600))
-            .into_iter();
+        let mut it = $crate::delay::__perpetual();
         loop {
             match $block {
                 Ok(res) => break res,
@@ -135,12 +445,16 @@ macro_rules! retry_perpetual {
 
 /// Retry an operation forever with exponential delay until it succeeds
 ///
+/// Accepts the same `escalate = &mut escalation` option as `retry_perpetual!`.
+///
 /// ```
 /// # use retry_block::{async_retry_perpetual, async_retry};
 /// # use retry_block::delay::Exponential;
 /// # use std::time::Duration;
 /// # #[tokio::main]
 /// # async fn main() {
+/// # #[cfg(any(feature = "random", feature = "fastrand"))]
+/// # {
 /// async_retry_perpetual!({
 ///     // ...
 /// #   Ok::<(), ()>(())
@@ -151,14 +465,38 @@ macro_rules! retry_perpetual {
 /// #   Ok::<(), ()>(())
 /// }).unwrap();
 /// # }
+/// # }
 /// ```
-#[cfg(feature = "future")]
+#[cfg(all(feature = "future", feature = "tokio"))]
 #[macro_export]
 macro_rules! async_retry_perpetual {
+    ($durations:expr, escalate = $escalation:expr, $block:block) => {{
+        let escalation: &mut $crate::Escalation = $escalation;
+        loop {
+            let mut it = $crate::delay::__faulty($durations.clone().into_iter());
+            let res = loop {
+                match $block {
+                    Ok(res) => break Some(res),
+                    Err(_) => match it.next() {
+                        Some(duration) => tokio::time::sleep(duration).await,
+                        None => break None,
+                    },
+                }
+            };
+            match res {
+                Some(res) => {
+                    escalation.succeeded();
+                    break res;
+                }
+                None => escalation.gave_up(),
+            }
+        }
+    }};
+    (escalate = $escalation:expr, $block:block) => {
+        $crate::async_retry_perpetual!($crate::delay::__perpetual(), escalate = $escalation, $block)
+    };
     ($block:block) => {{
-        let mut it = $crate::delay::Exponential::new(std::time::Duration::from_millis(100))
-            .bounded(std::time::Duration::from_secs(3600))
-            .into_iter();
+        let mut it = $crate::delay::__perpetual();
         loop {
             match $block {
                 Ok(res) => break res,
@@ -170,3 +508,93 @@ macro_rules! async_retry_perpetual {
         }
     }};
 }
+
+/// Retry a block with the default policy of the enclosing scope, like `retry!`
+///
+/// The delays are those of the innermost `with_default_policy` (or, within a task,
+/// `scope_default_policy`) scope, so that deeply nested code does not need to be handed a policy.
+/// See `default_delays` for the policy used outside any scope.
+///
+/// ```
+/// # use retry_block::{retry_default, with_default_policy, DefaultPolicy};
+/// # use retry_block::delay::NoDelay;
+/// let mut attempts = 0;
+/// let value = with_default_policy(DefaultPolicy::new(NoDelay.take(1)), || {
+///     retry_default!({
+///         attempts += 1;
+///         Err::<(), _>(attempts)
+///     })
+/// });
+/// assert_eq!(value, Err(2));
+/// ```
+#[macro_export]
+macro_rules! retry_default {
+    ($block:block) => {
+        $crate::retry!($crate::default_delays(), $block)
+    };
+}
+
+/// Retry an async block with the default policy of the enclosing scope, like `async_retry!`
+///
+/// ```
+/// # use retry_block::{async_retry_default, scope_default_policy, DefaultPolicy};
+/// # use retry_block::delay::NoDelay;
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let policy = DefaultPolicy::new(NoDelay.take(2));
+/// let value = scope_default_policy(policy, async {
+///     async_retry_default!({ Err::<(), _>("unavailable") })
+/// })
+/// .await;
+/// assert_eq!(value, Err("unavailable"));
+/// # }
+/// ```
+#[cfg(all(feature = "future", feature = "tokio"))]
+#[macro_export]
+macro_rules! async_retry_default {
+    ($block:block) => {
+        $crate::async_retry!($crate::default_delays(), $block)
+    };
+}
+
+/// Unwrap a `Result` inside the block of a retry macro, failing the whole retry immediately with
+/// its error rather than retrying
+///
+/// This lets a single block mix retryable and fatal sub-operations. It must be used directly in
+/// the block given to `retry!`, `async_retry!` or `async_retry_with!`, not within a nested loop or
+/// closure, since it breaks out of the retry loop of the macro. With a named macro, the error is
+/// wrapped in a `WithRetryContext` like any other final error.
+///
+/// ```
+/// # use retry_block::{fatal, retry};
+/// # use retry_block::delay::NoDelay;
+/// fn parse(input: &str) -> Result<u32, String> {
+///     input.parse().map_err(|_| format!("invalid input: {}", input))
+/// }
+///
+/// let mut attempts = 0;
+/// let value: Result<u32, String> = retry!(NoDelay.take(5), {
+///     attempts += 1;
+///     let value = fatal!(parse("forty-two"));
+///     Err(format!("{} is unavailable", value))
+/// });
+/// assert_eq!(value, Err("invalid input: forty-two".to_string()));
+/// assert_eq!(attempts, 1);
+///
+/// let value: Result<u32, _> = retry!("parse", NoDelay.take(5), {
+///     let value = fatal!(parse("forty-two"));
+///     Err(format!("{} is unavailable", value))
+/// });
+/// let error = value.unwrap_err();
+/// assert_eq!(error.attempts(), 1);
+/// assert_eq!(error.into_inner(), "invalid input: forty-two");
+/// ```
+#[macro_export]
+macro_rules! fatal {
+    ($result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(e) => break Err(e),
+        }
+    };
+}
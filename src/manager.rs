@@ -0,0 +1,317 @@
+use crate::delay::Faultable;
+use crate::time::{TimeSource, TokioTime};
+use crate::OperationResult;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+struct KeyState<I> {
+    delays: I,
+    failures: usize,
+    not_before: Instant,
+    last_used: Instant,
+}
+
+type Keys<K, I> = HashMap<K, KeyState<I>>;
+
+/// Independent backoffs per key (e.g. per remote host), shared by all the operations run on it
+///
+/// Each key follows its own copy of the given delays: an operation run on a key first waits for
+/// the current backoff of the key, and each retryable failure sets it to the next delay, so that
+/// concurrent operations on a failing host back off together while those on other hosts go on. A
+/// success resets the backoff of the key, and so does running out of delays, after which the
+/// operation gives up with its last error. Keys unused for the idle timeout (10 minutes by
+/// default) are evicted. Clones share the same keys.
+///
+/// Time is told and waited for with tokio, unless replaced with `RetryManager::with_time_source`.
+///
+/// This is the in-memory sibling of `persist::RetryHandle`: nothing survives a restart.
+///
+/// ```
+/// use retry_block::delay::Fixed;
+/// use retry_block::RetryManager;
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let manager = RetryManager::new(Fixed::exact(Duration::from_millis(10)).take(3));
+///     let result = manager
+///         .run("replica-1", || async { Err::<(), _>("connection refused") })
+///         .await;
+///     assert_eq!(result, Err("connection refused"));
+///     let result = manager.run("replica-2", || async { Ok::<_, ()>(42) }).await;
+///     assert_eq!(result, Ok(42));
+/// }
+/// ```
+pub struct RetryManager<K, D, T = TokioTime>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    durations: D,
+    idle_timeout: Duration,
+    time: T,
+    keys: Arc<Mutex<Keys<K, Faultable<D::IntoIter>>>>,
+}
+
+impl<K, D, T> Clone for RetryManager<K, D, T>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            durations: self.durations.clone(),
+            idle_timeout: self.idle_timeout,
+            time: self.time.clone(),
+            keys: self.keys.clone(),
+        }
+    }
+}
+
+impl<K, D> RetryManager<K, D>
+where
+    K: Eq + Hash + Clone,
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Create a manager giving each key a copy of the given cloneable delay iterator
+    pub fn new(durations: D) -> Self {
+        Self {
+            durations,
+            idle_timeout: Duration::from_secs(600),
+            time: TokioTime,
+            keys: Default::default(),
+        }
+    }
+}
+
+impl<K, D, T> RetryManager<K, D, T>
+where
+    K: Eq + Hash + Clone,
+    D: IntoIterator<Item = Duration> + Clone,
+    T: TimeSource,
+{
+    /// Tell the time and wait for the backoffs of the keys with the given `TimeSource` rather
+    /// than with tokio, e.g. `time::MockTime` in tests
+    ///
+    /// The keys currently backing off are kept, so that it is best called before any operation
+    /// is run.
+    pub fn with_time_source<U: TimeSource>(self, time: U) -> RetryManager<K, D, U> {
+        RetryManager {
+            durations: self.durations,
+            idle_timeout: self.idle_timeout,
+            time,
+            keys: self.keys,
+        }
+    }
+
+    /// Evict the keys unused for the given duration rather than 10 minutes
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    fn with_keys<R>(&self, f: impl FnOnce(&mut Keys<K, Faultable<D::IntoIter>>) -> R) -> R {
+        f(&mut self.keys.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Run the given operation on the given key, retrying it with the backoff of the key until it
+    /// succeeds, or until the delays of the key run out
+    pub async fn run<O, F, OR, R, E>(&self, key: K, mut operation: O) -> Result<R, E>
+    where
+        O: FnMut() -> F,
+        F: Future<Output = OR>,
+        OR: Into<OperationResult<R, E>>,
+    {
+        self.evict_idle();
+        loop {
+            let not_before = self.with_keys(|keys| {
+                let state = keys.get_mut(&key)?;
+                state.last_used = self.time.now();
+                Some(state.not_before)
+            });
+            if let Some(not_before) = not_before {
+                let backoff = not_before.saturating_duration_since(self.time.now());
+                if !backoff.is_zero() {
+                    self.time.sleep(backoff).await;
+                }
+            }
+            let e = match operation().await.into() {
+                OperationResult::Ok(res) => {
+                    self.with_keys(|keys| keys.remove(&key));
+                    return Ok(res);
+                }
+                OperationResult::Err(e) => return Err(e),
+                OperationResult::Retry(e) => e,
+            };
+            let retried = self.with_keys(|keys| {
+                let now = self.time.now();
+                let state = keys.entry(key.clone()).or_insert_with(|| KeyState {
+                    delays: crate::delay::__faulty(self.durations.clone().into_iter()),
+                    failures: 0,
+                    not_before: now,
+                    last_used: now,
+                });
+                state.failures += 1;
+                state.last_used = now;
+                match state.delays.next() {
+                    Some(delay) => {
+                        // concurrent failures do not shorten the backoff of the key
+                        state.not_before = state.not_before.max(now + delay);
+                        true
+                    }
+                    None => {
+                        keys.remove(&key);
+                        false
+                    }
+                }
+            });
+            if !retried {
+                return Err(e);
+            }
+        }
+    }
+
+    /// The number of consecutive retryable failures of the operations run on the given key
+    pub fn failures(&self, key: &K) -> usize {
+        self.with_keys(|keys| keys.get(key).map_or(0, |state| state.failures))
+    }
+
+    /// The number of keys currently backing off
+    pub fn len(&self) -> usize {
+        self.with_keys(|keys| keys.len())
+    }
+
+    /// Whether no key is currently backing off
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Evict the keys unused for the idle timeout, returning how many were
+    pub fn evict_idle(&self) -> usize {
+        let now = self.time.now();
+        self.with_keys(|keys| {
+            let before = keys.len();
+            keys.retain(|_, state| {
+                now.saturating_duration_since(state.last_used) < self.idle_timeout
+            });
+            before - keys.len()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delay::{Clock, Fixed};
+    use crate::time::MockTime;
+    use crate::{OperationResult, RetryManager};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn independent_keys() {
+        let manager = RetryManager::new(Fixed::exact(Duration::from_secs(10)).take(5))
+            .with_idle_timeout(Duration::from_secs(60));
+        let mut attempts = 0;
+        let result = manager
+            .run("a", || {
+                attempts += 1;
+                async move {
+                    if attempts < 3 {
+                        Err(())
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result, Ok(()));
+        assert!(manager.is_empty());
+
+        let start = Instant::now();
+        let fatal = || async { OperationResult::<(), _>::Err("fatal") };
+        let result = manager.run("a", fatal).await;
+        assert_eq!(result, Err("fatal"));
+        // a fatal error leaves the backoff as is
+        assert_eq!(manager.failures(&"a"), 0);
+
+        let failing = manager.clone();
+        let mut calls = 0;
+        let backing_off = failing.run("a", || {
+            calls += 1;
+            async move {
+                if calls < 2 {
+                    Err(())
+                } else {
+                    std::future::pending::<Result<(), ()>>().await
+                }
+            }
+        });
+        tokio::select! {
+            _ = backing_off => unreachable!(),
+            _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+        }
+        assert_eq!(manager.failures(&"a"), 1);
+        // another key is not held back by the backoff of the first one
+        assert_eq!(manager.run("b", || async { Ok::<_, ()>(()) }).await, Ok(()));
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+        // while an operation on the first one waits for its backoff
+        assert_eq!(manager.run("a", || async { Ok::<_, ()>(()) }).await, Ok(()));
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+
+        let abandoned = manager.run("c", || async { Err::<(), _>(()) });
+        tokio::select! {
+            _ = abandoned => unreachable!(),
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+        }
+        assert_eq!(manager.len(), 1);
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(manager.evict_idle(), 1);
+    }
+
+    #[tokio::test]
+    async fn mock_time_backoff() {
+        let time = MockTime::new();
+        let manager = RetryManager::new(Fixed::exact(Duration::from_secs(10)).take(2))
+            .with_idle_timeout(Duration::from_secs(60))
+            .with_time_source(time.clone());
+        let start = time.now();
+        let mut attempts = 0;
+        let result = manager
+            .run("a", || {
+                attempts += 1;
+                async move {
+                    if attempts < 3 {
+                        Err(())
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result, Ok(()));
+        // both backoffs were waited for on the mock clock
+        assert_eq!(time.now() - start, Duration::from_secs(20));
+
+        let mut calls = 0;
+        let backing_off = manager.run("b", || {
+            calls += 1;
+            async move {
+                if calls < 2 {
+                    Err(())
+                } else {
+                    std::future::pending::<Result<(), ()>>().await
+                }
+            }
+        });
+        tokio::select! {
+            biased;
+            _ = backing_off => unreachable!(),
+            _ = std::future::ready(()) => {}
+        }
+        assert_eq!(manager.failures(&"b"), 1);
+        time.advance(Duration::from_secs(60));
+        assert_eq!(manager.evict_idle(), 1);
+    }
+}
@@ -0,0 +1,90 @@
+//! Retried TCP connections
+//!
+//! ```
+//! # use retry_block::net::retry_connect;
+//! # use retry_block::delay::Fixed;
+//! # use std::net::TcpListener;
+//! # use std::time::Duration;
+//! let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+//! let addr = listener.local_addr().unwrap();
+//! let stream = retry_connect(addr, Fixed::exact(Duration::from_millis(10)).take(3)).unwrap();
+//! assert_eq!(stream.peer_addr().unwrap(), addr);
+//! ```
+
+use crate::{retry_fn, OperationResult};
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Whether the given connection error is likely to go away on retry, e.g. while the server is
+/// starting up or the network is recovering
+pub fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::HostUnreachable
+            | io::ErrorKind::NetworkUnreachable
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::Interrupted
+    )
+}
+
+/// Connect to the given address with `TcpStream::connect`, retrying on transient errors until the
+/// given `Duration` iterator ends
+pub fn retry_connect<A, D>(addr: A, durations: D) -> io::Result<TcpStream>
+where
+    A: ToSocketAddrs,
+    D: IntoIterator<Item = Duration>,
+{
+    retry_fn(durations, || {
+        OperationResult::retry_if(TcpStream::connect(&addr), is_transient)
+    })
+}
+
+/// Connect to the given address with `tokio::net::TcpStream::connect`, retrying on transient errors
+/// until the given `Duration` iterator ends
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub async fn async_retry_connect<A, D>(addr: A, durations: D) -> io::Result<tokio::net::TcpStream>
+where
+    A: tokio::net::ToSocketAddrs + Clone,
+    D: IntoIterator<Item = Duration>,
+{
+    crate::async_retry!(durations, {
+        OperationResult::retry_if(
+            tokio::net::TcpStream::connect(addr.clone()).await,
+            is_transient,
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::retry_connect;
+    use crate::delay::NoDelay;
+    use std::io::ErrorKind;
+    use std::net::TcpListener;
+
+    #[test]
+    fn refused_connection() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        // the listener is dropped, so nothing accepts connections on its address anymore
+        let error = retry_connect(addr, NoDelay.take(2)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ConnectionRefused);
+    }
+
+    #[cfg(all(feature = "future", feature = "tokio"))]
+    #[tokio::test]
+    async fn async_connection() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stream = super::async_retry_connect(addr, NoDelay.take(2))
+            .await
+            .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+    }
+}
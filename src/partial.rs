@@ -0,0 +1,194 @@
+use crate::OperationResult;
+use std::time::Duration;
+
+/// The result of an attempt that may have produced a partial or stale value along with its error
+///
+/// An `OperationResult` (or a `Result`) converts into the same variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartialResult<T, E> {
+    /// Contains the success value.
+    Ok(T),
+    /// Contains a value to fall back on if no attempt succeeds, and the error to retry with.
+    Partial(T, E),
+    /// Contains the error value if duration is exceeded.
+    Retry(E),
+    /// Contains an error value to return immediately.
+    Err(E),
+}
+
+impl<T, E> From<OperationResult<T, E>> for PartialResult<T, E> {
+    fn from(item: OperationResult<T, E>) -> Self {
+        match item {
+            OperationResult::Ok(v) => PartialResult::Ok(v),
+            OperationResult::Retry(e) => PartialResult::Retry(e),
+            OperationResult::Err(e) => PartialResult::Err(e),
+        }
+    }
+}
+
+impl<T, E> From<Result<T, E>> for PartialResult<T, E> {
+    fn from(item: Result<T, E>) -> Self {
+        OperationResult::from(item).into()
+    }
+}
+
+/// The value of an operation retried with `retry_fn_partial`, complete or degraded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Degraded<T, E> {
+    /// An attempt succeeded with this value.
+    Complete(T),
+    /// No attempt succeeded before the delays ran out, this is the last partial value, along with
+    /// the error of the last attempt.
+    Partial { value: T, error: E },
+}
+
+impl<T, E> Degraded<T, E> {
+    /// Returns `true` if no attempt succeeded.
+    pub fn is_partial(&self) -> bool {
+        matches!(self, Degraded::Partial { .. })
+    }
+
+    /// Returns the value, complete or not.
+    pub fn into_value(self) -> T {
+        match self {
+            Degraded::Complete(value) | Degraded::Partial { value, .. } => value,
+        }
+    }
+}
+
+/// Retry the given operation until it succeeds, or until the given `Duration` iterator ends, and
+/// then fall back on the last partial value it reported, if any.
+///
+/// This gives "serve stale on failure" semantics, e.g. to a cache refresh loop reporting the
+/// cached value along with the error of each failed refresh. Partial values are kept across
+/// attempts that fail without one, but not returned on a fatal error (`PartialResult::Err`).
+///
+/// ```
+/// # use retry_block::{retry_fn_partial, Degraded, PartialResult};
+/// # use retry_block::delay::NoDelay;
+/// let cached = "stale";
+/// let value = retry_fn_partial(NoDelay.take(2), || {
+///     PartialResult::Partial(cached, "origin unavailable")
+/// });
+/// assert_eq!(
+///     value,
+///     Ok(Degraded::Partial {
+///         value: "stale",
+///         error: "origin unavailable"
+///     })
+/// );
+///
+/// let value = retry_fn_partial(NoDelay.take(2), || Err::<&str, _>("origin unavailable"));
+/// assert_eq!(value, Err("origin unavailable"));
+/// ```
+pub fn retry_fn_partial<D, O, PR, R, E>(durations: D, mut operation: O) -> Result<Degraded<R, E>, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> PR,
+    PR: Into<PartialResult<R, E>>,
+{
+    let mut it = crate::delay::__faulty(durations.into_iter());
+    let mut partial = None;
+    let res = crate::retry_loop(
+        |e| it.next().ok_or(e),
+        crate::sleep::thread_sleep,
+        || keep_partial(operation().into(), &mut partial),
+    );
+    degrade(res, partial)
+}
+
+/// Retry the given asynchronous operation until it succeeds, or until the given `Duration`
+/// iterator ends, and then fall back on the last partial value it reported, if any.
+///
+/// See `retry_fn_partial`.
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub async fn async_retry_fn_partial<D, O, F, PR, R, E>(
+    durations: D,
+    mut operation: O,
+) -> Result<Degraded<R, E>, E>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    F: std::future::Future<Output = PR>,
+    PR: Into<PartialResult<R, E>>,
+{
+    let mut it = crate::delay::__faulty(durations.into_iter());
+    let partial = std::sync::Mutex::new(None);
+    let res = crate::future::async_retry_loop(
+        |e| it.next().ok_or(e),
+        || {
+            let attempt = operation();
+            async {
+                let res = attempt.await.into();
+                keep_partial(res, &mut partial.lock().unwrap_or_else(|e| e.into_inner()))
+            }
+        },
+    )
+    .await;
+    degrade(res, partial.into_inner().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Keep the partial value of an attempt, if any, dropping the last one on a fatal error
+fn keep_partial<R, E>(res: PartialResult<R, E>, partial: &mut Option<R>) -> OperationResult<R, E> {
+    match res {
+        PartialResult::Ok(res) => OperationResult::Ok(res),
+        PartialResult::Err(e) => {
+            *partial = None;
+            OperationResult::Err(e)
+        }
+        PartialResult::Partial(value, e) => {
+            *partial = Some(value);
+            OperationResult::Retry(e)
+        }
+        PartialResult::Retry(e) => OperationResult::Retry(e),
+    }
+}
+
+/// Fall back on the last partial value, if any, once the retry failed
+fn degrade<R, E>(res: Result<R, E>, partial: Option<R>) -> Result<Degraded<R, E>, E> {
+    match (res, partial) {
+        (Ok(res), _) => Ok(Degraded::Complete(res)),
+        (Err(error), Some(value)) => Ok(Degraded::Partial { value, error }),
+        (Err(error), None) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{retry_fn_partial, Degraded, PartialResult};
+    use crate::delay::NoDelay;
+
+    #[test]
+    fn last_partial_value() {
+        let mut attempts = 0;
+        let value = retry_fn_partial(NoDelay.take(3), || {
+            attempts += 1;
+            match attempts {
+                1 | 2 => PartialResult::Partial(attempts, "stale"),
+                _ => PartialResult::Retry("unavailable"),
+            }
+        });
+        assert_eq!(
+            value,
+            Ok(Degraded::Partial {
+                value: 2,
+                error: "unavailable"
+            })
+        );
+
+        let value = retry_fn_partial(NoDelay.take(3), || PartialResult::Partial(1, "stale"))
+            .map(Degraded::into_value);
+        assert_eq!(value, Ok(1));
+
+        let mut fatal = false;
+        let value = retry_fn_partial(NoDelay.take(3), || {
+            if fatal {
+                PartialResult::Err("rejected")
+            } else {
+                fatal = true;
+                PartialResult::Partial(1, "stale")
+            }
+        });
+        assert_eq!(value, Err("rejected"));
+    }
+}
@@ -0,0 +1,43 @@
+use super::Status;
+use lru::LruCache;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+/// An in-memory LRU of the recent outcomes of a `RetryHandle`, by id
+///
+/// Created by `RetryHandle::with_result_cache`, and used by `RetryHandle::retry_cached`.
+pub struct ResultCache<K, O, E> {
+    ttl: Duration,
+    entries: Option<Entries<K, O, E>>,
+}
+
+type Entries<K, O, E> = LruCache<K, (Instant, Status<O, E>)>;
+
+impl<K: Hash + Eq, O, E> ResultCache<K, O, E> {
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: NonZeroUsize::new(capacity).map(LruCache::new),
+        }
+    }
+
+    /// Return the outcome cached for the given id, if it is not older than the TTL at the given
+    /// time
+    pub(crate) fn get(&mut self, id: &K, now: Instant) -> Option<&Status<O, E>> {
+        let entries = self.entries.as_mut()?;
+        if now.saturating_duration_since(entries.peek(id)?.0) >= self.ttl {
+            entries.pop(id);
+            return None;
+        }
+        entries.get(id).map(|(_, status)| status)
+    }
+
+    /// Cache the outcome of the given id as of the given time, evicting the least recently used
+    /// one if full
+    pub(crate) fn insert(&mut self, id: K, status: Status<O, E>, now: Instant) {
+        if let Some(entries) = &mut self.entries {
+            entries.put(id, (now, status));
+        }
+    }
+}
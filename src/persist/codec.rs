@@ -0,0 +1,217 @@
+//! Helpers to encode inputs and statuses as bytes for storage backends
+#![cfg_attr(
+    feature = "json",
+    doc = r##"
+```
+use retry_block::persist::codec::{Json, Record};
+use retry_block::persist::Status;
+
+let record = Record::<Json>::encode(&3, &Status::<i64, ()>::Success(9)).unwrap();
+// store `record.input` and `record.status` in the backend of your choice
+
+let record = Record::<Json>::from_bytes(record.input, record.status);
+assert_eq!(record.decode_input::<i64>().unwrap(), 3);
+assert!(matches!(record.decode_status::<i64, ()>().unwrap(), Status::Success(9)));
+```
+"##
+)]
+
+use crate::persist::Status;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+
+/// A binary format to encode stored values with
+pub trait Codec {
+    /// The error returned when a value cannot be encoded or decoded
+    type Error: std::error::Error;
+
+    /// Encode a value as bytes
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error>;
+
+    /// Decode a value from bytes
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error>;
+}
+
+/// The JSON format (need `json` feature)
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Codec for Json {
+    type Error = serde_json::Error;
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        serde_json::from_slice(bytes)
+    }
+}
+
+/// The bincode format (need `bincode` feature)
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy)]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl Codec for Bincode {
+    type Error = bincode::Error;
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        bincode::serialize(value)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+/// The input and status of a persistent retry, encoded as bytes with a given codec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record<C> {
+    /// the encoded input
+    pub input: Vec<u8>,
+    /// the encoded status
+    pub status: Vec<u8>,
+    codec: PhantomData<C>,
+}
+
+impl<C: Codec> Record<C> {
+    /// Encode an input and its status
+    pub fn encode<I, O, E>(input: &I, status: &Status<O, E>) -> Result<Self, C::Error>
+    where
+        I: Serialize,
+        O: Serialize,
+        E: Serialize,
+    {
+        Ok(Self::from_bytes(C::encode(input)?, C::encode(status)?))
+    }
+
+    /// Wrap an input and a status previously encoded with the same codec
+    pub fn from_bytes(input: Vec<u8>, status: Vec<u8>) -> Self {
+        Self {
+            input,
+            status,
+            codec: PhantomData,
+        }
+    }
+
+    /// Decode the input
+    pub fn decode_input<I: DeserializeOwned>(&self) -> Result<I, C::Error> {
+        C::decode(&self.input)
+    }
+
+    /// Decode the status
+    pub fn decode_status<O, E>(&self) -> Result<Status<O, E>, C::Error>
+    where
+        O: DeserializeOwned,
+        E: DeserializeOwned,
+    {
+        C::decode(&self.status)
+    }
+}
+
+/// A hash of encoded bytes, deriving ids from the content of inputs
+pub trait Digest {
+    /// The id derived from the bytes
+    type Output;
+
+    /// Hash the given bytes
+    fn digest(&self, bytes: &[u8]) -> Self::Output;
+}
+
+/// The 64-bit FNV-1a hash, which is stable across platforms and releases, but not collision
+/// resistant
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fnv1a;
+
+impl Digest for Fnv1a {
+    type Output = u64;
+
+    fn digest(&self, bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+        })
+    }
+}
+
+/// The BLAKE3 hash, as a lowercase hexadecimal string (need `blake3` feature)
+#[cfg(feature = "blake3")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Blake3;
+
+#[cfg(feature = "blake3")]
+impl Digest for Blake3 {
+    type Output = String;
+
+    fn digest(&self, bytes: &[u8]) -> String {
+        blake3::hash(bytes).to_hex().to_string()
+    }
+}
+
+/// Ids derived by hashing inputs encoded with a given codec, for `RetryHandle::enqueue_auto`
+///
+/// Identical inputs always get the same id, as long as the codec encodes them the same way.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContentHash<C, D = Fnv1a> {
+    digest: D,
+    codec: PhantomData<C>,
+}
+
+impl<C: Codec, D: Digest> ContentHash<C, D> {
+    /// Hash the inputs encoded with the codec using the given digest
+    pub fn new(digest: D) -> Self {
+        Self {
+            digest,
+            codec: PhantomData,
+        }
+    }
+
+    /// The id of the given input
+    pub fn id<T: Serialize + ?Sized>(&self, input: &T) -> Result<D::Output, C::Error> {
+        Ok(self.digest.digest(&C::encode(input)?))
+    }
+}
+
+#[cfg(all(test, any(feature = "json", feature = "bincode")))]
+mod test {
+    use super::*;
+
+    fn roundtrip<C: Codec>()
+    where
+        C::Error: std::fmt::Debug,
+    {
+        let record =
+            Record::<C>::encode(&"input", &Status::<u8, String>::Failure("e".into())).unwrap();
+        let record = Record::<C>::from_bytes(record.input, record.status);
+        assert_eq!(record.decode_input::<String>().unwrap(), "input");
+        assert!(matches!(
+            record.decode_status::<u8, String>().unwrap(),
+            Status::Failure(e) if e == "e"
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn content_ids() {
+        let hash = ContentHash::<Json>::new(Fnv1a);
+        assert_eq!(Fnv1a.digest(b""), 0xcbf29ce484222325);
+        assert_eq!(hash.id(&(1, "a")).unwrap(), hash.id(&(1, "a")).unwrap());
+        assert_ne!(hash.id(&(1, "a")).unwrap(), hash.id(&(1, "b")).unwrap());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_roundtrip() {
+        roundtrip::<Json>();
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_roundtrip() {
+        roundtrip::<Bincode>();
+    }
+}
@@ -0,0 +1,98 @@
+use super::{RetryHandle, RetryInjector};
+use crate::RetryConfig;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A serializable configuration of the whole behavior of a `RetryHandle`, so that it can be
+/// operator-configured from one document
+///
+/// ```
+/// # use retry_block::persist::PersistConfig;
+/// # use std::time::Duration;
+/// let config: PersistConfig = serde_json::from_str(r#"{
+///     "retry": "5x 100ms..3s",
+///     "concurrency": 16,
+///     "attempt_timeout_ms": 30000,
+///     "poll_interval_ms": 60000,
+///     "dead_letter": true
+/// }"#).unwrap();
+/// assert_eq!(config.concurrency, 16);
+/// assert_eq!(config.attempt_timeout(), Some(Duration::from_secs(30)));
+/// assert_eq!(config.page_size, None);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersistConfig {
+    /// the delays between the attempts of each input
+    pub retry: RetryConfig,
+    /// how many inputs are retried concurrently
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// the amount of milliseconds after which an attempt is considered failed, if any
+    #[serde(default)]
+    pub attempt_timeout_ms: Option<u64>,
+    /// the amount of milliseconds between two `RetryHandle::poll_pending` runs, if any
+    #[serde(default)]
+    pub poll_interval_ms: Option<u64>,
+    /// how many pending inputs are loaded at once, if not the default
+    #[serde(default)]
+    pub page_size: Option<usize>,
+    /// the amount of milliseconds after which completed inputs are pruned, if any
+    #[serde(default)]
+    pub retention_ms: Option<u64>,
+    /// whether permanently failed inputs are moved to a dead-letter queue, see
+    /// `RetryHandle::with_dead_letter`
+    #[serde(default)]
+    pub dead_letter: bool,
+}
+
+fn default_concurrency() -> usize {
+    1
+}
+
+impl PersistConfig {
+    /// The per-attempt timeout, if any
+    pub fn attempt_timeout(&self) -> Option<Duration> {
+        self.attempt_timeout_ms.map(Duration::from_millis)
+    }
+
+    /// The interval between two `RetryHandle::poll_pending` runs, if any
+    pub fn poll_interval(&self) -> Option<Duration> {
+        self.poll_interval_ms.map(Duration::from_millis)
+    }
+
+    /// The retention of completed inputs, if any
+    pub fn retention(&self) -> Option<Duration> {
+        self.retention_ms.map(Duration::from_millis)
+    }
+}
+
+impl<'a, Inj> RetryHandle<Inj, RetryConfig>
+where
+    Inj: RetryInjector<'a>,
+{
+    /// Create a new persistent retry handle from an injector and a `PersistConfig`
+    ///
+    /// The concurrency and poll interval of the config are used by `RetryHandle::poll_pending`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the config has an attempt timeout, but `RetryInjector::timeout_error` returns no
+    /// error for it, see `RetryHandle::with_attempt_timeout`.
+    pub fn from_config(injector: Inj, config: PersistConfig) -> Self {
+        let mut handle = Self::new(injector, config.retry.clone());
+        if let Some(timeout) = config.attempt_timeout() {
+            handle = handle.with_attempt_timeout(timeout);
+        }
+        if let Some(page_size) = config.page_size {
+            handle = handle.with_page_size(page_size);
+        }
+        if let Some(retention) = config.retention() {
+            handle = handle.prune_completed_older_than(retention);
+        }
+        if config.dead_letter {
+            handle = handle.with_dead_letter();
+        }
+        handle.poll = Some((config.concurrency, config.poll_interval()));
+        handle
+    }
+}
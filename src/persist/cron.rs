@@ -0,0 +1,60 @@
+use chrono::Utc;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A delay iterator waiting until the next occurrence of a cron schedule in UTC, to re-attempt
+/// persistent retries at given times (e.g. every night at 02:00) rather than after given delays.
+/// (need `cron` feature)
+///
+/// ```
+/// # use retry_block::persist::CronSchedule;
+/// // at 02:00:00 every day, at most 3 times
+/// let schedule: CronSchedule = "0 0 2 * * *".parse().unwrap();
+/// let durations = schedule.take(3);
+/// # assert_eq!(durations.count(), 3);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    schedule: cron::Schedule,
+}
+
+impl CronSchedule {
+    /// Create a new `CronSchedule` from a parsed cron schedule
+    pub fn new(schedule: cron::Schedule) -> Self {
+        Self { schedule }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = cron::error::Error;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        expression.parse().map(Self::new)
+    }
+}
+
+impl Iterator for CronSchedule {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let next = self.schedule.upcoming(Utc).next()?;
+        Some((next - Utc::now()).to_std().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CronSchedule;
+    use std::time::Duration;
+
+    #[test]
+    fn cron_schedule() {
+        let mut schedule: CronSchedule = "* * * * * *".parse().unwrap();
+        assert!(schedule.next().unwrap() <= Duration::from_secs(1));
+
+        let mut schedule: CronSchedule = "0 0 0 1 1 * 2000".parse().unwrap();
+        assert_eq!(schedule.next(), None);
+
+        assert!("every night".parse::<CronSchedule>().is_err());
+    }
+}
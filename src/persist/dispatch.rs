@@ -0,0 +1,74 @@
+use futures_util::future::LocalBoxFuture;
+use std::collections::HashMap;
+use std::future::Future;
+
+type Handler<'f, I, R> = Box<dyn Fn(I) -> LocalBoxFuture<'f, R> + 'f>;
+
+/// The operations retrying the inputs of a `RetryHandle`, by the tag they were saved with
+///
+/// This lets one durable queue hold heterogeneous work, with `RetryHandle::retry_tagged` saving
+/// each input with the tag of its kind, and `RetryHandle::retry_pending_dispatched` routing each
+/// pending input back to the matching operation. Both need an injector implementing
+/// `TaggedInjector`.
+///
+/// ```
+/// # use retry_block::persist::Dispatcher;
+/// let dispatcher: Dispatcher<'_, u64, Result<(), ()>> = Dispatcher::new()
+///     .on("send_email", |user| async move { Ok(()) })
+///     .on("charge_card", |user| async move { Err(()) });
+/// ```
+pub struct Dispatcher<'f, I, R> {
+    handlers: HashMap<String, Handler<'f, I, R>>,
+    fallback: Option<Handler<'f, I, R>>,
+}
+
+impl<'f, I, R> Dispatcher<'f, I, R> {
+    /// Create a dispatcher without any operation
+    pub fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+            fallback: None,
+        }
+    }
+
+    /// Retry the inputs saved with the given tag using the given operation
+    pub fn on<O, F>(mut self, tag: impl Into<String>, operation: O) -> Self
+    where
+        O: Fn(I) -> F + 'f,
+        F: Future<Output = R> + 'f,
+    {
+        self.handlers.insert(tag.into(), boxed(operation));
+        self
+    }
+
+    /// Retry the inputs saved without a tag, or with a tag without operation, using the given
+    /// operation, rather than leaving them pending
+    pub fn fallback<O, F>(mut self, operation: O) -> Self
+    where
+        O: Fn(I) -> F + 'f,
+        F: Future<Output = R> + 'f,
+    {
+        self.fallback = Some(boxed(operation));
+        self
+    }
+
+    /// The operation retrying the inputs saved with the given tag, if any
+    pub(crate) fn handler(&self, tag: Option<&str>) -> Option<&Handler<'f, I, R>> {
+        tag.and_then(|tag| self.handlers.get(tag))
+            .or(self.fallback.as_ref())
+    }
+}
+
+impl<I, R> Default for Dispatcher<'_, I, R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn boxed<'f, I, R, O, F>(operation: O) -> Handler<'f, I, R>
+where
+    O: Fn(I) -> F + 'f,
+    F: Future<Output = R> + 'f,
+{
+    Box::new(move |input| Box::pin(operation(input)))
+}
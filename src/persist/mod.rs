@@ -11,7 +11,8 @@
 //! # use async_trait::async_trait;
 //! # use std::collections::HashMap;
 //! # use std::sync::Arc;
-//! # use tokio::sync::Mutex;
+//! # use std::time::SystemTime;
+//! # use tokio::sync::{mpsc, oneshot, Mutex};
 //!
 //! struct Injector {
 //!     ops: HashMap<u64, (Status<i64, ()>, i64)>,
@@ -24,10 +25,10 @@
 //!     type Error = ();
 //!     type Id = u64;
 //!     type Res = Result<i64, ()>;
-//!     async fn load_pending(&mut self) -> Vec<(u64, i64)> {
+//!     async fn load_pending(&mut self, now: SystemTime) -> Vec<(u64, i64)> {
 //!         self.ops
 //!             .iter()
-//!             .filter(|(_, (state, _))| matches!(state, Status::Pending))
+//!             .filter(|(_, (state, _))| state.is_due(now))
 //!             .map(|(id, (_, val))| (id.clone(), val.clone()))
 //!             .collect()
 //!     }
@@ -80,22 +81,161 @@
 //! }
 //! ```
 //!
-use crate::OperationResult;
+//! Any cloneable delay iterator can be used by the handle. With the `cron` feature, a
+//! `CronSchedule` can be used to re-attempt failed operations according to a cron expression
+//! instead, e.g. to only retry batch jobs outside of business hours.
+//!
+use crate::delay::Clock;
+use crate::time::{TimeSource, TokioTime};
+use crate::{AsyncSleeper, OperationResult, TokioSleeper};
 use async_trait::async_trait;
 use futures_util::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{mpsc, oneshot, Mutex, OwnedSemaphorePermit, Semaphore};
 
+mod cache;
+pub mod codec;
+#[cfg(feature = "config")]
+mod config;
+#[cfg(feature = "cron")]
+mod cron;
+mod dispatch;
+mod progress;
+#[cfg(feature = "bytes")]
+pub mod raw;
 #[cfg(test)]
 mod test;
+#[cfg(feature = "test-util")]
+pub mod test_support;
+
+#[cfg(feature = "cron")]
+pub use self::cron::CronSchedule;
+pub use cache::ResultCache;
+#[cfg(feature = "config")]
+pub use config::PersistConfig;
+pub use dispatch::Dispatcher;
+pub use progress::{Progress, ProgressSnapshot};
+
+use codec::{Codec, ContentHash, Digest};
 
 /// Status of a persistent retry
+///
+/// New statuses may be added in minor releases, so that matching on a status needs a wildcard
+/// arm. Storage backends should keep any status they do not know about as is.
+#[derive(Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub enum Status<O, E> {
     Pending,
+    /// Pending, but not to be attempted before the given time
+    ///
+    /// Saved by `RetryHandle::enqueue`.
+    Scheduled {
+        not_before: SystemTime,
+    },
+    /// Being retried by the given worker (if leased), as of the given time
+    ///
+    /// Only saved by handles configured with `RetryHandle::with_in_flight_status`.
+    InFlight {
+        worker: Option<String>,
+        since: SystemTime,
+    },
     Success(O),
     Failure(E),
+    /// Failed permanently for the given reason after the given number of attempts
+    ///
+    /// Only saved by handles configured with `RetryHandle::with_give_up_reasons`, instead of
+    /// `Status::Failure`.
+    GaveUp {
+        error: E,
+        reason: GiveUpReason,
+        attempts: usize,
+    },
+}
+
+/// Why a persistent retry gave up on an input
+///
+/// New reasons may be added in minor releases, like new statuses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum GiveUpReason {
+    /// The operation failed with a fatal error (`OperationResult::Err`), so replaying the input
+    /// as is would likely fail again
+    Fatal,
+    /// The operation kept failing with retryable errors until the delays ran out, so the input
+    /// may be worth replaying
+    Exhausted,
+}
+
+/// The failure of a storage backend to save the status of an input
+///
+/// Returned by `RetryInjector::try_save_status`. A `RetryHandle` retries saving on transient
+/// failures with the delays given to `RetryHandle::with_save_retries`, and keeps the error once
+/// they run out or on a permanent failure, to be taken with
+/// `RetryHandle::take_persistence_errors`. The status transition is then lost, but retrying goes
+/// on, so that the next status saved for the input may still reach storage.
+#[derive(Debug)]
+pub struct PersistenceError {
+    source: Box<dyn std::error::Error + Send + Sync>,
+    transient: bool,
+    unsupported: bool,
+}
+
+impl PersistenceError {
+    /// A failure worth retrying, e.g. a timeout or a lost connection
+    pub fn transient(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self {
+            source: source.into(),
+            transient: true,
+            unsupported: false,
+        }
+    }
+
+    /// A failure that saving again would not fix, e.g. a constraint violation
+    pub fn permanent(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self {
+            source: source.into(),
+            transient: false,
+            unsupported: false,
+        }
+    }
+
+    /// A failure of a storage not implementing what was asked of it, e.g. comparing statuses
+    /// without `RetryInjector::compare_and_save`
+    pub fn unsupported(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self {
+            source: source.into(),
+            transient: false,
+            unsupported: true,
+        }
+    }
+
+    /// Whether saving again may succeed
+    pub fn is_transient(&self) -> bool {
+        self.transient
+    }
+
+    /// Whether the storage does not support what was asked of it
+    pub fn is_unsupported(&self) -> bool {
+        self.unsupported
+    }
+}
+
+impl std::fmt::Display for PersistenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to save status: {}", self.source)
+    }
+}
+
+impl std::error::Error for PersistenceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
 }
 
 impl<O, E> std::fmt::Debug for Status<O, E>
@@ -106,31 +246,87 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Pending => write!(f, "Pending"),
+            Self::Scheduled { not_before } => f
+                .debug_struct("Scheduled")
+                .field("not_before", not_before)
+                .finish(),
+            Self::InFlight { worker, since } => f
+                .debug_struct("InFlight")
+                .field("worker", worker)
+                .field("since", since)
+                .finish(),
             Self::Success(o) => write!(f, "Success({:?})", o),
             Self::Failure(e) => write!(f, "Failure({:?})", e),
+            Self::GaveUp {
+                error,
+                reason,
+                attempts,
+            } => f
+                .debug_struct("GaveUp")
+                .field("error", error)
+                .field("reason", reason)
+                .field("attempts", attempts)
+                .finish(),
+        }
+    }
+}
+
+impl<O, E> Status<O, E> {
+    /// Whether an input with this status is to be loaded by `RetryInjector::load_pending` at the
+    /// given time, i.e. whether it is `Status::Pending`, or `Status::Scheduled` no later than
+    /// `now`
+    pub fn is_due(&self, now: SystemTime) -> bool {
+        match self {
+            Self::Pending => true,
+            Self::Scheduled { not_before } => *not_before <= now,
+            _ => false,
+        }
+    }
+
+    /// The error of a permanently failed input, i.e. one with a status of `Status::Failure` or
+    /// `Status::GaveUp`
+    pub fn error(&self) -> Option<&E> {
+        match self {
+            Self::Failure(error) | Self::GaveUp { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// A copy of a status holding neither an output nor an error
+    fn copy_unfinished(&self) -> Option<Self> {
+        match self {
+            Self::Pending => Some(Self::Pending),
+            Self::Scheduled { not_before } => Some(Self::Scheduled {
+                not_before: *not_before,
+            }),
+            Self::InFlight { worker, since } => Some(Self::InFlight {
+                worker: worker.clone(),
+                since: *since,
+            }),
+            _ => None,
         }
     }
 }
 
 /// A trait to specify how to save and retrieve the status of a retried operation
 #[async_trait]
-pub trait RetryInjector<'a>: Sized {
+pub trait RetryInjector<'a>: Sized + Send {
     /// The input value of a retry operation
     ///
     /// Will be saved to repeat the operation
-    type Input: Serialize + Deserialize<'a> + Clone;
+    type Input: Serialize + Deserialize<'a> + Clone + Send;
     /// The positive output value of a retry operation
     ///
     /// Will be saved if the operation succeeds
-    type Output;
+    type Output: Send;
     /// The negative output value of a retry operation
     ///
     /// Will be saved if the operation fails permanently
-    type Error;
+    type Error: Send + Sync;
     /// An identifier for a given input
     ///
     /// Will be saved to repeat the operation
-    type Id: Clone;
+    type Id: Clone + Send;
     /// A `Result` type for the output of the retry operation
     ///
     /// typically either:
@@ -138,8 +334,193 @@ pub trait RetryInjector<'a>: Sized {
     /// * `Result<Self::Output, Self::Error>`
     type Res: Into<OperationResult<Self::Output, Self::Error>>;
 
-    /// Return the stored inputs with a status of `Status::Pending`
-    async fn load_pending(&mut self) -> Vec<(Self::Id, Self::Input)>;
+    /// Return the stored inputs with a status of `Status::Pending`, including those
+    /// `Status::Scheduled` no later than `now` (see `Status::is_due`)
+    ///
+    /// `now` is the time of the `TimeSource` of the handle (see `RetryHandle::with_time_source`),
+    /// so that scheduled inputs become due consistently with its clock.
+    async fn load_pending(&mut self, now: SystemTime) -> Vec<(Self::Id, Self::Input)>;
+
+    /// Return the stored inputs with a status of `Status::Failure` (or `Status::GaveUp`), along
+    /// with their error
+    ///
+    /// Used by `RetryHandle::retry_failed` to replay failed inputs. Returns nothing by default.
+    async fn load_failed(&mut self) -> Vec<(Self::Id, Self::Input, Self::Error)> {
+        Vec::new()
+    }
+
+    /// Return a page of at most `limit` stored inputs with a status of `Status::Pending`, ordered
+    /// by id and starting right after the given id (or from the first one if `None`)
+    ///
+    /// Used by `RetryHandle::retry_pending` to consume pending inputs incrementally rather than
+    /// loading them all in memory at once. Defaults to returning the whole `load_pending` result
+    /// as a single page.
+    async fn load_pending_after(
+        &mut self,
+        after: Option<Self::Id>,
+        limit: usize,
+        now: SystemTime,
+    ) -> Vec<(Self::Id, Self::Input)>
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = limit;
+        match after {
+            None => self.load_pending(now).await,
+            Some(_) => Vec::new(),
+        }
+    }
+
+    /// Return the stored inputs with a status of `Status::Pending` that are not claimed by another
+    /// worker, atomically marking them as claimed by the given worker until the lease expires
+    ///
+    /// Used instead of `load_pending` by handles configured with `RetryHandle::with_lease`, so
+    /// that workers sharing a storage never retry the same input concurrently. Defaults to
+    /// `load_pending`, without any claim.
+    async fn claim_pending(
+        &mut self,
+        worker: &str,
+        lease: Duration,
+        now: SystemTime,
+    ) -> Vec<(Self::Id, Self::Input)> {
+        let _ = (worker, lease);
+        self.load_pending(now).await
+    }
+
+    /// Extend the claim of the given worker on an input until the lease expires
+    ///
+    /// Called by handles configured with `RetryHandle::with_lease` before waiting for the next
+    /// attempt. Does nothing by default.
+    async fn renew_lease(&mut self, id: Self::Id, worker: &str, lease: Duration)
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = (id, worker, lease);
+    }
+
+    /// Record that the given failed attempt (counted from 1) of an operation will be followed by
+    /// another one after the given delay
+    ///
+    /// Called by `RetryHandle` right before waiting for the next attempt, e.g. to persist when it
+    /// will happen so that operators can tell stuck inputs from scheduled ones. Does nothing by
+    /// default.
+    async fn on_delay(&mut self, id: Self::Id, attempt: usize, delay: Duration)
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = (id, attempt, delay);
+    }
+
+    /// Return the error to consider a timed out attempt failed with, given the timeout
+    ///
+    /// Required by handles configured with `RetryHandle::with_attempt_timeout` or retrying with
+    /// `RetryHandle::retry_with_timeout`, which check that an error is returned for their timeout,
+    /// so that an input whose last attempt timed out is saved with a final status like any other
+    /// failed one. Returns `None` by default, i.e. attempt timeouts are not supported.
+    fn timeout_error(&self, timeout: Duration) -> Option<Self::Error> {
+        let _ = timeout;
+        None
+    }
+
+    /// Return the delay to wait after an attempt failed with the given error, given the next
+    /// delay of the handle
+    ///
+    /// Lets the kind of failure drive the schedule, e.g. long delays after a rate limit and short
+    /// ones after a network blip. The policy of the handle still decides how many attempts are
+    /// made. Returns the scheduled delay by default; injectors of `RetryableError` errors (e.g.
+    /// `Hinted` ones) honor the delays suggested by their errors by returning
+    /// `RetryableError::next_delay`.
+    fn reschedule(&self, error: &Self::Error, scheduled: Duration) -> Duration {
+        let _ = error;
+        scheduled
+    }
+
+    /// Return the last saved status of the given input, if it is known
+    ///
+    /// Used by `RetryHandle::status`. Defaults to `None`.
+    async fn load_status(&mut self, id: Self::Id) -> Option<Status<Self::Output, Self::Error>>
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = id;
+        None
+    }
+
+    /// Return the number of attempts already made on the given input, e.g. the last `attempt`
+    /// recorded through `on_delay`
+    ///
+    /// Called by `RetryHandle` before retrying an input, so that after a restart it resumes with
+    /// the next delay of its schedule rather than the first one. Ignored for inputs whose
+    /// `RetryInjector::load_status` is a final one, e.g. failures replayed by
+    /// `RetryHandle::retry_failed`, which start their delays over. Defaults to 0.
+    async fn load_attempts(&mut self, id: Self::Id) -> usize
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = id;
+        0
+    }
+
+    /// Move a permanently failed input, along with its last error, to a dead-letter queue where
+    /// it can be inspected or replayed by hand
+    ///
+    /// Called by handles configured with `RetryHandle::with_dead_letter` once an input failed for
+    /// good, before saving its final status. Fails with `PersistenceError::unsupported` by
+    /// default, upon which handles keep the error, and warn with the `tracing` feature.
+    async fn dead_letter(
+        &mut self,
+        id: Self::Id,
+        input: Self::Input,
+        error: &Self::Error,
+    ) -> Result<(), PersistenceError>
+    where
+        Self::Id: 'async_trait,
+        Self::Input: 'async_trait,
+    {
+        let _ = (id, input, error);
+        Err(PersistenceError::unsupported(
+            "dead_letter is not implemented",
+        ))
+    }
+
+    /// Append a failed attempt (counted from 1) of an operation to its history, along with the
+    /// delay before the next attempt, or `None` if it was the last one
+    ///
+    /// Called by `RetryHandle` after each failed attempt, so that backends can keep a per-input
+    /// attempt history for post-mortems, separate from its latest `Status`. Does nothing by
+    /// default.
+    async fn append_history(
+        &mut self,
+        id: Self::Id,
+        attempt: usize,
+        error: &Self::Error,
+        delay: Option<Duration>,
+    ) where
+        Self::Id: 'async_trait,
+    {
+        let _ = (id, attempt, error, delay);
+    }
+
+    /// Return the stored inputs with a status of `Status::InFlight` saved longer than the given
+    /// duration ago to `Status::Pending`, and return how many were recovered
+    ///
+    /// Such inputs were most likely being retried by a process that crashed. Called by
+    /// `RetryHandle::recover_orphans`. Recovers nothing by default.
+    async fn recover_orphans(&mut self, older_than: Duration) -> usize {
+        let _ = older_than;
+        0
+    }
+
+    /// Delete the stored inputs with a final status (`Status::Success`, `Status::Failure` or
+    /// `Status::GaveUp`) that was saved longer than the given duration ago, and return how many
+    /// were deleted
+    ///
+    /// Called by handles configured with `RetryHandle::prune_completed_older_than` at the start of
+    /// each `retry_pending` run. Deletes nothing by default.
+    async fn prune(&mut self, older_than: Duration) -> usize {
+        let _ = older_than;
+        0
+    }
 
     /// Save the status of a given operation
     async fn save_status(
@@ -148,27 +529,511 @@ pub trait RetryInjector<'a>: Sized {
         input: Self::Input,
         status: Status<Self::Output, Self::Error>,
     );
+
+    /// Save the status of a given operation, giving it back along with the error if it could not
+    /// be saved
+    ///
+    /// Used by `RetryHandle` in place of `save_status`, which it calls by default, assuming it
+    /// never fails. Storage backends that can fail should implement this as well (and
+    /// `save_status` by ignoring its result) so that the handle can retry saving and report
+    /// failures.
+    async fn try_save_status(
+        &mut self,
+        id: Self::Id,
+        input: Self::Input,
+        status: Status<Self::Output, Self::Error>,
+    ) -> Result<(), (PersistenceError, Status<Self::Output, Self::Error>)>
+    where
+        Self::Id: 'async_trait,
+        Self::Input: 'async_trait,
+        Self::Output: 'async_trait,
+        Self::Error: 'async_trait,
+    {
+        self.save_status(id, input, status).await;
+        Ok(())
+    }
+
+    /// Save the status of a given operation only if its stored status is still the expected one,
+    /// and tell whether it was saved, giving the status back along with the error if the storage
+    /// failed
+    ///
+    /// Used by handles configured with `RetryHandle::with_compare_and_save` in place of
+    /// `try_save_status` for every status saved by a retry, the expected one being the status
+    /// loaded with `load_status` before the first one, then the status they saved last. No
+    /// expected status means that none is expected to be stored. Storage shared by several
+    /// workers should compare and save atomically (e.g. with a conditional update or insert), so
+    /// that a status changed concurrently by another worker is never overwritten.
+    ///
+    /// Fails with `PersistenceError::unsupported` by default, upon which handles keep the error,
+    /// warn with the `tracing` feature, and save without comparing from then on.
+    ///
+    /// The expected status is only borrowed mutably so that the returned future is `Send` without
+    /// requiring outputs and errors to be `Sync`, and must be left as is.
+    async fn compare_and_save(
+        &mut self,
+        id: Self::Id,
+        input: Self::Input,
+        expected: &mut Option<Status<Self::Output, Self::Error>>,
+        new: Status<Self::Output, Self::Error>,
+    ) -> Result<bool, (PersistenceError, Status<Self::Output, Self::Error>)>
+    where
+        Self::Id: 'async_trait,
+        Self::Input: 'async_trait,
+        Self::Output: 'async_trait,
+        Self::Error: 'async_trait,
+    {
+        let _ = (id, input, expected);
+        let e = PersistenceError::unsupported("compare_and_save is not implemented");
+        Err((e, new))
+    }
+
+    /// Begin a storage transaction before an attempt of the operation on the given input
+    ///
+    /// The transaction is committed with `commit_transaction` right after saving the
+    /// `Status::Success` of a successful attempt, or discarded with `rollback_transaction` after a
+    /// failed one. If the operation writes its side effects through the same transaction (e.g. a
+    /// connection shared with the injector), they are committed atomically with its status, so
+    /// that a crash can never make a successful operation run again on restart. Does nothing by
+    /// default.
+    async fn begin_transaction(&mut self, id: Self::Id)
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = id;
+    }
+
+    /// Commit the storage transaction begun before a successful attempt, after its
+    /// `Status::Success` was saved
+    ///
+    /// Does nothing by default.
+    async fn commit_transaction(&mut self, id: Self::Id)
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = id;
+    }
+
+    /// Discard the storage transaction begun before a failed attempt
+    ///
+    /// The input last saved by a `Checkpoint` during the attempt is saved again afterwards, outside
+    /// of the transaction. Does nothing by default.
+    async fn rollback_transaction(&mut self, id: Self::Id)
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = id;
+    }
+}
+
+/// An injector whose storage can isolate the inputs of several namespaces, needed by
+/// `RetryHandle::with_namespace`
+pub trait NamespacedInjector<'a>: RetryInjector<'a> {
+    /// Scope all the inputs loaded and saved from now on to the given namespace (e.g. a tenant id
+    /// or queue name), isolating them from those of other namespaces in the same storage
+    fn set_namespace(&mut self, namespace: &str);
+}
+
+/// An injector able to save a tag with each input, naming the kind of operation retrying it,
+/// needed by `RetryHandle::retry_tagged`, `RetryHandle::enqueue_tagged` and
+/// `RetryHandle::retry_pending_dispatched`
+#[async_trait]
+pub trait TaggedInjector<'a>: RetryInjector<'a> {
+    /// Save the given tag of an input, naming the kind of operation retrying it
+    async fn save_tag(&mut self, id: Self::Id, tag: &str)
+    where
+        Self::Id: 'async_trait;
+
+    /// Return the stored inputs with a status of `Status::Pending`, including those
+    /// `Status::Scheduled` no later than `now`, along with their saved tag if any
+    async fn load_pending_tagged(
+        &mut self,
+        now: SystemTime,
+    ) -> Vec<(Self::Id, Self::Input, Option<String>)>;
+}
+
+/// The number of persistence errors kept by a `RetryHandle` until they are taken, the oldest ones
+/// being dropped first
+const MAX_PERSISTENCE_ERRORS: usize = 100;
+
+/// The slots of the attempts running concurrently, none meaning no limit
+fn slots(concurrency_limit: usize) -> Option<Arc<Semaphore>> {
+    (concurrency_limit > 0).then(|| Arc::new(Semaphore::new(concurrency_limit)))
+}
+
+/// Wait for a free slot, if attempts are limited
+async fn take_slot(slots: Option<&Arc<Semaphore>>) -> Option<OwnedSemaphorePermit> {
+    slots?.clone().acquire_owned().await.ok()
+}
+
+/// A handle given to an operation to save its partial progress as the new persisted input
+pub struct Checkpoint<I> {
+    sender: mpsc::UnboundedSender<(I, oneshot::Sender<()>)>,
+}
+
+impl<I> Clone for Checkpoint<I> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<I> Checkpoint<I> {
+    /// Persist the given partial state as the input of the operation, returning once saved
+    pub async fn save(&self, state: I) {
+        let (saved, persisted) = oneshot::channel();
+        if self.sender.send((state, saved)).is_ok() {
+            let _ = persisted.await;
+        }
+    }
+}
+
+/// Limits applied by a `RetryHandle` to the consumption of its input stream when the storage
+/// behind its injector is slow
+#[derive(Debug, Clone)]
+pub struct Backpressure {
+    /// the maximum number of inputs taken from the stream being attempted or saved at once, an
+    /// input sleeping until its next attempt giving its place back meanwhile
+    pub max_pending_saves: usize,
+    /// the storage latency above which taking inputs from the stream is paused for as long as
+    /// status saves take, on a moving average
+    pub max_save_latency: Duration,
+}
+
+/// Gradual increase of the concurrency of a `RetryHandle` consuming an input stream, e.g. to avoid
+/// overwhelming a recovering downstream with a large backlog of pending inputs
+///
+/// Applies to `RetryHandle::retry_stream`, and to `RetryHandle::retry_pending` and
+/// `RetryHandle::retry_failed`, which retry the inputs they load through it. `retry_pending` does
+/// so page by page (see `RetryHandle::with_page_size`), starting each page over from the initial
+/// concurrency.
+#[derive(Debug, Clone)]
+pub struct RampUp {
+    /// the concurrency to start with, up to the concurrency limit
+    pub initial_concurrency: usize,
+    /// how often the concurrency is doubled, up to the concurrency limit if any, as waited by the
+    /// sleeper of the handle
+    pub interval: Duration,
+    /// the minimum proportion of the retries finished during an interval that must have succeeded
+    /// for the concurrency to be doubled at its end
+    pub min_success_rate: f64,
 }
 
 /// Persistent retry handle
-pub struct RetryHandle<Inj, Dur> {
+pub struct RetryHandle<Inj, Dur, Sl = TokioSleeper, Cache = ()> {
     injector: Inj,
     durations: Dur,
+    sleeper: Sl,
+    clock: Arc<dyn Clock + Send + Sync>,
+    namespace: Option<String>,
+    lease: Option<(String, Duration)>,
+    in_flight: bool,
+    compare_and_save: bool,
+    conflicts: usize,
+    give_up_reasons: bool,
+    dead_letter: bool,
+    retention: Option<Duration>,
+    #[cfg(feature = "governor")]
+    rate_limiter: Option<Arc<governor::DefaultDirectRateLimiter>>,
+    page_size: usize,
+    progress: Progress,
+    backpressure: Option<(Backpressure, Arc<Semaphore>)>,
+    ramp_up: Option<RampUp>,
+    attempt_timeout: Option<Duration>,
+    result_cache: Cache,
+    coalesce: Option<(Duration, Instant)>,
+    poll: Option<(usize, Option<Duration>)>,
+    save_latency: Arc<AtomicU64>,
+    save_retries: Vec<Duration>,
+    persistence_errors: VecDeque<PersistenceError>,
 }
 
 impl<'a, Inj, Dur> RetryHandle<Inj, Dur>
 where
     Inj: RetryInjector<'a>,
-    Dur: IntoIterator<Item = std::time::Duration> + Clone,
+    Dur: IntoIterator<Item = Duration> + Clone,
 {
     /// Create a new persistent retry handle from an injector and a cloneable delay iterator
     pub fn new(injector: Inj, durations: Dur) -> Self {
         Self {
             injector,
             durations,
+            sleeper: TokioSleeper,
+            clock: Arc::new(TokioTime),
+            namespace: None,
+            lease: None,
+            in_flight: false,
+            compare_and_save: false,
+            conflicts: 0,
+            give_up_reasons: false,
+            dead_letter: false,
+            retention: None,
+            #[cfg(feature = "governor")]
+            rate_limiter: None,
+            page_size: 1000,
+            progress: Progress::default(),
+            backpressure: None,
+            ramp_up: None,
+            attempt_timeout: None,
+            result_cache: (),
+            coalesce: None,
+            poll: None,
+            save_latency: Default::default(),
+            save_retries: vec![
+                Duration::from_millis(10),
+                Duration::from_millis(100),
+                Duration::from_secs(1),
+            ],
+            persistence_errors: VecDeque::new(),
+        }
+    }
+}
+
+impl<'a, Inj, Dur, Sl, Cache> RetryHandle<Inj, Dur, Sl, Cache>
+where
+    Inj: NamespacedInjector<'a>,
+{
+    /// Retry the inputs of the given namespace (e.g. a tenant id or queue name) only, so that one
+    /// storage can serve several isolated retry queues
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        let namespace = namespace.into();
+        self.injector.set_namespace(&namespace);
+        self.namespace = Some(namespace);
+        self
+    }
+}
+
+impl<'a, Inj, Dur, Sl, Cache> RetryHandle<Inj, Dur, Sl, Cache>
+where
+    Inj: RetryInjector<'a>,
+    Dur: IntoIterator<Item = Duration> + Clone,
+    Sl: AsyncSleeper,
+{
+    /// Tell the time and wait between attempts with the given `TimeSource` rather than with
+    /// tokio, e.g. `time::MockTime` in tests
+    ///
+    /// The time of the source is the one saved in statuses, against which scheduled inputs become
+    /// due, results are cached and the latency of the storage is measured, and sleeps are
+    /// coalesced on its clock. Besides the delays between attempts, it waits for attempt
+    /// timeouts, backpressure pauses and ramp-up intervals.
+    pub fn with_time_source<T>(self, time: T) -> RetryHandle<Inj, Dur, T, Cache>
+    where
+        T: TimeSource + Clone + Send + Sync + 'static,
+    {
+        let mut handle = self.with_sleeper(time.clone());
+        if let Some((_, epoch)) = &mut handle.coalesce {
+            *epoch = time.now();
+        }
+        handle.clock = Arc::new(time);
+        handle
+    }
+
+    /// Wait between attempts, for attempt timeouts, backpressure pauses and ramp-up intervals with
+    /// the given sleeper rather than `tokio::time::sleep`
+    ///
+    /// The time is still told by tokio; see `RetryHandle::with_time_source` to replace both.
+    pub fn with_sleeper<S: AsyncSleeper>(self, sleeper: S) -> RetryHandle<Inj, Dur, S, Cache> {
+        self.map_parts(|_, result_cache| (sleeper, result_cache))
+    }
+
+    fn map_parts<S, C>(
+        self,
+        parts: impl FnOnce(Sl, Cache) -> (S, C),
+    ) -> RetryHandle<Inj, Dur, S, C> {
+        let (sleeper, result_cache) = parts(self.sleeper, self.result_cache);
+        RetryHandle {
+            injector: self.injector,
+            durations: self.durations,
+            sleeper,
+            clock: self.clock,
+            namespace: self.namespace,
+            lease: self.lease,
+            in_flight: self.in_flight,
+            compare_and_save: self.compare_and_save,
+            conflicts: self.conflicts,
+            give_up_reasons: self.give_up_reasons,
+            dead_letter: self.dead_letter,
+            retention: self.retention,
+            #[cfg(feature = "governor")]
+            rate_limiter: self.rate_limiter,
+            page_size: self.page_size,
+            progress: self.progress,
+            backpressure: self.backpressure,
+            ramp_up: self.ramp_up,
+            attempt_timeout: self.attempt_timeout,
+            result_cache,
+            coalesce: self.coalesce,
+            poll: self.poll,
+            save_latency: self.save_latency,
+            save_retries: self.save_retries,
+            persistence_errors: self.persistence_errors,
         }
     }
 
+    /// Retry saving statuses failing with a transient `PersistenceError` with the given delays,
+    /// rather than 10ms, 100ms then 1s
+    pub fn with_save_retries(mut self, durations: impl IntoIterator<Item = Duration>) -> Self {
+        self.save_retries = durations.into_iter().collect();
+        self
+    }
+
+    /// Take the errors of the statuses that could not be saved since the last call, oldest first
+    ///
+    /// Only the last 100 errors are kept, so that a storage down for long does not fill the memory
+    /// of a handle nobody takes them from.
+    pub fn take_persistence_errors(&mut self) -> Vec<PersistenceError> {
+        self.persistence_errors.drain(..).collect()
+    }
+
+    /// Apply backpressure to the consumption of input streams when the storage is slow
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> Self {
+        let permits = Arc::new(Semaphore::new(backpressure.max_pending_saves));
+        self.backpressure = Some((backpressure, permits));
+        self
+    }
+
+    /// Consider attempts that take longer than the given timeout as retryable failures, so that a
+    /// hung operation cannot occupy a concurrency slot indefinitely
+    ///
+    /// Timed out attempts fail with the error returned by `RetryInjector::timeout_error`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `RetryInjector::timeout_error` returns no error for the given timeout.
+    pub fn with_attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.check_timeout_error(timeout);
+        self.attempt_timeout = Some(timeout);
+        self
+    }
+
+    /// Round the end of each delay up to a multiple of the given granularity, so that inputs
+    /// backing off around the same time wake up together
+    ///
+    /// When thousands of inputs of a `RetryHandle::retry_stream` are in backoff at once, this
+    /// batches their wakeups onto a few timer deadlines instead of one each.
+    pub fn with_coalesced_sleeps(mut self, granularity: Duration) -> Self {
+        if !granularity.is_zero() {
+            self.coalesce = Some((granularity, self.clock.now()));
+        }
+        self
+    }
+
+    /// Keep the outcomes of the last `capacity` inputs retried with `RetryHandle::retry_cached` in
+    /// memory for the given TTL, so that retrying them again within that window returns the
+    /// cached outcome without loading or saving anything through the injector
+    #[allow(clippy::type_complexity)]
+    pub fn with_result_cache(
+        self,
+        capacity: usize,
+        ttl: Duration,
+    ) -> RetryHandle<Inj, Dur, Sl, ResultCache<Inj::Id, Inj::Output, Inj::Error>>
+    where
+        Inj::Id: Hash + Eq,
+    {
+        self.map_parts(|sleeper, _| (sleeper, ResultCache::new(capacity, ttl)))
+    }
+
+    /// Start consuming input streams with a low concurrency, ramping it up while retries succeed
+    pub fn with_ramp_up(mut self, ramp_up: RampUp) -> Self {
+        self.ramp_up = Some(ramp_up);
+        self
+    }
+
+    /// Return a live view over the progress of the current `retry_pending` run
+    pub fn progress(&self) -> Progress {
+        self.progress.clone()
+    }
+
+    /// Load pending inputs by pages of at most the given size (1000 by default)
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Rate limit every attempt, including first ones, with the given limiter, waiting until it is
+    /// ready whenever it denies an attempt
+    ///
+    /// The wait of a retried attempt overlaps with its delay, so that it lasts for the longest of
+    /// the two rather than for their sum. Other inputs keep using the handle meanwhile.
+    #[cfg(feature = "governor")]
+    pub fn with_rate_limiter(
+        mut self,
+        rate_limiter: Arc<governor::DefaultDirectRateLimiter>,
+    ) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Return the namespace the inputs of this handle are scoped to, if any
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// Save inputs with a `Status::InFlight` rather than `Status::Pending` while they are being
+    /// retried, so that those left behind by a crash can be told apart and recovered with
+    /// `recover_orphans`
+    pub fn with_in_flight_status(mut self) -> Self {
+        self.in_flight = true;
+        self
+    }
+
+    /// Save every status of a retry with `RetryInjector::compare_and_save`, expecting the status
+    /// loaded before the first one, then the status saved last, and leave the input alone as
+    /// soon as it was changed by another worker sharing the same storage
+    ///
+    /// An input loaded with a `Status::InFlight` (being retried by another worker) or a
+    /// `Status::Success` is left alone right away. The transaction of a successful attempt whose
+    /// status could not be saved is rolled back. The number of inputs left alone is returned by
+    /// `conflicts`.
+    ///
+    /// The injector must implement `compare_and_save`: otherwise, statuses are saved without
+    /// comparing them, as without this option, once the unsupported `PersistenceError` is kept.
+    pub fn with_compare_and_save(mut self) -> Self {
+        self.compare_and_save = true;
+        self
+    }
+
+    /// Return the number of inputs left alone because their status was changed concurrently, see
+    /// `with_compare_and_save`
+    pub fn conflicts(&self) -> usize {
+        self.conflicts
+    }
+
+    /// Move permanently failed inputs to a dead-letter queue with `RetryInjector::dead_letter`,
+    /// which the injector must implement
+    pub fn with_dead_letter(mut self) -> Self {
+        self.dead_letter = true;
+        self
+    }
+
+    /// Save permanently failed inputs with a `Status::GaveUp` rather than `Status::Failure`,
+    /// recording whether they failed fatally or exhausted their retries and after how many
+    /// attempts, so that those worth replaying can be told apart
+    pub fn with_give_up_reasons(mut self) -> Self {
+        self.give_up_reasons = true;
+        self
+    }
+
+    /// Return the inputs left in flight for longer than the given duration, e.g. by a crashed
+    /// process, to `Status::Pending`, and return how many were recovered
+    pub async fn recover_orphans(&mut self, older_than: Duration) -> usize {
+        self.injector.recover_orphans(older_than).await
+    }
+
+    /// Prune the inputs whose final status was saved longer than the given duration ago at the
+    /// start of each `retry_pending` run, so that they do not accumulate in storage
+    pub fn prune_completed_older_than(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Claim pending inputs on behalf of the given worker for the given lease duration, renewing
+    /// the lease while they are being retried
+    pub fn with_lease(mut self, worker: impl Into<String>, lease: Duration) -> Self {
+        self.lease = Some((worker.into(), lease));
+        self
+    }
+
     /// Start concurrent persistent retry of pending input loaded from the injector using the given
     /// operation and concurrency limit
     pub async fn retry_pending<F>(
@@ -178,13 +1043,80 @@ where
     ) where
         F: Future<Output = Inj::Res>,
     {
-        let pending = self.injector.load_pending().await;
-        self.retry_stream(tokio_stream::iter(pending), concurrency_limit, operation)
+        self.progress.reset();
+        if let Some(retention) = self.retention {
+            self.injector.prune(retention).await;
+        }
+        if let Some((worker, lease)) = &self.lease {
+            let now = self.clock.system_now();
+            let pending = self.injector.claim_pending(worker, *lease, now).await;
+            self.progress.loaded(pending.len());
+            self.retry_stream(tokio_stream::iter(pending), concurrency_limit, operation)
+                .await;
+            return;
+        }
+
+        let mut after = None;
+        loop {
+            let page = self
+                .injector
+                .load_pending_after(after.clone(), self.page_size, self.clock.system_now())
+                .await;
+            let Some((last, _)) = page.last() else { break };
+            after = Some(last.clone());
+            self.progress.loaded(page.len());
+            self.retry_stream(tokio_stream::iter(page), concurrency_limit, operation)
+                .await;
+        }
+    }
+
+    /// Retry the pending inputs of the injector with the concurrency of the `PersistConfig` of the
+    /// handle, and again after each of its poll interval if any
+    ///
+    /// Without a poll interval, or for a handle not created with `RetryHandle::from_config`, the
+    /// pending inputs are retried once, sequentially in the latter case.
+    pub async fn poll_pending<F>(&mut self, operation: &dyn Fn(Inj::Input) -> F)
+    where
+        F: Future<Output = Inj::Res>,
+    {
+        let (concurrency, interval) = self.poll.unwrap_or((1, None));
+        loop {
+            self.retry_pending(concurrency, operation).await;
+            let Some(interval) = interval else { return };
+            self.sleeper.sleep(interval).await;
+        }
+    }
+
+    /// Start concurrent persistent retry of failed inputs loaded from the injector whose error
+    /// matches the given filter, using the given operation and concurrency limit
+    pub async fn retry_failed<F>(
+        &mut self,
+        filter: &dyn Fn(&Inj::Error) -> bool,
+        concurrency_limit: usize,
+        operation: &dyn Fn(Inj::Input) -> F,
+    ) where
+        F: Future<Output = Inj::Res>,
+    {
+        self.progress.reset();
+        let failed: Vec<_> = self
+            .injector
+            .load_failed()
+            .await
+            .into_iter()
+            .filter(|(_, _, error)| filter(error))
+            .map(|(id, input, _)| (id, input))
+            .collect();
+        self.progress.loaded(failed.len());
+        self.retry_stream(tokio_stream::iter(failed), concurrency_limit, operation)
             .await;
     }
 
     /// Start concurrent persistent retry of input loaded from the given stream using the given
     /// operation and concurrency limit
+    ///
+    /// The limit applies to the attempts running at once, not to the inputs waiting for their
+    /// next attempt: an input gives its slot back while sleeping and takes one again when due, so
+    /// that inputs with long delays do not hold back the others. A limit of 0 means no limit.
     pub async fn retry_stream<F, S>(
         &mut self,
         stream: S,
@@ -194,14 +1126,265 @@ where
         F: Future<Output = Inj::Res>,
         S: Stream<Item = (Inj::Id, Inj::Input)>,
     {
+        let timeout = self.attempt_timeout;
+        let slots = slots(concurrency_limit);
+        let backpressure = self.backpressure.clone();
+        let save_latency = self.save_latency.clone();
+        let progress = self.progress.clone();
+        // the concurrency ramped up to, at most the largest a semaphore allows without a limit
+        let ramp_limit = match concurrency_limit {
+            0 => Semaphore::MAX_PERMITS,
+            limit => limit,
+        };
+        let ramp_up = self.ramp_up.clone().map(|ramp_up| {
+            let initial = ramp_up.initial_concurrency.clamp(1, ramp_limit);
+            (ramp_up, Arc::new(Semaphore::new(initial)), initial)
+        });
+        let handle = Arc::new(Mutex::new(self));
+        // wait for backpressure to be relieved before taking each input from the stream
+        let retries = futures_util::stream::unfold(Box::pin(stream), |mut stream| async {
+            let ramp_up_permit = match &ramp_up {
+                Some((_, permits, _)) => permits.clone().acquire_owned().await.ok(),
+                None => None,
+            };
+            let pressure = match &backpressure {
+                Some((backpressure, permits)) => {
+                    let permit = permits.clone().acquire_owned().await.ok();
+                    let latency = Duration::from_nanos(save_latency.load(Ordering::Relaxed));
+                    if latency > backpressure.max_save_latency {
+                        let pause = handle.lock().await.sleeper.sleep(latency);
+                        pause.await;
+                    }
+                    permit.map(|permit| (permits, permit))
+                }
+                None => None,
+            };
+            let slot = take_slot(slots.as_ref()).await;
+            let item = stream.next().await?;
+            Some(((item, pressure, ramp_up_permit, slot), stream))
+        })
+        .for_each_concurrent(
+            None,
+            |((id, input), pressure, ramp_up_permit, slot)| async {
+                Self::retry_shared(
+                    &handle,
+                    slots.as_ref(),
+                    slot,
+                    pressure,
+                    id,
+                    input,
+                    timeout,
+                    &|input, _| operation(input),
+                    |_: &Status<_, _>| {},
+                )
+                .await;
+                drop(ramp_up_permit);
+            },
+        );
+
+        let Some((ramp_up, permits, mut concurrency)) = ramp_up.clone() else {
+            return retries.await;
+        };
+        // double the concurrency at the end of each interval where retries mostly succeeded
+        let ramp = async {
+            let mut last = progress.snapshot();
+            // no need to wake up once the limit is reached
+            while concurrency < ramp_limit {
+                let interval = handle.lock().await.sleeper.sleep(ramp_up.interval);
+                interval.await;
+                let current = progress.snapshot();
+                let succeeded = current.succeeded.saturating_sub(last.succeeded);
+                let failed = current.failed.saturating_sub(last.failed);
+                last = current;
+                let finished = succeeded + failed;
+                if finished == 0 || succeeded as f64 >= ramp_up.min_success_rate * finished as f64 {
+                    let added = concurrency.min(ramp_limit - concurrency);
+                    permits.add_permits(added);
+                    concurrency += added;
+                }
+            }
+            std::future::pending().await
+        };
+        tokio::select! {
+            _ = retries => {}
+            _ = ramp => {}
+        }
+    }
+
+    /// Start concurrent persistent retry of the given inputs using the given operation and
+    /// concurrency limit, and return their outcomes in order
+    ///
+    /// The statuses are saved through the injector as with `RetryHandle::retry`, so that a batch
+    /// submitter can act on failures right away rather than query them afterwards. Inputs left
+    /// alone because of a conflict (see `RetryHandle::with_compare_and_save`) are omitted from
+    /// the outcomes. A limit of 0 means no limit.
+    pub async fn retry_many<F, I>(
+        &mut self,
+        items: I,
+        concurrency_limit: usize,
+        operation: &dyn Fn(Inj::Input) -> F,
+    ) -> Vec<(Inj::Id, Result<Inj::Output, Inj::Error>)>
+    where
+        F: Future<Output = Inj::Res>,
+        I: IntoIterator<Item = (Inj::Id, Inj::Input)>,
+        Inj::Output: Clone,
+        Inj::Error: Clone,
+    {
+        let timeout = self.attempt_timeout;
+        let handle = &Mutex::new(self);
+        futures_util::stream::iter(items)
+            .map(|(id, input)| async move {
+                let mut outcome = None;
+                let record = |status: &Status<Inj::Output, Inj::Error>| {
+                    outcome = match status {
+                        Status::Success(output) => Some(Ok(output.clone())),
+                        status => status.error().cloned().map(Err),
+                    }
+                };
+                Self::retry_shared(
+                    handle,
+                    None,
+                    None,
+                    None,
+                    id.clone(),
+                    input,
+                    timeout,
+                    &|input, _| operation(input),
+                    record,
+                )
+                .await;
+                outcome.map(|outcome| (id, outcome))
+            })
+            .buffered(match concurrency_limit {
+                0 => usize::MAX,
+                limit => limit,
+            })
+            .filter_map(std::future::ready)
+            .collect()
+            .await
+    }
+
+    /// Start concurrent persistent retry of input loaded from the given stream using the given
+    /// operation and concurrency limit, returning a stream of their outcomes as they complete
+    ///
+    /// Unlike `RetryHandle::retry_stream`, which only returns once the whole input stream is
+    /// consumed, this lets a downstream pipeline consume completions incrementally. Inputs left
+    /// alone because of a conflict (see `RetryHandle::with_compare_and_save`) are omitted from
+    /// the outcomes. A limit of 0 means no limit.
+    pub fn retry_stream_outcomes<'s, F, S>(
+        &'s mut self,
+        stream: S,
+        concurrency_limit: usize,
+        operation: &'s dyn Fn(Inj::Input) -> F,
+    ) -> impl Stream<Item = (Inj::Id, Result<Inj::Output, Inj::Error>)> + 's
+    where
+        F: Future<Output = Inj::Res> + 's,
+        S: Stream<Item = (Inj::Id, Inj::Input)> + 's,
+        'a: 's,
+        Inj::Output: Clone,
+        Inj::Error: Clone,
+    {
+        let timeout = self.attempt_timeout;
         let handle = Arc::new(Mutex::new(self));
         stream
-            .for_each_concurrent(concurrency_limit, |(id, input)| async {
-                handle.lock().await.retry(id, input, operation).await;
+            .map(move |(id, input)| {
+                let handle = handle.clone();
+                async move {
+                    let mut outcome = None;
+                    let record = |status: &Status<Inj::Output, Inj::Error>| {
+                        outcome = match status {
+                            Status::Success(output) => Some(Ok(output.clone())),
+                            status => status.error().cloned().map(Err),
+                        }
+                    };
+                    Self::retry_shared(
+                        &handle,
+                        None,
+                        None,
+                        None,
+                        id.clone(),
+                        input,
+                        timeout,
+                        &|input, _| operation(input),
+                        record,
+                    )
+                    .await;
+                    outcome.map(|outcome| (id, outcome))
+                }
+            })
+            .buffer_unordered(match concurrency_limit {
+                0 => usize::MAX,
+                limit => limit,
             })
+            .filter_map(std::future::ready)
+    }
+
+    /// Save a given input (uniquely identified by the given id) as pending, to be retried by the
+    /// first `retry_pending` run no earlier than the given time
+    pub async fn enqueue(&mut self, id: Inj::Id, input: Inj::Input, not_before: SystemTime) {
+        self.save_status(id, input, Status::Scheduled { not_before })
             .await;
     }
 
+    /// Save a given input as pending like `enqueue`, identified by the hash of its content, and
+    /// return its id
+    ///
+    /// The input is only saved if no status is stored for it yet, with
+    /// `RetryInjector::compare_and_save` expecting none, so that a payload submitted twice, even
+    /// concurrently, is only retried once. The injector must implement `compare_and_save`:
+    /// otherwise, the input is saved regardless, once the unsupported `PersistenceError` is
+    /// kept.
+    ///
+    /// ```
+    /// # #[cfg(feature = "json")]
+    /// # {
+    /// use retry_block::delay::NoDelay;
+    /// use retry_block::persist::codec::{ContentHash, Fnv1a, Json};
+    /// use retry_block::persist::test_support::{run_virtual, MemoryInjector};
+    /// use retry_block::persist::RetryHandle;
+    /// use std::time::SystemTime;
+    ///
+    /// let injector = MemoryInjector::<String, (), ()>::new();
+    /// let ids = ContentHash::<Json>::new(Fnv1a);
+    /// run_virtual(async {
+    ///     let mut handle = RetryHandle::new(injector.clone(), NoDelay.take(1));
+    ///     let now = SystemTime::now();
+    ///     let first = handle.enqueue_auto(&ids, "mail".into(), now).await.unwrap();
+    ///     let second = handle.enqueue_auto(&ids, "mail".into(), now).await.unwrap();
+    ///     assert_eq!(first, second);
+    ///     assert_eq!(injector.history(first).len(), 1);
+    /// });
+    /// # }
+    /// ```
+    pub async fn enqueue_auto<C, D>(
+        &mut self,
+        ids: &ContentHash<C, D>,
+        input: Inj::Input,
+        not_before: SystemTime,
+    ) -> Result<Inj::Id, C::Error>
+    where
+        C: Codec,
+        D: Digest<Output = Inj::Id>,
+    {
+        let id = ids.id(&input)?;
+        let status = Status::Scheduled { not_before };
+        if let Err(e) = self
+            .try_save(id.clone(), input, Some(&mut None), status)
+            .await
+        {
+            self.keep_persistence_error(e);
+        }
+        Ok(id)
+    }
+
+    /// Return the last saved status of the given input, through `RetryInjector::load_status`
+    ///
+    /// Useful to check whether a previously submitted input is still pending, or succeeded or
+    /// failed, without reaching around the handle into storage.
+    pub async fn status(&mut self, id: Inj::Id) -> Option<Status<Inj::Output, Inj::Error>> {
+        self.injector.load_status(id).await
+    }
+
     /// Persistently retry a given input (uniquely identified by the given id) using the given
     /// operation
     pub async fn retry<F>(
@@ -212,30 +1395,612 @@ where
     ) where
         F: Future<Output = Inj::Res>,
     {
-        self.injector
-            .save_status(id.clone(), input.clone(), Status::Pending)
-            .await;
-        let mut it = self.durations.clone().into_iter();
-        let res = loop {
-            match operation(input.clone()).await.into() {
-                OperationResult::Ok(res) => break Ok(res),
-                OperationResult::Err(e) => break Err(e),
+        self.retry_with_checkpoint(id, input, &|input, _| operation(input))
+            .await
+    }
+
+    /// Persistently retry a given input (uniquely identified by the given id) using the given
+    /// operation, which can save its partial progress through a `Checkpoint`
+    ///
+    /// Each saved checkpoint replaces the persisted input of the operation, so that it is used by
+    /// the next attempts, including those resumed from storage after a crash.
+    pub async fn retry_with_checkpoint<F>(
+        &mut self,
+        id: Inj::Id,
+        input: Inj::Input,
+        operation: &dyn Fn(Inj::Input, Checkpoint<Inj::Input>) -> F,
+    ) where
+        F: Future<Output = Inj::Res>,
+    {
+        self.retry_inner(
+            id,
+            input,
+            self.attempt_timeout,
+            operation,
+            |_: &Status<_, _>| {},
+        )
+        .await
+    }
+
+    /// Persistently retry a given input (uniquely identified by the given id) using the given
+    /// operation, which can return an updated input along with its result
+    ///
+    /// An updated input (e.g. with a refreshed token or an advanced cursor) is persisted before
+    /// the next attempt, which is given it instead of the previous one, as with a `Checkpoint`.
+    pub async fn retry_updating<F>(
+        &mut self,
+        id: Inj::Id,
+        input: Inj::Input,
+        operation: &dyn Fn(Inj::Input) -> F,
+    ) where
+        F: Future<Output = (Inj::Res, Option<Inj::Input>)>,
+    {
+        self.retry_with_checkpoint(id, input, &|input, checkpoint| {
+            let attempt = operation(input);
+            async move {
+                let (res, updated) = attempt.await;
+                if let Some(updated) = updated {
+                    checkpoint.save(updated).await;
+                }
+                res
+            }
+        })
+        .await
+    }
+
+    /// Persistently retry a given input (uniquely identified by the given id) using the given
+    /// operation, overriding the attempt timeout of the handle with the given one
+    ///
+    /// # Panics
+    ///
+    /// Panics if `RetryInjector::timeout_error` returns no error for the given timeout.
+    pub async fn retry_with_timeout<F>(
+        &mut self,
+        id: Inj::Id,
+        input: Inj::Input,
+        timeout: Duration,
+        operation: &dyn Fn(Inj::Input) -> F,
+    ) where
+        F: Future<Output = Inj::Res>,
+    {
+        self.check_timeout_error(timeout);
+        self.retry_inner(
+            id,
+            input,
+            Some(timeout),
+            &|input, _| operation(input),
+            |_: &Status<_, _>| {},
+        )
+        .await
+    }
+
+    async fn retry_inner<F>(
+        &mut self,
+        id: Inj::Id,
+        input: Inj::Input,
+        timeout: Option<Duration>,
+        operation: &dyn Fn(Inj::Input, Checkpoint<Inj::Input>) -> F,
+        record: impl FnMut(&Status<Inj::Output, Inj::Error>),
+    ) where
+        F: Future<Output = Inj::Res>,
+    {
+        Self::retry_shared(
+            &Mutex::new(self),
+            None,
+            None,
+            None,
+            id,
+            input,
+            timeout,
+            operation,
+            record,
+        )
+        .await
+    }
+
+    /// Retry a given input with a handle shared with other concurrent retries, locking it only
+    /// to reach the injector, so that the others go on while this one attempts or sleeps
+    ///
+    /// With slots, each attempt first takes one (unless the given one was taken for it), and gives
+    /// it back before sleeping until the next attempt, so that inputs waiting for long delays do not
+    /// hold back those that are due. The same goes for the given backpressure permit, if any.
+    #[allow(clippy::too_many_arguments)]
+    async fn retry_shared<F>(
+        handle: &Mutex<&mut Self>,
+        slots: Option<&Arc<Semaphore>>,
+        mut slot: Option<OwnedSemaphorePermit>,
+        pressure: Option<(&Arc<Semaphore>, OwnedSemaphorePermit)>,
+        id: Inj::Id,
+        mut input: Inj::Input,
+        timeout: Option<Duration>,
+        operation: &dyn Fn(Inj::Input, Checkpoint<Inj::Input>) -> F,
+        mut record: impl FnMut(&Status<Inj::Output, Inj::Error>),
+    ) where
+        F: Future<Output = Inj::Res>,
+    {
+        let (pressure, mut pressure_permit) = pressure.unzip();
+        // the status last saved, to compare the stored one with before saving the next one
+        let mut last;
+        #[cfg(feature = "governor")]
+        let rate_limiter;
+        let (mut attempts, mut it) = {
+            let mut this = handle.lock().await;
+            #[cfg(feature = "governor")]
+            {
+                rate_limiter = this.rate_limiter.clone();
+            }
+            this.progress.started();
+            let status = this.running_status();
+            last = None;
+            let mut attempts = this.injector.load_attempts(id.clone()).await;
+            let expected = if this.compare_and_save || attempts > 0 {
+                this.injector.load_status(id.clone()).await
+            } else {
+                None
+            };
+            // resume the delays of an interrupted input only, a replayed one starts them over
+            if matches!(
+                expected,
+                Some(Status::Success(_) | Status::Failure(_) | Status::GaveUp { .. })
+            ) {
+                attempts = 0;
+            }
+            if this.compare_and_save {
+                // being retried by another worker, or done already
+                let taken = matches!(expected, Some(Status::InFlight { .. } | Status::Success(_)));
+                if taken {
+                    this.conflicts += 1;
+                }
+                if taken
+                    || !this
+                        .save_expecting(id.clone(), input.clone(), expected, &mut last, status)
+                        .await
+                {
+                    this.progress.abandoned();
+                    return;
+                }
+            } else {
+                this.save_status(id.clone(), input.clone(), status).await;
+            }
+            let it = this.durations.clone().into_iter().skip(attempts);
+            (attempts, crate::delay::__faulty(it))
+        };
+        let (err, reason) = loop {
+            attempts += 1;
+            // taken in the same order as by `retry_stream`
+            if pressure_permit.is_none() {
+                if let Some(pressure) = pressure {
+                    pressure_permit = pressure.clone().acquire_owned().await.ok();
+                }
+            }
+            if slot.is_none() {
+                if let Some(slots) = slots {
+                    slot = slots.clone().acquire_owned().await.ok();
+                }
+            }
+            // waited after the delay, which the limiter saw elapse
+            #[cfg(feature = "governor")]
+            if let Some(rate_limiter) = &rate_limiter {
+                rate_limiter.until_ready().await;
+            }
+            handle
+                .lock()
+                .await
+                .injector
+                .begin_transaction(id.clone())
+                .await;
+            // the checkpoints are saved within the transaction of the attempt, so they have to be
+            // saved again if it is rolled back, over the status last saved outside of it
+            let committed = last.as_ref().and_then(Status::copy_unfinished);
+            let mut checkpointed = false;
+            let (sender, mut receiver) = mpsc::unbounded_channel();
+            let attempt = operation(input.clone(), Checkpoint { sender });
+            // only started once the attempt is pending, as sleeps may start when created
+            let timed_out = async {
+                match timeout {
+                    Some(timeout) => {
+                        let sleep = handle.lock().await.sleeper.sleep(timeout);
+                        sleep.await
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::pin!(attempt, timed_out);
+            let res = loop {
+                tokio::select! {
+                    // an attempt done by the time it times out succeeds
+                    biased;
+                    res = &mut attempt => break Some(res),
+                    _ = &mut timed_out => break None,
+                    Some((state, saved)) = receiver.recv() => {
+                        let mut this = handle.lock().await;
+                        let status = this.running_status();
+                        if !this.save_over(id.clone(), state.clone(), &mut last, status).await {
+                            this.progress.abandoned();
+                            return;
+                        }
+                        input = state;
+                        checkpointed = true;
+                        let _ = saved.send(());
+                    }
+                }
+            };
+            let mut guard = handle.lock().await;
+            let this = &mut **guard;
+            // a timed out attempt is a retryable failure
+            let res = match res {
+                Some(res) => res.into(),
+                None => OperationResult::Retry(
+                    timeout
+                        .and_then(|timeout| this.injector.timeout_error(timeout))
+                        .expect("timeout_error returned no error for an attempt timeout"),
+                ),
+            };
+            match res {
+                OperationResult::Ok(res) => {
+                    let status = Status::Success(res);
+                    record(&status);
+                    if this.save_over(id.clone(), input, &mut last, status).await {
+                        this.injector.commit_transaction(id).await;
+                        this.progress.finished(true);
+                    } else {
+                        this.injector.rollback_transaction(id).await;
+                        this.progress.abandoned();
+                    }
+                    return;
+                }
+                OperationResult::Err(e) => {
+                    this.injector.rollback_transaction(id.clone()).await;
+                    // the checkpointed input is saved along with the final status
+                    last = committed;
+                    this.injector
+                        .append_history(id.clone(), attempts, &e, None)
+                        .await;
+                    break (e, GiveUpReason::Fatal);
+                }
                 OperationResult::Retry(e) => {
-                    if let Some(duration) = it.next() {
-                        tokio::time::sleep(duration).await;
+                    this.injector.rollback_transaction(id.clone()).await;
+                    last = committed;
+                    if checkpointed {
+                        let status = this.running_status();
+                        if !this
+                            .save_over(id.clone(), input.clone(), &mut last, status)
+                            .await
+                        {
+                            this.progress.abandoned();
+                            return;
+                        }
+                    }
+                    let delay = it.next().map(|delay| this.injector.reschedule(&e, delay));
+                    this.injector
+                        .append_history(id.clone(), attempts, &e, delay)
+                        .await;
+                    if let Some(duration) = delay {
+                        this.injector.on_delay(id.clone(), attempts, duration).await;
+                        if let Some((worker, lease)) = &this.lease {
+                            this.injector
+                                .renew_lease(id.clone(), worker, duration + *lease)
+                                .await;
+                        }
+                        let duration = this.coalesced(duration);
+                        let sleep = (!duration.is_zero()).then(|| this.sleeper.sleep(duration));
+                        drop(guard);
+                        if let Some(sleep) = sleep {
+                            slot = None;
+                            pressure_permit = None;
+                            sleep.await;
+                        }
                     } else {
-                        break Err(e);
+                        break (e, GiveUpReason::Exhausted);
                     }
                 }
             }
         };
 
-        let status = match res {
-            Ok(ok) => Status::Success(ok),
-            Err(err) => Status::Failure(err),
+        let mut this = handle.lock().await;
+        if this.dead_letter {
+            let dead_letter = this
+                .injector
+                .dead_letter(id.clone(), input.clone(), &err)
+                .await;
+            if let Err(e) = dead_letter {
+                #[cfg(feature = "tracing")]
+                if e.is_unsupported() {
+                    tracing::warn!("dead_letter is not supported by the injector");
+                }
+                this.keep_persistence_error(e);
+            }
+        }
+        let status = if this.give_up_reasons {
+            Status::GaveUp {
+                error: err,
+                reason,
+                attempts,
+            }
+        } else {
+            Status::Failure(err)
         };
-        self.injector
-            .save_status(id.clone(), input.clone(), status)
+        record(&status);
+        if this.save_over(id, input, &mut last, status).await {
+            this.progress.finished(false);
+        } else {
+            this.progress.abandoned();
+        }
+    }
+
+    /// Save the given status of an input, with `RetryInjector::compare_and_save` if a status was
+    /// last saved for it, and tell whether it was not changed concurrently
+    ///
+    /// A status that the storage failed to save is not a conflict: the error is kept and the
+    /// stored status is still expected to be the one saved last.
+    async fn save_over(
+        &mut self,
+        id: Inj::Id,
+        input: Inj::Input,
+        last: &mut Option<Status<Inj::Output, Inj::Error>>,
+        status: Status<Inj::Output, Inj::Error>,
+    ) -> bool {
+        let Some(expected) = last.take() else {
+            self.save_status(id, input, status).await;
+            return true;
+        };
+        self.save_expecting(id, input, Some(expected), last, status)
             .await
     }
+
+    /// Save the given status of an input with `RetryInjector::compare_and_save`, expecting the
+    /// given stored one, and tell whether it was not changed concurrently, keeping the status
+    /// expected next in `last`
+    async fn save_expecting(
+        &mut self,
+        id: Inj::Id,
+        input: Inj::Input,
+        mut expected: Option<Status<Inj::Output, Inj::Error>>,
+        last: &mut Option<Status<Inj::Output, Inj::Error>>,
+        status: Status<Inj::Output, Inj::Error>,
+    ) -> bool {
+        let next = status.copy_unfinished();
+        match self.try_save(id, input, Some(&mut expected), status).await {
+            Ok(true) => {
+                *last = next.filter(|_| self.compare_and_save);
+                true
+            }
+            Ok(false) => {
+                self.conflicts += 1;
+                false
+            }
+            Err(e) => {
+                self.keep_persistence_error(e);
+                *last = expected;
+                true
+            }
+        }
+    }
+
+    /// Lengthen the given delay so that it ends on a multiple of the coalescing granularity
+    fn coalesced(&self, duration: Duration) -> Duration {
+        let Some((granularity, epoch)) = self.coalesce else {
+            return duration;
+        };
+        let now = self.clock.now();
+        let end = (now + duration).duration_since(epoch).as_nanos();
+        let granularity = granularity.as_nanos();
+        let rounded = end.div_ceil(granularity) * granularity;
+        let rounded = Duration::from_nanos(rounded.try_into().unwrap_or(u64::MAX));
+        (epoch + rounded).saturating_duration_since(now)
+    }
+
+    fn running_status(&self) -> Status<Inj::Output, Inj::Error> {
+        if self.in_flight {
+            Status::InFlight {
+                worker: self.lease.as_ref().map(|(worker, _)| worker.clone()),
+                since: self.clock.system_now(),
+            }
+        } else {
+            Status::Pending
+        }
+    }
+
+    async fn save_status(
+        &mut self,
+        id: Inj::Id,
+        input: Inj::Input,
+        status: Status<Inj::Output, Inj::Error>,
+    ) {
+        if let Err(e) = self.try_save(id, input, None, status).await {
+            self.keep_persistence_error(e);
+        }
+    }
+
+    /// Save the given status of an input, comparing the stored one with the expected one if any,
+    /// and retry on transient failures with the save retries of the handle
+    async fn try_save(
+        &mut self,
+        id: Inj::Id,
+        input: Inj::Input,
+        mut expected: Option<&mut Option<Status<Inj::Output, Inj::Error>>>,
+        mut status: Status<Inj::Output, Inj::Error>,
+    ) -> Result<bool, PersistenceError> {
+        let start = self.clock.now();
+        let mut retries = self.save_retries.iter();
+        let mut unsupported = None;
+        let saved = loop {
+            let saved = match &mut expected {
+                Some(expected) => {
+                    self.injector
+                        .compare_and_save(id.clone(), input.clone(), expected, status)
+                        .await
+                }
+                None => self
+                    .injector
+                    .try_save_status(id.clone(), input.clone(), status)
+                    .await
+                    .map(|()| true),
+            };
+            match saved {
+                Ok(saved) => break Ok(saved),
+                Err((e, unsaved)) if e.is_unsupported() && expected.is_some() => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        "compare_and_save is not supported by the injector, saving statuses \
+                         without comparing them"
+                    );
+                    unsupported = Some(e);
+                    self.compare_and_save = false;
+                    expected = None;
+                    status = unsaved;
+                }
+                Err((e, unsaved)) => match retries.next() {
+                    Some(duration) if e.is_transient() => {
+                        self.sleeper.sleep(*duration).await;
+                        status = unsaved;
+                    }
+                    _ => break Err(e),
+                },
+            }
+        };
+        // an exponential moving average, so that one slow save does not pause the stream alone
+        let latency = self.clock.now().saturating_duration_since(start);
+        let latency: u64 = latency.as_nanos().try_into().unwrap_or(u64::MAX);
+        let average = match self.save_latency.load(Ordering::Relaxed) {
+            0 => latency,
+            average => average - average / 8 + latency / 8,
+        };
+        self.save_latency.store(average, Ordering::Relaxed);
+        if let Some(e) = unsupported {
+            self.keep_persistence_error(e);
+        }
+        saved
+    }
+
+    fn check_timeout_error(&self, timeout: Duration) {
+        assert!(
+            self.injector.timeout_error(timeout).is_some(),
+            "attempt timeouts need RetryInjector::timeout_error to return an error"
+        );
+    }
+
+    fn keep_persistence_error(&mut self, e: PersistenceError) {
+        if self.persistence_errors.len() == MAX_PERSISTENCE_ERRORS {
+            self.persistence_errors.pop_front();
+        }
+        self.persistence_errors.push_back(e);
+    }
+}
+
+impl<'a, Inj, Dur, Sl> RetryHandle<Inj, Dur, Sl, ResultCache<Inj::Id, Inj::Output, Inj::Error>>
+where
+    Inj: RetryInjector<'a>,
+    Inj::Id: Hash + Eq,
+    Dur: IntoIterator<Item = Duration> + Clone,
+    Sl: AsyncSleeper,
+{
+    /// Persistently retry a given input (uniquely identified by the given id) using the given
+    /// operation, and return its final status
+    ///
+    /// The outcome of an input that recently succeeded or failed is returned from memory, without
+    /// running the operation again.
+    pub async fn retry_cached<F>(
+        &mut self,
+        id: Inj::Id,
+        input: Inj::Input,
+        operation: &dyn Fn(Inj::Input) -> F,
+    ) -> Status<Inj::Output, Inj::Error>
+    where
+        F: Future<Output = Inj::Res>,
+        Inj::Output: Clone,
+        Inj::Error: Clone,
+    {
+        if let Some(status) = self.result_cache.get(&id, self.clock.now()) {
+            return status.clone();
+        }
+        let mut outcome = Status::Pending;
+        self.retry_inner(
+            id.clone(),
+            input,
+            self.attempt_timeout,
+            &|input, _| operation(input),
+            |status: &Status<_, _>| outcome = status.clone(),
+        )
+        .await;
+        if let Status::Success(_) | Status::Failure(_) | Status::GaveUp { .. } = &outcome {
+            let now = self.clock.now();
+            self.result_cache.insert(id, outcome.clone(), now);
+        }
+        outcome
+    }
+}
+
+impl<'a, Inj, Dur, Sl, Cache> RetryHandle<Inj, Dur, Sl, Cache>
+where
+    Inj: TaggedInjector<'a>,
+    Dur: IntoIterator<Item = Duration> + Clone,
+    Sl: AsyncSleeper,
+{
+    /// Start concurrent persistent retry of pending inputs loaded from the injector, each using
+    /// the operation of the given dispatcher matching its saved tag
+    ///
+    /// Inputs without a matching operation nor a fallback one are left pending.
+    pub async fn retry_pending_dispatched(
+        &mut self,
+        concurrency_limit: usize,
+        dispatcher: &Dispatcher<'_, Inj::Input, Inj::Res>,
+    ) {
+        self.progress.reset();
+        let now = self.clock.system_now();
+        let pending = self.injector.load_pending_tagged(now).await;
+        self.progress.loaded(pending.len());
+        let timeout = self.attempt_timeout;
+        let slots = &slots(concurrency_limit);
+        let handle = &Mutex::new(self);
+        futures_util::stream::iter(pending)
+            .then(|item| async move { (item, take_slot(slots.as_ref()).await) })
+            .for_each_concurrent(None, |((id, input, tag), slot)| async move {
+                let Some(operation) = dispatcher.handler(tag.as_deref()) else {
+                    return;
+                };
+                Self::retry_shared(
+                    handle,
+                    slots.as_ref(),
+                    slot,
+                    None,
+                    id,
+                    input,
+                    timeout,
+                    &|input, _| operation(input),
+                    |_: &Status<_, _>| {},
+                )
+                .await;
+            })
+            .await;
+    }
+
+    /// Persistently retry a given input (uniquely identified by the given id) using the given
+    /// operation, saving it with the given tag so that it is routed back to the matching
+    /// operation by `RetryHandle::retry_pending_dispatched`
+    pub async fn retry_tagged<F>(
+        &mut self,
+        id: Inj::Id,
+        input: Inj::Input,
+        tag: &str,
+        operation: &dyn Fn(Inj::Input) -> F,
+    ) where
+        F: Future<Output = Inj::Res>,
+    {
+        self.injector.save_tag(id.clone(), tag).await;
+        self.retry(id, input, operation).await
+    }
+
+    /// Save a given input (uniquely identified by the given id) with the given tag as pending, to
+    /// be retried by the first `retry_pending_dispatched` run no earlier than the given time
+    pub async fn enqueue_tagged(
+        &mut self,
+        id: Inj::Id,
+        input: Inj::Input,
+        tag: &str,
+        not_before: SystemTime,
+    ) {
+        self.injector.save_tag(id.clone(), tag).await;
+        self.enqueue(id, input, not_before).await
+    }
 }
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A live view over the progress of the persistent retries of a `RetryHandle`
+///
+/// It can be cloned and moved to another task, e.g. to render a progress bar while
+/// `RetryHandle::retry_pending` is running.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    counts: Arc<Counts>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    pending: AtomicUsize,
+    in_flight: AtomicUsize,
+    succeeded: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+/// The progress of the persistent retries of a `RetryHandle` at a given time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProgressSnapshot {
+    /// loaded inputs waiting for their retry to start
+    pub pending: usize,
+    /// inputs currently being retried
+    pub in_flight: usize,
+    /// inputs whose retry succeeded
+    pub succeeded: usize,
+    /// inputs whose retry failed permanently
+    pub failed: usize,
+}
+
+impl Progress {
+    /// Return the current progress
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            pending: self.counts.pending.load(Ordering::Relaxed),
+            in_flight: self.counts.in_flight.load(Ordering::Relaxed),
+            succeeded: self.counts.succeeded.load(Ordering::Relaxed),
+            failed: self.counts.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        self.counts.pending.store(0, Ordering::Relaxed);
+        self.counts.in_flight.store(0, Ordering::Relaxed);
+        self.counts.succeeded.store(0, Ordering::Relaxed);
+        self.counts.failed.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn loaded(&self, count: usize) {
+        self.counts.pending.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn started(&self) {
+        // inputs retried outside of `retry_pending` were never counted as pending
+        let _ = self
+            .counts
+            .pending
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+        self.counts.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn finished(&self, succeeded: bool) {
+        self.counts.in_flight.fetch_sub(1, Ordering::Relaxed);
+        if succeeded {
+            self.counts.succeeded.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.counts.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// An input was left to another worker before its retry finished
+    pub(crate) fn abandoned(&self) {
+        self.counts.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,483 @@
+//! Persistent retries of raw payloads, shared rather than copied (need `bytes` feature)
+//!
+//! The input of a `RetryHandle` is cloned for each attempt and for each status saved. A
+//! `RawInjector` stores its inputs as raw bytes, loaded as `bytes::Bytes` whose clones share the
+//! same buffer, and saved from a borrowed `&[u8]`, so that large payloads (e.g. media files) are
+//! never copied nor serialized by the retries. Wrapped in `Raw`, it is a `RetryInjector` of
+//! `Bytes` inputs, forwarding every method of the `RetryInjector` trait, and those of
+//! `NamespacedInjector` and `TaggedInjector` for a `RawNamespacedInjector` and a
+//! `RawTaggedInjector`.
+//!
+//! ```
+//! use async_trait::async_trait;
+//! use bytes::Bytes;
+//! use retry_block::delay::NoDelay;
+//! use retry_block::persist::raw::{Raw, RawInjector};
+//! use retry_block::persist::{RetryHandle, Status};
+//! use std::collections::HashMap;
+//! use std::sync::{Arc, Mutex};
+//! use std::time::SystemTime;
+//!
+//! struct Spool {
+//!     lengths: Arc<Mutex<HashMap<u64, usize>>>,
+//! }
+//!
+//! #[async_trait]
+//! impl RawInjector for Spool {
+//!     type Output = usize;
+//!     type Error = String;
+//!     type Id = u64;
+//!     type Res = Result<usize, String>;
+//!     async fn load_pending(&mut self, _now: SystemTime) -> Vec<(u64, Bytes)> {
+//!         // e.g. slices of a memory-mapped spool file
+//!         vec![(0, Bytes::from_static(b"frame"))]
+//!     }
+//!     async fn save_status(&mut self, id: u64, input: &[u8], status: Status<usize, String>) {
+//!         if let Status::Success(_) = status {
+//!             self.lengths.lock().unwrap().insert(id, input.len());
+//!         }
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let lengths = Arc::new(Mutex::new(HashMap::new()));
+//!     let spool = Spool {
+//!         lengths: lengths.clone(),
+//!     };
+//!     let mut handle = RetryHandle::new(Raw(spool), NoDelay.take(1));
+//!     handle
+//!         .retry_pending(1, &|payload: Bytes| async move { Ok(payload.len()) })
+//!         .await;
+//!     assert_eq!(lengths.lock().unwrap()[&0], 5);
+//! }
+//! ```
+
+use super::{NamespacedInjector, PersistenceError, RetryInjector, Status, TaggedInjector};
+use crate::OperationResult;
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::time::{Duration, SystemTime};
+
+/// A trait to specify how to save and retrieve raw inputs along with the status of their retry
+///
+/// The counterpart of `RetryInjector` whose inputs are always `Bytes`, borrowed as `&[u8]` when
+/// saved, see the module documentation. Each method behaves, and defaults, as the `RetryInjector`
+/// method of the same name.
+#[async_trait]
+pub trait RawInjector: Send {
+    /// The positive output value of a retry operation
+    type Output: Send;
+    /// The negative output value of a retry operation
+    type Error: Send + Sync;
+    /// An identifier for a given input
+    type Id: Clone + Send;
+    /// A `Result` type for the output of the retry operation
+    type Res: Into<OperationResult<Self::Output, Self::Error>>;
+
+    /// See `RetryInjector::load_pending`
+    async fn load_pending(&mut self, now: SystemTime) -> Vec<(Self::Id, Bytes)>;
+
+    /// See `RetryInjector::load_failed`
+    async fn load_failed(&mut self) -> Vec<(Self::Id, Bytes, Self::Error)> {
+        Vec::new()
+    }
+
+    /// See `RetryInjector::load_pending_after`
+    async fn load_pending_after(
+        &mut self,
+        after: Option<Self::Id>,
+        limit: usize,
+        now: SystemTime,
+    ) -> Vec<(Self::Id, Bytes)>
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = limit;
+        match after {
+            None => self.load_pending(now).await,
+            Some(_) => Vec::new(),
+        }
+    }
+
+    /// See `RetryInjector::claim_pending`
+    async fn claim_pending(
+        &mut self,
+        worker: &str,
+        lease: Duration,
+        now: SystemTime,
+    ) -> Vec<(Self::Id, Bytes)> {
+        let _ = (worker, lease);
+        self.load_pending(now).await
+    }
+
+    /// See `RetryInjector::renew_lease`
+    async fn renew_lease(&mut self, id: Self::Id, worker: &str, lease: Duration)
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = (id, worker, lease);
+    }
+
+    /// See `RetryInjector::on_delay`
+    async fn on_delay(&mut self, id: Self::Id, attempt: usize, delay: Duration)
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = (id, attempt, delay);
+    }
+
+    /// See `RetryInjector::timeout_error`
+    fn timeout_error(&self, timeout: Duration) -> Option<Self::Error> {
+        let _ = timeout;
+        None
+    }
+
+    /// See `RetryInjector::reschedule`
+    fn reschedule(&self, error: &Self::Error, scheduled: Duration) -> Duration {
+        let _ = error;
+        scheduled
+    }
+
+    /// See `RetryInjector::load_status`
+    async fn load_status(&mut self, id: Self::Id) -> Option<Status<Self::Output, Self::Error>>
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = id;
+        None
+    }
+
+    /// See `RetryInjector::load_attempts`
+    async fn load_attempts(&mut self, id: Self::Id) -> usize
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = id;
+        0
+    }
+
+    /// See `RetryInjector::dead_letter`
+    async fn dead_letter(
+        &mut self,
+        id: Self::Id,
+        input: &[u8],
+        error: &Self::Error,
+    ) -> Result<(), PersistenceError>
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = (id, input, error);
+        Err(PersistenceError::unsupported(
+            "dead_letter is not implemented",
+        ))
+    }
+
+    /// See `RetryInjector::append_history`
+    async fn append_history(
+        &mut self,
+        id: Self::Id,
+        attempt: usize,
+        error: &Self::Error,
+        delay: Option<Duration>,
+    ) where
+        Self::Id: 'async_trait,
+    {
+        let _ = (id, attempt, error, delay);
+    }
+
+    /// See `RetryInjector::recover_orphans`
+    async fn recover_orphans(&mut self, older_than: Duration) -> usize {
+        let _ = older_than;
+        0
+    }
+
+    /// See `RetryInjector::prune`
+    async fn prune(&mut self, older_than: Duration) -> usize {
+        let _ = older_than;
+        0
+    }
+
+    /// Save the status of a given operation, along with its input
+    async fn save_status(
+        &mut self,
+        id: Self::Id,
+        input: &[u8],
+        status: Status<Self::Output, Self::Error>,
+    );
+
+    /// See `RetryInjector::try_save_status`
+    async fn try_save_status(
+        &mut self,
+        id: Self::Id,
+        input: &[u8],
+        status: Status<Self::Output, Self::Error>,
+    ) -> Result<(), (PersistenceError, Status<Self::Output, Self::Error>)>
+    where
+        Self::Id: 'async_trait,
+        Self::Output: 'async_trait,
+        Self::Error: 'async_trait,
+    {
+        self.save_status(id, input, status).await;
+        Ok(())
+    }
+
+    /// See `RetryInjector::compare_and_save`
+    async fn compare_and_save(
+        &mut self,
+        id: Self::Id,
+        input: &[u8],
+        expected: &mut Option<Status<Self::Output, Self::Error>>,
+        new: Status<Self::Output, Self::Error>,
+    ) -> Result<bool, (PersistenceError, Status<Self::Output, Self::Error>)>
+    where
+        Self::Id: 'async_trait,
+        Self::Output: 'async_trait,
+        Self::Error: 'async_trait,
+    {
+        let _ = (id, input, expected);
+        let e = PersistenceError::unsupported("compare_and_save is not implemented");
+        Err((e, new))
+    }
+
+    /// See `RetryInjector::begin_transaction`
+    async fn begin_transaction(&mut self, id: Self::Id)
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = id;
+    }
+
+    /// See `RetryInjector::commit_transaction`
+    async fn commit_transaction(&mut self, id: Self::Id)
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = id;
+    }
+
+    /// See `RetryInjector::rollback_transaction`
+    async fn rollback_transaction(&mut self, id: Self::Id)
+    where
+        Self::Id: 'async_trait,
+    {
+        let _ = id;
+    }
+}
+
+/// The counterpart of `NamespacedInjector` for a `RawInjector`
+pub trait RawNamespacedInjector: RawInjector {
+    /// See `NamespacedInjector::set_namespace`
+    fn set_namespace(&mut self, namespace: &str);
+}
+
+/// The counterpart of `TaggedInjector` for a `RawInjector`
+#[async_trait]
+pub trait RawTaggedInjector: RawInjector {
+    /// See `TaggedInjector::save_tag`
+    async fn save_tag(&mut self, id: Self::Id, tag: &str)
+    where
+        Self::Id: 'async_trait;
+
+    /// See `TaggedInjector::load_pending_tagged`
+    async fn load_pending_tagged(
+        &mut self,
+        now: SystemTime,
+    ) -> Vec<(Self::Id, Bytes, Option<String>)>;
+}
+
+/// A `RetryInjector` of `Bytes` inputs, saving and retrieving them with the wrapped `RawInjector`
+#[derive(Debug, Clone, Default)]
+pub struct Raw<R>(pub R);
+
+#[async_trait]
+impl<'a, R> RetryInjector<'a> for Raw<R>
+where
+    R: RawInjector,
+{
+    type Input = Bytes;
+    type Output = R::Output;
+    type Error = R::Error;
+    type Id = R::Id;
+    type Res = R::Res;
+
+    async fn load_pending(&mut self, now: SystemTime) -> Vec<(R::Id, Bytes)> {
+        self.0.load_pending(now).await
+    }
+
+    async fn load_failed(&mut self) -> Vec<(R::Id, Bytes, R::Error)> {
+        self.0.load_failed().await
+    }
+
+    async fn load_pending_after(
+        &mut self,
+        after: Option<R::Id>,
+        limit: usize,
+        now: SystemTime,
+    ) -> Vec<(R::Id, Bytes)>
+    where
+        R::Id: 'async_trait,
+    {
+        self.0.load_pending_after(after, limit, now).await
+    }
+
+    async fn claim_pending(
+        &mut self,
+        worker: &str,
+        lease: Duration,
+        now: SystemTime,
+    ) -> Vec<(R::Id, Bytes)> {
+        self.0.claim_pending(worker, lease, now).await
+    }
+
+    async fn renew_lease(&mut self, id: R::Id, worker: &str, lease: Duration)
+    where
+        R::Id: 'async_trait,
+    {
+        self.0.renew_lease(id, worker, lease).await
+    }
+
+    async fn on_delay(&mut self, id: R::Id, attempt: usize, delay: Duration)
+    where
+        R::Id: 'async_trait,
+    {
+        self.0.on_delay(id, attempt, delay).await
+    }
+
+    fn timeout_error(&self, timeout: Duration) -> Option<R::Error> {
+        self.0.timeout_error(timeout)
+    }
+
+    fn reschedule(&self, error: &R::Error, scheduled: Duration) -> Duration {
+        self.0.reschedule(error, scheduled)
+    }
+
+    async fn load_status(&mut self, id: R::Id) -> Option<Status<R::Output, R::Error>>
+    where
+        R::Id: 'async_trait,
+    {
+        self.0.load_status(id).await
+    }
+
+    async fn load_attempts(&mut self, id: R::Id) -> usize
+    where
+        R::Id: 'async_trait,
+    {
+        self.0.load_attempts(id).await
+    }
+
+    async fn dead_letter(
+        &mut self,
+        id: R::Id,
+        input: Bytes,
+        error: &R::Error,
+    ) -> Result<(), PersistenceError>
+    where
+        R::Id: 'async_trait,
+    {
+        self.0.dead_letter(id, &input, error).await
+    }
+
+    async fn append_history(
+        &mut self,
+        id: R::Id,
+        attempt: usize,
+        error: &R::Error,
+        delay: Option<Duration>,
+    ) where
+        R::Id: 'async_trait,
+    {
+        self.0.append_history(id, attempt, error, delay).await
+    }
+
+    async fn recover_orphans(&mut self, older_than: Duration) -> usize {
+        self.0.recover_orphans(older_than).await
+    }
+
+    async fn prune(&mut self, older_than: Duration) -> usize {
+        self.0.prune(older_than).await
+    }
+
+    async fn save_status(&mut self, id: R::Id, input: Bytes, status: Status<R::Output, R::Error>) {
+        self.0.save_status(id, &input, status).await
+    }
+
+    async fn try_save_status(
+        &mut self,
+        id: R::Id,
+        input: Bytes,
+        status: Status<R::Output, R::Error>,
+    ) -> Result<(), (PersistenceError, Status<R::Output, R::Error>)>
+    where
+        R::Id: 'async_trait,
+        R::Output: 'async_trait,
+        R::Error: 'async_trait,
+    {
+        self.0.try_save_status(id, &input, status).await
+    }
+
+    async fn compare_and_save(
+        &mut self,
+        id: R::Id,
+        input: Bytes,
+        expected: &mut Option<Status<R::Output, R::Error>>,
+        new: Status<R::Output, R::Error>,
+    ) -> Result<bool, (PersistenceError, Status<R::Output, R::Error>)>
+    where
+        R::Id: 'async_trait,
+        R::Output: 'async_trait,
+        R::Error: 'async_trait,
+    {
+        self.0.compare_and_save(id, &input, expected, new).await
+    }
+
+    async fn begin_transaction(&mut self, id: R::Id)
+    where
+        R::Id: 'async_trait,
+    {
+        self.0.begin_transaction(id).await
+    }
+
+    async fn commit_transaction(&mut self, id: R::Id)
+    where
+        R::Id: 'async_trait,
+    {
+        self.0.commit_transaction(id).await
+    }
+
+    async fn rollback_transaction(&mut self, id: R::Id)
+    where
+        R::Id: 'async_trait,
+    {
+        self.0.rollback_transaction(id).await
+    }
+}
+
+impl<'a, R> NamespacedInjector<'a> for Raw<R>
+where
+    R: RawNamespacedInjector,
+{
+    fn set_namespace(&mut self, namespace: &str) {
+        self.0.set_namespace(namespace)
+    }
+}
+
+#[async_trait]
+impl<'a, R> TaggedInjector<'a> for Raw<R>
+where
+    R: RawTaggedInjector,
+{
+    async fn save_tag(&mut self, id: R::Id, tag: &str)
+    where
+        R::Id: 'async_trait,
+    {
+        self.0.save_tag(id, tag).await
+    }
+
+    async fn load_pending_tagged(
+        &mut self,
+        now: SystemTime,
+    ) -> Vec<(R::Id, Bytes, Option<String>)> {
+        self.0.load_pending_tagged(now).await
+    }
+}
@@ -1,40 +1,365 @@
-use crate::persist::{RetryHandle, RetryInjector, Status};
+use crate::persist::{
+    Backpressure, NamespacedInjector, PersistenceError, ProgressSnapshot, RampUp, RetryHandle,
+    RetryInjector, Status, TaggedInjector,
+};
+use crate::OperationResult;
+#[cfg(any(feature = "random", feature = "fastrand"))]
 use crate::RetryConfig;
 use async_trait::async_trait;
+use futures_util::StreamExt;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
 
-type OpsStorage = Arc<Mutex<HashMap<u64, (Status<i64, ()>, i64)>>>;
-struct Injector {
-    ops: OpsStorage,
+/// What an `Injector` stores, along with the calls made to its hooks
+struct Storage<E> {
+    /// the status and input of each id, by namespace
+    ops: HashMap<(String, u64), (Status<i64, E>, i64)>,
+    tags: HashMap<u64, String>,
+    /// the statuses saved and the transactions begun, committed or rolled back, in order
+    log: Vec<String>,
+    leases: Vec<(u64, String, Duration)>,
+    pages: Vec<Option<u64>>,
+    delays: Vec<(u64, usize, Duration)>,
+    pruned: Vec<Duration>,
+    history: Vec<(u64, usize, E, Option<Duration>)>,
+    dead_letters: Vec<(u64, i64, E)>,
+    /// the failures of the next saves, popped from the end, transient if true
+    failures: Vec<bool>,
+}
+
+impl<E> Default for Storage<E> {
+    fn default() -> Self {
+        Self {
+            ops: HashMap::new(),
+            tags: HashMap::new(),
+            log: Vec::new(),
+            leases: Vec::new(),
+            pages: Vec::new(),
+            delays: Vec::new(),
+            pruned: Vec::new(),
+            history: Vec::new(),
+            dead_letters: Vec::new(),
+            failures: Vec::new(),
+        }
+    }
+}
+
+/// An in-memory injector implementing every hook, shared by the tests
+///
+/// Clones share the same storage, so that a test can inspect what a handle saved.
+struct Injector<E = (), R = Result<i64, E>> {
+    storage: Arc<Mutex<Storage<E>>>,
+    namespace: String,
+    latency: Duration,
+    reschedule: fn(&E) -> Option<Duration>,
+    timeout_error: fn(Duration) -> Option<E>,
+    compare_and_save: bool,
+    res: PhantomData<fn() -> R>,
+}
+
+impl<E, R> Default for Injector<E, R> {
+    fn default() -> Self {
+        Self {
+            storage: Default::default(),
+            namespace: String::new(),
+            latency: Duration::ZERO,
+            reschedule: |_| None,
+            timeout_error: |_| None,
+            compare_and_save: true,
+            res: PhantomData,
+        }
+    }
+}
+
+impl<E, R> Clone for Injector<E, R> {
+    fn clone(&self) -> Self {
+        Self {
+            storage: self.storage.clone(),
+            namespace: self.namespace.clone(),
+            latency: self.latency,
+            reschedule: self.reschedule,
+            timeout_error: self.timeout_error,
+            compare_and_save: self.compare_and_save,
+            res: PhantomData,
+        }
+    }
+}
+
+impl<E: Clone, R> Injector<E, R> {
+    /// Take the given time to save each status
+    fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Wait for the delay given by the error of a failed attempt, if any
+    fn with_reschedule(mut self, reschedule: fn(&E) -> Option<Duration>) -> Self {
+        self.reschedule = reschedule;
+        self
+    }
+
+    /// Fail timed out attempts with the given error
+    fn with_timeout_error(mut self, timeout_error: fn(Duration) -> Option<E>) -> Self {
+        self.timeout_error = timeout_error;
+        self
+    }
+
+    /// Leave `compare_and_save` unsupported, as by default
+    fn without_compare_and_save(mut self) -> Self {
+        self.compare_and_save = false;
+        self
+    }
+
+    /// Fail the next saves, transiently if true
+    fn failing(self, failures: Vec<bool>) -> Self {
+        self.storage().failures = failures;
+        self
+    }
+
+    fn storage(&self) -> MutexGuard<'_, Storage<E>> {
+        self.storage.lock().unwrap()
+    }
+
+    /// Store the given input with the given status, outside of any namespace
+    fn insert(&self, id: u64, input: i64, status: Status<i64, E>) {
+        self.storage()
+            .ops
+            .insert((String::new(), id), (status, input));
+    }
+
+    /// The status and input stored for the given id, outside of any namespace
+    fn get(&self, id: u64) -> Option<(Status<i64, E>, i64)> {
+        self.storage().ops.get(&(String::new(), id)).cloned()
+    }
+
+    /// The due inputs of the namespace of the injector at the given time, by id
+    fn pending(&self, now: SystemTime) -> Vec<(u64, i64)> {
+        let mut pending: Vec<_> = self
+            .storage()
+            .ops
+            .iter()
+            .filter(|((namespace, _), (status, _))| {
+                *namespace == self.namespace && status.is_due(now)
+            })
+            .map(|((_, id), (_, input))| (*id, *input))
+            .collect();
+        pending.sort();
+        pending
+    }
 }
 
 #[async_trait]
-impl<'a> RetryInjector<'a> for Injector {
+impl<'a, E, R> RetryInjector<'a> for Injector<E, R>
+where
+    E: Clone + std::fmt::Debug + Send + Sync + 'static,
+    R: Into<OperationResult<i64, E>> + 'static,
+{
     type Input = i64;
     type Output = i64;
-    type Error = ();
+    type Error = E;
     type Id = u64;
-    type Res = Result<i64, ()>;
-    async fn load_pending(&mut self) -> Vec<(u64, i64)> {
-        self.ops
-            .lock()
-            .await
+    type Res = R;
+    async fn load_pending(&mut self, now: SystemTime) -> Vec<(u64, i64)> {
+        self.pending(now)
+    }
+    async fn load_failed(&mut self) -> Vec<(u64, i64, E)> {
+        self.storage()
+            .ops
             .iter()
-            .filter(|(_, (state, _))| matches!(state, Status::Pending))
-            .map(|(id, (_, val))| (*id, *val))
+            .filter(|((namespace, _), _)| *namespace == self.namespace)
+            .filter_map(|((_, id), (status, input))| Some((*id, *input, status.error()?.clone())))
             .collect()
     }
-    async fn save_status(&mut self, id: u64, input: i64, status: Status<i64, ()>) {
-        self.ops.lock().await.insert(id, (status, input));
+    async fn load_pending_after(
+        &mut self,
+        after: Option<u64>,
+        limit: usize,
+        now: SystemTime,
+    ) -> Vec<(u64, i64)> {
+        self.storage().pages.push(after);
+        let mut pending = self.pending(now);
+        pending.retain(|(id, _)| after.is_none_or(|after| *id > after));
+        pending.truncate(limit);
+        pending
+    }
+    async fn claim_pending(
+        &mut self,
+        worker: &str,
+        lease: Duration,
+        now: SystemTime,
+    ) -> Vec<(u64, i64)> {
+        let pending = self.pending(now);
+        let mut storage = self.storage();
+        for (id, _) in &pending {
+            storage.leases.push((*id, worker.to_owned(), lease));
+        }
+        pending
+    }
+    async fn renew_lease(&mut self, id: u64, worker: &str, lease: Duration) {
+        self.storage().leases.push((id, worker.to_owned(), lease));
+    }
+    async fn on_delay(&mut self, id: u64, attempt: usize, delay: Duration) {
+        self.storage().delays.push((id, attempt, delay));
+    }
+    fn timeout_error(&self, timeout: Duration) -> Option<E> {
+        (self.timeout_error)(timeout)
+    }
+    fn reschedule(&self, error: &E, scheduled: Duration) -> Duration {
+        (self.reschedule)(error).unwrap_or(scheduled)
+    }
+    async fn load_status(&mut self, id: u64) -> Option<Status<i64, E>> {
+        let key = (self.namespace.clone(), id);
+        self.storage()
+            .ops
+            .get(&key)
+            .map(|(status, _)| status.clone())
+    }
+    async fn load_attempts(&mut self, id: u64) -> usize {
+        let storage = self.storage();
+        let attempts = storage
+            .delays
+            .iter()
+            .filter(|(delayed, _, _)| *delayed == id);
+        attempts.map(|(_, attempt, _)| *attempt).max().unwrap_or(0)
+    }
+    async fn dead_letter(
+        &mut self,
+        id: u64,
+        input: i64,
+        error: &E,
+    ) -> Result<(), PersistenceError> {
+        let error = error.clone();
+        self.storage().dead_letters.push((id, input, error));
+        Ok(())
+    }
+    async fn append_history(
+        &mut self,
+        id: u64,
+        attempt: usize,
+        error: &E,
+        delay: Option<Duration>,
+    ) {
+        let error = error.clone();
+        self.storage().history.push((id, attempt, error, delay));
+    }
+    async fn recover_orphans(&mut self, older_than: Duration) -> usize {
+        let mut recovered = 0;
+        for (status, _) in self.storage().ops.values_mut() {
+            if let Status::InFlight { since, .. } = status {
+                if since.elapsed().unwrap_or_default() >= older_than {
+                    *status = Status::Pending;
+                    recovered += 1;
+                }
+            }
+        }
+        recovered
+    }
+    async fn prune(&mut self, older_than: Duration) -> usize {
+        let mut storage = self.storage();
+        storage.pruned.push(older_than);
+        let before = storage.ops.len();
+        storage.ops.retain(|_, (status, _)| {
+            !matches!(status, Status::Success(_)) && status.error().is_none()
+        });
+        before - storage.ops.len()
+    }
+    async fn save_status(&mut self, id: u64, input: i64, status: Status<i64, E>) {
+        let _ = self.try_save_status(id, input, status).await;
+    }
+    async fn try_save_status(
+        &mut self,
+        id: u64,
+        input: i64,
+        status: Status<i64, E>,
+    ) -> Result<(), (PersistenceError, Status<i64, E>)> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        let mut storage = self.storage();
+        if let Some(transient) = storage.failures.pop() {
+            let e = "connection reset";
+            let e = if transient {
+                PersistenceError::transient(e)
+            } else {
+                PersistenceError::permanent(e)
+            };
+            return Err((e, status));
+        }
+        storage.log.push(format!("{:?}", status));
+        storage
+            .ops
+            .insert((self.namespace.clone(), id), (status, input));
+        Ok(())
+    }
+    async fn compare_and_save(
+        &mut self,
+        id: u64,
+        input: i64,
+        expected: &mut Option<Status<i64, E>>,
+        new: Status<i64, E>,
+    ) -> Result<bool, (PersistenceError, Status<i64, E>)> {
+        if !self.compare_and_save {
+            return Err((PersistenceError::unsupported("not implemented"), new));
+        }
+        let key = (self.namespace.clone(), id);
+        let stored = self
+            .storage()
+            .ops
+            .get(&key)
+            .map(|(status, _)| format!("{:?}", status));
+        if stored != expected.as_ref().map(|status| format!("{:?}", status)) {
+            return Ok(false);
+        }
+        self.try_save_status(id, input, new).await.map(|()| true)
+    }
+    async fn begin_transaction(&mut self, _id: u64) {
+        self.storage().log.push("begin".to_owned());
+    }
+    async fn commit_transaction(&mut self, _id: u64) {
+        self.storage().log.push("commit".to_owned());
+    }
+    async fn rollback_transaction(&mut self, _id: u64) {
+        self.storage().log.push("rollback".to_owned());
+    }
+}
+
+impl<'a, E, R> NamespacedInjector<'a> for Injector<E, R>
+where
+    E: Clone + std::fmt::Debug + Send + Sync + 'static,
+    R: Into<OperationResult<i64, E>> + 'static,
+{
+    fn set_namespace(&mut self, namespace: &str) {
+        self.namespace = namespace.to_owned();
+    }
+}
+
+#[async_trait]
+impl<'a, E, R> TaggedInjector<'a> for Injector<E, R>
+where
+    E: Clone + std::fmt::Debug + Send + Sync + 'static,
+    R: Into<OperationResult<i64, E>> + 'static,
+{
+    async fn save_tag(&mut self, id: u64, tag: &str) {
+        self.storage().tags.insert(id, tag.to_owned());
+    }
+    async fn load_pending_tagged(&mut self, now: SystemTime) -> Vec<(u64, i64, Option<String>)> {
+        let pending = self.pending(now);
+        let storage = self.storage();
+        pending
+            .into_iter()
+            .map(|(id, input)| (id, input, storage.tags.get(&id).cloned()))
+            .collect()
     }
 }
 
+#[cfg(any(feature = "random", feature = "fastrand"))]
 #[tokio::test]
 async fn persistent_retry() {
-    let counter = Arc::new(Mutex::new(0));
-    let ops = Arc::new(Mutex::new(HashMap::from([(0, (Status::Pending, 3))])));
+    let counter = Arc::new(tokio::sync::Mutex::new(0));
+    let injector: Injector = Injector::default();
+    injector.insert(0, 3, Status::Pending);
 
     let increment = |input| {
         let counter = counter.clone();
@@ -46,7 +371,7 @@ async fn persistent_retry() {
     };
 
     let mut handle = RetryHandle::new(
-        Injector { ops: ops.clone() },
+        injector.clone(),
         RetryConfig {
             count: 10,
             min_backoff: 500,
@@ -57,41 +382,26 @@ async fn persistent_retry() {
     let mut id = 0;
 
     assert_eq!(*counter.lock().await, 0);
-    assert!(matches!(
-        ops.lock().await.get(&0).unwrap(),
-        (Status::Pending, 3)
-    ));
+    assert!(matches!(injector.get(0), Some((Status::Pending, 3))));
 
     handle.retry_pending(1, &increment).await;
     assert_eq!(*counter.lock().await, 3);
-    assert!(matches!(
-        ops.lock().await.get(&id).unwrap(),
-        (Status::Success(3), 3)
-    ));
+    assert!(matches!(injector.get(id), Some((Status::Success(3), 3))));
     id += 1;
 
     handle.retry(id, 6, &increment).await;
     assert_eq!(*counter.lock().await, 9);
-    assert!(matches!(
-        ops.lock().await.get(&id).unwrap(),
-        (Status::Success(9), 6)
-    ));
+    assert!(matches!(injector.get(id), Some((Status::Success(9), 6))));
     id += 1;
 
     handle.retry(id, 4, &increment).await;
     assert_eq!(*counter.lock().await, 13);
-    assert!(matches!(
-        ops.lock().await.get(&id).unwrap(),
-        (Status::Success(13), 4)
-    ));
+    assert!(matches!(injector.get(id), Some((Status::Success(13), 4))));
     id += 1;
 
     handle.retry(id, -1, &increment).await;
     assert_eq!(*counter.lock().await, 12);
-    assert!(matches!(
-        ops.lock().await.get(&id).unwrap(),
-        (Status::Success(12), -1)
-    ));
+    assert!(matches!(injector.get(id), Some((Status::Success(12), -1))));
     id += 1;
 
     handle
@@ -105,9 +415,1363 @@ async fn persistent_retry() {
         })
         .await;
     assert_eq!(*counter.lock().await, 24);
+    assert!(matches!(injector.get(id), Some((Status::Success(24), 2))));
+    assert!(matches!(handle.status(id).await, Some(Status::Success(24))));
+    assert!(handle.status(id + 1).await.is_none());
+}
+
+#[tokio::test]
+async fn leased_retry() {
+    let injector: Injector = Injector::default();
+    injector.insert(0, 3, Status::Pending);
+    let attempts = Arc::new(Mutex::new(0));
+
+    let mut handle = RetryHandle::new(injector.clone(), [Duration::from_millis(1)])
+        .with_lease("worker-1", Duration::from_secs(60));
+
+    handle
+        .retry_pending(1, &|input| {
+            let mut attempts = attempts.lock().unwrap();
+            *attempts += 1;
+            let res = if *attempts > 1 { Ok(input) } else { Err(()) };
+            async move { res }
+        })
+        .await;
+
+    assert!(matches!(injector.get(0), Some((Status::Success(3), 3))));
+    assert_eq!(
+        injector.storage().leases,
+        vec![
+            (0, "worker-1".to_owned(), Duration::from_secs(60)),
+            (0, "worker-1".to_owned(), Duration::from_millis(60_001)),
+        ]
+    );
+    // leased handles claim pending inputs rather than loading them
+    assert!(injector.storage().pages.is_empty());
+}
+
+#[tokio::test]
+async fn paged_retry_pending() {
+    let injector: Injector = Injector::default();
+    for id in 0..5 {
+        injector.insert(id, id as i64, Status::Pending);
+    }
+
+    let mut handle =
+        RetryHandle::new(injector.clone(), [Duration::from_millis(1)]).with_page_size(2);
+
+    handle
+        .retry_pending(2, &|input| async move { Ok(input * 10) })
+        .await;
+
+    let storage = injector.storage();
+    assert_eq!(storage.pages, vec![None, Some(1), Some(3), Some(4)]);
+    for ((_, id), (status, _)) in storage.ops.iter() {
+        assert!(matches!(status, Status::Success(n) if *n == *id as i64 * 10));
+    }
+}
+
+#[tokio::test]
+async fn checkpointed_retry() {
+    let injector: Injector = Injector::default();
+    let inputs = Arc::new(Mutex::new(Vec::new()));
+
+    let mut handle = RetryHandle::new(injector.clone(), [Duration::from_millis(1)]);
+
+    handle
+        .retry_with_checkpoint(0, 0, &|input, checkpoint| {
+            let inputs = inputs.clone();
+            async move {
+                inputs.lock().unwrap().push(input);
+                if input < 2 {
+                    checkpoint.save(input + 2).await;
+                    Err(())
+                } else {
+                    Ok(input)
+                }
+            }
+        })
+        .await;
+
+    assert_eq!(*inputs.lock().unwrap(), vec![0, 2]);
+    assert!(matches!(injector.get(0), Some((Status::Success(2), 2))));
+}
+
+#[tokio::test]
+async fn checkpoint_after_rollback() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(injector.clone(), [Duration::from_millis(1)]);
+
+    handle
+        .retry_with_checkpoint(0, 0, &|input, checkpoint| {
+            let injector = injector.clone();
+            async move {
+                if input == 0 {
+                    checkpoint.save(1).await;
+                    Err(())
+                } else {
+                    // the checkpoint was saved outside of the rolled back transaction
+                    assert!(matches!(injector.get(0), Some((Status::Pending, 1))));
+                    Ok(input)
+                }
+            }
+        })
+        .await;
+
+    assert_eq!(
+        injector.storage().log,
+        [
+            "Pending",
+            "begin",
+            "Pending",
+            "rollback",
+            "Pending",
+            "begin",
+            "Success(1)",
+            "commit",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn retry_progress() {
+    let injector: Injector = Injector::default();
+    for id in 0..4 {
+        injector.insert(id, id as i64, Status::Pending);
+    }
+    let mut handle = RetryHandle::new(injector, Vec::new());
+    let progress = handle.progress();
+    let snapshots = Arc::new(Mutex::new(Vec::new()));
+
+    handle
+        .retry_pending(1, &|input| {
+            snapshots.lock().unwrap().push(progress.snapshot());
+            async move {
+                if input % 2 == 0 {
+                    Ok(input)
+                } else {
+                    Err(())
+                }
+            }
+        })
+        .await;
+
+    let snapshots = snapshots.lock().unwrap();
+    assert_eq!(snapshots.len(), 4);
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        assert_eq!(snapshot.pending, 3 - i);
+        assert_eq!(snapshot.in_flight, 1);
+        assert_eq!(snapshot.succeeded + snapshot.failed, i);
+    }
+    assert_eq!(
+        progress.snapshot(),
+        ProgressSnapshot {
+            pending: 0,
+            in_flight: 0,
+            succeeded: 2,
+            failed: 2,
+        }
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn backpressured_retry_stream() {
+    let injector: Injector = Injector::default().with_latency(Duration::from_millis(10));
+    let mut handle =
+        RetryHandle::new(injector.clone(), Vec::new()).with_backpressure(Backpressure {
+            max_pending_saves: 1,
+            max_save_latency: Duration::from_millis(5),
+        });
+
+    let start = tokio::time::Instant::now();
+    let taken = Mutex::new(Vec::new());
+    let stream = futures_util::stream::iter((0..3).map(|id| (id, id as i64)))
+        .inspect(|_| taken.lock().unwrap().push(start.elapsed()));
+    handle
+        .retry_stream(stream, 4, &|input| async move { Ok(input) })
+        .await;
+
+    // each input waits for the previous status to be saved (twice 10ms), then for the latency
+    assert_eq!(
+        *taken.lock().unwrap(),
+        vec![
+            Duration::ZERO,
+            Duration::from_millis(30),
+            Duration::from_millis(60)
+        ]
+    );
+    assert_eq!(injector.storage().ops.len(), 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn backpressure_released_while_sleeping() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        crate::delay::Fixed::exact(Duration::from_secs(1)).take(1),
+    )
+    .with_backpressure(Backpressure {
+        max_pending_saves: 1,
+        max_save_latency: Duration::from_secs(1),
+    });
+
+    let start = tokio::time::Instant::now();
+    let taken = Mutex::new(Vec::new());
+    let stream = futures_util::stream::iter([(0, 0), (1, 1)])
+        .inspect(|_| taken.lock().unwrap().push(start.elapsed()));
+    let attempted = Mutex::new(Vec::new());
+    handle
+        .retry_stream(stream, 0, &|input| {
+            let first = !attempted.lock().unwrap().contains(&input);
+            attempted.lock().unwrap().push(input);
+            async move {
+                if input == 0 && first {
+                    Err(())
+                } else {
+                    Ok(input)
+                }
+            }
+        })
+        .await;
+
+    // the second input is taken while the first one sleeps until its next attempt
+    assert_eq!(*taken.lock().unwrap(), vec![Duration::ZERO; 2]);
+    assert!(matches!(injector.get(0), Some((Status::Success(0), 0))));
+    assert!(matches!(injector.get(1), Some((Status::Success(1), 1))));
+}
+
+#[tokio::test(start_paused = true)]
+async fn ramped_up_retry_stream() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(injector.clone(), Vec::new()).with_ramp_up(RampUp {
+        initial_concurrency: 1,
+        interval: Duration::from_millis(50),
+        min_success_rate: 0.9,
+    });
+
+    let start = tokio::time::Instant::now();
+    let taken = Mutex::new(Vec::new());
+    let stream = futures_util::stream::iter((0..3).map(|id| (id, id as i64)))
+        .inspect(|_| taken.lock().unwrap().push(start.elapsed()));
+    handle
+        .retry_stream(stream, 4, &|input| async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(input)
+        })
+        .await;
+
+    // the second input waits for the concurrency to double at the end of the first interval
+    let taken = taken.into_inner().unwrap();
+    assert_eq!(taken[..2], [Duration::ZERO, Duration::from_millis(50)]);
+    assert_eq!(injector.storage().ops.len(), 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn unlimited_ramp_up() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(injector.clone(), Vec::new()).with_ramp_up(RampUp {
+        initial_concurrency: 1,
+        interval: Duration::from_millis(50),
+        min_success_rate: 0.9,
+    });
+
+    let start = tokio::time::Instant::now();
+    let taken = Mutex::new(Vec::new());
+    let stream = futures_util::stream::iter((0..4).map(|id| (id, id as i64)))
+        .inspect(|_| taken.lock().unwrap().push(start.elapsed()));
+    handle
+        .retry_stream(stream, 0, &|input| async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(input)
+        })
+        .await;
+
+    // the concurrency keeps doubling without a limit
+    let millis = Duration::from_millis;
+    assert_eq!(
+        taken.into_inner().unwrap(),
+        [Duration::ZERO, millis(50), millis(100), millis(100)]
+    );
+    assert_eq!(injector.storage().ops.len(), 4);
+}
+
+#[tokio::test]
+async fn transactional_retry() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(injector.clone(), [Duration::from_millis(1)]);
+
+    let attempts = Mutex::new(0);
+    let operation = |input| {
+        let injector = injector.clone();
+        let mut attempts = attempts.lock().unwrap();
+        *attempts += 1;
+        let res = if *attempts % 2 == 0 {
+            Ok(input)
+        } else {
+            Err(())
+        };
+        async move {
+            injector.storage().log.push("side effect".to_owned());
+            res
+        }
+    };
+    handle.retry(0, 1, &operation).await;
+    assert_eq!(
+        injector.storage().log,
+        [
+            "Pending",
+            "begin",
+            "side effect",
+            "rollback",
+            "begin",
+            "side effect",
+            "Success(1)",
+            "commit",
+        ]
+    );
+
+    injector.storage().log.clear();
+    handle
+        .retry(1, 1, &|_| {
+            let injector = injector.clone();
+            async move {
+                injector.storage().log.push("side effect".to_owned());
+                Err(())
+            }
+        })
+        .await;
+    assert_eq!(
+        injector.storage().log,
+        [
+            "Pending",
+            "begin",
+            "side effect",
+            "rollback",
+            "begin",
+            "side effect",
+            "rollback",
+            "Failure(())",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn custom_sleeper() {
+    let injector: Injector = Injector::default();
+    let slept = Arc::new(Mutex::new(Vec::new()));
+
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        crate::delay::Fixed::exact(Duration::from_secs(3600)).take(2),
+    )
+    .with_sleeper(|duration| {
+        slept.lock().unwrap().push(duration);
+        std::future::ready(())
+    });
+
+    handle.retry(0, 1, &|_| async { Err(()) }).await;
+    assert!(matches!(injector.get(0), Some((Status::Failure(()), 1))));
+    assert_eq!(*slept.lock().unwrap(), vec![Duration::from_secs(3600); 2]);
+}
+
+#[cfg(feature = "governor")]
+#[tokio::test]
+async fn rate_limited_retry() {
+    use governor::{Quota, RateLimiter};
+
+    let injector: Injector = Injector::default();
+    let waits = Arc::new(Mutex::new(Vec::new()));
+    let rate_limiter = RateLimiter::direct(Quota::with_period(Duration::from_millis(20)).unwrap());
+
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        crate::delay::Fixed::exact(Duration::from_millis(1)).take(2),
+    )
+    .with_rate_limiter(Arc::new(rate_limiter))
+    .with_sleeper(|duration| {
+        waits.lock().unwrap().push(duration);
+        tokio::time::sleep(duration)
+    });
+
+    let start = std::time::Instant::now();
+    handle.retry(0, 1, &|_| async { Err(()) }).await;
+    assert!(matches!(injector.get(0), Some((Status::Failure(()), 1))));
+    // the first attempt is allowed right away, the next ones wait for the limiter
+    assert!(start.elapsed() >= Duration::from_millis(40));
+    // while their delays are still slept with the sleeper of the handle
+    assert_eq!(*waits.lock().unwrap(), vec![Duration::from_millis(1); 2]);
+}
+
+#[tokio::test]
+async fn delay_hook() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        crate::delay::Fixed::exact(Duration::from_millis(1)).take(2),
+    );
+
+    handle.retry(7, 1, &|_| async { Err(()) }).await;
+    assert_eq!(
+        injector.storage().delays,
+        vec![
+            (7, 1, Duration::from_millis(1)),
+            (7, 2, Duration::from_millis(1))
+        ]
+    );
+}
+
+#[tokio::test]
+async fn pruned_retry_pending() {
+    let injector: Injector = Injector::default();
+    injector.insert(0, 1, Status::Success(1));
+    injector.insert(1, 2, Status::Pending);
+
+    let mut handle = RetryHandle::new(injector.clone(), crate::delay::NoDelay.take(1))
+        .prune_completed_older_than(Duration::from_secs(86400));
+
+    handle
+        .retry_pending(1, &|input| async move { Ok(input) })
+        .await;
+    assert_eq!(injector.storage().pruned, vec![Duration::from_secs(86400)]);
+    assert!(injector.get(0).is_none());
+    assert!(matches!(injector.get(1), Some((Status::Success(2), 2))));
+}
+
+#[tokio::test]
+async fn retry_history() {
+    let injector: Injector<&'static str> = Injector::default();
+    let mut handle = RetryHandle::new(injector.clone(), crate::delay::NoDelay.take(1));
+
+    handle.retry(3, 1, &|_| async { Err("failed") }).await;
+    assert_eq!(
+        injector.storage().history,
+        vec![
+            (3, 1, "failed", Some(Duration::ZERO)),
+            (3, 2, "failed", None)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn namespaced_retry_pending() {
+    let injector: Injector = Injector::default();
+    for (namespace, input) in [("emails", 1), ("sms", 2)] {
+        let key = (namespace.to_string(), 0);
+        injector.storage().ops.insert(key, (Status::Pending, input));
+    }
+
+    let mut handle =
+        RetryHandle::new(injector.clone(), crate::delay::NoDelay.take(1)).with_namespace("emails");
+    assert_eq!(handle.namespace(), Some("emails"));
+
+    handle
+        .retry_pending(1, &|input| async move { Ok(input) })
+        .await;
+    let storage = injector.storage();
+    assert!(matches!(
+        storage.ops.get(&("emails".to_string(), 0)).unwrap(),
+        (Status::Success(1), 1)
+    ));
+    assert!(matches!(
+        storage.ops.get(&("sms".to_string(), 0)).unwrap(),
+        (Status::Pending, 2)
+    ));
+}
+
+#[tokio::test]
+async fn orphan_recovery() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(injector.clone(), crate::delay::NoDelay.take(1))
+        .with_lease("worker", Duration::from_secs(60))
+        .with_in_flight_status();
+
+    // crash in the middle of an attempt
+    let crashed = handle.retry(0, 1, &|_| std::future::pending());
+    assert!(tokio::time::timeout(Duration::from_millis(1), crashed)
+        .await
+        .is_err());
+    assert!(matches!(
+        injector.get(0),
+        Some((Status::InFlight { worker: Some(worker), .. }, 1)) if worker == "worker"
+    ));
+
+    assert_eq!(handle.recover_orphans(Duration::from_secs(60)).await, 0);
+    assert_eq!(handle.recover_orphans(Duration::ZERO).await, 1);
+    assert!(matches!(injector.get(0), Some((Status::Pending, 1))));
+
+    handle
+        .retry_pending(1, &|input| async move { Ok(input) })
+        .await;
+    assert!(matches!(injector.get(0), Some((Status::Success(1), 1))));
+}
+
+#[tokio::test]
+async fn scheduled_retry_pending() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(injector.clone(), crate::delay::NoDelay.take(1));
+
+    let now = SystemTime::now();
+    handle.enqueue(0, 1, now).await;
+    handle.enqueue(1, 2, now + Duration::from_secs(600)).await;
+
+    handle
+        .retry_pending(1, &|input| async move { Ok(input) })
+        .await;
+    assert!(matches!(injector.get(0), Some((Status::Success(1), 1))));
+    assert!(matches!(
+        injector.get(1),
+        Some((Status::Scheduled { not_before }, 2)) if not_before > now
+    ));
+}
+
+#[tokio::test]
+async fn time_source_schedule() {
+    use crate::delay::Clock;
+    use crate::time::MockTime;
+
+    let injector: Injector = Injector::default();
+    let time = MockTime::new();
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        crate::delay::Fixed::exact(Duration::from_secs(3600)).take(1),
+    )
+    .with_time_source(time.clone());
+
+    let start = time.system_now();
+    handle.enqueue(0, 1, start + Duration::from_secs(600)).await;
+    handle
+        .retry_pending(1, &|input| async move { Ok(input) })
+        .await;
+    assert!(matches!(
+        injector.get(0),
+        Some((Status::Scheduled { .. }, 1))
+    ));
+
+    time.advance(Duration::from_secs(600));
+    let failed = std::sync::atomic::AtomicBool::new(false);
+    handle
+        .retry_pending(1, &|input| {
+            let res = if failed.swap(true, std::sync::atomic::Ordering::Relaxed) {
+                Ok(input)
+            } else {
+                Err(())
+            };
+            async move { res }
+        })
+        .await;
+    assert!(matches!(injector.get(0), Some((Status::Success(1), 1))));
+    // the delay was slept on the mock clock
+    assert_eq!(
+        time.system_now().duration_since(start).unwrap(),
+        Duration::from_secs(4200)
+    );
+}
+
+#[test]
+fn time_source_timeouts_and_cache() {
+    use crate::delay::Clock;
+    use crate::time::MockTime;
+
+    let injector: Injector<String> = Injector::default()
+        .with_timeout_error(|timeout| Some(format!("timed out after {:?}", timeout)));
+    let time = MockTime::new();
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        crate::delay::Fixed::exact(Duration::from_secs(10)).take(1),
+    )
+    .with_time_source(time.clone())
+    .with_attempt_timeout(Duration::from_secs(60))
+    .with_result_cache(1, Duration::from_secs(3600));
+    let start = time.now();
+    let attempts = Mutex::new(0);
+    let hang_once = |input| {
+        let mut attempts = attempts.lock().unwrap();
+        *attempts += 1;
+        let hang = *attempts == 1;
+        async move {
+            if hang {
+                std::future::pending::<()>().await;
+            }
+            Ok(input)
+        }
+    };
+
+    // no tokio runtime is needed, the timeout and the delay being waited on the mock clock
+    crate::sleep::block_on(async {
+        assert!(matches!(
+            handle.retry_cached(0, 3, &hang_once).await,
+            Status::Success(3)
+        ));
+        assert_eq!(time.now() - start, Duration::from_secs(70));
+
+        // cached until the TTL elapses on the mock clock
+        handle.retry_cached(0, 3, &hang_once).await;
+        assert_eq!(*attempts.lock().unwrap(), 2);
+        time.advance(Duration::from_secs(3600));
+        handle.retry_cached(0, 3, &hang_once).await;
+        assert_eq!(*attempts.lock().unwrap(), 3);
+    });
+}
+
+#[tokio::test]
+async fn replayed_retry_failed() {
+    let injector: Injector<&'static str> = Injector::default();
+    injector.insert(0, 1, Status::Failure("timeout"));
+    injector.insert(1, 2, Status::Failure("invalid"));
+    let mut handle = RetryHandle::new(injector.clone(), crate::delay::NoDelay.take(1));
+
+    handle
+        .retry_failed(&|e| *e == "timeout", 1, &|input| async move { Ok(input) })
+        .await;
+    assert!(matches!(injector.get(0), Some((Status::Success(1), 1))));
+    assert!(matches!(
+        injector.get(1),
+        Some((Status::Failure("invalid"), 2))
+    ));
+}
+
+#[tokio::test]
+async fn resumed_backoff_position() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        crate::delay::Exponential::exact_with_factor(Duration::from_millis(1), 2.0).take(4),
+    );
+
+    // fail twice, then crash in the middle of the third attempt
+    let calls = AtomicUsize::new(0);
+    let crash = |_| {
+        let call = calls.fetch_add(1, Ordering::SeqCst);
+        async move {
+            if call == 2 {
+                std::future::pending::<()>().await;
+            }
+            Err(())
+        }
+    };
+    let crashed = handle.retry(0, 1, &crash);
+    assert!(tokio::time::timeout(Duration::from_millis(100), crashed)
+        .await
+        .is_err());
+
+    // resume with the third delay
+    let calls = AtomicUsize::new(0);
+    handle
+        .retry(0, 1, &|input| {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if call == 0 {
+                    Err(())
+                } else {
+                    Ok(input)
+                }
+            }
+        })
+        .await;
+    assert!(matches!(injector.get(0), Some((Status::Success(1), 1))));
+    assert_eq!(
+        injector.storage().delays,
+        vec![
+            (0, 1, Duration::from_millis(1)),
+            (0, 2, Duration::from_millis(2)),
+            (0, 3, Duration::from_millis(4))
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn replayed_backoff_position() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        crate::delay::Exponential::exact_with_factor(Duration::from_millis(1), 2.0).take(2),
+    );
+    handle.retry(0, 1, &|_| async { Err(()) }).await;
+    assert!(matches!(injector.get(0), Some((Status::Failure(()), 1))));
+
+    // a replayed failure starts its delays over
+    handle
+        .retry_failed(&|_| true, 1, &|_| async { Err(()) })
+        .await;
+    assert_eq!(
+        injector.storage().delays,
+        vec![
+            (0, 1, Duration::from_millis(1)),
+            (0, 2, Duration::from_millis(2)),
+            (0, 1, Duration::from_millis(1)),
+            (0, 2, Duration::from_millis(2))
+        ]
+    );
+}
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
+#[tokio::test(start_paused = true)]
+async fn timed_out_attempts() {
+    let injector: Injector<String> = Injector::default()
+        .with_timeout_error(|timeout| Some(format!("timed out after {:?}", timeout)));
+    let attempts = Arc::new(Mutex::new(0));
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        RetryConfig {
+            count: 2,
+            min_backoff: 10,
+            max_backoff: 10,
+        },
+    )
+    .with_attempt_timeout(Duration::from_secs(1));
+
+    let hang_once = |input| {
+        let mut attempts = attempts.lock().unwrap();
+        *attempts += 1;
+        let hang = *attempts == 1;
+        async move {
+            if hang {
+                tokio::time::sleep(Duration::from_secs(3600)).await;
+            }
+            Ok(input)
+        }
+    };
+    handle.retry(0, 3, &hang_once).await;
+    assert_eq!(*attempts.lock().unwrap(), 2);
+    assert!(matches!(injector.get(0), Some((Status::Success(3), 3))));
+
+    // an input timing out on every attempt fails with the timeout error, and is reported as such
+    let hang = |_| async {
+        tokio::time::sleep(Duration::from_secs(3600)).await;
+        Ok(0)
+    };
+    handle
+        .retry_with_timeout(1, 4, Duration::from_millis(100), &hang)
+        .await;
+    assert!(
+        matches!(injector.get(1), Some((Status::Failure(e), 4)) if e == "timed out after 100ms")
+    );
+    assert_eq!(
+        handle.retry_many([(2, 5)], 1, &hang).await,
+        vec![(2, Err("timed out after 1s".to_owned()))]
+    );
+}
+
+#[test]
+#[should_panic(expected = "attempt timeouts need RetryInjector::timeout_error to return an error")]
+fn attempt_timeout_without_error() {
+    let injector: Injector = Injector::default();
+    let _ = RetryHandle::new(injector, crate::delay::NoDelay)
+        .with_attempt_timeout(Duration::from_secs(1));
+}
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
+#[tokio::test(start_paused = true)]
+async fn cached_results() {
+    let injector: Injector = Injector::default();
+    let attempts = Arc::new(Mutex::new(0));
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        RetryConfig {
+            count: 1,
+            min_backoff: 10,
+            max_backoff: 10,
+        },
+    )
+    .with_result_cache(1, Duration::from_secs(60));
+
+    let increment = |input| {
+        *attempts.lock().unwrap() += 1;
+        async move { Ok(input + 1) }
+    };
+    assert!(matches!(
+        handle.retry_cached(0, 3, &increment).await,
+        Status::Success(4)
+    ));
+    injector.storage().ops.clear();
     assert!(matches!(
-        ops.lock().await.get(&id).unwrap(),
-        (Status::Success(24), 2)
+        handle.retry_cached(0, 3, &increment).await,
+        Status::Success(4)
     ));
-    // id += 1;
+    assert_eq!(*attempts.lock().unwrap(), 1);
+    assert!(injector.storage().ops.is_empty());
+
+    // evicted by a more recent input
+    handle.retry_cached(1, 5, &increment).await;
+    handle.retry_cached(0, 3, &increment).await;
+    assert_eq!(*attempts.lock().unwrap(), 3);
+
+    // expired
+    tokio::time::advance(Duration::from_secs(61)).await;
+    handle.retry_cached(0, 3, &increment).await;
+    assert_eq!(*attempts.lock().unwrap(), 4);
+}
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
+#[tokio::test]
+async fn retry_many_outcomes() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        RetryConfig {
+            count: 1,
+            min_backoff: 1,
+            max_backoff: 1,
+        },
+    );
+    let outcomes = handle
+        .retry_many([(0, 1), (1, -1), (2, 2)], 2, &|input| async move {
+            if input < 0 {
+                Err(())
+            } else {
+                Ok(input * 2)
+            }
+        })
+        .await;
+    assert_eq!(outcomes, vec![(0, Ok(2)), (1, Err(())), (2, Ok(4))]);
+    assert!(matches!(injector.get(1), Some((Status::Failure(()), -1))));
+
+    // no limit
+    let outcomes = handle
+        .retry_many([(3, 3), (4, 4)], 0, &|input| async move { Ok(input) })
+        .await;
+    assert_eq!(outcomes, vec![(3, Ok(3)), (4, Ok(4))]);
+}
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
+#[tokio::test(start_paused = true)]
+async fn streamed_outcomes() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(
+        injector,
+        RetryConfig {
+            count: 1,
+            min_backoff: 1,
+            max_backoff: 1,
+        },
+    );
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    let inputs = tokio_stream::wrappers::UnboundedReceiverStream::new(receiver);
+    let double = |input: i64| async move {
+        if input < 0 {
+            Err(())
+        } else {
+            Ok(input * 2)
+        }
+    };
+    let outcomes = handle.retry_stream_outcomes(inputs, 2, &double);
+    tokio::pin!(outcomes);
+
+    // each outcome is yielded before the next input is even sent
+    sender.send((0, 1)).unwrap();
+    assert_eq!(outcomes.next().await, Some((0, Ok(2))));
+    sender.send((1, -1)).unwrap();
+    assert_eq!(outcomes.next().await, Some((1, Err(()))));
+    drop(sender);
+    assert_eq!(outcomes.next().await, None);
+}
+
+#[tokio::test(start_paused = true)]
+async fn unlimited_streamed_outcomes() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(injector, crate::delay::NoDelay.take(1));
+    let inputs = futures_util::stream::iter([(0, 10), (1, 20)]);
+    // the first input takes longer, so that both run at once for the second to finish first
+    let wait = |input: i64| async move {
+        tokio::time::sleep(Duration::from_millis(30 - input as u64)).await;
+        Ok(input)
+    };
+    let outcomes: Vec<_> = handle
+        .retry_stream_outcomes(inputs, 0, &wait)
+        .collect()
+        .await;
+    assert_eq!(outcomes, vec![(1, Ok(20)), (0, Ok(10))]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn coalesced_sleeps() {
+    let injector: Injector = Injector::default();
+    let slept = Arc::new(Mutex::new(Vec::new()));
+
+    let mut handle = RetryHandle::new(
+        injector,
+        crate::delay::Fixed::exact(Duration::from_millis(25)).take(2),
+    )
+    .with_coalesced_sleeps(Duration::from_millis(10))
+    .with_sleeper(|duration| {
+        slept.lock().unwrap().push(duration);
+        tokio::time::sleep(duration)
+    });
+
+    tokio::time::advance(Duration::from_millis(3)).await;
+    handle.retry(0, 1, &|_| async { Err(()) }).await;
+    // woken at 30ms and 60ms rather than 28ms and 53ms
+    assert_eq!(
+        *slept.lock().unwrap(),
+        vec![Duration::from_millis(27), Duration::from_millis(30)]
+    );
+}
+
+#[cfg(feature = "config")]
+#[tokio::test(start_paused = true)]
+async fn configured_handle() {
+    use crate::persist::PersistConfig;
+
+    let config: PersistConfig = serde_json::from_str(
+        r#"{"retry": "3x 10ms..10ms", "concurrency": 2, "attempt_timeout_ms": 1000}"#,
+    )
+    .unwrap();
+    let config: PersistConfig =
+        serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+    assert_eq!(config.retry.count, 3);
+    assert_eq!(config.concurrency, 2);
+    assert_eq!(config.poll_interval(), None);
+
+    let injector: Injector = Injector::default().with_timeout_error(|_| Some(()));
+    injector.insert(0, 3, Status::Pending);
+    injector.insert(1, 4, Status::Pending);
+    let attempts = Arc::new(Mutex::new(0));
+    let mut handle = RetryHandle::from_config(injector.clone(), config);
+
+    handle
+        .poll_pending(&|input| {
+            let mut attempts = attempts.lock().unwrap();
+            *attempts += 1;
+            let hang = input == 3 && *attempts <= 2;
+            async move {
+                if hang {
+                    tokio::time::sleep(Duration::from_secs(3600)).await;
+                }
+                Ok(input)
+            }
+        })
+        .await;
+    assert!(matches!(injector.get(0), Some((Status::Success(3), 3))));
+    assert!(matches!(injector.get(1), Some((Status::Success(4), 4))));
+    assert!(injector.storage().dead_letters.is_empty());
+}
+
+#[cfg(feature = "config")]
+#[tokio::test(start_paused = true)]
+async fn configured_dead_letter() {
+    use crate::persist::PersistConfig;
+
+    let config: PersistConfig = serde_json::from_str(r#"{"retry": "1x 10ms..10ms"}"#).unwrap();
+    assert!(!config.dead_letter);
+    let config: PersistConfig =
+        serde_json::from_str(r#"{"retry": "1x 10ms..10ms", "dead_letter": true}"#).unwrap();
+    assert!(config.dead_letter);
+
+    let injector: Injector<&str> = Injector::default();
+    let mut handle = RetryHandle::from_config(injector.clone(), config);
+    handle.retry(0, 1, &|input| async move { Ok(input) }).await;
+    handle.retry(1, 2, &|_| async { Err("unavailable") }).await;
+    assert_eq!(injector.storage().dead_letters, vec![(1, 2, "unavailable")]);
+    assert!(matches!(
+        injector.get(1),
+        Some((Status::Failure("unavailable"), 2))
+    ));
+}
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
+#[tokio::test(start_paused = true)]
+async fn give_up_reasons() {
+    use crate::persist::GiveUpReason;
+
+    let config = RetryConfig {
+        count: 2,
+        min_backoff: 10,
+        max_backoff: 10,
+    };
+
+    let exhausted: Injector = Injector::default();
+    RetryHandle::new(exhausted.clone(), config.clone())
+        .with_give_up_reasons()
+        .retry(0, 1, &|_| async { Err(()) })
+        .await;
+    assert!(matches!(
+        exhausted.get(0),
+        Some((
+            Status::GaveUp {
+                error: (),
+                reason: GiveUpReason::Exhausted,
+                attempts: 3
+            },
+            1
+        ))
+    ));
+
+    let fatal: Injector<(), OperationResult<i64, ()>> = Injector::default();
+    RetryHandle::new(fatal.clone(), config)
+        .with_give_up_reasons()
+        .retry(1, 2, &|_| async { OperationResult::Err(()) })
+        .await;
+    let (status, input) = fatal.get(1).unwrap();
+    assert!(matches!(
+        status,
+        Status::GaveUp {
+            error: (),
+            reason: GiveUpReason::Fatal,
+            attempts: 1
+        }
+    ));
+    assert_eq!(input, 2);
+    assert_eq!(status.error(), Some(&()));
+}
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
+#[tokio::test(start_paused = true)]
+async fn updated_inputs() {
+    let injector: Injector = Injector::default();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        RetryConfig {
+            count: 5,
+            min_backoff: 10,
+            max_backoff: 10,
+        },
+    );
+
+    // each attempt advances the cursor it resumes from
+    handle
+        .retry_updating(0, 1, &|cursor| {
+            seen.lock().unwrap().push(cursor);
+            async move {
+                if cursor < 3 {
+                    (Err(()), Some(cursor + 1))
+                } else {
+                    (Ok(cursor * 10), None)
+                }
+            }
+        })
+        .await;
+    assert_eq!(*seen.lock().unwrap(), vec![1, 2, 3]);
+    assert!(matches!(injector.get(0), Some((Status::Success(30), 3))));
+}
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
+#[tokio::test]
+async fn dispatched_retry() {
+    use crate::persist::Dispatcher;
+
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        RetryConfig {
+            count: 0,
+            min_backoff: 1,
+            max_backoff: 1,
+        },
+    );
+    let now = SystemTime::now();
+    handle.enqueue_tagged(0, 1, "double", now).await;
+    handle.enqueue_tagged(1, 2, "negate", now).await;
+    handle.enqueue_tagged(2, 3, "unknown", now).await;
+
+    let dispatcher = Dispatcher::new()
+        .on("double", |input| async move { Ok(input * 2) })
+        .on("negate", |input: i64| async move { Ok(-input) });
+    handle.retry_pending_dispatched(2, &dispatcher).await;
+    assert!(matches!(injector.get(0), Some((Status::Success(2), 1))));
+    assert!(matches!(injector.get(1), Some((Status::Success(-2), 2))));
+    // no operation for its tag
+    assert!(matches!(
+        injector.get(2),
+        Some((Status::Scheduled { .. }, 3))
+    ));
+
+    let dispatcher = dispatcher.fallback(|input| async move { Ok(input) });
+    handle.retry_pending_dispatched(2, &dispatcher).await;
+    assert!(matches!(injector.get(2), Some((Status::Success(3), 3))));
+}
+
+#[tokio::test(start_paused = true)]
+async fn rescheduled_by_error() {
+    // errors carry the delay asked for by the remote end, if any
+    let injector: Injector<u64> = Injector::default()
+        .with_reschedule(|error| (*error > 0).then(|| Duration::from_millis(*error)));
+    let errors = Mutex::new(vec![0, 500, 0]);
+    let mut handle = RetryHandle::new(
+        injector.clone(),
+        crate::delay::Fixed::exact(Duration::from_millis(1)).take(2),
+    );
+
+    handle
+        .retry(3, 1, &|_| {
+            let error = errors.lock().unwrap().remove(0);
+            async move { Err(error) }
+        })
+        .await;
+    assert_eq!(
+        injector.storage().delays,
+        vec![
+            (3, 1, Duration::from_millis(1)),
+            (3, 2, Duration::from_millis(500))
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn resilient_saves() {
+    let injector: Injector = Injector::default().failing(vec![true, true]);
+    let mut handle = RetryHandle::new(injector, crate::delay::NoDelay.take(1));
+    handle.retry(0, 1, &|input| async move { Ok(input) }).await;
+    assert!(handle.take_persistence_errors().is_empty());
+
+    // the success fails permanently after the pending status was saved once retried
+    let injector: Injector = Injector::default().failing(vec![false, true]);
+    let mut handle = RetryHandle::new(injector, crate::delay::NoDelay.take(1));
+    handle.retry(0, 1, &|input| async move { Ok(input) }).await;
+    let errors = handle.take_persistence_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(!errors[0].is_transient());
+    assert_eq!(
+        errors[0].to_string(),
+        "failed to save status: connection reset"
+    );
+
+    // storage failures are retried when comparing too, and never taken for conflicts
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(injector.clone(), crate::delay::NoDelay.take(1))
+        .with_in_flight_status()
+        .with_compare_and_save();
+    handle
+        .retry(0, 1, &|input| {
+            injector.storage().failures = vec![false, true];
+            async move { Ok(input) }
+        })
+        .await;
+    assert_eq!(handle.conflicts(), 0);
+    assert_eq!(handle.take_persistence_errors().len(), 1);
+
+    // enqueued inputs are saved the same way, and only the last errors are kept
+    let injector: Injector = Injector::default().failing(vec![false; 150]);
+    let mut handle = RetryHandle::new(injector, crate::delay::NoDelay.take(1));
+    for id in 0..150 {
+        handle.enqueue(id, 1, SystemTime::now()).await;
+    }
+    assert_eq!(handle.take_persistence_errors().len(), 100);
+    assert!(handle.take_persistence_errors().is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn sleeping_inputs_release_slots() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(
+        injector,
+        crate::delay::Fixed::exact(Duration::from_secs(3600)).take(1),
+    );
+    let start = tokio::time::Instant::now();
+    let finished = Mutex::new(Vec::new());
+    // the first input fails once and sleeps for an hour, the others succeed right away
+    let inputs = (0..4).map(|id| (id, id as i64));
+    handle
+        .retry_stream(tokio_stream::iter(inputs), 1, &|input| {
+            let failed = input == 0 && start.elapsed().is_zero();
+            let finished = &finished;
+            async move {
+                if failed {
+                    return Err(());
+                }
+                finished
+                    .lock()
+                    .unwrap()
+                    .push((input, start.elapsed().as_secs()));
+                Ok(input)
+            }
+        })
+        .await;
+    assert_eq!(
+        finished.into_inner().unwrap(),
+        vec![(1, 0), (2, 0), (3, 0), (0, 3600)]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn concurrent_writers() {
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(injector.clone(), crate::delay::NoDelay.take(1))
+        .with_in_flight_status()
+        .with_compare_and_save();
+    handle.retry(0, 1, &|input| async move { Ok(input) }).await;
+    assert!(matches!(injector.get(0), Some((Status::Success(1), 1))));
+    assert_eq!(handle.conflicts(), 0);
+
+    // another worker saves its own success while this one is attempting
+    handle
+        .retry(1, 1, &|input| {
+            injector.insert(1, 1, Status::Success(2));
+            async move { Ok(input) }
+        })
+        .await;
+    assert!(matches!(injector.get(1), Some((Status::Success(2), 1))));
+    assert_eq!(handle.conflicts(), 1);
+    assert_eq!(handle.progress().snapshot().in_flight, 0);
+
+    // inputs already succeeded or being retried by another worker are not attempted again
+    let worker = Status::InFlight {
+        worker: Some("other".to_owned()),
+        since: SystemTime::now(),
+    };
+    injector.insert(2, 1, worker);
+    for id in [0, 2] {
+        handle
+            .retry(id, 1, &|_| async { panic!("attempted") })
+            .await;
+    }
+    assert!(matches!(injector.get(0), Some((Status::Success(1), 1))));
+    assert!(matches!(
+        injector.get(2),
+        Some((Status::InFlight { .. }, 1))
+    ));
+    assert_eq!(handle.conflicts(), 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn compare_and_save_unsupported() {
+    let injector: Injector = Injector::default().without_compare_and_save();
+    let mut handle =
+        RetryHandle::new(injector.clone(), crate::delay::NoDelay.take(1)).with_compare_and_save();
+    handle.retry(0, 1, &|input| async move { Ok(input) }).await;
+    handle.retry(1, 1, &|input| async move { Ok(input) }).await;
+    // the statuses are saved without comparing them, after the error is kept once
+    assert!(matches!(injector.get(0), Some((Status::Success(1), 1))));
+    assert!(matches!(injector.get(1), Some((Status::Success(1), 1))));
+    let errors = handle.take_persistence_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].is_unsupported());
+}
+
+#[cfg(feature = "json")]
+#[tokio::test(start_paused = true)]
+async fn idempotent_enqueue() {
+    use crate::persist::codec::{ContentHash, Fnv1a, Json};
+
+    let ids = ContentHash::<Json>::new(Fnv1a);
+    let injector: Injector = Injector::default();
+    let mut handle = RetryHandle::new(injector.clone(), crate::delay::NoDelay.take(1));
+    let now = SystemTime::now();
+    let first = handle.enqueue_auto(&ids, 1, now).await.unwrap();
+    assert_eq!(handle.enqueue_auto(&ids, 1, now).await.unwrap(), first);
+    assert_eq!(injector.storage().log.len(), 1);
+
+    // an input already retried is left as is
+    injector.insert(first, 1, Status::Success(2));
+    handle.enqueue_auto(&ids, 1, now).await.unwrap();
+    assert!(matches!(injector.get(first), Some((Status::Success(2), 1))));
+    assert!(handle.take_persistence_errors().is_empty());
+}
+
+#[cfg(feature = "bytes")]
+#[tokio::test(start_paused = true)]
+async fn shared_bytes_payloads() {
+    use bytes::Bytes;
+
+    struct Payloads {
+        payload: Bytes,
+        saved: Arc<std::sync::Mutex<Vec<usize>>>,
+    }
+
+    #[async_trait]
+    impl<'a> RetryInjector<'a> for Payloads {
+        type Input = Bytes;
+        type Output = ();
+        type Error = ();
+        type Id = u64;
+        type Res = Result<(), ()>;
+        async fn load_pending(&mut self, _now: SystemTime) -> Vec<(u64, Bytes)> {
+            vec![(0, self.payload.clone())]
+        }
+        async fn save_status(&mut self, _id: u64, input: Bytes, _status: Status<(), ()>) {
+            self.saved.lock().unwrap().push(input.as_ptr() as usize);
+        }
+    }
+
+    let payload = Bytes::from(vec![0u8; 1 << 20]);
+    let address = payload.as_ptr() as usize;
+    let attempts = std::sync::Mutex::new(Vec::new());
+    let saved = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut handle = RetryHandle::new(
+        Payloads {
+            payload,
+            saved: saved.clone(),
+        },
+        crate::delay::NoDelay.take(2),
+    );
+    handle
+        .retry_pending(1, &|payload: Bytes| {
+            attempts.lock().unwrap().push(payload.as_ptr() as usize);
+            async { Err(()) }
+        })
+        .await;
+    // every attempt and saved status saw the same buffer
+    assert_eq!(attempts.into_inner().unwrap(), vec![address; 3]);
+    let saved = saved.lock().unwrap();
+    assert_eq!(saved.len(), 2);
+    assert!(saved.iter().all(|saved| *saved == address));
+}
+
+#[cfg(feature = "bytes")]
+#[tokio::test(start_paused = true)]
+async fn shared_raw_payloads() {
+    use crate::persist::raw::{Raw, RawInjector};
+    use bytes::Bytes;
+
+    struct Payloads {
+        payload: Bytes,
+        saved: Arc<std::sync::Mutex<Vec<(usize, String)>>>,
+    }
+
+    #[async_trait]
+    impl RawInjector for Payloads {
+        type Output = ();
+        type Error = ();
+        type Id = u64;
+        type Res = Result<(), ()>;
+        async fn load_pending(&mut self, _now: SystemTime) -> Vec<(u64, Bytes)> {
+            vec![(0, self.payload.clone())]
+        }
+        async fn save_status(&mut self, _id: u64, input: &[u8], status: Status<(), ()>) {
+            let saved = (input.as_ptr() as usize, format!("{:?}", status));
+            self.saved.lock().unwrap().push(saved);
+        }
+        async fn compare_and_save(
+            &mut self,
+            id: u64,
+            input: &[u8],
+            _expected: &mut Option<Status<(), ()>>,
+            new: Status<(), ()>,
+        ) -> Result<bool, (PersistenceError, Status<(), ()>)> {
+            self.save_status(id, input, new).await;
+            Ok(true)
+        }
+    }
+
+    let payload = Bytes::from(vec![0u8; 1 << 20]);
+    let address = payload.as_ptr() as usize;
+    let attempts = std::sync::Mutex::new(Vec::new());
+    let saved = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut handle = RetryHandle::new(
+        Raw(Payloads {
+            payload,
+            saved: saved.clone(),
+        }),
+        crate::delay::NoDelay.take(2),
+    )
+    .with_compare_and_save();
+    handle
+        .retry_pending(1, &|payload: Bytes| {
+            attempts.lock().unwrap().push(payload.as_ptr() as usize);
+            async { Err(()) }
+        })
+        .await;
+    // every attempt saw the same buffer, borrowed by every status saved through compare_and_save
+    assert_eq!(attempts.into_inner().unwrap(), vec![address; 3]);
+    assert_eq!(handle.take_persistence_errors().len(), 0);
+    let saved = saved.lock().unwrap();
+    assert_eq!(
+        *saved,
+        vec![
+            (address, "Pending".to_owned()),
+            (address, "Failure(())".to_owned())
+        ]
+    );
 }
@@ -0,0 +1,300 @@
+//! Tools to test persistent retries and custom injectors (need `test-util` feature)
+//!
+//! `MemoryInjector` is a reference implementation of `RetryInjector` keeping every status saved,
+//! so that the expected contract of a `RetryHandle` can be checked against it with
+//! `MemoryInjector::check_invariants`, and the statuses saved by a custom injector against the
+//! same rules with `check_history`. `run_virtual` runs a test on a runtime whose clock only moves
+//! forward when all tasks are idle, making retries with long delays fast and deterministic.
+//!
+//! ```
+//! use retry_block::delay::Fixed;
+//! use retry_block::persist::test_support::{run_virtual, MemoryInjector};
+//! use retry_block::persist::{RetryHandle, Status};
+//! use std::time::Duration;
+//!
+//! let injector = MemoryInjector::<i64, i64, String>::new();
+//! injector.insert(0, 3, Status::Pending);
+//! run_virtual(async {
+//!     let delays = Fixed::exact(Duration::from_secs(3600)).take(2);
+//!     let mut handle = RetryHandle::new(injector.clone(), delays);
+//!     handle
+//!         .retry_pending(1, &|input| async move { Ok::<_, String>(input * 2) })
+//!         .await;
+//!     handle
+//!         .retry(1, 5, &|_| async { Err("unavailable".to_string()) })
+//!         .await;
+//! });
+//! assert!(matches!(injector.status(0), Some(Status::Success(6))));
+//! assert!(matches!(injector.status(1), Some(Status::Failure(_))));
+//! injector.check_invariants().unwrap();
+//! ```
+
+use super::{RetryInjector, Status};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// A rule of the persistent retry contract broken by a history of statuses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContractViolation<Id> {
+    /// A status was saved for the input after it had succeeded
+    SavedAfterSuccess(Id),
+    /// The last status saved for the input is not terminal, i.e. it was left pending
+    NotTerminal(Id),
+}
+
+impl<Id: std::fmt::Debug> std::fmt::Display for ContractViolation<Id> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SavedAfterSuccess(id) => write!(f, "status of {id:?} saved after its success"),
+            Self::NotTerminal(id) => write!(f, "{id:?} did not reach a terminal status"),
+        }
+    }
+}
+
+impl<Id: std::fmt::Debug> std::error::Error for ContractViolation<Id> {}
+
+/// Check the statuses saved for an input, in order, once all retries are done
+///
+/// An input must end with a terminal status (`Status::Success`, `Status::Failure` or
+/// `Status::GaveUp`), and nothing may be saved for it after a success.
+pub fn check_history<Id, O, E>(
+    id: Id,
+    history: &[Status<O, E>],
+) -> Result<(), ContractViolation<Id>> {
+    let success = history
+        .iter()
+        .position(|status| matches!(status, Status::Success(_)));
+    if success.is_some_and(|success| success + 1 < history.len()) {
+        return Err(ContractViolation::SavedAfterSuccess(id));
+    }
+    match history.last() {
+        None | Some(Status::Success(_) | Status::Failure(_) | Status::GaveUp { .. }) => Ok(()),
+        Some(_) => Err(ContractViolation::NotTerminal(id)),
+    }
+}
+
+struct Entry<I, O, E> {
+    input: I,
+    history: Vec<Status<O, E>>,
+}
+
+type Entries<I, O, E> = BTreeMap<u64, Entry<I, O, E>>;
+
+/// An in-memory injector keeping the history of the statuses saved for each input
+///
+/// Clones share the same storage, so that a test can inspect what a handle saved.
+pub struct MemoryInjector<I, O, E> {
+    entries: Arc<Mutex<Entries<I, O, E>>>,
+}
+
+impl<I, O, E> Clone for MemoryInjector<I, O, E> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<I, O, E> Default for MemoryInjector<I, O, E> {
+    fn default() -> Self {
+        Self {
+            entries: Default::default(),
+        }
+    }
+}
+
+impl<I, O, E> MemoryInjector<I, O, E>
+where
+    I: Clone,
+    O: Clone,
+    E: Clone,
+{
+    /// Create an empty injector
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_entries<R>(&self, f: impl FnOnce(&mut Entries<I, O, E>) -> R) -> R {
+        f(&mut self.entries.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Store the given input with the given status, as if saved by a previous run
+    pub fn insert(&self, id: u64, input: I, status: Status<O, E>) {
+        self.with_entries(|entries| {
+            entries.insert(
+                id,
+                Entry {
+                    input,
+                    history: vec![status],
+                },
+            )
+        });
+    }
+
+    /// The last status saved for the given input, if any
+    pub fn status(&self, id: u64) -> Option<Status<O, E>> {
+        self.with_entries(|entries| entries.get(&id)?.history.last().cloned())
+    }
+
+    /// All the statuses saved for the given input, in order
+    pub fn history(&self, id: u64) -> Vec<Status<O, E>> {
+        self.with_entries(|entries| {
+            entries
+                .get(&id)
+                .map(|entry| entry.history.clone())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Check the history of every input with `check_history`
+    pub fn check_invariants(&self) -> Result<(), ContractViolation<u64>> {
+        self.with_entries(|entries| {
+            entries
+                .iter()
+                .try_for_each(|(id, entry)| check_history(*id, &entry.history))
+        })
+    }
+}
+
+#[async_trait]
+impl<'a, I, O, E> RetryInjector<'a> for MemoryInjector<I, O, E>
+where
+    I: Serialize + Deserialize<'a> + Clone + Send + 'static,
+    O: Clone + Send + 'static,
+    E: Clone + Send + Sync + 'static,
+{
+    type Input = I;
+    type Output = O;
+    type Error = E;
+    type Id = u64;
+    type Res = Result<O, E>;
+
+    async fn load_pending(&mut self, now: SystemTime) -> Vec<(u64, I)> {
+        self.with_entries(|entries| {
+            entries
+                .iter()
+                .filter(|(_, entry)| entry.history.last().is_some_and(|s| s.is_due(now)))
+                .map(|(id, entry)| (*id, entry.input.clone()))
+                .collect()
+        })
+    }
+
+    async fn load_failed(&mut self) -> Vec<(u64, I, E)> {
+        self.with_entries(|entries| {
+            entries
+                .iter()
+                .filter_map(|(id, entry)| {
+                    let error = entry.history.last()?.error()?;
+                    Some((*id, entry.input.clone(), error.clone()))
+                })
+                .collect()
+        })
+    }
+
+    async fn load_status(&mut self, id: u64) -> Option<Status<O, E>> {
+        self.status(id)
+    }
+
+    async fn save_status(&mut self, id: u64, input: I, status: Status<O, E>) {
+        self.with_entries(|entries| {
+            let entry = entries.entry(id).or_insert_with(|| Entry {
+                input: input.clone(),
+                history: Vec::new(),
+            });
+            entry.input = input;
+            entry.history.push(status);
+        })
+    }
+
+    async fn compare_and_save(
+        &mut self,
+        id: u64,
+        input: I,
+        expected: &mut Option<Status<O, E>>,
+        new: Status<O, E>,
+    ) -> Result<bool, (super::PersistenceError, Status<O, E>)> {
+        self.with_entries(|entries| {
+            let stored = entries.get(&id).and_then(|entry| entry.history.last());
+            let same = match (stored, &*expected) {
+                (None, None) => true,
+                (Some(stored), Some(expected)) => same_status(stored, expected),
+                _ => false,
+            };
+            if same {
+                let entry = entries.entry(id).or_insert_with(|| Entry {
+                    input: input.clone(),
+                    history: Vec::new(),
+                });
+                entry.input = input;
+                entry.history.push(new);
+            }
+            Ok(same)
+        })
+    }
+}
+
+/// Whether two statuses are the same, comparing final ones by variant only since neither outputs
+/// nor errors can be compared
+fn same_status<O, E>(a: &Status<O, E>, b: &Status<O, E>) -> bool {
+    match (a, b) {
+        (Status::Scheduled { not_before: a }, Status::Scheduled { not_before: b }) => a == b,
+        (
+            Status::InFlight { worker, since },
+            Status::InFlight {
+                worker: other,
+                since: other_since,
+            },
+        ) => worker == other && since == other_since,
+        (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b),
+    }
+}
+
+/// Run the given future to completion on a single threaded runtime with a paused clock
+///
+/// The clock jumps to the next timer whenever all tasks are idle, so delays take no real time and
+/// timers always fire in the same order.
+///
+/// # Panics
+///
+/// Panics if called from within a runtime.
+pub fn run_virtual<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .expect("failed to build the virtual time runtime")
+        .block_on(future)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_history, run_virtual, ContractViolation, MemoryInjector};
+    use crate::delay::NoDelay;
+    use crate::persist::{RetryHandle, Status};
+
+    #[test]
+    fn broken_contract() {
+        assert_eq!(
+            check_history(0, &[Status::<(), ()>::Success(()), Status::Pending]),
+            Err(ContractViolation::SavedAfterSuccess(0))
+        );
+        assert_eq!(
+            check_history(1, &[Status::<(), ()>::Pending]),
+            Err(ContractViolation::NotTerminal(1))
+        );
+
+        let injector = MemoryInjector::<i64, i64, ()>::new();
+        run_virtual(async {
+            RetryHandle::new(injector.clone(), NoDelay.take(1))
+                .retry(2, 1, &|input| async move { Ok(input) })
+                .await;
+        });
+        assert_eq!(injector.history(2).len(), 2);
+        assert!(matches!(injector.history(2)[0], Status::Pending));
+        injector.check_invariants().unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+use crate::delay::{BoxedDelay, Fixed, IntoBoxed};
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A cloneable retry policy, handing out a fresh copy of its delays to each retry
+///
+/// Installed for a scope with `with_default_policy` (or `scope_default_policy` for a task), it is
+/// what `retry_default!` and `default_delays` retry with.
+#[derive(Clone)]
+pub struct DefaultPolicy {
+    delays: Arc<dyn Fn() -> BoxedDelay + Send + Sync>,
+}
+
+impl DefaultPolicy {
+    /// A policy retrying with a clone of the given delays each time
+    pub fn new<D>(durations: D) -> Self
+    where
+        D: IntoIterator<Item = Duration> + Clone + Send + Sync + 'static,
+        D::IntoIter: Send + 'static,
+    {
+        Self {
+            delays: Arc::new(move || durations.clone().into_boxed()),
+        }
+    }
+
+    /// The delays of a new retry following this policy
+    pub fn delays(&self) -> BoxedDelay {
+        (self.delays)()
+    }
+}
+
+impl std::fmt::Debug for DefaultPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DefaultPolicy").finish_non_exhaustive()
+    }
+}
+
+impl Default for DefaultPolicy {
+    /// Three retries, 100ms apart
+    fn default() -> Self {
+        Self::new(Fixed::exact(Duration::from_millis(100)).take(3))
+    }
+}
+
+thread_local! {
+    static THREAD_POLICY: RefCell<Option<DefaultPolicy>> = const { RefCell::new(None) };
+}
+
+#[cfg(feature = "tokio")]
+tokio::task_local! {
+    static TASK_POLICY: DefaultPolicy;
+}
+
+/// Restores the policy of the enclosing scope when dropped, even on panic
+struct Restore(Option<DefaultPolicy>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        THREAD_POLICY.with(|policy| *policy.borrow_mut() = previous);
+    }
+}
+
+/// Run the given closure with the given policy as the default of the current thread
+///
+/// Every `retry_default!` (or call to `default_delays`) made while the closure runs, however
+/// deeply nested, retries with this policy. Scopes nest: the innermost one wins, and the
+/// enclosing policy is restored once the closure returns or unwinds.
+///
+/// ```
+/// # use retry_block::{retry_default, with_default_policy, DefaultPolicy};
+/// # use retry_block::delay::NoDelay;
+/// fn flaky(attempts: &mut u32) -> Result<u32, u32> {
+///     retry_default!({
+///         *attempts += 1;
+///         Err(*attempts)
+///     })
+/// }
+///
+/// let mut attempts = 0;
+/// let value = with_default_policy(DefaultPolicy::new(NoDelay.take(4)), || flaky(&mut attempts));
+/// assert_eq!(value, Err(5));
+/// ```
+pub fn with_default_policy<F, R>(policy: DefaultPolicy, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let previous = THREAD_POLICY.with(|current| current.borrow_mut().replace(policy));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// Run the given future with the given policy as the default of the current task
+///
+/// Unlike `with_default_policy`, the policy follows the future across the threads of a
+/// multi-threaded runtime and the points where it yields. A `with_default_policy` scope entered
+/// within the future still takes precedence while it runs.
+#[cfg(feature = "tokio")]
+pub async fn scope_default_policy<F>(policy: DefaultPolicy, future: F) -> F::Output
+where
+    F: std::future::Future,
+{
+    TASK_POLICY.scope(policy, future).await
+}
+
+/// The delays of the policy of the innermost enclosing scope
+///
+/// The policy set by `with_default_policy` on the current thread is looked up first, then the one
+/// set by `scope_default_policy` for the current task, and `DefaultPolicy::default()` otherwise.
+pub fn default_delays() -> BoxedDelay {
+    if let Some(delays) = THREAD_POLICY.with(|policy| policy.borrow().as_ref().map(|p| p.delays()))
+    {
+        return delays;
+    }
+    #[cfg(feature = "tokio")]
+    if let Ok(delays) = TASK_POLICY.try_with(|policy| policy.delays()) {
+        return delays;
+    }
+    DefaultPolicy::default().delays()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{default_delays, with_default_policy, DefaultPolicy};
+    use crate::delay::NoDelay;
+    use std::time::Duration;
+
+    #[test]
+    fn nested_scopes() {
+        assert_eq!(default_delays().count(), 3);
+        with_default_policy(DefaultPolicy::new(NoDelay.take(1)), || {
+            let inner = std::panic::catch_unwind(|| {
+                with_default_policy(DefaultPolicy::new(NoDelay.take(5)), || {
+                    assert_eq!(default_delays().count(), 5);
+                    panic!("unwound");
+                })
+            });
+            assert!(inner.is_err());
+            assert_eq!(default_delays().collect::<Vec<_>>(), vec![Duration::ZERO]);
+        });
+        assert_eq!(default_delays().count(), 3);
+    }
+}
@@ -0,0 +1,35 @@
+//! The most commonly used items of the crate, to import at once
+//!
+//! ```
+//! use retry_block::prelude::*;
+//! # use std::time::Duration;
+//!
+//! let value = retry!(Exponential::exact(Duration::from_millis(1)).take(3), {
+//!     OperationResult::<_, ()>::Ok(42)
+//! });
+//! assert_eq!(value, Ok(42));
+//! ```
+
+#[cfg(all(
+    feature = "future",
+    feature = "tokio",
+    any(feature = "random", feature = "fastrand")
+))]
+pub use crate::async_retry_perpetual;
+#[cfg(any(feature = "random", feature = "fastrand"))]
+pub use crate::delay::{jitter, Jitter, JitterExt, Range};
+pub use crate::delay::{
+    Decay, DelayBuilder, Exponential, Fibonacci, Fixed, IntoBoxed, NoDelay, Schedule,
+};
+pub use crate::iter::IteratorRetryExt;
+#[cfg(any(feature = "random", feature = "fastrand"))]
+pub use crate::retry_perpetual;
+#[cfg(feature = "stream")]
+pub use crate::stream::StreamRetryExt;
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub use crate::{async_retry, async_retry_default, async_retry_fn};
+#[cfg(feature = "future")]
+pub use crate::{async_retry_fn_with_sleeper, async_retry_with, AsyncSleeper, FutureFactoryExt};
+pub use crate::{fatal, retry, retry_default, retry_fn, OperationResult};
+#[cfg(any(feature = "random", feature = "fastrand"))]
+pub use crate::{RetryConfig, RetryConfigV2};
@@ -0,0 +1,187 @@
+//! Retry metrics exposed through a `prometheus` registry (need `prometheus` feature)
+//!
+//! The stats of the operations accounted with `DelayAccounting::named` are read on each scrape by
+//! `AccountingCollector`, while `DelayHistogram` observes each delay as it is taken. Services
+//! without a metrics pipeline of their own can serve the text returned by `encode` as is.
+//!
+//! ```
+//! use retry_block::delay::Fixed;
+//! use retry_block::DelayAccounting;
+//! use std::time::Duration;
+//!
+//! let registry = prometheus::Registry::new();
+//! let histogram = retry_block::prometheus::register(&registry).unwrap();
+//!
+//! let delays = Fixed::exact(Duration::from_millis(1)).take(2);
+//! let delays = histogram.observe("fetch_config", delays);
+//! let delays = DelayAccounting::named("fetch_config").track(delays);
+//! let _ = retry_block::retry_fn(delays, || Err::<(), _>("unavailable"));
+//!
+//! let text = retry_block::prometheus::encode(&registry).unwrap();
+//! assert!(text.contains(r#"retry_attempts_total{operation="fetch_config"} 3"#));
+//! assert!(text.contains(r#"retry_delay_seconds_count{operation="fetch_config"} 2"#));
+//! ```
+
+use crate::DelayAccounting;
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{
+    CounterVec, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A collector of the stats of all the operations accounted with `DelayAccounting::named`,
+/// labelled by operation
+///
+/// Exposes `retry_calls_total`, `retry_attempts_total`, `retry_sleep_seconds_total` and
+/// `retry_max_sleep_seconds`.
+pub struct AccountingCollector {
+    calls: IntCounterVec,
+    attempts: IntCounterVec,
+    sleep: CounterVec,
+    max_sleep: GaugeVec,
+    scrape: Mutex<()>,
+}
+
+impl AccountingCollector {
+    /// Create a collector, to be registered with a registry
+    pub fn new() -> prometheus::Result<Self> {
+        let labels = &["operation"];
+        Ok(Self {
+            calls: IntCounterVec::new(Opts::new("retry_calls_total", "Retried calls"), labels)?,
+            attempts: IntCounterVec::new(
+                Opts::new("retry_attempts_total", "Attempts of retried calls"),
+                labels,
+            )?,
+            sleep: CounterVec::new(
+                Opts::new("retry_sleep_seconds_total", "Time slept between attempts"),
+                labels,
+            )?,
+            max_sleep: GaugeVec::new(
+                Opts::new("retry_max_sleep_seconds", "Longest sleep between attempts"),
+                labels,
+            )?,
+            scrape: Mutex::new(()),
+        })
+    }
+}
+
+impl Collector for AccountingCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        let mut desc = self.calls.desc();
+        desc.extend(self.attempts.desc());
+        desc.extend(self.sleep.desc());
+        desc.extend(self.max_sleep.desc());
+        desc
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        // the metrics are rebuilt from the accounting, one scrape at a time
+        let _scrape = self.scrape.lock().unwrap_or_else(|e| e.into_inner());
+        self.calls.reset();
+        self.attempts.reset();
+        self.sleep.reset();
+        self.max_sleep.reset();
+        for (operation, stats) in DelayAccounting::all_named() {
+            let labels = &[operation];
+            self.calls.with_label_values(labels).inc_by(stats.calls);
+            self.attempts
+                .with_label_values(labels)
+                .inc_by(stats.attempts);
+            self.sleep
+                .with_label_values(labels)
+                .inc_by(stats.total_sleep.as_secs_f64());
+            self.max_sleep
+                .with_label_values(labels)
+                .set(stats.max_sleep.as_secs_f64());
+        }
+        let mut families = self.calls.collect();
+        families.extend(self.attempts.collect());
+        families.extend(self.sleep.collect());
+        families.extend(self.max_sleep.collect());
+        families
+    }
+}
+
+/// A histogram of the delays taken between attempts, labelled by operation, exposed as
+/// `retry_delay_seconds`
+///
+/// Clones observe into the same histogram.
+#[derive(Clone)]
+pub struct DelayHistogram {
+    delays: HistogramVec,
+}
+
+impl DelayHistogram {
+    /// Create a histogram with buckets from 1ms to about 4 minutes, to be registered with a
+    /// registry
+    pub fn new() -> prometheus::Result<Self> {
+        let opts = HistogramOpts::new("retry_delay_seconds", "Delays between attempts")
+            .buckets(prometheus::exponential_buckets(0.001, 4.0, 10)?);
+        Ok(Self {
+            delays: HistogramVec::new(opts, &["operation"])?,
+        })
+    }
+
+    /// Observe the given delays of the given operation as they are taken
+    pub fn observe<D>(&self, operation: &str, durations: D) -> impl Iterator<Item = Duration>
+    where
+        D: IntoIterator<Item = Duration>,
+    {
+        let histogram = self.delays.with_label_values(&[operation]);
+        durations
+            .into_iter()
+            .inspect(move |delay| histogram.observe(delay.as_secs_f64()))
+    }
+}
+
+impl Collector for DelayHistogram {
+    fn desc(&self) -> Vec<&Desc> {
+        self.delays.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.delays.collect()
+    }
+}
+
+/// Register an `AccountingCollector` and a `DelayHistogram` with the given registry, returning the
+/// histogram to observe delays with
+pub fn register(registry: &Registry) -> prometheus::Result<DelayHistogram> {
+    registry.register(Box::new(AccountingCollector::new()?))?;
+    let histogram = DelayHistogram::new()?;
+    registry.register(Box::new(histogram.clone()))?;
+    Ok(histogram)
+}
+
+/// Encode all the metrics of the given registry in the Prometheus text format, e.g. to serve them
+/// on a `/metrics` endpoint
+pub fn encode(registry: &Registry) -> prometheus::Result<String> {
+    TextEncoder::new().encode_to_string(&registry.gather())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode, AccountingCollector};
+    use crate::delay::NoDelay;
+    use crate::DelayAccounting;
+
+    #[test]
+    fn scraped_twice() {
+        let registry = prometheus::Registry::new();
+        registry
+            .register(Box::new(AccountingCollector::new().unwrap()))
+            .unwrap();
+        let accounting = DelayAccounting::named("scraped_twice");
+        let _ = crate::retry_fn(accounting.track(NoDelay.take(1)), || Err::<(), _>(()));
+        assert!(encode(&registry)
+            .unwrap()
+            .contains(r#"retry_calls_total{operation="scraped_twice"} 1"#));
+        // the counters follow the accounting instead of adding it up again
+        let _ = crate::retry_fn(accounting.track(NoDelay.take(1)), || Err::<(), _>(()));
+        let text = encode(&registry).unwrap();
+        assert!(text.contains(r#"retry_calls_total{operation="scraped_twice"} 2"#));
+        assert!(text.contains(r#"retry_attempts_total{operation="scraped_twice"} 4"#));
+    }
+}
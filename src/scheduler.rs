@@ -0,0 +1,209 @@
+use crate::AsyncSleeper;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+/// A hashed timer wheel multiplexing the sleeps of many concurrent retries onto a single timer
+///
+/// Each `RetryScheduler::sleep` registers a slot entry instead of its own tokio timer, and a single
+/// background task advances the wheel every `resolution`, waking the sleeps that are due. Sleeps
+/// are counted from the last advance of the wheel and rounded up to the resolution, so that they
+/// never end early, and at most one resolution late. Clones share the same wheel, and it can be used wherever an
+/// `AsyncSleeper` is accepted, e.g. with `async_retry_fn_with_sleeper` or
+/// `RetryHandle::with_sleeper`.
+///
+/// ```
+/// use retry_block::future::async_retry_fn_with_sleeper;
+/// use retry_block::delay::Fixed;
+/// use retry_block::RetryScheduler;
+/// use std::time::Duration;
+///
+/// #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// async fn main() {
+///     let scheduler = RetryScheduler::new(Duration::from_millis(10), 512);
+///     let retries = (0..1000).map(|_| {
+///         async_retry_fn_with_sleeper(
+///             scheduler.clone(),
+///             Fixed::exact(Duration::from_millis(20)).take(2),
+///             || async { Err::<(), _>(()) },
+///         )
+///     });
+///     for retry in retries {
+///         assert_eq!(retry.await, Err(()));
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryScheduler {
+    wheel: Arc<Mutex<Wheel>>,
+    resolution: Duration,
+}
+
+#[derive(Debug)]
+struct Wheel {
+    current: usize,
+    last_tick: Instant,
+    slots: Vec<Vec<Entry>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    rounds: usize,
+    wake: oneshot::Sender<()>,
+}
+
+impl Wheel {
+    fn tick(&mut self) {
+        self.current = (self.current + 1) % self.slots.len();
+        self.last_tick = Instant::now();
+        let slot = std::mem::take(&mut self.slots[self.current]);
+        // the entries of cancelled sleeps are dropped once their slot is reached
+        for mut entry in slot.into_iter().filter(|entry| !entry.wake.is_closed()) {
+            if entry.rounds == 0 {
+                let _ = entry.wake.send(());
+            } else {
+                entry.rounds -= 1;
+                self.slots[self.current].push(entry);
+            }
+        }
+    }
+}
+
+impl RetryScheduler {
+    /// Create a timer wheel of the given number of slots, advancing every `resolution`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resolution is zero, if there are no slots, or if called outside of a tokio
+    /// runtime.
+    pub fn new(resolution: Duration, slots: usize) -> Self {
+        assert!(!resolution.is_zero(), "the resolution must not be zero");
+        assert!(slots > 0, "the wheel must have at least one slot");
+        let wheel = Arc::new(Mutex::new(Wheel {
+            current: 0,
+            last_tick: Instant::now(),
+            slots: (0..slots).map(|_| Vec::new()).collect(),
+        }));
+        tokio::spawn(drive(Arc::downgrade(&wheel), resolution));
+        Self { wheel, resolution }
+    }
+
+    /// The number of sleeps currently registered, not counting those cancelled
+    pub fn pending(&self) -> usize {
+        let wheel = self.wheel.lock().unwrap_or_else(|e| e.into_inner());
+        wheel
+            .slots
+            .iter()
+            .flatten()
+            .filter(|entry| !entry.wake.is_closed())
+            .count()
+    }
+}
+
+/// Advance the wheel until the scheduler is dropped
+async fn drive(wheel: Weak<Mutex<Wheel>>, resolution: Duration) {
+    let mut interval = tokio::time::interval(resolution);
+    // the first tick completes immediately
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let Some(wheel) = wheel.upgrade() else { return };
+        wheel.lock().unwrap_or_else(|e| e.into_inner()).tick();
+    }
+}
+
+impl AsyncSleeper for RetryScheduler {
+    type Sleep = SchedulerSleep;
+
+    fn sleep(&self, duration: Duration) -> SchedulerSleep {
+        if duration.is_zero() {
+            return SchedulerSleep { woken: None };
+        }
+        let (wake, woken) = oneshot::channel();
+        let mut wheel = self.wheel.lock().unwrap_or_else(|e| e.into_inner());
+        // counted from the last tick rather than from now, which may be up to a resolution later
+        let since_tick = wheel.last_tick.elapsed();
+        let ticks = (since_tick + duration)
+            .as_nanos()
+            .div_ceil(self.resolution.as_nanos());
+        let ticks = usize::try_from(ticks).unwrap_or(usize::MAX);
+        let len = wheel.slots.len();
+        let slot = (wheel.current + ticks % len) % len;
+        wheel.slots[slot].push(Entry {
+            rounds: (ticks - 1) / len,
+            wake,
+        });
+        SchedulerSleep { woken: Some(woken) }
+    }
+}
+
+/// The future returned by `RetryScheduler::sleep`
+#[derive(Debug)]
+pub struct SchedulerSleep {
+    woken: Option<oneshot::Receiver<()>>,
+}
+
+impl Future for SchedulerSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match &mut self.woken {
+            // also done if the scheduler was dropped, rather than sleeping forever
+            Some(woken) => Pin::new(woken).poll(cx).map(|_| ()),
+            None => Poll::Ready(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AsyncSleeper, RetryScheduler};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn rounded_up_sleeps() {
+        let scheduler = RetryScheduler::new(Duration::from_millis(10), 4);
+        let start = Instant::now();
+        let short = scheduler.sleep(Duration::from_millis(25));
+        // longer than a whole turn of the wheel
+        let long = scheduler.sleep(Duration::from_millis(65));
+        assert_eq!(scheduler.pending(), 2);
+        short.await;
+        assert_eq!(start.elapsed(), Duration::from_millis(30));
+        long.await;
+        assert_eq!(start.elapsed(), Duration::from_millis(70));
+        assert_eq!(scheduler.pending(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn never_early() {
+        let scheduler = RetryScheduler::new(Duration::from_millis(10), 4);
+        let start = Instant::now();
+        // registered between two ticks of the wheel
+        tokio::time::sleep(Duration::from_millis(15)).await;
+        scheduler.sleep(Duration::from_millis(10)).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_sleeps() {
+        let scheduler = RetryScheduler::new(Duration::from_millis(10), 4);
+        let kept = scheduler.sleep(Duration::from_millis(20));
+        drop(scheduler.sleep(Duration::from_millis(20)));
+        assert_eq!(scheduler.pending(), 1);
+        kept.await;
+        assert_eq!(scheduler.pending(), 0);
+        assert!(scheduler
+            .wheel
+            .lock()
+            .unwrap()
+            .slots
+            .iter()
+            .all(Vec::is_empty));
+    }
+}
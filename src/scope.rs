@@ -0,0 +1,237 @@
+use crate::delay::{Clock, Faultable, SystemClock};
+use crate::{Accounted, DelayAccounting, OperationResult, RetryState, Step};
+use std::time::{Duration, Instant};
+
+/// What a `RetryScope` reports when dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScopeReport {
+    /// The number of attempts made, including the first one
+    pub attempts: usize,
+    /// The time elapsed since the creation of the scope
+    pub elapsed: Duration,
+}
+
+/// A guard counting and delaying the attempts of a hand-written retry loop
+///
+/// For loops that cannot be expressed as a closure given to `retry_fn` or `retry!`, each failed
+/// attempt is handed to `RetryScope::failed`, which sleeps for the next delay, or returns the error
+/// once the delays are exhausted. The attempts made and the time elapsed are reported when the
+/// scope is dropped, as measured by a `Clock`, `SystemClock` unless replaced with
+/// `RetryScope::with_clock` (e.g. with `time::TokioTime` to follow the paused time of tokio in
+/// tests).
+///
+/// The delays are followed by a `RetryState`, as with the `retry!` macro: zero delays are not
+/// slept, and the global fault injector applies. A scope created with `RetryScope::named`
+/// accounts its delays in `DelayAccounting::named`, as the named arms of the macros do.
+///
+/// ```
+/// # use retry_block::{RetryScope, ScopeReport};
+/// # use retry_block::delay::NoDelay;
+/// fn fetch() -> Result<u32, &'static str> {
+///     let mut answers = vec![Ok(42), Err("try again")];
+///     let mut report = None;
+///     let mut scope = RetryScope::new(NoDelay.take(3)).with_report(|r| report = Some(r));
+///     let value = loop {
+///         match answers.pop().unwrap() {
+///             Ok(value) => break value,
+///             Err(e) => scope.failed(e)?,
+///         }
+///     };
+///     drop(scope);
+///     assert_eq!(report.unwrap().attempts, 2);
+///     Ok(value)
+/// }
+/// assert_eq!(fetch(), Ok(42));
+/// ```
+pub struct RetryScope<'a, I: Iterator<Item = Duration>, C: Clock = SystemClock> {
+    state: RetryState<Accounted<Faultable<I>>>,
+    gave_up: bool,
+    clock: C,
+    start: Instant,
+    report: Option<Box<dyn FnOnce(ScopeReport) + 'a>>,
+}
+
+impl<'a, I> RetryScope<'a, I>
+where
+    I: Iterator<Item = Duration>,
+{
+    /// Start the first attempt of a retry loop following the given delays
+    pub fn new<D>(durations: D) -> Self
+    where
+        D: IntoIterator<Item = Duration, IntoIter = I>,
+    {
+        Self::with_clock(durations, SystemClock)
+    }
+
+    /// Start the first attempt of a retry loop following the given delays, accounted in
+    /// `DelayAccounting::named` for the given operation
+    pub fn named<D>(operation: &'static str, durations: D) -> Self
+    where
+        D: IntoIterator<Item = Duration, IntoIter = I>,
+    {
+        Self::accounted(DelayAccounting::named(operation), durations, SystemClock)
+    }
+}
+
+impl<'a, I, C> RetryScope<'a, I, C>
+where
+    I: Iterator<Item = Duration>,
+    C: Clock,
+{
+    /// Start the first attempt of a retry loop following the given delays, measuring the time
+    /// elapsed with the given clock
+    pub fn with_clock<D>(durations: D, clock: C) -> Self
+    where
+        D: IntoIterator<Item = Duration, IntoIter = I>,
+    {
+        Self::accounted(DelayAccounting::new(), durations, clock)
+    }
+
+    fn accounted<D>(accounting: DelayAccounting, durations: D, clock: C) -> Self
+    where
+        D: IntoIterator<Item = Duration, IntoIter = I>,
+    {
+        let durations = accounting.track(crate::delay::__faulty(durations.into_iter()));
+        Self {
+            state: RetryState::new(durations),
+            gave_up: false,
+            start: clock.now(),
+            clock,
+            report: None,
+        }
+    }
+
+    /// Call the given function with a `ScopeReport` when the scope is dropped
+    pub fn with_report(mut self, report: impl FnOnce(ScopeReport) + 'a) -> Self {
+        self.report = Some(Box::new(report));
+        self
+    }
+
+    /// Record the failure of the current attempt, and sleep for the next delay before the next
+    /// one, or return the given error if there are no delays left
+    pub fn failed<E>(&mut self, error: E) -> Result<(), E> {
+        let duration = self.next_delay(error)?;
+        if !duration.is_zero() {
+            crate::sleep::thread_sleep(duration);
+        }
+        Ok(())
+    }
+
+    /// Record the failure of the current attempt, and sleep asynchronously for the next delay
+    /// before the next one, or return the given error if there are no delays left
+    #[cfg(feature = "tokio")]
+    pub async fn async_failed<E>(&mut self, error: E) -> Result<(), E> {
+        let duration = self.next_delay(error)?;
+        if !duration.is_zero() {
+            tokio::time::sleep(duration).await;
+        }
+        Ok(())
+    }
+
+    /// Account for the failure of the current attempt, and return the delay before the next one
+    fn next_delay<E>(&mut self, error: E) -> Result<Duration, E> {
+        match self.state.next_step(OperationResult::<(), E>::Retry(error)) {
+            Step::Attempt => Ok(Duration::ZERO),
+            Step::Sleep(duration) => Ok(duration),
+            Step::Done(res) => {
+                self.gave_up = true;
+                res.map(|()| Duration::ZERO)
+            }
+        }
+    }
+
+    /// The number of the current attempt, counted from 1
+    pub fn attempts(&self) -> usize {
+        // the state accounts for the failed attempts, past the last one once given up
+        self.state.attempts() + usize::from(!self.gave_up)
+    }
+
+    /// The time elapsed since the creation of the scope
+    pub fn elapsed(&self) -> Duration {
+        self.clock.now().saturating_duration_since(self.start)
+    }
+}
+
+impl<I: Iterator<Item = Duration>, C: Clock> Drop for RetryScope<'_, I, C> {
+    fn drop(&mut self) {
+        let report = ScopeReport {
+            attempts: self.attempts(),
+            elapsed: self.clock.now().saturating_duration_since(self.start),
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            attempts = report.attempts,
+            elapsed_ms = report.elapsed.as_millis() as u64,
+            "retry scope ended"
+        );
+        if let Some(callback) = self.report.take() {
+            callback(report);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delay::NoDelay;
+    use crate::RetryScope;
+
+    #[test]
+    fn exhausted_scope() {
+        let mut attempts = 0;
+        {
+            let mut scope = RetryScope::new(NoDelay.take(2)).with_report(|r| attempts = r.attempts);
+            assert_eq!(scope.failed("a"), Ok(()));
+            assert_eq!(scope.failed("b"), Ok(()));
+            assert_eq!(scope.failed("c"), Err("c"));
+        }
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn named_scope() {
+        use crate::delay::Fixed;
+        use crate::DelayAccounting;
+        use std::time::Duration;
+
+        let mut scope = RetryScope::named(
+            "named_scope",
+            Fixed::exact(Duration::from_millis(1)).take(1),
+        );
+        assert_eq!(scope.failed("a"), Ok(()));
+        assert_eq!(scope.attempts(), 2);
+        assert_eq!(scope.failed("b"), Err("b"));
+        assert_eq!(scope.attempts(), 2);
+        drop(scope);
+
+        let stats = DelayAccounting::named("named_scope").stats();
+        assert_eq!(stats.calls, 1);
+        assert_eq!(stats.attempts, 2);
+        assert_eq!(stats.total_sleep, Duration::from_millis(1));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn paused_scope() {
+        use crate::delay::Fixed;
+        use crate::time::TokioTime;
+        use crate::ScopeReport;
+        use std::time::Duration;
+
+        let mut report = None;
+        {
+            let delays = Fixed::exact(Duration::from_secs(5)).take(2);
+            let mut scope =
+                RetryScope::with_clock(delays, TokioTime).with_report(|r| report = Some(r));
+            assert_eq!(scope.async_failed("a").await, Ok(()));
+            assert_eq!(scope.elapsed(), Duration::from_secs(5));
+            assert_eq!(scope.async_failed("b").await, Ok(()));
+        }
+        assert_eq!(
+            report,
+            Some(ScopeReport {
+                attempts: 3,
+                elapsed: Duration::from_secs(10)
+            })
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use crate::delay::BoxedDelay;
+use crate::OperationResult;
+use std::time::Duration;
+
+/// A hook switching the delays of a retry after a given retryable error
+///
+/// Returning a schedule replaces whatever remains of the current one, starting it over from its
+/// beginning; returning `None` keeps the current one going. It is implemented for closures.
+pub trait ScheduleSelector<E> {
+    /// The schedule to switch to after the given error, if any
+    fn select(&mut self, error: &E) -> Option<BoxedDelay>;
+}
+
+impl<E, F> ScheduleSelector<E> for F
+where
+    F: FnMut(&E) -> Option<BoxedDelay>,
+{
+    fn select(&mut self, error: &E) -> Option<BoxedDelay> {
+        self(error)
+    }
+}
+
+/// The delays of a retry, as given or as last selected
+struct Selected<I> {
+    initial: I,
+    selected: Option<BoxedDelay>,
+}
+
+impl<I> Selected<I>
+where
+    I: Iterator<Item = Duration>,
+{
+    fn next_after<E>(
+        &mut self,
+        selector: &mut impl ScheduleSelector<E>,
+        error: &E,
+    ) -> Option<Duration> {
+        if let Some(schedule) = selector.select(error) {
+            self.selected = Some(schedule);
+        }
+        match &mut self.selected {
+            Some(schedule) => schedule.next(),
+            None => self.initial.next(),
+        }
+    }
+}
+
+/// Retry the given operation until it succeeds, or until the current delays end, letting the
+/// given selector switch to other delays after specific errors.
+///
+/// This lets e.g. a quota error turn fast exponential backoff into slow polling. A selector
+/// returning a schedule every time an error occurs restarts it each time, so that a recurring
+/// error would be retried forever; taking the schedule out of an `Option` switches only once.
+///
+/// ```
+/// # use retry_block::retry_fn_selecting;
+/// # use retry_block::delay::{Fixed, IntoBoxed, NoDelay};
+/// # use std::time::Duration;
+/// let mut polling = Some(Fixed::exact(Duration::from_millis(1)).take(2).into_boxed());
+/// let mut attempts = 0;
+/// let value = retry_fn_selecting(
+///     NoDelay.take(10),
+///     |e: &&str| if *e == "quota exceeded" { polling.take() } else { None },
+///     || {
+///         attempts += 1;
+///         Err::<(), _>(if attempts < 3 { "unavailable" } else { "quota exceeded" })
+///     },
+/// );
+/// assert_eq!(value, Err("quota exceeded"));
+/// // two fast retries, then two polling ones
+/// assert_eq!(attempts, 5);
+/// ```
+pub fn retry_fn_selecting<D, S, O, OR, R, E>(
+    durations: D,
+    mut selector: S,
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    S: ScheduleSelector<E>,
+    O: FnMut() -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut delays = Selected {
+        initial: crate::delay::__faulty(durations.into_iter()),
+        selected: None,
+    };
+    crate::retry_loop(
+        |e| delays.next_after(&mut selector, &e).ok_or(e),
+        crate::sleep::thread_sleep,
+        || operation().into(),
+    )
+}
+
+/// Retry the given asynchronous operation until it succeeds, or until the current delays end,
+/// letting the given selector switch to other delays after specific errors.
+///
+/// See `retry_fn_selecting`.
+///
+/// ```
+/// use retry_block::async_retry_fn_selecting;
+/// use retry_block::delay::{IntoBoxed, NoDelay};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut polling = Some(NoDelay.take(1).into_boxed());
+///     let selector = |_: &&str| polling.take();
+///     let value = async_retry_fn_selecting(NoDelay.take(3), selector, || async {
+///         Err::<(), _>("quota exceeded")
+///     })
+///     .await;
+///     assert_eq!(value, Err("quota exceeded"));
+/// }
+/// ```
+#[cfg(all(feature = "future", feature = "tokio"))]
+pub async fn async_retry_fn_selecting<D, S, O, F, OR, R, E>(
+    durations: D,
+    mut selector: S,
+    mut operation: O,
+) -> Result<R, E>
+where
+    D: IntoIterator<Item = Duration>,
+    S: ScheduleSelector<E>,
+    O: FnMut() -> F,
+    F: std::future::Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    let mut delays = Selected {
+        initial: crate::delay::__faulty(durations.into_iter()),
+        selected: None,
+    };
+    crate::future::async_retry_loop(
+        |e| delays.next_after(&mut selector, &e).ok_or(e),
+        || {
+            let attempt = operation();
+            async { attempt.await.into() }
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::retry_fn_selecting;
+    use crate::delay::{IntoBoxed, NoDelay};
+    use crate::OperationResult;
+
+    #[test]
+    fn restarted_schedule() {
+        // a schedule selected on every error restarts each time, unlike the initial one
+        let mut attempts = 0;
+        let value = retry_fn_selecting(
+            NoDelay.take(1),
+            |e: &u32| (*e < 5).then(|| NoDelay.take(1).into_boxed()),
+            || {
+                attempts += 1;
+                if attempts < 8 {
+                    OperationResult::Retry(attempts)
+                } else {
+                    OperationResult::<(), _>::Err(attempts)
+                }
+            },
+        );
+        assert_eq!(value, Err(5));
+    }
+}
@@ -0,0 +1,255 @@
+//! Retries that stop waiting for their next attempt when the process is asked to terminate
+//!
+//! This lets command line tools stuck in a long delay react to Ctrl-C right away.
+//!
+//! Listening for SIGINT (Ctrl-C) or SIGTERM through tokio takes over their handling for the rest
+//! of the life of the process: from then on, they no longer terminate it by themselves, and
+//! shutting down is up to the application, e.g. once its retries were cancelled.
+
+use crate::future::{async_retry_fn_cancellable, CancelError};
+use crate::{AsyncSleeper, OperationResult, TokioSleeper};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::futures::OwnedNotified;
+use tokio::sync::Notify;
+
+/// The signals asking the process to terminate, listened to from the creation of this value
+struct Shutdown {
+    #[cfg(unix)]
+    terminate: Option<tokio::signal::unix::Signal>,
+}
+
+impl Shutdown {
+    fn listen() -> Self {
+        Self {
+            #[cfg(unix)]
+            terminate: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                .ok(),
+        }
+    }
+
+    async fn recv(self) {
+        #[cfg(unix)]
+        if let Some(mut terminate) = self.terminate {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Wait until the process receives SIGINT (Ctrl-C) or, on Unix, SIGTERM
+///
+/// This takes over the handling of these signals for the rest of the life of the process, see the
+/// module documentation.
+pub async fn shutdown() {
+    Shutdown::listen().recv().await
+}
+
+/// Retry the given operation until it succeeds, until the given `Duration` iterator ends, or until
+/// the process receives SIGINT or SIGTERM while waiting for the next attempt, in which case
+/// `CancelError::Cancelled` is returned.
+///
+/// This takes over the handling of these signals for the rest of the life of the process, see the
+/// module documentation.
+///
+/// ```no_run
+/// use retry_block::signal::async_retry_fn_until_signal;
+/// use retry_block::delay::Exponential;
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let result = async_retry_fn_until_signal(Exponential::new(Duration::from_secs(1)), || async {
+///         Err::<(), _>("server unavailable")
+///     })
+///     .await;
+///     if let Err(e) = result {
+///         eprintln!("{}", e);
+///     }
+/// }
+/// ```
+pub async fn async_retry_fn_until_signal<D, O, F, OR, R, E>(
+    durations: D,
+    operation: O,
+) -> Result<R, CancelError<E>>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    async_retry_fn_cancellable(durations, shutdown(), operation).await
+}
+
+/// Stops listening for signals once the last clone of a `SignalSleeper` is dropped
+#[derive(Debug)]
+struct Listener(tokio::task::JoinHandle<()>);
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// A sleeper whose sleeps end early once the process receives SIGINT or SIGTERM
+///
+/// Once a signal is received, every sleep ends right away and `SignalSleeper::is_shutting_down`
+/// returns `true`, so that the operations of the retries sleeping with it (e.g. with
+/// `async_retry_with!` or `persist::RetryHandle::with_sleeper`) can give up on their next attempt.
+///
+/// The signals are listened to from the creation of the sleeper, which takes over their handling
+/// for the rest of the life of the process, see the module documentation.
+///
+/// ```no_run
+/// use retry_block::async_retry_with;
+/// use retry_block::delay::Exponential;
+/// use retry_block::signal::SignalSleeper;
+/// use retry_block::{AsyncSleeper, OperationResult};
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let sleeper = SignalSleeper::new();
+///     let result = async_retry_with!(
+///         |duration| sleeper.sleep(duration),
+///         Exponential::new(Duration::from_secs(1)),
+///         {
+///             if sleeper.is_shutting_down() {
+///                 OperationResult::<(), _>::Err("shutting down")
+///             } else {
+///                 OperationResult::Retry("server unavailable")
+///             }
+///         }
+///     );
+///     assert_eq!(result, Err("shutting down"));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SignalSleeper<S = TokioSleeper> {
+    sleeper: S,
+    received: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+    _listener: Arc<Listener>,
+}
+
+impl SignalSleeper {
+    /// Sleep with `tokio::time::sleep` until a signal is received
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn new() -> Self {
+        Self::wrap(TokioSleeper)
+    }
+}
+
+impl Default for SignalSleeper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> SignalSleeper<S> {
+    /// Sleep with the given sleeper until a signal is received
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn wrap(sleeper: S) -> Self {
+        let received = Arc::new(AtomicBool::new(false));
+        let notify = Arc::new(Notify::new());
+        let shutdown = Shutdown::listen();
+        let listener = tokio::spawn({
+            let received = received.clone();
+            let notify = notify.clone();
+            async move {
+                shutdown.recv().await;
+                received.store(true, Ordering::SeqCst);
+                notify.notify_waiters();
+            }
+        });
+        Self {
+            sleeper,
+            received,
+            notify,
+            _listener: Arc::new(Listener(listener)),
+        }
+    }
+
+    /// Whether the process received SIGINT or SIGTERM since the creation of this sleeper
+    pub fn is_shutting_down(&self) -> bool {
+        self.received.load(Ordering::SeqCst)
+    }
+}
+
+impl<S: AsyncSleeper> AsyncSleeper for SignalSleeper<S> {
+    type Sleep = SignalSleep<S::Sleep>;
+
+    fn sleep(&self, duration: Duration) -> SignalSleep<S::Sleep> {
+        SignalSleep {
+            sleep: Box::pin(self.sleeper.sleep(duration)),
+            notified: Box::pin(self.notify.clone().notified_owned()),
+            received: self.received.clone(),
+        }
+    }
+}
+
+/// The future returned by `SignalSleeper::sleep`
+pub struct SignalSleep<F> {
+    sleep: Pin<Box<F>>,
+    notified: Pin<Box<OwnedNotified>>,
+    received: Arc<AtomicBool>,
+}
+
+impl<F: Future<Output = ()>> Future for SignalSleep<F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.received.load(Ordering::SeqCst) || self.notified.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(());
+        }
+        self.sleep.as_mut().poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SignalSleeper;
+    use crate::AsyncSleeper;
+    use std::time::Duration;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn raised_signal() {
+        let sleeper = SignalSleeper::new();
+        assert!(!sleeper.is_shutting_down());
+        let sleep = sleeper.sleep(Duration::from_secs(3600));
+
+        // the sleeper listens to SIGTERM already, which no longer terminates the process
+        let status = std::process::Command::new("kill")
+            .args(["-TERM", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        tokio::time::timeout(Duration::from_secs(10), sleep)
+            .await
+            .expect("the sleep did not end on SIGTERM");
+        assert!(sleeper.is_shutting_down());
+        // later sleeps end right away
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            sleeper.sleep(Duration::from_secs(3600)),
+        )
+        .await
+        .unwrap();
+    }
+}
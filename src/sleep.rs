@@ -0,0 +1,115 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// A timer to wait between asynchronous attempts with
+///
+/// Implemented by `TokioSleeper` (need `tokio` feature), by the sources of `time` (e.g.
+/// `time::WasmTime` for JavaScript timers, need `wasm` feature) and by any `Fn(Duration) -> impl
+/// Future<Output = ()>`, so that executors other than tokio can plug their own timer: no sleeper
+/// is provided for async-std, whose `async_std::task::sleep` function already is one.
+///
+/// ```
+/// # use retry_block::AsyncSleeper;
+/// # use std::time::Duration;
+/// async fn sleep(duration: Duration) {
+///     // hand the delay over to the timer of your runtime of choice
+/// #   let _ = duration;
+/// }
+///
+/// fn assert_sleeper(_: impl AsyncSleeper) {}
+/// assert_sleeper(sleep);
+/// ```
+pub trait AsyncSleeper {
+    /// The future waiting for a delay
+    type Sleep: Future<Output = ()>;
+
+    /// Wait for the given delay
+    fn sleep(&self, duration: Duration) -> Self::Sleep;
+}
+
+impl<T, F> AsyncSleeper for T
+where
+    T: Fn(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    type Sleep = F;
+
+    fn sleep(&self, duration: Duration) -> F {
+        self(duration)
+    }
+}
+
+/// Yield once to the runtime of the current task, whatever it is
+///
+/// Used by the `yield_now = true` argument of `async_retry_with!` and `async_retry!` in place of
+/// zero delays, so that a retry of a contended resource lets the other tasks of its worker thread,
+/// possibly the holder of that resource, run between attempts.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// The future returned by `yield_now`
+#[derive(Debug)]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        // be polled again only once the other ready tasks had their turn
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Poll the given future to completion on the current thread
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    struct Unpark(std::thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(Unpark(std::thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+/// Sleep the current thread for the given delay, skipping the syscall for zero delays
+pub(crate) fn thread_sleep(duration: Duration) {
+    if !duration.is_zero() {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Sleep with `tokio::time::sleep`
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioSleeper;
+
+#[cfg(feature = "tokio")]
+impl AsyncSleeper for TokioSleeper {
+    type Sleep = tokio::time::Sleep;
+
+    fn sleep(&self, duration: Duration) -> tokio::time::Sleep {
+        tokio::time::sleep(duration)
+    }
+}
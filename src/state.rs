@@ -0,0 +1,115 @@
+//! A poll-based retry state machine
+
+use crate::OperationResult;
+use std::time::Duration;
+
+/// What to do next after an attempt, as returned by `RetryState::next_step`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step<R, E> {
+    /// Attempt the operation again right away
+    Attempt,
+    /// Attempt the operation again once the given delay has elapsed
+    Sleep(Duration),
+    /// Stop retrying with the given outcome
+    Done(Result<R, E>),
+}
+
+/// The state of a retried operation between its attempts, driven by the outcomes of its attempts
+///
+/// `RetryState` follows a delay strategy and accounts for attempts and delays exactly as the
+/// `retry!` macro does, but leaves running attempts and sleeping to the caller, e.g. to retry
+/// from a game loop or an io_uring reactor:
+///
+/// ```
+/// # use retry_block::delay::Fixed;
+/// # use retry_block::{RetryState, Step};
+/// # use std::time::Duration;
+/// let mut state = RetryState::new(Fixed::exact(Duration::from_millis(10)).take(2));
+/// let mut outcomes = vec![Err("busy"), Err("busy"), Ok(42)].into_iter();
+/// let value = loop {
+///     match state.next_step(outcomes.next().unwrap()) {
+///         Step::Attempt => {}
+///         // schedule the next attempt on the reactor instead of blocking
+///         Step::Sleep(delay) => assert_eq!(delay, Duration::from_millis(10)),
+///         Step::Done(value) => break value,
+///     }
+/// };
+/// assert_eq!(value, Ok(42));
+/// assert_eq!(state.attempts(), 3);
+/// assert_eq!(state.total_delay(), Duration::from_millis(20));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryState<D> {
+    durations: D,
+    attempts: usize,
+    total_delay: Duration,
+}
+
+impl<D> RetryState<D>
+where
+    D: Iterator<Item = Duration>,
+{
+    /// Create the state of an operation retried according to the given delays, before its first
+    /// attempt
+    pub fn new<I>(durations: I) -> Self
+    where
+        I: IntoIterator<Item = Duration, IntoIter = D>,
+    {
+        Self {
+            durations: durations.into_iter(),
+            attempts: 0,
+            total_delay: Duration::ZERO,
+        }
+    }
+
+    /// Account for the outcome of an attempt, and return what to do next
+    ///
+    /// A zero delay yields `Step::Attempt` rather than `Step::Sleep`. Once the delays run out, a
+    /// retryable error yields `Step::Done` with that error.
+    pub fn next_step<R, E>(&mut self, outcome: impl Into<OperationResult<R, E>>) -> Step<R, E> {
+        self.attempts += 1;
+        match outcome.into() {
+            OperationResult::Ok(res) => Step::Done(Ok(res)),
+            OperationResult::Err(e) => Step::Done(Err(e)),
+            OperationResult::Retry(e) => match self.durations.next() {
+                Some(duration) if duration.is_zero() => Step::Attempt,
+                Some(duration) => {
+                    self.total_delay = self.total_delay.saturating_add(duration);
+                    Step::Sleep(duration)
+                }
+                None => Step::Done(Err(e)),
+            },
+        }
+    }
+
+    /// The number of attempts accounted for so far
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// The sum of the delays returned so far
+    pub fn total_delay(&self) -> Duration {
+        self.total_delay
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RetryState, Step};
+    use crate::delay::NoDelay;
+    use crate::OperationResult;
+
+    #[test]
+    fn fatal_and_exhausted() {
+        let mut state = RetryState::new(NoDelay.take(1));
+        assert_eq!(state.next_step(Err::<(), _>(1)), Step::Attempt);
+        assert_eq!(state.next_step(Err::<(), _>(2)), Step::Done(Err(2)));
+
+        let mut state = RetryState::new(NoDelay);
+        assert_eq!(
+            state.next_step(OperationResult::<(), _>::Err(1)),
+            Step::Done(Err(1))
+        );
+        assert_eq!(state.attempts(), 1);
+    }
+}
@@ -0,0 +1,209 @@
+//! Stream adapters retrying the processing of each item
+//!
+//! # Usage
+//!
+//! ```
+//! use retry_block::stream::StreamRetryExt;
+//! use retry_block::delay::Fixed;
+//! use futures_util::StreamExt;
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut failures = 2;
+//!
+//!     let results: Vec<Result<u32, &str>> = futures_util::stream::iter(vec![1, 2, 3])
+//!         .retry_each(Fixed::new(Duration::from_millis(1)).take(1), |n| {
+//!             let res = if n == 2 && failures > 0 {
+//!                 failures -= 1;
+//!                 Err("transient failure")
+//!             } else {
+//!                 Ok(n * 10)
+//!             };
+//!             async move { res }
+//!         })
+//!         .collect()
+//!         .await;
+//!
+//!     assert_eq!(results, vec![Ok(10), Err("transient failure"), Ok(30)]);
+//! }
+//! ```
+
+use crate::future::State;
+use crate::OperationResult;
+use futures_util::Stream;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+/// An extension trait for `Stream`s to retry the processing of each item
+pub trait StreamRetryExt: Stream + Sized {
+    /// Process each item of the stream with the given operation, retrying it with an independent
+    /// copy of the given delays until it succeeds or the delays end, and yield the per-item results
+    fn retry_each<D, O, F, OR, R, E>(
+        self,
+        durations: D,
+        operation: O,
+    ) -> RetryEach<Self, D, O, F, R, E>
+    where
+        D: IntoIterator<Item = Duration> + Clone,
+        O: FnMut(Self::Item) -> F,
+        F: Future<Output = OR>,
+        OR: Into<OperationResult<R, E>>,
+        Self::Item: Clone,
+    {
+        RetryEach {
+            stream: Box::pin(self),
+            durations,
+            operation,
+            current: None,
+            output: PhantomData,
+        }
+    }
+}
+
+impl<St: Stream> StreamRetryExt for St {}
+
+struct Current<I, D, F> {
+    item: I,
+    durations: D,
+    state: State<F, tokio::time::Sleep>,
+}
+
+/// Stream for the `retry_each` method
+pub struct RetryEach<St, D, O, F, R, E>
+where
+    St: Stream,
+    D: IntoIterator,
+{
+    stream: Pin<Box<St>>,
+    durations: D,
+    operation: O,
+    current: Option<Current<St::Item, crate::delay::Faultable<D::IntoIter>, F>>,
+    output: PhantomData<fn() -> Result<R, E>>,
+}
+
+// The inner stream, attempt and sleep futures are boxed, so no field is ever structurally pinned.
+impl<St, D, O, F, R, E> Unpin for RetryEach<St, D, O, F, R, E>
+where
+    St: Stream,
+    D: IntoIterator,
+{
+}
+
+impl<St, D, O, F, OR, R, E> Stream for RetryEach<St, D, O, F, R, E>
+where
+    St: Stream,
+    St::Item: Clone,
+    D: IntoIterator<Item = Duration> + Clone,
+    O: FnMut(St::Item) -> F,
+    F: Future<Output = OR>,
+    OR: Into<OperationResult<R, E>>,
+{
+    type Item = Result<R, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let current = match &mut this.current {
+                Some(current) => current,
+                None => match ready!(this.stream.as_mut().poll_next(cx)) {
+                    Some(item) => this.current.insert(Current {
+                        item,
+                        durations: crate::delay::__faulty(this.durations.clone().into_iter()),
+                        state: State::Ready,
+                    }),
+                    None => return Poll::Ready(None),
+                },
+            };
+            match &mut current.state {
+                State::Ready => {
+                    current.state = State::Attempt(Box::pin((this.operation)(current.item.clone())))
+                }
+                State::Attempt(attempt) => match ready!(attempt.as_mut().poll(cx)).into() {
+                    OperationResult::Ok(res) => {
+                        this.current = None;
+                        return Poll::Ready(Some(Ok(res)));
+                    }
+                    OperationResult::Err(e) => {
+                        this.current = None;
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                    OperationResult::Retry(e) => {
+                        if let Some(duration) = current.durations.next() {
+                            current.state = State::Sleep(Box::pin(tokio::time::sleep(duration)));
+                        } else {
+                            this.current = None;
+                            return Poll::Ready(Some(Err(e)));
+                        }
+                    }
+                },
+                State::Sleep(sleep) => {
+                    ready!(sleep.as_mut().poll(cx));
+                    current.state = State::Ready;
+                }
+                State::Done => unreachable!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StreamRetryExt;
+    use crate::delay::Fixed;
+    use crate::OperationResult;
+    use futures_util::StreamExt;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn independent_delays() {
+        let start = Instant::now();
+        let mut attempts = Vec::new();
+        let results: Vec<Result<u32, u32>> = futures_util::stream::iter(vec![1, 2])
+            .retry_each(Fixed::exact(Duration::from_secs(1)).take(2), |n| {
+                attempts.push((n, start.elapsed().as_secs()));
+                async move { Err(n) }
+            })
+            .collect()
+            .await;
+        assert_eq!(results, vec![Err(1), Err(2)]);
+        // the second item starts its delays over once the first one gave up
+        assert_eq!(
+            attempts,
+            vec![(1, 0), (1, 1), (1, 2), (2, 2), (2, 3), (2, 4)]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fatal_errors() {
+        let mut attempts = 0;
+        let results: Vec<Result<u32, &str>> = futures_util::stream::iter(vec![1, 2, 3])
+            .retry_each(Fixed::exact(Duration::from_secs(1)).take(3), |n| {
+                attempts += 1;
+                async move {
+                    match n {
+                        2 => OperationResult::Err("invalid"),
+                        n => OperationResult::Ok(n),
+                    }
+                }
+            })
+            .collect()
+            .await;
+        // a fatal error is yielded without retrying, and the next items are still processed
+        assert_eq!(results, vec![Ok(1), Err("invalid"), Ok(3)]);
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn empty_stream() {
+        let results: Vec<Result<(), ()>> = futures_util::stream::iter(Vec::<u32>::new())
+            .retry_each(Fixed::exact(Duration::from_secs(1)), |_| async { Ok(()) })
+            .collect()
+            .await;
+        assert!(results.is_empty());
+    }
+}
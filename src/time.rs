@@ -0,0 +1,327 @@
+//! Sources of time, to run time-dependent retries consistently across runtimes and in tests
+//!
+//! A `TimeSource` is both a `delay::Clock` telling the time and an `AsyncSleeper` waiting for a
+//! delay, so that deadlines (e.g. `retry_fn_with_deadline_using`), `delay::MaxElapsed`,
+//! persistent retries (`RetryHandle::with_time_source`) and the `async_retry_with!` macro all
+//! measure and wait with the same time. The following ones are provided:
+//!
+//! - `StdTime`: the system clock, waiting on a thread of its own, independently of any runtime
+//! - `TokioTime`: the clock and timer of tokio, which follow its paused time in tests (need
+//!   `tokio` feature)
+//! - `MockTime`: a manual clock, which sleeps advance instantly
+//! - `WasmTime`: JavaScript timers, on `wasm32` targets (need `wasm` feature)
+//!
+//! Other runtimes can plug their own by implementing both traits.
+//!
+//! ```
+//! # use retry_block::delay::{Fixed, MaxElapsed};
+//! # use retry_block::time::MockTime;
+//! # use std::time::Duration;
+//! let time = MockTime::new();
+//! let mut delays = MaxElapsed::with_clock(
+//!     Fixed::exact(Duration::from_secs(1)),
+//!     Duration::from_secs(60),
+//!     time.clone(),
+//! );
+//! assert_eq!(delays.next(), Some(Duration::from_secs(1)));
+//! time.advance(Duration::from_secs(60));
+//! assert_eq!(delays.next(), None);
+//! ```
+
+use crate::delay::Clock;
+use crate::AsyncSleeper;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant, SystemTime};
+
+/// A source of time, telling the current one and waiting for a delay
+pub trait TimeSource: Clock + AsyncSleeper {}
+
+impl<T> TimeSource for T where T: Clock + AsyncSleeper {}
+
+/// The system clock, waiting for delays on a thread of its own
+///
+/// This works with any executor, at the cost of a thread per pending sleep.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdTime;
+
+impl Clock for StdTime {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl AsyncSleeper for StdTime {
+    type Sleep = StdSleep;
+
+    fn sleep(&self, duration: Duration) -> StdSleep {
+        StdSleep {
+            duration,
+            state: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SleepState {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+/// The future returned by `StdTime::sleep`
+#[derive(Debug)]
+pub struct StdSleep {
+    duration: Duration,
+    state: Option<Arc<Mutex<SleepState>>>,
+}
+
+impl Future for StdSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.duration.is_zero() {
+            return Poll::Ready(());
+        }
+        let duration = self.duration;
+        let state = self.state.get_or_insert_with(|| {
+            let state = Arc::new(Mutex::new(SleepState::default()));
+            let woken = state.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(duration);
+                let mut woken = woken.lock().unwrap_or_else(|e| e.into_inner());
+                woken.done = true;
+                if let Some(waker) = woken.waker.take() {
+                    waker.wake();
+                }
+            });
+            state
+        });
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.done {
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// The clock and timer of tokio, which follow its paused time in tests (need `tokio` feature)
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioTime;
+
+#[cfg(feature = "tokio")]
+impl Clock for TokioTime {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    /// The system time when first called, moved along with the (possibly paused) tokio clock
+    fn system_now(&self) -> SystemTime {
+        static ORIGIN: std::sync::OnceLock<(Instant, SystemTime)> = std::sync::OnceLock::new();
+        let (instant, system) = *ORIGIN.get_or_init(|| (self.now(), SystemTime::now()));
+        let now = self.now();
+        match now.checked_duration_since(instant) {
+            Some(elapsed) => system + elapsed,
+            None => system - instant.duration_since(now),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncSleeper for TokioTime {
+    type Sleep = tokio::time::Sleep;
+
+    fn sleep(&self, duration: Duration) -> tokio::time::Sleep {
+        tokio::time::sleep(duration)
+    }
+}
+
+/// A manual clock, starting at the time of its creation and moving only when advanced
+///
+/// Sleeping advances the clock by the delay and completes immediately, so that time-dependent
+/// retries can be tested without real waits. Clones share the same clock.
+#[derive(Debug, Clone)]
+pub struct MockTime {
+    now: Arc<Mutex<Instant>>,
+    origin: (Instant, SystemTime),
+}
+
+impl MockTime {
+    /// Create a clock stopped at the current time
+    pub fn new() -> Self {
+        let origin = (Instant::now(), SystemTime::now());
+        Self {
+            now: Arc::new(Mutex::new(origin.0)),
+            origin,
+        }
+    }
+
+    /// Move the clock forward by the given duration
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+}
+
+impl Default for MockTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockTime {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn system_now(&self) -> SystemTime {
+        let (instant, system) = self.origin;
+        system + (self.now() - instant)
+    }
+}
+
+impl AsyncSleeper for MockTime {
+    type Sleep = std::future::Ready<()>;
+
+    fn sleep(&self, duration: Duration) -> std::future::Ready<()> {
+        self.advance(duration);
+        std::future::ready(())
+    }
+}
+
+/// JavaScript timers, on `wasm32` targets (need `wasm` feature)
+///
+/// Sleeps are `setTimeout` timers, so they need a JavaScript host, and the wall-clock time is
+/// `Date.now()`. The monotonic time is still `Instant::now`, which the standard library of the
+/// target must support.
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WasmTime;
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+impl Clock for WasmTime {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+impl AsyncSleeper for WasmTime {
+    type Sleep = WasmSleep;
+
+    fn sleep(&self, duration: Duration) -> WasmSleep {
+        WasmSleep {
+            duration,
+            timer: None,
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+mod js {
+    use wasm_bindgen::prelude::wasm_bindgen;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_name = setTimeout)]
+        pub fn set_timeout(handler: &js_sys::Function, timeout: i32) -> i32;
+        #[wasm_bindgen(js_name = clearTimeout)]
+        pub fn clear_timeout(handle: i32);
+    }
+}
+
+/// The future returned by `WasmTime::sleep`, clearing its timer when dropped early
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+pub struct WasmSleep {
+    duration: Duration,
+    timer: Option<(
+        Arc<Mutex<SleepState>>,
+        i32,
+        wasm_bindgen::closure::Closure<dyn FnMut()>,
+    )>,
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+impl Future for WasmSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        use wasm_bindgen::JsCast;
+
+        if self.duration.is_zero() {
+            return Poll::Ready(());
+        }
+        let timeout = self.duration.as_millis().try_into().unwrap_or(i32::MAX);
+        let (state, _, _) = self.timer.get_or_insert_with(|| {
+            let state = Arc::new(Mutex::new(SleepState::default()));
+            let woken = state.clone();
+            let handler = wasm_bindgen::closure::Closure::<dyn FnMut()>::new(move || {
+                let mut woken = woken.lock().unwrap_or_else(|e| e.into_inner());
+                woken.done = true;
+                if let Some(waker) = woken.waker.take() {
+                    waker.wake();
+                }
+            });
+            let handle = js::set_timeout(handler.as_ref().unchecked_ref(), timeout);
+            (state, handle, handler)
+        });
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.done {
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+impl Drop for WasmSleep {
+    fn drop(&mut self) {
+        if let Some((_, handle, _)) = &self.timer {
+            js::clear_timeout(*handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MockTime, StdTime};
+    use crate::delay::Clock;
+    use crate::sleep::block_on;
+    use crate::AsyncSleeper;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn std_sleep() {
+        let start = Instant::now();
+        block_on(StdTime.sleep(Duration::from_millis(10)));
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+
+    #[test]
+    fn mock_sleep() {
+        let time = MockTime::new();
+        let start = time.now();
+        block_on(time.sleep(Duration::from_secs(3600)));
+        assert_eq!(time.now() - start, Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn mock_system_time() {
+        let time = MockTime::new();
+        let start = time.system_now();
+        time.advance(Duration::from_secs(60));
+        assert_eq!(
+            time.system_now().duration_since(start).unwrap(),
+            Duration::from_secs(60)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Classification of `tonic::Status` errors
+//!
+//! ```
+//! use retry_block::RetryableError;
+//! use std::time::Duration;
+//! use tonic::Status;
+//!
+//! assert!(Status::unavailable("overloaded").is_retryable());
+//! assert!(!Status::invalid_argument("no such field").is_retryable());
+//!
+//! let mut status = Status::resource_exhausted("slow down");
+//! status
+//!     .metadata_mut()
+//!     .insert("grpc-retry-pushback-ms", "250".parse().unwrap());
+//! assert_eq!(status.retry_after(), Some(Duration::from_millis(250)));
+//! ```
+
+use crate::RetryableError;
+use std::time::Duration;
+use tonic::{Code, Status};
+
+/// The metadata key through which a server pushes back on the delay of the next retry
+const PUSHBACK_KEY: &str = "grpc-retry-pushback-ms";
+
+/// The pushback of the server, if any: `Some(None)` when told not to retry at all
+fn pushback(status: &Status) -> Option<Option<Duration>> {
+    let value = status.metadata().get(PUSHBACK_KEY)?;
+    // a negative or malformed pushback means that the call should not be retried
+    Some(
+        value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .map(Duration::from_millis),
+    )
+}
+
+impl RetryableError for Status {
+    fn is_retryable(&self) -> bool {
+        let retryable_code = matches!(
+            self.code(),
+            Code::Unavailable | Code::ResourceExhausted | Code::DeadlineExceeded
+        );
+        retryable_code && !matches!(pushback(self), Some(None))
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        pushback(self).flatten()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::RetryableError;
+    use tonic::Status;
+
+    #[test]
+    fn negative_pushback() {
+        let mut status = Status::unavailable("down");
+        status
+            .metadata_mut()
+            .insert("grpc-retry-pushback-ms", "-1".parse().unwrap());
+        assert!(!status.is_retryable());
+        assert_eq!(status.retry_after(), None);
+    }
+}
@@ -0,0 +1,287 @@
+//! `tower` middleware retrying requests with configurable backoff behavior (need `tower` feature)
+//!
+//! # Usage
+//!
+//! ```
+//! use retry_block::tower::RetryLayer;
+//! use retry_block::{RetryConfig, RetryableError};
+//! use tower::{service_fn, ServiceBuilder, ServiceExt};
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//!
+//! #[derive(Debug)]
+//! struct Unavailable;
+//!
+//! impl RetryableError for Unavailable {
+//!     fn is_retryable(&self) -> bool {
+//!         true
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let calls = Arc::new(AtomicUsize::new(0));
+//!     let service = ServiceBuilder::new()
+//!         .layer(RetryLayer::from(RetryConfig {
+//!             count: 3,
+//!             min_backoff: 1,
+//!             max_backoff: 10,
+//!         }))
+//!         .service(service_fn(|request: u32| {
+//!             let calls = calls.clone();
+//!             async move {
+//!                 if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+//!                     Err(Unavailable)
+//!                 } else {
+//!                     Ok(request * 2)
+//!                 }
+//!             }
+//!         }));
+//!
+//!     assert_eq!(service.oneshot(21).await.unwrap(), 42);
+//! }
+//! ```
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
+use crate::RetryConfig;
+use crate::RetryableError;
+use std::time::Duration;
+use tower::retry::{Policy, Retry};
+use tower::Layer;
+
+struct Schedule<D: IntoIterator> {
+    durations: D,
+    current: Option<crate::delay::Faultable<D::IntoIter>>,
+}
+
+impl<D> Schedule<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    fn new(durations: D) -> Self {
+        Self {
+            durations,
+            current: None,
+        }
+    }
+
+    /// Sleep for the next delay, as adjusted by the given function, as long as delays remain
+    fn next_sleep(
+        &mut self,
+        adjust: impl FnOnce(Duration) -> Duration,
+    ) -> Option<tokio::time::Sleep> {
+        let durations = &self.durations;
+        let duration = self
+            .current
+            .get_or_insert_with(|| crate::delay::__faulty(durations.clone().into_iter()))
+            .next()?;
+        Some(tokio::time::sleep(adjust(duration)))
+    }
+}
+
+impl<D> Clone for Schedule<D>
+where
+    D: IntoIterator + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            durations: self.durations.clone(),
+            current: None,
+        }
+    }
+}
+
+/// A `tower::retry::Policy` retrying failed requests whose error is a retryable `RetryableError`,
+/// waiting between attempts according to a cloneable delay iterator
+///
+/// A delay suggested by the error is waited instead of the next one of the iterator, see
+/// `RetryableError::next_delay`.
+///
+/// Each request starts over from the beginning of the delays.
+#[derive(Clone)]
+pub struct RetryPolicy<D: IntoIterator> {
+    schedule: Schedule<D>,
+}
+
+impl<D> RetryPolicy<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Create a new retry policy from a cloneable delay iterator
+    pub fn new(durations: D) -> Self {
+        Self {
+            schedule: Schedule::new(durations),
+        }
+    }
+}
+
+impl<D, Req, Res, E> Policy<Req, Res, E> for RetryPolicy<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    Req: Clone,
+    E: RetryableError,
+{
+    type Future = tokio::time::Sleep;
+
+    fn retry(&mut self, _req: &mut Req, result: &mut Result<Res, E>) -> Option<Self::Future> {
+        match result {
+            Err(e) if e.is_retryable() => self.schedule.next_sleep(|delay| e.next_delay(delay)),
+            _ => None,
+        }
+    }
+
+    fn clone_request(&mut self, req: &Req) -> Option<Req> {
+        Some(req.clone())
+    }
+}
+
+/// A `tower::retry::Policy` retrying requests whose result is deemed retryable by a classifier,
+/// waiting between attempts according to a cloneable delay iterator
+///
+/// The classifier sees both successful responses and errors, so that e.g. an HTTP response with a
+/// `503 Service Unavailable` status can be retried. Each request starts over from the beginning of
+/// the delays.
+///
+/// ```
+/// use retry_block::tower::ClassifyPolicy;
+/// use retry_block::delay::Fixed;
+/// use tower::retry::RetryLayer;
+/// use tower::{service_fn, ServiceBuilder, ServiceExt};
+/// use std::time::Duration;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut statuses = vec![200, 503, 503].into_iter();
+/// let service = ServiceBuilder::new()
+///     .layer(RetryLayer::new(ClassifyPolicy::new(
+///         Fixed::new(Duration::from_millis(1)).take(3),
+///         |result: &Result<u16, ()>| matches!(result, Ok(503) | Err(_)),
+///     )))
+///     .service(service_fn(move |_request: ()| {
+///         let status = statuses.next_back().unwrap();
+///         async move { Ok::<_, ()>(status) }
+///     }));
+///
+/// assert_eq!(service.oneshot(()).await, Ok(200));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ClassifyPolicy<D: IntoIterator, C> {
+    schedule: Schedule<D>,
+    classifier: C,
+}
+
+impl<D, C> ClassifyPolicy<D, C>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Create a new retry policy from a cloneable delay iterator and a classifier returning
+    /// whether a given result should be retried
+    pub fn new(durations: D, classifier: C) -> Self {
+        Self {
+            schedule: Schedule::new(durations),
+            classifier,
+        }
+    }
+}
+
+impl<D, C, Req, Res, E> Policy<Req, Res, E> for ClassifyPolicy<D, C>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    C: FnMut(&Result<Res, E>) -> bool,
+    Req: Clone,
+{
+    type Future = tokio::time::Sleep;
+
+    fn retry(&mut self, _req: &mut Req, result: &mut Result<Res, E>) -> Option<Self::Future> {
+        if (self.classifier)(result) {
+            self.schedule.next_sleep(|delay| delay)
+        } else {
+            None
+        }
+    }
+
+    fn clone_request(&mut self, req: &Req) -> Option<Req> {
+        Some(req.clone())
+    }
+}
+
+/// A `tower::Layer` retrying failed requests of the wrapped service according to a
+/// `RetryPolicy`
+#[derive(Clone)]
+pub struct RetryLayer<D: IntoIterator> {
+    policy: RetryPolicy<D>,
+}
+
+impl<D> RetryLayer<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Create a new retry layer from a cloneable delay iterator
+    pub fn new(durations: D) -> Self {
+        Self {
+            policy: RetryPolicy::new(durations),
+        }
+    }
+}
+
+#[cfg(any(feature = "random", feature = "fastrand"))]
+impl From<RetryConfig> for RetryLayer<RetryConfig> {
+    fn from(config: RetryConfig) -> Self {
+        Self::new(config)
+    }
+}
+
+impl<S, D> Layer<S> for RetryLayer<D>
+where
+    D: IntoIterator + Clone,
+{
+    type Service = Retry<RetryPolicy<D>, S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        Retry::new(self.policy.clone(), service)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delay::NoDelay;
+    use crate::tower::RetryLayer;
+    use crate::RetryableError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tower::{service_fn, Layer, ServiceExt};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Error {
+        Transient,
+        Fatal,
+    }
+
+    impl RetryableError for Error {
+        fn is_retryable(&self) -> bool {
+            *self == Error::Transient
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_layer_classification() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let service = RetryLayer::new(NoDelay.take(5)).layer(service_fn(|error: Error| {
+            let calls = calls.clone();
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(error)
+            }
+        }));
+
+        assert_eq!(
+            service.clone().oneshot(Error::Transient).await,
+            Err(Error::Transient)
+        );
+        assert_eq!(calls.swap(0, Ordering::SeqCst), 6);
+
+        assert_eq!(service.oneshot(Error::Fatal).await, Err(Error::Fatal));
+        assert_eq!(calls.swap(0, Ordering::SeqCst), 1);
+    }
+}
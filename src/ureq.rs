@@ -0,0 +1,158 @@
+//! Retried blocking HTTP requests with `ureq` agents (need `ureq` feature)
+//!
+//! ```no_run
+//! use retry_block::delay::Exponential;
+//! use retry_block::ureq::RetryAgent;
+//! use std::time::Duration;
+//!
+//! let agent = RetryAgent::new(
+//!     ureq::Agent::new_with_defaults(),
+//!     Exponential::exact(Duration::from_millis(100)).take(3),
+//! );
+//! let body = agent
+//!     .call(|agent| agent.get("http://example.com/health").call())
+//!     .unwrap()
+//!     .into_body()
+//!     .read_to_string()
+//!     .unwrap();
+//! ```
+
+use crate::RetryableError;
+use std::time::Duration;
+use ureq::http::{header, Response};
+use ureq::{Agent, Body, Error};
+
+/// Whether a response with the given status code is worth retrying, i.e. a timeout, a rate limit
+/// or a transient server error
+pub fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 408 | 429 | 500 | 502 | 503 | 504)
+}
+
+impl RetryableError for Error {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::StatusCode(status) => is_retryable_status(*status),
+            Error::Io(e) => crate::net::is_transient(e),
+            Error::Timeout(_) | Error::ConnectionFailed => true,
+            _ => false,
+        }
+    }
+}
+
+/// The delay asked for by the `Retry-After` header of the given response, in whole seconds
+fn retry_after(response: &Response<Body>) -> Option<Duration> {
+    let value = response.headers().get(header::RETRY_AFTER)?;
+    let seconds = value.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+type Classifier = Box<dyn Fn(&Result<Response<Body>, Error>) -> bool + Send + Sync>;
+
+/// A `ureq::Agent` retrying the requests made through it according to a cloneable delay iterator
+///
+/// By default, requests failing with a retryable `ureq::Error` are retried, as well as responses
+/// with a retryable status for agents configured not to treat them as errors, in which case a
+/// `Retry-After` header in seconds is waited instead of the next delay. Each call starts over from
+/// the beginning of the delays, and returns the last result once they run out.
+pub struct RetryAgent<D> {
+    agent: Agent,
+    durations: D,
+    classifier: Classifier,
+}
+
+impl<D> RetryAgent<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Wrap the given agent, retrying with the given delays
+    pub fn new(agent: Agent, durations: D) -> Self {
+        Self {
+            agent,
+            durations,
+            classifier: Box::new(|result| match result {
+                Ok(response) => is_retryable_status(response.status().as_u16()),
+                Err(e) => e.is_retryable(),
+            }),
+        }
+    }
+
+    /// Decide which results are retried with the given classifier instead
+    pub fn with_classifier<C>(mut self, classifier: C) -> Self
+    where
+        C: Fn(&Result<Response<Body>, Error>) -> bool + Send + Sync + 'static,
+    {
+        self.classifier = Box::new(classifier);
+        self
+    }
+
+    /// The wrapped agent
+    pub fn agent(&self) -> &Agent {
+        &self.agent
+    }
+
+    /// Make the request built and sent by the given function with the wrapped agent, retrying it
+    /// as long as its result is deemed retryable and delays remain
+    pub fn call<F>(&self, mut request: F) -> Result<Response<Body>, Error>
+    where
+        F: FnMut(&Agent) -> Result<Response<Body>, Error>,
+    {
+        let mut it = crate::delay::__faulty(self.durations.clone().into_iter());
+        loop {
+            let result = request(&self.agent);
+            if !(self.classifier)(&result) {
+                return result;
+            }
+            let Some(duration) = it.next() else {
+                return result;
+            };
+            let duration = match &result {
+                Ok(response) => retry_after(response).unwrap_or(duration),
+                Err(_) => duration,
+            };
+            crate::sleep::thread_sleep(duration);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RetryAgent;
+    use crate::delay::NoDelay;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serve the given statuses in order, one request per connection
+    fn serve(statuses: &'static [u16]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                )
+                .unwrap();
+            }
+        });
+        format!("http://{addr}/")
+    }
+
+    #[test]
+    fn retried_statuses() {
+        let url = serve(&[503, 429, 200]);
+        let agent = RetryAgent::new(ureq::Agent::new_with_defaults(), NoDelay.take(2));
+        let response = agent.call(|agent| agent.get(&url).call()).unwrap();
+        assert_eq!(response.status(), 200);
+
+        let url = serve(&[503, 404]);
+        let agent = RetryAgent::new(ureq::Agent::new_with_defaults(), NoDelay.take(5));
+        let result = agent.call(|agent| agent.get(&url).call());
+        assert!(matches!(result, Err(ureq::Error::StatusCode(404))));
+    }
+}